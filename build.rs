@@ -0,0 +1,36 @@
+use std::process::Command;
+
+/// The trimmed output of a command, or "unknown" if it can't be run.
+fn command_output(program: &str, args: &[&str]) -> String {
+    Command::new(program)
+        .args(args)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+fn main() {
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let rustc_version = command_output(&rustc, &["--version"]);
+    // "rustc 1.95.0 (hash date)" -> "1.95.0"
+    let rustc_version = rustc_version
+        .split_whitespace()
+        .nth(1)
+        .unwrap_or(&rustc_version)
+        .to_string();
+
+    println!(
+        "cargo:rustc-env=STATUS_PAGE_RUSTC_VERSION={}",
+        rustc_version
+    );
+    println!(
+        "cargo:rustc-env=STATUS_PAGE_GIT_SHA={}",
+        command_output("git", &["rev-parse", "--short=12", "HEAD"])
+    );
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+}
@@ -32,4 +32,27 @@ document.addEventListener('DOMContentLoaded', function () {
     .addEventListener('click', open_repositories);
   document.getElementById('syncservers_handler')
     .addEventListener('click', open_syncservers);
-});
\ No newline at end of file
+});
+// How old the data is, shown once a minute, and marked as stale past the threshold.
+function show_data_age(el) {
+  var generated = parseInt(el.dataset.generatedAtEpoch, 10);
+  var staleAfter = parseInt(el.dataset.staleAfter, 10);
+  var age = Math.max(0, Math.floor(Date.now() / 1000) - generated);
+  el.textContent = el.dataset.text.replace('{minutes}', Math.floor(age / 60));
+  el.classList.toggle('stale-data', age > staleAfter);
+}
+
+document.addEventListener('DOMContentLoaded', function () {
+  document.querySelectorAll('.data-age').forEach(function (el) {
+    show_data_age(el);
+    setInterval(function () { show_data_age(el); }, 60000);
+  });
+});
+// Expanding the members of a load balanced server shouldn't collapse the infobox.
+document.addEventListener('DOMContentLoaded', function () {
+  document.querySelectorAll('details.members').forEach(function (el) {
+    el.addEventListener('click', function (event) {
+      event.stopPropagation();
+    });
+  });
+});
@@ -0,0 +1,104 @@
+use anyhow::{Context, Result};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::config::{AnnouncementSeverity, ConfigSection};
+use crate::incidents::markdown_to_html;
+
+/// The file, in the destination directory, with the announcement unless another one is given.
+pub const ANNOUNCEMENT_FILE: &str = "announcement.md";
+
+/// The banner at the top of the page.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Announcement {
+    /// The markdown, as written.
+    pub text: String,
+    pub html: String,
+    pub severity: AnnouncementSeverity,
+}
+
+/// The announcement from the file at `path`, or else from the configuration. A missing or
+/// empty file falls back to the configuration, and there is no announcement if neither has one.
+pub fn load(path: &Path, meta: &ConfigSection) -> Result<Option<Announcement>> {
+    let from_file = if path.exists() {
+        fs::read_to_string(path).context(format!("Failed to read {:?}", path))?
+    } else {
+        debug!("No announcement file at {:?}", path);
+        String::new()
+    };
+
+    let text = [Some(from_file.as_str()), meta.announcement.as_deref()]
+        .into_iter()
+        .flatten()
+        .map(str::trim)
+        .find(|text| !text.is_empty());
+    Ok(text.map(|text| Announcement {
+        text: text.to_string(),
+        html: markdown_to_html(text),
+        severity: meta.announcement_severity,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yare::parameterized;
+
+    fn meta(announcement: Option<&str>) -> ConfigSection {
+        ConfigSection {
+            title: "EESSI".to_string(),
+            logging_level: "info".to_string(),
+            contact_email: "support@eessi.io".to_string(),
+            repo_url: "https://eessi.io".to_string(),
+            repo_url_text: "eessi.io".to_string(),
+            announcement: announcement.map(str::to_string),
+            announcement_severity: AnnouncementSeverity::Warning,
+            template_dir: None,
+            strict_templates: false,
+            extra: Default::default(),
+            links: vec![],
+            locale: "en".to_string(),
+            refresh_seconds: None,
+            stale_after_seconds: None,
+            embed_data: false,
+            embed_data_warn_bytes: 1_000_000,
+            minify_html: false,
+            hashed_assets: false,
+            hashed_assets_keep_runs: 10,
+            base_url: None,
+            description: None,
+            og_image: None,
+            resources_dir: None,
+            resources_link: false,
+            exclude_resources: vec![],
+            durability: Default::default(),
+            output_file_mode: 0o644,
+        }
+    }
+
+    #[parameterized(
+        file_wins = { Some("Maintenance **Saturday**\n"), Some("From the config"), Some("Maintenance **Saturday**") },
+        config = { None, Some("From the config"), Some("From the config") },
+        empty_file = { Some(" \n"), Some("From the config"), Some("From the config") },
+        empty_config = { None, Some(""), None },
+        none = { None, None, None },
+    )]
+    fn test_load(file: Option<&str>, config: Option<&str>, expected: Option<&str>) -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join(ANNOUNCEMENT_FILE);
+        if let Some(contents) = file {
+            fs::write(&path, contents)?;
+        }
+
+        let announcement = load(&path, &meta(config))?;
+
+        assert_eq!(announcement.as_ref().map(|a| a.text.as_str()), expected);
+        if let Some(announcement) = announcement {
+            assert_eq!(announcement.html, markdown_to_html(&announcement.text));
+            assert_eq!(announcement.severity, AnnouncementSeverity::Warning);
+        }
+        Ok(())
+    }
+}
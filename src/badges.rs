@@ -0,0 +1,249 @@
+use anyhow::Result;
+use log::info;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+
+use crate::dependencies::OutputSink;
+use crate::models::{Status, StatusPageData};
+
+/// The subdirectory of the destination the badges are written to.
+pub const BADGES_DIR: &str = "badges";
+
+/// The subdirectory of the destination the shields.io endpoints are written to.
+pub const ENDPOINTS_DIR: &str = "badge";
+
+/// The components other than repositories that can have a badge.
+pub const COMPONENTS: [&str; 4] = ["eessi", "stratum0", "stratum1", "syncservers"];
+
+/// The prefix of the badge of a repository, followed by its name.
+pub const REPOSITORY_PREFIX: &str = "repo-";
+
+/// A badge for a component, written to `badge-<name>.svg`.
+#[derive(Debug, PartialEq)]
+pub struct Badge {
+    pub name: String,
+    pub label: String,
+    pub status: Status,
+}
+
+/// Whether `name` is a component that can have a badge. Repositories aren't checked against
+/// the configuration, as they may also be found by scraping.
+pub fn is_valid_name(name: &str) -> bool {
+    COMPONENTS.contains(&name)
+        || name
+            .strip_prefix(REPOSITORY_PREFIX)
+            .is_some_and(|repository| !repository.is_empty() && !repository.contains('/'))
+}
+
+/// The badges of every component, or only of the `wanted` ones.
+pub fn badges(data: &StatusPageData, wanted: Option<&[String]>) -> Vec<Badge> {
+    let components = [
+        ("eessi", data.eessi_status.status),
+        ("stratum0", data.stratum0.status),
+        ("stratum1", data.stratum1.status),
+        ("syncservers", data.syncservers.status),
+    ];
+    let components = components.iter().map(|(name, status)| Badge {
+        name: name.to_string(),
+        label: name.to_string(),
+        status: *status,
+    });
+    let repositories = data.repositories.iter().map(|r| Badge {
+        name: format!("{}{}", REPOSITORY_PREFIX, r.name),
+        label: r.name.clone(),
+        status: r.status,
+    });
+
+    components
+        .chain(repositories)
+        .filter(|badge| wanted.is_none_or(|wanted| wanted.contains(&badge.name)))
+        .collect()
+}
+
+/// The color of a status, in hex without the leading `#` as shields.io expects it. The favicon
+/// has the same colors.
+pub fn color(status: Status) -> &'static str {
+    match status {
+        Status::OK => "2ecc71",
+        Status::DEGRADED => "f1c40f",
+        Status::WARNING => "e67e22",
+        Status::FAILED => "e74c3c",
+        Status::MAINTENANCE => "3498db",
+    }
+}
+
+/// An estimate of the width in pixels of `text` in 11px Verdana, the font of the badges.
+fn text_width(text: &str) -> u32 {
+    let width: f64 = text
+        .chars()
+        .map(|c| match c {
+            'i' | 'j' | 'l' | '.' | ',' | ':' | ';' | '\'' | '|' | '!' => 3.5,
+            'f' | 'r' | 't' | ' ' | '-' | '(' | ')' | '/' | 'I' => 4.5,
+            'm' | 'w' => 10.0,
+            'M' | 'W' => 11.5,
+            'A'..='Z' => 7.5,
+            _ => 6.8,
+        })
+        .sum();
+    width.ceil() as u32
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Render a badge in the flat style of shields.io, with the label on the left and the status
+/// text on a background in the color of the status on the right.
+pub fn render(badge: &Badge) -> String {
+    let label = escape(&badge.label);
+    let message = escape(badge.status.text());
+    let label_width = text_width(&badge.label) + 10;
+    let message_width = text_width(badge.status.text()) + 10;
+    let width = label_width + message_width;
+    let label_x = label_width as f64 / 2.0;
+    let message_x = label_width as f64 + message_width as f64 / 2.0;
+
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="20" role="img" aria-label="{label}: {message}">
+<title>{label}: {message}</title>
+<linearGradient id="s" x2="0" y2="100%"><stop offset="0" stop-color="#bbb" stop-opacity=".1"/><stop offset="1" stop-opacity=".1"/></linearGradient>
+<clipPath id="r"><rect width="{width}" height="20" rx="3" fill="#fff"/></clipPath>
+<g clip-path="url(#r)"><rect width="{label_width}" height="20" fill="#555"/><rect x="{label_width}" width="{message_width}" height="20" fill="#{color}"/><rect width="{width}" height="20" fill="url(#s)"/></g>
+<g fill="#fff" text-anchor="middle" font-family="Verdana,Geneva,DejaVu Sans,sans-serif" font-size="11">
+<text x="{label_x}" y="15" fill="#010101" fill-opacity=".3">{label}</text><text x="{label_x}" y="14">{label}</text>
+<text x="{message_x}" y="15" fill="#010101" fill-opacity=".3">{message}</text><text x="{message_x}" y="14">{message}</text>
+</g>
+</svg>
+"##,
+        color = color(badge.status),
+    )
+}
+
+/// The badge as a shields.io endpoint, which shields.io renders in its own styles.
+pub fn endpoint(badge: &Badge) -> Value {
+    let mut endpoint = json!({
+        "schemaVersion": 1,
+        "label": badge.label,
+        "message": badge.status.text(),
+        "color": color(badge.status),
+    });
+    if badge.status == Status::FAILED {
+        endpoint["isError"] = json!(true);
+    }
+    endpoint
+}
+
+/// Write the badges as shields.io endpoints to `badge/<name>.json` under `destination`,
+/// returning the paths written.
+pub fn write_endpoints(
+    destination: &Path,
+    badges: &[Badge],
+    sink: &OutputSink,
+) -> Result<Vec<PathBuf>> {
+    let dir = destination.join(ENDPOINTS_DIR);
+    sink.create_dir_all(&dir)?;
+    let mut written = vec![];
+    for badge in badges {
+        let path = dir.join(format!("{}.json", badge.name));
+        sink.atomic_write(&path, endpoint(badge).to_string().as_bytes())?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// Write the badges to the badges directory under `destination`.
+pub fn write(destination: &Path, badges: &[Badge], sink: &OutputSink) -> Result<()> {
+    let dir = destination.join(BADGES_DIR);
+    sink.create_dir_all(&dir)?;
+    for badge in badges {
+        let path = dir.join(format!("badge-{}.svg", badge.name));
+        sink.atomic_write(&path, render(badge).as_bytes())?;
+    }
+    info!("{} badges written to: {:?}", badges.len(), dir);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use yare::parameterized;
+
+    fn badge(label: &str, status: Status) -> Badge {
+        Badge {
+            name: label.to_string(),
+            label: label.to_string(),
+            status,
+        }
+    }
+
+    #[parameterized(
+        ok = { "stratum1", Status::OK, include_str!("testdata/badge-stratum1-ok.svg") },
+        failed = { "software.eessi.io", Status::FAILED, include_str!("testdata/badge-software.eessi.io-failed.svg") },
+    )]
+    fn test_render(label: &str, status: Status, expected: &str) {
+        assert_eq!(render(&badge(label, status)), expected);
+    }
+
+    #[parameterized(
+        ok = { Status::OK, json!({ "schemaVersion": 1, "label": "stratum1", "message": "Normal service", "color": "2ecc71" }) },
+        warning = { Status::WARNING, json!({ "schemaVersion": 1, "label": "stratum1", "message": "Warning", "color": "e67e22" }) },
+        failed = { Status::FAILED, json!({ "schemaVersion": 1, "label": "stratum1", "message": "Failed", "color": "e74c3c", "isError": true }) },
+    )]
+    fn test_endpoint(status: Status, expected: Value) {
+        assert_eq!(endpoint(&badge("stratum1", status)), expected);
+    }
+
+    #[test]
+    fn test_write_endpoints() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let badges = [Badge {
+            name: "repo-software.eessi.io".to_string(),
+            label: "software.eessi.io".to_string(),
+            status: Status::OK,
+        }];
+
+        let written = write_endpoints(dir.path(), &badges, &OutputSink::default())?;
+
+        let path = dir.path().join("badge/repo-software.eessi.io.json");
+        assert_eq!(written, std::slice::from_ref(&path));
+        let contents: Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+        assert_eq!(contents, endpoint(&badges[0]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_escapes() {
+        let rendered = render(&badge("a<b>&c", Status::OK));
+        assert!(
+            rendered.contains(">a&lt;b&gt;&amp;c</text>"),
+            "{}",
+            rendered
+        );
+    }
+
+    #[parameterized(
+        empty = { "", 0 },
+        narrow = { "il", 7 },
+        wide = { "mW", 22 },
+        mixed = { "Normal service", 86 },
+    )]
+    fn test_text_width(text: &str, expected: u32) {
+        assert_eq!(text_width(text), expected);
+    }
+
+    #[parameterized(
+        overall = { "eessi", true },
+        stratum1 = { "stratum1", true },
+        repository = { "repo-software.eessi.io", true },
+        empty_repository = { "repo-", false },
+        path = { "repo-../x", false },
+        unknown = { "stratum2", false },
+    )]
+    fn test_is_valid_name(name: &str, valid: bool) {
+        assert_eq!(is_valid_name(name), valid);
+    }
+}
@@ -0,0 +1,192 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::dependencies::OutputSink;
+use crate::models::Server;
+
+/// The file, in the destination directory, holding the last successful scrape of each server.
+pub const LAST_SCRAPE_FILE: &str = "last_scrape.json";
+
+/// The last successfully scraped state of every server.
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct LastScrape {
+    pub servers: Vec<CachedServer>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CachedServer {
+    pub scraped_at: DateTime<Utc>,
+    pub server: Server,
+}
+
+impl LastScrape {
+    /// Read the last scrape from `dir`, if there is one.
+    pub fn load(dir: &Path) -> Result<Option<Self>> {
+        let path = dir.join(LAST_SCRAPE_FILE);
+        if !path.exists() {
+            debug!("No previous scrape found at {:?}", path);
+            return Ok(None);
+        }
+
+        let contents = fs::read_to_string(&path).context(format!("Failed to read {:?}", path))?;
+        let last_scrape =
+            serde_json::from_str(&contents).context(format!("Failed to parse {:?}", path))?;
+        Ok(Some(last_scrape))
+    }
+
+    pub fn save(&self, dir: &Path, sink: &OutputSink) -> Result<()> {
+        let path = dir.join(LAST_SCRAPE_FILE);
+        let json = serde_json::to_string_pretty(self)?;
+        sink.atomic_write(&path, json.as_bytes())?;
+        info!("Last scrape written to: {:?}", path);
+        Ok(())
+    }
+
+    /// Get the cached state of a server.
+    pub fn get(&self, server: &Server) -> Option<&CachedServer> {
+        self.servers
+            .iter()
+            .find(|cached| cached.server.hostname == server.hostname)
+    }
+
+    /// Build the cache for the next run.
+    ///
+    /// Servers that were scraped successfully this run are stored as of `now`, while servers
+    /// that failed (or are shown from stale data) keep their previous entry.
+    pub fn updated(previous: Option<&LastScrape>, servers: &[Server], now: DateTime<Utc>) -> Self {
+        let servers = servers
+            .iter()
+            .filter_map(|server| {
+                if server.scraped_successfully() {
+                    Some(CachedServer {
+                        scraped_at: now,
+                        server: server.clone(),
+                    })
+                } else {
+                    previous.and_then(|p| p.get(server)).cloned()
+                }
+            })
+            .collect();
+
+        LastScrape { servers }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::MaintenanceAggregation;
+    use crate::models::{Status, StatusManager};
+    use chrono::{Duration, TimeZone};
+    use cvmfs_server_scraper::{
+        Hostname, MaybeRfc2822DateTime, ServerBackendType, ServerMetadata, ServerType,
+    };
+    use yare::parameterized;
+
+    fn server(hostname: &str, scraped: bool) -> Server {
+        Server {
+            server_type: ServerType::Stratum1,
+            backend_type: ServerBackendType::CVMFS,
+            backend_detected: scraped.then_some(ServerBackendType::CVMFS),
+            hostname: Hostname::try_from(hostname).unwrap(),
+            repositories: vec![],
+            status: if scraped { Status::OK } else { Status::FAILED },
+            metadata: scraped.then(|| ServerMetadata {
+                schema_version: Some(1),
+                cvmfs_version: Some(semver::Version::new(2, 11, 5)),
+                last_geodb_update: MaybeRfc2822DateTime(None),
+                os_version_id: None,
+                os_pretty_name: Some("AlmaLinux 9".to_string()),
+                os_id: None,
+                administrator: None,
+                email: None,
+                organisation: None,
+                custom: None,
+            }),
+            geodb_status: Status::OK,
+            cert_expiry: None,
+            cert_expiry_days: None,
+            scrape_duration_ms: Some(10),
+            address: None,
+            members: vec![],
+            data_age_seconds: None,
+            scrape_retried: false,
+        }
+    }
+
+    #[test]
+    fn test_last_scrape_round_trip() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let now = Utc.with_ymd_and_hms(2025, 10, 2, 7, 28, 0).unwrap();
+        LastScrape::updated(None, &[server("s1.example.com", true)], now)
+            .save(dir.path(), &OutputSink::default())?;
+
+        let loaded = LastScrape::load(dir.path())?.expect("last scrape was saved");
+        assert_eq!(loaded.servers.len(), 1);
+        assert_eq!(loaded.servers[0].scraped_at, now);
+        let metadata = loaded.servers[0].server.metadata.as_ref().unwrap();
+        assert_eq!(metadata.cvmfs_version, Some(semver::Version::new(2, 11, 5)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_updated_keeps_previous_entry_for_failed_servers() {
+        let then = Utc.with_ymd_and_hms(2025, 10, 2, 7, 0, 0).unwrap();
+        let now = then + Duration::minutes(30);
+        let previous = LastScrape::updated(
+            None,
+            &[
+                server("s1.example.com", true),
+                server("s2.example.com", true),
+            ],
+            then,
+        );
+
+        let updated = LastScrape::updated(
+            Some(&previous),
+            &[
+                server("s1.example.com", false),
+                server("s2.example.com", true),
+                server("s3.example.com", false),
+            ],
+            now,
+        );
+
+        assert_eq!(updated.servers.len(), 2);
+        assert_eq!(updated.servers[0].scraped_at, then);
+        assert_eq!(updated.servers[1].scraped_at, now);
+    }
+
+    #[parameterized(
+        recent = { 10, 60, Status::DEGRADED, Some(600) },
+        at_limit = { 60, 60, Status::DEGRADED, Some(3600) },
+        too_old = { 61, 60, Status::FAILED, None },
+    )]
+    fn test_apply_stale_data(
+        age_minutes: i64,
+        max_age_minutes: i64,
+        expected: Status,
+        expected_age: Option<i64>,
+    ) {
+        let now = Utc.with_ymd_and_hms(2025, 10, 2, 7, 28, 0).unwrap();
+        let last_scrape = LastScrape::updated(
+            None,
+            &[server("s1.example.com", true)],
+            now - Duration::minutes(age_minutes),
+        );
+        let mut status_manager = StatusManager {
+            servers: vec![server("s1.example.com", false)],
+            maintenance_aggregation: MaintenanceAggregation::default(),
+        };
+
+        status_manager.apply_stale_data(&last_scrape, max_age_minutes, now);
+
+        let server = &status_manager.servers[0];
+        assert_eq!(server.status, expected);
+        assert_eq!(server.data_age_seconds, expected_age);
+    }
+}
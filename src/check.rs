@@ -0,0 +1,116 @@
+use std::fmt;
+
+use crate::config::{CheckConfig, NagiosCode};
+use crate::models::{Status, StatusPageData};
+
+/// The outcome of the `check` subcommand: a line in the format of Nagios plugins, and the
+/// state that is its exit code.
+#[derive(Debug, PartialEq)]
+pub struct CheckReport {
+    pub code: NagiosCode,
+    pub summary: String,
+    /// Performance data, as `label=value;warn;crit;min;max`.
+    pub perfdata: Vec<String>,
+}
+
+impl CheckReport {
+    pub fn new(data: &StatusPageData, config: &CheckConfig) -> Self {
+        let stratum1 = &data.stratum1.servers;
+        let stratum1_ok = stratum1.iter().filter(|s| s.status == Status::OK).count();
+        let repos_lagging = data
+            .repositories
+            .iter()
+            .filter(|r| r.status != Status::OK)
+            .count();
+
+        let mut summary = format!("{}/{} stratum1 in sync", stratum1_ok, stratum1.len());
+        let status = data.eessi_status.status;
+        if status != Status::OK {
+            summary = format!("{}, {}", status.text(), summary);
+        }
+
+        CheckReport {
+            code: config.code(status),
+            summary,
+            perfdata: vec![
+                format!("stratum1_ok={};;;0;{}", stratum1_ok, stratum1.len()),
+                format!("repos_lagging={}", repos_lagging),
+            ],
+        }
+    }
+
+    /// The check couldn't be done, such as when the configuration is invalid.
+    pub fn unknown(error: &anyhow::Error) -> Self {
+        CheckReport {
+            code: NagiosCode::Unknown,
+            summary: format!("{:#}", error).replace('\n', " "),
+            perfdata: vec![],
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        self.code as i32
+    }
+}
+
+impl fmt::Display for CheckReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "EESSI {} - {}", self.code.label(), self.summary)?;
+        if !self.perfdata.is_empty() {
+            write!(f, " | {}", self.perfdata.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yare::parameterized;
+
+    fn data(status: Status) -> StatusPageData {
+        let mut data: StatusPageData =
+            serde_json::from_str(include_str!("testdata/status-schema-0.json")).unwrap();
+        data.eessi_status.status = status;
+        data.stratum1.servers[0].status = Status::OK;
+        data
+    }
+
+    #[parameterized(
+        ok = { Status::OK, 0 },
+        warning = { Status::WARNING, 1 },
+        degraded = { Status::DEGRADED, 2 },
+        failed = { Status::FAILED, 2 },
+        maintenance = { Status::MAINTENANCE, 0 },
+    )]
+    fn test_exit_code(status: Status, expected: i32) {
+        let report = CheckReport::new(&data(status), &CheckConfig::default());
+        assert_eq!(report.exit_code(), expected);
+    }
+
+    #[test]
+    fn test_exit_code_configured() {
+        let config = CheckConfig {
+            degraded: NagiosCode::Warning,
+            ..CheckConfig::default()
+        };
+        let report = CheckReport::new(&data(Status::DEGRADED), &config);
+        assert_eq!(report.exit_code(), 1);
+    }
+
+    #[test]
+    fn test_exit_code_unknown() {
+        let report = CheckReport::unknown(&anyhow::anyhow!("No servers configured"));
+        assert_eq!(report.exit_code(), 3);
+        assert_eq!(report.to_string(), "EESSI UNKNOWN - No servers configured");
+    }
+
+    #[parameterized(
+        ok = { Status::OK, "EESSI OK - 1/1 stratum1 in sync | stratum1_ok=1;;;0;1 repos_lagging=0" },
+        failed = { Status::FAILED, "EESSI CRITICAL - Failed, 1/1 stratum1 in sync | stratum1_ok=1;;;0;1 repos_lagging=0" },
+    )]
+    fn test_display(status: Status, expected: &str) {
+        let report = CheckReport::new(&data(status), &CheckConfig::default());
+        assert_eq!(report.to_string(), expected);
+    }
+}
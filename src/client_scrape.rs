@@ -0,0 +1,451 @@
+use futures::stream::{self, StreamExt, TryStreamExt};
+use rand::distributions::{Alphanumeric, DistString};
+use serde::Deserialize;
+use std::collections::BTreeSet;
+
+use cvmfs_server_scraper::{
+    CVMFSScraperError, GeoapiServerQuery, Hostname, Manifest, ManifestError, MaybeRfc2822DateTime,
+    PopulatedRepositoryOrReplica, PopulatedServer, ScrapeError, ScrapedServer, Server,
+    ServerBackendType, ServerMetadata, ServerType, DEFAULT_GEOAPI_SERVERS,
+};
+
+/// The parts of repositories.json we need to list the repositories on a server, and for its
+/// metadata.
+#[derive(Deserialize)]
+pub struct RepositoriesJson {
+    #[serde(default)]
+    schema: Option<u32>,
+    #[serde(default)]
+    last_geodb_update: MaybeRfc2822DateTime,
+    #[serde(default)]
+    cvmfs_version: Option<String>,
+    #[serde(default)]
+    os_id: Option<String>,
+    #[serde(default)]
+    os_version_id: Option<String>,
+    #[serde(default)]
+    os_pretty_name: Option<String>,
+    #[serde(default)]
+    repositories: Vec<RepositoriesJsonEntry>,
+    #[serde(default)]
+    replicas: Vec<RepositoriesJsonEntry>,
+}
+
+impl RepositoriesJson {
+    /// The names of the repositories and replicas on the server.
+    pub fn names(&self) -> impl Iterator<Item = String> + '_ {
+        self.repositories
+            .iter()
+            .chain(&self.replicas)
+            .map(|r| r.name.clone())
+    }
+}
+
+/// The meta.json of a server.
+#[derive(Deserialize)]
+struct MetaJson {
+    administrator: String,
+    email: String,
+    organisation: String,
+    custom: serde_json::Value,
+}
+
+/// The .cvmfs_status.json of a repository.
+#[derive(Deserialize)]
+struct StatusJson {
+    #[serde(default)]
+    last_snapshot: Option<MaybeRfc2822DateTime>,
+    #[serde(default)]
+    last_gc: Option<MaybeRfc2822DateTime>,
+}
+
+#[derive(Deserialize)]
+struct RepositoriesJsonEntry {
+    name: String,
+}
+
+/// Fetch `url` with `client` and parse it as JSON.
+async fn fetch_json<T: serde::de::DeserializeOwned>(
+    client: &reqwest::Client,
+    url: &str,
+) -> Result<T, ScrapeError> {
+    let text = client
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    Ok(serde_json::from_str(&text)?)
+}
+
+/// Fetch the repository list of the server at `hostname` with `client`.
+pub async fn fetch_listing(
+    client: &reqwest::Client,
+    hostname: &Hostname,
+) -> Result<RepositoriesJson, ScrapeError> {
+    fetch_listing_from(client, &origin(hostname)).await
+}
+
+async fn fetch_listing_from(
+    client: &reqwest::Client,
+    origin: &str,
+) -> Result<RepositoriesJson, ScrapeError> {
+    fetch_json(
+        client,
+        &format!("{}/cvmfs/info/v1/repositories.json", origin),
+    )
+    .await
+}
+
+/// The scheme and authority of the URLs of the server at `hostname`.
+fn origin(hostname: &Hostname) -> String {
+    format!("http://{}", hostname)
+}
+
+/// Does the repository list of a server of `server_type` have the replicas it should?
+fn check_server_type(server: &Server, listing: &RepositoriesJson) -> Result<(), ScrapeError> {
+    let mismatch = match (server.server_type, listing.replicas.is_empty()) {
+        (ServerType::Stratum0, false) => "is a Stratum0 server, but replicas were found",
+        (ServerType::Stratum1, true) => "is a Stratum1 server, but no replicas were found",
+        (ServerType::SyncServer, true) => "is a SyncServer, but no replicas were found",
+        _ => return Ok(()),
+    };
+    Err(ScrapeError::ServerTypeMismatch(format!(
+        "{} {} in the repositories.json",
+        server.hostname, mismatch
+    )))
+}
+
+/// Scrape `server` like `Server::scrape` does, but with `client`, such as one from
+/// `resolving_client`. The scraper always uses a client of its own. Up to `concurrency`
+/// repositories are fetched at once.
+pub async fn scrape_with_client(
+    client: &reqwest::Client,
+    server: &Server,
+    repositories: Vec<String>,
+    ignored_repositories: &[String],
+    only_scrape_forced_repositories: bool,
+    concurrency: usize,
+) -> Result<ScrapedServer, CVMFSScraperError> {
+    scrape_from(
+        client,
+        &origin(&server.hostname),
+        server,
+        repositories,
+        ignored_repositories,
+        only_scrape_forced_repositories,
+        concurrency,
+    )
+    .await
+}
+
+/// `scrape_with_client`, with the URLs of the server starting with `origin`.
+async fn scrape_from(
+    client: &reqwest::Client,
+    origin: &str,
+    server: &Server,
+    repositories: Vec<String>,
+    ignored_repositories: &[String],
+    only_scrape_forced_repositories: bool,
+    concurrency: usize,
+) -> Result<ScrapedServer, CVMFSScraperError> {
+    let hostname = &server.hostname;
+    let mut names: BTreeSet<String> = repositories
+        .into_iter()
+        .filter(|name| !ignored_repositories.contains(name))
+        .collect();
+
+    let listing = match server.backend_type {
+        ServerBackendType::S3 if names.is_empty() => {
+            return Err(ScrapeError::EmptyRepositoryList(hostname.to_string()).into())
+        }
+        ServerBackendType::S3 => None,
+        ServerBackendType::CVMFS => Some(fetch_listing_from(client, origin).await?),
+        ServerBackendType::AutoDetect => match fetch_listing_from(client, origin).await {
+            Ok(listing) => Some(listing),
+            Err(ScrapeError::FetchError(_)) => None,
+            Err(e) => return Err(e.into()),
+        },
+    };
+    let backend_detected = match &listing {
+        Some(_) => ServerBackendType::CVMFS,
+        None => ServerBackendType::S3,
+    };
+
+    let mut metadata = ServerMetadata {
+        schema_version: None,
+        cvmfs_version: None,
+        last_geodb_update: MaybeRfc2822DateTime(None),
+        os_version_id: None,
+        os_pretty_name: None,
+        os_id: None,
+        administrator: None,
+        email: None,
+        organisation: None,
+        custom: None,
+    };
+    if let Some(listing) = listing {
+        check_server_type(server, &listing)?;
+        if !only_scrape_forced_repositories {
+            names.extend(
+                listing
+                    .names()
+                    .filter(|name| !ignored_repositories.contains(name)),
+            );
+        }
+        metadata.schema_version = listing.schema;
+        metadata.cvmfs_version = listing
+            .cvmfs_version
+            .map(|v| v.parse::<semver::Version>())
+            .transpose()
+            .map_err(|e| ScrapeError::ConversionError(e.to_string()))?;
+        metadata.last_geodb_update = listing.last_geodb_update;
+        metadata.os_version_id = listing.os_version_id;
+        metadata.os_pretty_name = listing.os_pretty_name;
+        metadata.os_id = listing.os_id;
+    }
+
+    // In the order of the names, as the scraper lists them.
+    let populated: Vec<PopulatedRepositoryOrReplica> = stream::iter(names)
+        .map(|name| fetch_repository(client, origin, name))
+        .buffered(concurrency.max(1))
+        .try_collect()
+        .await?;
+
+    let meta_url = format!("{}/cvmfs/info/v1/meta.json", origin);
+    if let Ok(meta) = fetch_json::<MetaJson>(client, &meta_url).await {
+        metadata.administrator = Some(meta.administrator);
+        metadata.email = Some(meta.email);
+        metadata.organisation = Some(meta.organisation);
+        metadata.custom = Some(meta.custom);
+    }
+
+    let geoapi_hosts = DEFAULT_GEOAPI_SERVERS.clone();
+    let mut response = Vec::new();
+    if let Some(first) = populated.first().filter(|_| {
+        server.server_type != ServerType::Stratum0 && backend_detected != ServerBackendType::S3
+    }) {
+        let url = format!(
+            "{}/cvmfs/{}/api/v1.0/geo/{}/{}",
+            origin,
+            first.name,
+            Alphanumeric.sample_string(&mut rand::thread_rng(), 12),
+            geoapi_hosts
+                .iter()
+                .map(|h| h.to_str())
+                .collect::<Vec<_>>()
+                .join(",")
+        );
+        let text = client
+            .get(&url)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| ScrapeError::GeoAPIFailure(e.to_string()))?
+            .text()
+            .await
+            .map_err(|e| ScrapeError::GeoAPIFailure(e.to_string()))?;
+        response = text
+            .trim()
+            .split(',')
+            .map(|x| x.parse::<u32>())
+            .collect::<Result<_, _>>()
+            .map_err(|e| ScrapeError::GeoAPIFailure(e.to_string()))?;
+    }
+
+    Ok(ScrapedServer::Populated(PopulatedServer {
+        server_type: server.server_type,
+        backend_type: server.backend_type,
+        backend_detected,
+        hostname: hostname.clone(),
+        repositories: populated,
+        metadata,
+        geoapi: GeoapiServerQuery {
+            hostname: hostname.clone(),
+            geoapi_hosts,
+            response,
+        },
+    }))
+}
+
+/// Fetch the status and the manifest of the repository `name`.
+async fn fetch_repository(
+    client: &reqwest::Client,
+    origin: &str,
+    name: String,
+) -> Result<PopulatedRepositoryOrReplica, CVMFSScraperError> {
+    let base = format!("{}/cvmfs/{}", origin, name);
+    let status: StatusJson = fetch_json(client, &format!("{}/.cvmfs_status.json", base)).await?;
+    let manifest: Manifest = client
+        .get(format!("{}/.cvmfspublished", base))
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(ManifestError::from)?
+        .text()
+        .await
+        .map_err(ManifestError::from)?
+        .parse()?;
+    Ok(PopulatedRepositoryOrReplica {
+        name,
+        manifest,
+        last_snapshot: status.last_snapshot,
+        last_gc: status.last_gc,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    /// A stratum1 with one replica, as files under the paths of their URLs.
+    const FIXTURE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/testdata/server");
+
+    /// Serve the fixture server, answering every geo API query alike, returning its origin.
+    async fn serve_fixture() -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let origin = format!("http://{}", listener.local_addr().unwrap());
+        let router = axum::Router::new().fallback(|uri: axum::http::Uri| async move {
+            if uri.path().contains("/api/v1.0/geo/") {
+                return Ok("2,1,3".to_string());
+            }
+            std::fs::read_to_string(Path::new(FIXTURE_DIR).join(&uri.path()[1..]))
+                .map_err(|_| axum::http::StatusCode::NOT_FOUND)
+        });
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        origin
+    }
+
+    fn server(server_type: ServerType, backend_type: ServerBackendType) -> Server {
+        Server::new(
+            server_type,
+            backend_type,
+            Hostname::try_from("s1.example.com").unwrap(),
+        )
+    }
+
+    /// The fixture of the repository `name`, parsed by the scraper.
+    fn fixture_manifest(name: &str) -> Manifest {
+        let path = Path::new(FIXTURE_DIR).join(format!("cvmfs/{}/.cvmfspublished", name));
+        std::fs::read_to_string(path).unwrap().parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_scrape_from() {
+        let origin = serve_fixture().await;
+        let server = server(ServerType::Stratum1, ServerBackendType::AutoDetect);
+
+        let scraped = scrape_from(
+            &reqwest::Client::new(),
+            &origin,
+            &server,
+            vec![],
+            &[],
+            false,
+            4,
+        )
+        .await
+        .unwrap();
+
+        let ScrapedServer::Populated(scraped) = scraped else {
+            panic!("The fixture server failed to scrape");
+        };
+        assert_eq!(scraped.backend_detected, ServerBackendType::CVMFS);
+        assert_eq!(
+            scraped
+                .repositories
+                .iter()
+                .map(|r| r.name.as_str())
+                .collect::<Vec<_>>(),
+            ["dev.eessi.io", "software.eessi.io"]
+        );
+        for repository in &scraped.repositories {
+            assert_eq!(
+                serde_json::to_value(&repository.manifest).unwrap(),
+                serde_json::to_value(fixture_manifest(&repository.name)).unwrap(),
+                "{}",
+                repository.name
+            );
+        }
+        assert!(scraped.repositories[1].last_snapshot.is_some());
+        let metadata = &scraped.metadata;
+        assert_eq!(metadata.schema_version, Some(1));
+        assert_eq!(
+            metadata.cvmfs_version.as_ref().map(|v| v.to_string()),
+            Some("2.11.5".to_string())
+        );
+        assert_eq!(metadata.os_id.as_deref(), Some("rhel"));
+        assert_eq!(metadata.organisation.as_deref(), Some("EESSI"));
+        assert_eq!(scraped.geoapi.response, [2, 1, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_scrape_from_forced_repositories() {
+        let origin = serve_fixture().await;
+        let server = server(ServerType::Stratum1, ServerBackendType::S3);
+
+        let scraped = scrape_from(
+            &reqwest::Client::new(),
+            &origin,
+            &server,
+            vec!["software.eessi.io".to_string(), "dev.eessi.io".to_string()],
+            &["dev.eessi.io".to_string()],
+            true,
+            4,
+        )
+        .await
+        .unwrap();
+
+        let ScrapedServer::Populated(scraped) = scraped else {
+            panic!("The fixture server failed to scrape");
+        };
+        // S3 servers have no repository list, and no geo API.
+        assert_eq!(scraped.backend_detected, ServerBackendType::S3);
+        assert_eq!(scraped.repositories.len(), 1);
+        assert_eq!(scraped.repositories[0].name, "software.eessi.io");
+        assert!(scraped.geoapi.response.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_scrape_from_server_type_mismatch() {
+        let origin = serve_fixture().await;
+        let server = server(ServerType::Stratum0, ServerBackendType::CVMFS);
+
+        let scraped = scrape_from(
+            &reqwest::Client::new(),
+            &origin,
+            &server,
+            vec![],
+            &[],
+            false,
+            4,
+        )
+        .await;
+
+        match scraped {
+            Err(e) => assert!(e.to_string().contains("replicas were found"), "{}", e),
+            Ok(_) => panic!("A stratum0 with replicas was scraped"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scrape_from_missing_repository() {
+        let origin = serve_fixture().await;
+        let server = server(ServerType::Stratum1, ServerBackendType::CVMFS);
+
+        let scraped = scrape_from(
+            &reqwest::Client::new(),
+            &origin,
+            &server,
+            vec!["missing.eessi.io".to_string()],
+            &[],
+            false,
+            4,
+        )
+        .await;
+
+        assert!(scraped.is_err());
+    }
+}
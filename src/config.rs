@@ -1,16 +1,23 @@
-use once_cell::sync::OnceCell;
+use chrono::{DateTime, Utc};
+use log::warn;
+use reqwest::Url;
 use serde::{Deserialize, Serialize};
-use std::fs::File;
-use std::io::BufReader;
-use std::sync::RwLock;
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
 
+use crate::dependencies::Durability;
+use crate::logging::LogFormat;
 use crate::models::Status;
+use crate::prometheus::{is_valid_label_name, is_valid_metric_name};
 
-use cvmfs_server_scraper::{Server, ServerBackendType};
+use cvmfs_server_scraper::{Hostname, Server, ServerBackendType, ServerType};
 
 #[derive(Debug)]
 pub struct ConfigManager {
-    pub config: RwLock<ConfigFile>,
+    /// Shared with the runs that got it from `get_config`, and replaced as a whole on reload.
+    pub config: RwLock<Arc<ConfigFile>>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -20,23 +27,1365 @@ pub struct ConfigSection {
     pub contact_email: String,
     pub repo_url: String,
     pub repo_url_text: String,
+    /// A banner at the top of the page, in markdown. An announcement file takes precedence.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub announcement: Option<String>,
+    #[serde(default)]
+    pub announcement_severity: AnnouncementSeverity,
+    /// A directory of templates that replace the built-in ones, relative to the configuration
+    /// file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template_dir: Option<PathBuf>,
+    /// Fail a render that refers to undefined variables, rather than warning about them.
+    #[serde(default)]
+    pub strict_templates: bool,
+    /// Site specific values for the templates, such as a Grafana URL, as `data.extra`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub extra: BTreeMap<String, serde_json::Value>,
+    /// Links shown in a navigation bar under the header, such as the documentation.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub links: Vec<Link>,
+    /// The language of the page, a built-in locale or one in the template directory.
+    #[serde(default = "crate::locale::default_locale")]
+    pub locale: String,
+    /// Reload the page in the browser this often.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_seconds: Option<u64>,
+    /// Mark the data on the page as stale once it is this old, by default after three refreshes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stale_after_seconds: Option<u64>,
+    /// Embed the status, as in status.json, in the pages for scripts on the page.
+    #[serde(default)]
+    pub embed_data: bool,
+    /// Warn when the embedded status is larger than this.
+    #[serde(default = "default_embed_data_warn_bytes")]
+    pub embed_data_warn_bytes: usize,
+    /// Collapse the whitespace and remove the comments of the rendered HTML pages.
+    #[serde(default)]
+    pub minify_html: bool,
+    /// Also write the stylesheets and scripts under names with a hash of their contents, which
+    /// the pages load, so caches pick up a change right away.
+    #[serde(default)]
+    pub hashed_assets: bool,
+    /// Delete the hashed stylesheets and scripts this many runs after they were replaced.
+    #[serde(default = "default_hashed_assets_keep_runs")]
+    pub hashed_assets_keep_runs: usize,
+    /// The absolute URL the page is published at, for the link previews of chat applications.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+    /// A line about the page for the link previews.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// The image of the link previews, absolute or relative to `base_url`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub og_image: Option<String>,
+    /// A directory of stylesheets, scripts, fonts and images to write to the destination
+    /// rather than the built-in ones, relative to the configuration file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resources_dir: Option<PathBuf>,
+    /// Link to the files in `resources_dir` from the destination, rather than copy them.
+    #[serde(default)]
+    pub resources_link: bool,
+    /// Glob patterns of the resources not to write, such as `webfonts/**`, relative to the
+    /// destination.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude_resources: Vec<String>,
+    /// Whether to sync the files written, and their directories, so they survive a crash.
+    #[serde(default)]
+    pub durability: Durability,
+    /// The permissions of the files written for the first time, in octal such as `"0644"`.
+    /// A file that is replaced keeps its permissions.
+    #[serde(default = "default_output_file_mode", with = "octal_mode")]
+    pub output_file_mode: u32,
+}
+
+fn default_embed_data_warn_bytes() -> usize {
+    1_000_000
+}
+
+fn default_hashed_assets_keep_runs() -> usize {
+    10
+}
+
+fn default_output_file_mode() -> u32 {
+    0o644
+}
+
+/// File permissions, written in octal in a string, as JSON has no octal numbers.
+mod octal_mode {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(mode: &u32, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{:04o}", mode))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        u32::from_str_radix(&s, 8)
+            .ok()
+            .filter(|mode| *mode <= 0o7777)
+            .ok_or_else(|| de::Error::custom(format!("Invalid file mode {:?}", s)))
+    }
+}
+
+/// How many refreshes of the page may be missed before its data is marked as stale.
+const STALE_AFTER_REFRESHES: u64 = 3;
+
+impl ConfigSection {
+    /// How old the data on the page may be before it is marked as stale, if it is marked at all.
+    pub fn stale_after_seconds(&self) -> Option<u64> {
+        self.stale_after_seconds.or(self
+            .refresh_seconds
+            .map(|refresh| refresh * STALE_AFTER_REFRESHES))
+    }
+
+    /// The absolute URL of the image of the link previews.
+    pub fn og_image_url(&self) -> Option<String> {
+        let image = self.og_image.as_ref()?;
+        let base = Url::parse(self.base_url.as_ref()?).ok()?;
+        base.join(image).ok().map(String::from)
+    }
+
+    /// `base_url` is needed for link previews, and must be absolute.
+    fn validate_open_graph(&self) -> Result<(), String> {
+        let base_url = match &self.base_url {
+            Some(base_url) => base_url,
+            None if self.description.is_some() || self.og_image.is_some() => {
+                return Err(
+                    "meta.base_url is required with meta.description or meta.og_image".to_string(),
+                )
+            }
+            None => return Ok(()),
+        };
+        match Url::parse(base_url) {
+            Ok(url) if ["http", "https"].contains(&url.scheme()) => Ok(()),
+            _ => Err(format!(
+                "meta.base_url must be an absolute http(s) URL, got {:?}",
+                base_url
+            )),
+        }
+    }
+}
+
+/// A link in the navigation bar of the page.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Link {
+    pub text: String,
+    pub url: String,
+    /// Font Awesome classes of an icon in front of the text, such as `fas fa-book`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+}
+
+impl Link {
+    fn validate(&self) -> Result<(), String> {
+        if self.text.trim().is_empty() {
+            return Err(format!("link to {:?} has no text", self.url));
+        }
+        if self.url.trim().is_empty() {
+            return Err(format!("link {:?} has no url", self.text));
+        }
+        if self
+            .icon
+            .as_ref()
+            .is_some_and(|icon| icon.trim().is_empty())
+        {
+            return Err(format!("link {:?} has an empty icon", self.text));
+        }
+        Ok(())
+    }
+}
+
+/// The color of the announcement banner.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AnnouncementSeverity {
+    #[default]
+    Info,
+    Warning,
+    Critical,
 }
 
 fn scrape_only_explicit_repositories() -> bool {
     false
 }
 
+fn default_geodb_max_age_days() -> i64 {
+    30
+}
+
+fn default_missing_reference_status() -> Status {
+    Status::WARNING
+}
+
+fn default_strict_validation() -> bool {
+    true
+}
+
+/// A server entry in the configuration file.
+///
+/// The scraper's own server definition is flattened into the entry, so the keys `hostname`,
+/// `server_type` and `backend_type` live next to the status page specific keys.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ServerConfig {
+    #[serde(flatten)]
+    pub server: Server,
+    /// The server is also served over HTTPS, check its certificate.
+    #[serde(default)]
+    pub https: bool,
+    /// Connect to this address (IP or alternate hostname) instead of the hostname.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolve_to: Option<String>,
+    /// The members (IPs or hostnames) of a load balanced service behind the hostname.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub members: Vec<String>,
+    /// How many members must be healthy for the service to be healthy, defaults to all.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quorum: Option<usize>,
+}
+
+impl ServerConfig {
+    /// The parsed `resolve_to` address, if any.
+    pub fn resolve_to_address(&self) -> Result<Option<String>, String> {
+        self.resolve_to.as_deref().map(parse_address).transpose()
+    }
+
+    /// The parsed addresses of the members of a load balanced service.
+    pub fn member_addresses(&self) -> Result<Vec<String>, String> {
+        self.members.iter().map(|m| parse_address(m)).collect()
+    }
+
+    /// Check the combination of `resolve_to`, `members` and `quorum`.
+    pub fn validate_addresses(&self) -> Result<(), String> {
+        self.resolve_to_address()?;
+        self.member_addresses()?;
+
+        if !self.members.is_empty() && self.resolve_to.is_some() {
+            return Err("members and resolve_to cannot be combined".to_string());
+        }
+
+        match self.quorum {
+            Some(_) if self.members.is_empty() => {
+                Err("quorum is only valid together with members".to_string())
+            }
+            Some(quorum) if quorum == 0 || quorum > self.members.len() => Err(format!(
+                "quorum must be between 1 and the number of members ({})",
+                self.members.len()
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Parse an address to connect to, either an IP address or a hostname. IPv6 addresses may be
+/// written in brackets.
+fn parse_address(address: &str) -> Result<String, String> {
+    let unbracketed = address
+        .strip_prefix('[')
+        .and_then(|a| a.strip_suffix(']'))
+        .unwrap_or(address);
+    let looks_like_ip =
+        unbracketed.contains(':') || unbracketed.chars().all(|c| c.is_ascii_digit() || c == '.');
+
+    if looks_like_ip {
+        return unbracketed
+            .parse::<IpAddr>()
+            .map(|ip| ip.to_string())
+            .map_err(|_| format!("{}: invalid IP address", address));
+    }
+
+    Hostname::try_from(address)
+        .map(|hostname| hostname.to_string())
+        .map_err(|e| format!("{}: {}", address, e))
+}
+
+/// Is a proxy configured through the environment? Proxies make `resolve_to` ineffective.
+fn proxy_configured() -> bool {
+    ["http_proxy", "HTTP_PROXY", "all_proxy", "ALL_PROXY"]
+        .iter()
+        .any(|var| std::env::var(var).is_ok_and(|v| !v.is_empty()))
+}
+
+/// Thresholds (in days) for TLS certificate expiry of servers served over HTTPS.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct CertificateExpiryConfig {
+    #[serde(default = "default_cert_warning_days")]
+    pub warning_days: i64,
+    #[serde(default = "default_cert_failed_days")]
+    pub failed_days: i64,
+}
+
+fn default_cert_warning_days() -> i64 {
+    14
+}
+
+fn default_cert_failed_days() -> i64 {
+    3
+}
+
+/// The stage of an incident, as in the notices of most status pages.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum IncidentStatus {
+    Investigating,
+    Identified,
+    Monitoring,
+    Resolved,
+}
+
+/// A human-written notice about an incident, shown on the page.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Incident {
+    pub id: String,
+    pub title: String,
+    /// Markdown, rendered to HTML on the page.
+    #[serde(default)]
+    pub body: String,
+    pub status: IncidentStatus,
+    pub started_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolved_at: Option<DateTime<Utc>>,
+    /// The affected components, such as `stratum1` or a repository.
+    #[serde(default)]
+    pub components: Vec<String>,
+}
+
+impl Incident {
+    pub fn is_resolved(&self) -> bool {
+        self.status == IncidentStatus::Resolved || self.resolved_at.is_some()
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        match self.resolved_at {
+            Some(resolved_at) if resolved_at < self.started_at => Err(format!(
+                "incident {} is resolved at {} before it started at {}",
+                self.id, resolved_at, self.started_at
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Retention of the history of runs, and how uptime is computed from it.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct HistoryConfig {
+    #[serde(default = "default_history_max_age_days")]
+    pub max_age_days: i64,
+    /// The windows, in days, that uptime is shown for.
+    #[serde(default = "default_uptime_days")]
+    pub uptime_days: Vec<i64>,
+    /// How many days the daily status bars cover.
+    #[serde(default = "default_daily_days")]
+    pub daily_days: i64,
+    /// How long a status holds without a next run, beyond which it is unknown.
+    #[serde(default = "default_max_gap_minutes")]
+    pub max_gap_minutes: i64,
+    /// Count MAINTENANCE as unknown rather than as down.
+    #[serde(default)]
+    pub exclude_maintenance: bool,
+}
+
+fn default_history_max_age_days() -> i64 {
+    90
+}
+
+fn default_uptime_days() -> Vec<i64> {
+    vec![7, 30, 90]
+}
+
+fn default_daily_days() -> i64 {
+    90
+}
+
+fn default_max_gap_minutes() -> i64 {
+    60
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        HistoryConfig {
+            max_age_days: default_history_max_age_days(),
+            uptime_days: default_uptime_days(),
+            daily_days: default_daily_days(),
+            max_gap_minutes: default_max_gap_minutes(),
+            exclude_maintenance: false,
+        }
+    }
+}
+
+/// The states of a Nagios or Icinga check, with their exit codes.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum NagiosCode {
+    Ok = 0,
+    Warning = 1,
+    Critical = 2,
+    Unknown = 3,
+}
+
+impl NagiosCode {
+    pub fn label(&self) -> &'static str {
+        match self {
+            NagiosCode::Ok => "OK",
+            NagiosCode::Warning => "WARNING",
+            NagiosCode::Critical => "CRITICAL",
+            NagiosCode::Unknown => "UNKNOWN",
+        }
+    }
+}
+
+/// The state the `check` subcommand reports for each overall status.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct CheckConfig {
+    #[serde(default = "nagios_ok")]
+    pub ok: NagiosCode,
+    #[serde(default = "nagios_critical")]
+    pub degraded: NagiosCode,
+    #[serde(default = "nagios_warning")]
+    pub warning: NagiosCode,
+    #[serde(default = "nagios_critical")]
+    pub failed: NagiosCode,
+    #[serde(default = "nagios_ok")]
+    pub maintenance: NagiosCode,
+}
+
+fn nagios_ok() -> NagiosCode {
+    NagiosCode::Ok
+}
+
+fn nagios_warning() -> NagiosCode {
+    NagiosCode::Warning
+}
+
+fn nagios_critical() -> NagiosCode {
+    NagiosCode::Critical
+}
+
+impl Default for CheckConfig {
+    fn default() -> Self {
+        CheckConfig {
+            ok: nagios_ok(),
+            degraded: nagios_critical(),
+            warning: nagios_warning(),
+            failed: nagios_critical(),
+            maintenance: nagios_ok(),
+        }
+    }
+}
+
+impl CheckConfig {
+    pub fn code(&self, status: Status) -> NagiosCode {
+        match status {
+            Status::OK => self.ok,
+            Status::DEGRADED => self.degraded,
+            Status::WARNING => self.warning,
+            Status::FAILED => self.failed,
+            Status::MAINTENANCE => self.maintenance,
+        }
+    }
+}
+
+/// What of the configuration and the servers is published in status.json and on the page.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct PrivacyConfig {
+    /// Embed the configuration in status.json.
+    #[serde(default = "expose_config_by_default")]
+    pub expose_config: bool,
+    /// Servers that are scraped and in the metrics, but left out of status.json, the page and
+    /// the embedded configuration, such as internal sync servers.
+    #[serde(default)]
+    pub hidden_servers: Vec<String>,
+}
+
+fn expose_config_by_default() -> bool {
+    true
+}
+
+impl Default for PrivacyConfig {
+    fn default() -> Self {
+        PrivacyConfig {
+            expose_config: expose_config_by_default(),
+            hidden_servers: vec![],
+        }
+    }
+}
+
+/// Which of the status page and the JSON status are written, for deployments that only want
+/// the metrics or only the JSON.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct OutputConfig {
+    /// The page, the repository pages and the resources they need.
+    #[serde(default = "write_output_by_default")]
+    pub html: bool,
+    /// status.json, summary.json and the repository JSON documents.
+    #[serde(default = "write_output_by_default")]
+    pub json: bool,
+}
+
+fn write_output_by_default() -> bool {
+    true
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        OutputConfig {
+            html: write_output_by_default(),
+            json: write_output_by_default(),
+        }
+    }
+}
+
+/// Who a page is for, which decides what of the status its template gets.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+    /// The same data as status.json, without the hidden servers.
+    #[default]
+    Public,
+    /// Also the hidden servers, and the whole configuration as `config`, for an operations
+    /// page that isn't published.
+    Internal,
+}
+
+/// A page rendered from a template into the destination, after every run.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct PageConfig {
+    /// The name of the template, such as `status.html`.
+    pub template: String,
+    /// Relative to the destination.
+    pub output_file: PathBuf,
+    #[serde(default)]
+    pub visibility: Visibility,
+}
+
+impl PageConfig {
+    fn validate(&self) -> Result<(), String> {
+        if self.template.is_empty() {
+            return Err(format!("page {:?} has no template", self.output_file));
+        }
+        let outside = self
+            .output_file
+            .components()
+            .any(|c| !matches!(c, std::path::Component::Normal(_)));
+        if self.output_file.as_os_str().is_empty() || outside {
+            return Err(format!(
+                "page {} has output_file {:?}, which must be a path in the destination",
+                self.template, self.output_file
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Pruning of the history of status changes, and how much of it the page shows.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct EventsConfig {
+    #[serde(default = "default_max_events")]
+    pub max_events: usize,
+    #[serde(default = "default_events_max_age_days")]
+    pub max_age_days: i64,
+    /// How many of the most recent events are shown on the page.
+    #[serde(default = "default_recent_events")]
+    pub recent: usize,
+}
+
+fn default_max_events() -> usize {
+    1000
+}
+
+fn default_events_max_age_days() -> i64 {
+    90
+}
+
+fn default_recent_events() -> usize {
+    10
+}
+
+impl Default for EventsConfig {
+    fn default() -> Self {
+        EventsConfig {
+            max_events: default_max_events(),
+            max_age_days: default_events_max_age_days(),
+            recent: default_recent_events(),
+        }
+    }
+}
+
+fn default_scrape_duration_buckets() -> Vec<f64> {
+    vec![0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0]
+}
+
+/// Naming and bucketing of the exported metrics.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct MetricsConfig {
+    /// Prepended to the name of every metric, such as `eessi_statuspage_`.
+    #[serde(default)]
+    pub prefix: String,
+    /// Labels added to every metric. A metric's own label wins over a constant one.
+    #[serde(default)]
+    pub constant_labels: BTreeMap<String, String>,
+    /// Upper bounds, in seconds, of the buckets of the scrape duration histogram.
+    #[serde(default = "default_scrape_duration_buckets")]
+    pub scrape_duration_buckets: Vec<f64>,
+    /// Whether samples carry the time of the run. Unset, the metrics file gets timestamps and
+    /// the `/metrics` endpoint doesn't. Pushes to the Pushgateway never do.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub include_timestamps: Option<bool>,
+    /// The metrics file, relative to the destination directory or absolute, such as a
+    /// `.prom` file in the directory of the node_exporter textfile collector. Setting it
+    /// enables the file, which is `metrics` in the destination with `--prometheus-metrics`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<PathBuf>,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        MetricsConfig {
+            prefix: String::new(),
+            constant_labels: BTreeMap::new(),
+            scrape_duration_buckets: default_scrape_duration_buckets(),
+            include_timestamps: None,
+            output: None,
+        }
+    }
+}
+
+impl MetricsConfig {
+    /// Check that the prefixed metric names and the label names are valid. As the metric names
+    /// themselves are valid, they remain valid after prefixing if the prefix is valid as a name.
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.prefix.is_empty() && !is_valid_metric_name(&self.prefix) {
+            return Err(format!(
+                "metric prefix '{}' doesn't produce valid metric names",
+                self.prefix
+            ));
+        }
+
+        let invalid: Vec<&str> = self
+            .constant_labels
+            .keys()
+            .filter(|name| !is_valid_label_name(name))
+            .map(|name| name.as_str())
+            .collect();
+        if !invalid.is_empty() {
+            return Err(format!("invalid label names {}", invalid.join(", ")));
+        }
+        Ok(())
+    }
+}
+
+/// Push the metrics to a Prometheus Pushgateway after each run.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct PushGatewayConfig {
+    /// The base URL of the Pushgateway, such as `http://pushgateway:9091`.
+    pub url: String,
+    #[serde(default = "default_push_gateway_job")]
+    pub job: String,
+    /// Grouping labels added to the push URL after the job.
+    #[serde(default)]
+    pub grouping: BTreeMap<String, String>,
+    /// Environment variables holding the basic auth username and password.
+    #[serde(default)]
+    pub username_env: Option<String>,
+    #[serde(default)]
+    pub password_env: Option<String>,
+    #[serde(default = "default_push_gateway_timeout_seconds")]
+    pub timeout_seconds: u64,
+}
+
+fn default_push_gateway_job() -> String {
+    "cvmfs_status_page".to_string()
+}
+
+fn default_push_gateway_timeout_seconds() -> u64 {
+    10
+}
+
+impl PushGatewayConfig {
+    /// A configuration with only the URL set, for --push-gateway-url without a config section.
+    pub fn with_url(url: &str) -> Self {
+        PushGatewayConfig {
+            url: url.to_string(),
+            job: default_push_gateway_job(),
+            grouping: BTreeMap::new(),
+            username_env: None,
+            password_env: None,
+            timeout_seconds: default_push_gateway_timeout_seconds(),
+        }
+    }
+}
+
+/// Write the metrics as InfluxDB line protocol after each run, to a file and/or an HTTP write
+/// endpoint.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct InfluxConfig {
+    /// The file to write the line protocol to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<PathBuf>,
+    /// The write endpoint, such as `http://influxdb:8086/api/v2/write?org=eessi&bucket=status`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Environment variable holding the API token.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_env: Option<String>,
+    #[serde(default = "default_influx_timeout_seconds")]
+    pub timeout_seconds: u64,
+}
+
+fn default_influx_timeout_seconds() -> u64 {
+    10
+}
+
+impl InfluxConfig {
+    /// A configuration with only the output file set, for --influx-output without a config
+    /// section.
+    pub fn with_output(output: PathBuf) -> Self {
+        InfluxConfig {
+            output: Some(output),
+            url: None,
+            token_env: None,
+            timeout_seconds: default_influx_timeout_seconds(),
+        }
+    }
+}
+
+/// Send the metrics to a Graphite server after each run, in the plaintext protocol.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct GraphiteConfig {
+    pub host: String,
+    #[serde(default = "default_graphite_port")]
+    pub port: u16,
+    /// Prepended to the path of every metric, such as `eessi`.
+    #[serde(default)]
+    pub prefix: String,
+    #[serde(default)]
+    pub protocol: GraphiteProtocol,
+    #[serde(default = "default_graphite_timeout_seconds")]
+    pub timeout_seconds: u64,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum GraphiteProtocol {
+    #[default]
+    Tcp,
+    Udp,
+}
+
+fn default_graphite_port() -> u16 {
+    2003
+}
+
+fn default_graphite_timeout_seconds() -> u64 {
+    10
+}
+
+impl GraphiteConfig {
+    /// A configuration with only the host set.
+    #[cfg(test)]
+    pub fn with_host(host: &str) -> Self {
+        GraphiteConfig {
+            host: host.to_string(),
+            port: default_graphite_port(),
+            prefix: String::new(),
+            protocol: GraphiteProtocol::default(),
+            timeout_seconds: default_graphite_timeout_seconds(),
+        }
+    }
+}
+
+/// Send the metrics as gauges to a StatsD or DogStatsD agent over UDP after each run.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct StatsdConfig {
+    /// The address of the agent, such as `127.0.0.1:8125`.
+    pub address: String,
+    /// Prepended to the name of every gauge, such as `eessi`.
+    #[serde(default)]
+    pub prefix: String,
+    #[serde(default)]
+    pub tag_style: TagStyle,
+}
+
+/// How the labels of a metric are sent to StatsD.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TagStyle {
+    /// `name:value|g|#key:value`
+    #[default]
+    Datadog,
+    /// `name#key=value:value|g`
+    Librato,
+    /// No tags, the label values are part of the name.
+    None,
+}
+
+/// Post a Slack message when the status of a component changes.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct SlackConfig {
+    /// Environment variable holding the incoming webhook URL, which is a secret.
+    pub webhook_url_env: String,
+    /// Post to this channel instead of the default channel of the webhook.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel: Option<String>,
+    /// Only post about components changing to one of these statuses, such as `["FAILED",
+    /// "OK"]` for failures and recoveries. All statuses by default.
+    #[serde(default = "Status::all")]
+    pub statuses: Vec<Status>,
+    /// The public URL of the status page, linked from the messages.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status_page_url: Option<String>,
+    #[serde(default = "default_slack_timeout_seconds")]
+    pub timeout_seconds: u64,
+}
+
+fn default_slack_timeout_seconds() -> u64 {
+    10
+}
+
+/// Trigger a PagerDuty incident when a component fails, and resolve it when the component
+/// is OK again.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct PagerDutyConfig {
+    /// Environment variable holding the integration key of the service.
+    pub routing_key_env: String,
+    /// The components to page for.
+    #[serde(default = "default_pagerduty_components")]
+    pub components: Vec<String>,
+    #[serde(default = "default_pagerduty_url")]
+    pub url: String,
+    /// How often an event is retried when PagerDuty is rate limiting or unavailable.
+    #[serde(default = "default_pagerduty_retries")]
+    pub retries: u32,
+    #[serde(default = "default_pagerduty_timeout_seconds")]
+    pub timeout_seconds: u64,
+}
+
+fn default_pagerduty_components() -> Vec<String> {
+    vec!["overall".to_string()]
+}
+
+fn default_pagerduty_url() -> String {
+    "https://events.pagerduty.com/v2/enqueue".to_string()
+}
+
+fn default_pagerduty_retries() -> u32 {
+    3
+}
+
+fn default_pagerduty_timeout_seconds() -> u64 {
+    10
+}
+
+/// Send an email when components change to or from a severe status.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct EmailConfig {
+    /// The SMTP server.
+    pub host: String,
+    #[serde(default = "default_email_port")]
+    pub port: u16,
+    #[serde(default)]
+    pub tls: SmtpTls,
+    /// Environment variables holding the SMTP username and password.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username_env: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_env: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+    /// Only email about components changing to or from this status or a more severe one.
+    #[serde(default = "default_email_min_status")]
+    pub min_status: Status,
+    #[serde(default = "default_email_timeout_seconds")]
+    pub timeout_seconds: u64,
+}
+
+/// How the connection to the SMTP server is encrypted.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpTls {
+    /// Upgrade a plain connection, usually on port 587.
+    #[default]
+    Starttls,
+    /// TLS from the start, usually on port 465.
+    Implicit,
+    /// Unencrypted, for a relay on localhost.
+    None,
+}
+
+fn default_heartbeat_timeout_seconds() -> u64 {
+    10
+}
+
+fn default_hook_timeout_seconds() -> u64 {
+    60
+}
+
+fn default_email_port() -> u16 {
+    587
+}
+
+fn default_email_min_status() -> Status {
+    Status::FAILED
+}
+
+fn default_email_timeout_seconds() -> u64 {
+    10
+}
+
+/// Limits of the scrape of the servers.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ScrapeConfig {
+    /// Of each request to a server, and of connecting to it.
+    #[serde(default = "default_scrape_timeout_seconds")]
+    pub timeout_seconds: u64,
+    /// Of the whole scrape of a server, after which it is FAILED.
+    #[serde(default = "default_scrape_deadline_seconds")]
+    pub deadline_seconds: u64,
+    /// Maximum number of repositories of a server fetched at once: those of servers with
+    /// `resolve_to`, and those retried one by one after the scrape of a server failed.
+    #[serde(default = "default_scrape_concurrency")]
+    pub concurrency: usize,
+}
+
+fn default_scrape_timeout_seconds() -> u64 {
+    10
+}
+
+fn default_scrape_deadline_seconds() -> u64 {
+    120
+}
+
+fn default_scrape_concurrency() -> usize {
+    8
+}
+
+impl Default for ScrapeConfig {
+    fn default() -> Self {
+        ScrapeConfig {
+            timeout_seconds: default_scrape_timeout_seconds(),
+            deadline_seconds: default_scrape_deadline_seconds(),
+            concurrency: default_scrape_concurrency(),
+        }
+    }
+}
+
+/// Settings for the deep check, which fetches the root catalog referenced by each manifest.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct DeepCheckConfig {
+    /// Deep check all repositories, unless overridden per repository.
+    #[serde(default)]
+    pub enabled: bool,
+    /// Verify the content hash of the fetched catalog, not only the HTTP status.
+    #[serde(default)]
+    pub verify_hash: bool,
+    #[serde(default = "default_deep_check_timeout_seconds")]
+    pub timeout_seconds: u64,
+    /// Maximum number of deep checks in flight at once.
+    #[serde(default = "default_deep_check_concurrency")]
+    pub concurrency: usize,
+}
+
+fn default_deep_check_timeout_seconds() -> u64 {
+    10
+}
+
+fn default_deep_check_concurrency() -> usize {
+    8
+}
+
+impl Default for DeepCheckConfig {
+    fn default() -> Self {
+        DeepCheckConfig {
+            enabled: false,
+            verify_hash: false,
+            timeout_seconds: default_deep_check_timeout_seconds(),
+            concurrency: default_deep_check_concurrency(),
+        }
+    }
+}
+
+/// Gzipped copies of the files in the destination, next to them, and with the `brotli` feature
+/// brotli ones, for web servers that serve those rather than compress on the fly, such as nginx
+/// with `gzip_static` and `brotli_static`.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PrecompressConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// From 1, fastest, to 9, smallest.
+    #[serde(default = "default_precompress_level")]
+    pub level: u32,
+    /// Of the brotli copies, from 0, fastest, to 11, smallest.
+    #[serde(default = "default_precompress_brotli_level")]
+    pub brotli_level: u32,
+    /// Files smaller than this aren't worth compressing.
+    #[serde(default = "default_precompress_min_bytes")]
+    pub min_bytes: u64,
+}
+
+fn default_precompress_level() -> u32 {
+    6
+}
+
+fn default_precompress_brotli_level() -> u32 {
+    11
+}
+
+fn default_precompress_min_bytes() -> u64 {
+    1024
+}
+
+impl Default for PrecompressConfig {
+    fn default() -> Self {
+        PrecompressConfig {
+            enabled: false,
+            level: default_precompress_level(),
+            brotli_level: default_precompress_brotli_level(),
+            min_bytes: default_precompress_min_bytes(),
+        }
+    }
+}
+
+/// How the status of a server is derived from the status of its repositories.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ServerStatusFromRepos {
+    /// The server takes the worst status of its repositories.
+    Worst,
+    /// Repositories that failed to scrape only degrade the server, as long as at least one
+    /// repository was scraped successfully.
+    #[default]
+    DegradedIfPartial,
+}
+
+/// How MAINTENANCE and FAILED combine when statuses are aggregated, such as into the status of
+/// a server from its repositories.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum MaintenanceAggregation {
+    /// MAINTENANCE hides FAILED.
+    #[default]
+    Masks,
+    /// FAILED hides MAINTENANCE.
+    Masked,
+    /// MAINTENANCE hides FAILED, but the page tells how many servers are failing.
+    Alongside,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct LoggingConfig {
+    /// The log format, unless overridden by `--log-format`.
+    #[serde(default)]
+    pub format: LogFormat,
+}
+
+/// Per repository settings, keyed by repository name.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct RepositorySettings {
+    /// Override the global deep check setting for this repository.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deep_check: Option<bool>,
+    /// The fingerprints of the certificates the manifests of this repository may be signed
+    /// with, either one or a list during a rotation. Unpinned if empty.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub expected_cert_fingerprint: Vec<String>,
+}
+
+/// Deserialize a single string, or a list of them.
+fn deserialize_one_or_many<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(value) => vec![value],
+        OneOrMany::Many(values) => values,
+    })
+}
+
+/// Compare certificate fingerprints the way they're written, in either case and with or
+/// without colons between the bytes.
+pub fn normalize_fingerprint(fingerprint: &str) -> String {
+    fingerprint
+        .chars()
+        .filter(|c| *c != ':')
+        .collect::<String>()
+        .to_ascii_lowercase()
+}
+
+impl Default for CertificateExpiryConfig {
+    fn default() -> Self {
+        CertificateExpiryConfig {
+            warning_days: default_cert_warning_days(),
+            failed_days: default_cert_failed_days(),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ConfigFile {
     pub meta: ConfigSection,
-    pub servers: Vec<Server>,
+    pub servers: Vec<ServerConfig>,
     pub repositories: Vec<String>,
     #[serde(default = "scrape_only_explicit_repositories")]
     pub limit_scraping_to_repositories: bool,
     pub ignored_repositories: Vec<String>,
+    #[serde(default = "default_geodb_max_age_days")]
+    pub geodb_max_age_days: i64,
+    #[serde(default)]
+    pub certificate_expiry: CertificateExpiryConfig,
+    #[serde(default)]
+    pub scrape: ScrapeConfig,
+    #[serde(default)]
+    pub deep_check: DeepCheckConfig,
+    #[serde(default)]
+    pub repository_settings: HashMap<String, RepositorySettings>,
+    #[serde(default)]
+    pub server_status_from_repos: ServerStatusFromRepos,
+    #[serde(default)]
+    pub maintenance_aggregation: MaintenanceAggregation,
+    /// The status of a repository that there's no revision to compare with: one the stratum0
+    /// doesn't have, or, without a stratum0, no other stratum1 has.
+    #[serde(default = "default_missing_reference_status")]
+    pub missing_reference_status: Status,
+    /// Repositories whose revision may go backwards, such as ones that are reset at times.
+    #[serde(default)]
+    pub revision_regression_exempt: Vec<String>,
+    /// Show the last known data of a server that fails to scrape, for up to this many minutes.
+    #[serde(default)]
+    pub use_stale_data_minutes: Option<i64>,
+    /// Reject a configuration with duplicate servers, repositories or rules, rather than
+    /// warn about them and keep the first of each.
+    #[serde(default = "default_strict_validation")]
+    pub strict_validation: bool,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub push_gateway: Option<PushGatewayConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub influx: Option<InfluxConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub graphite: Option<GraphiteConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub statsd: Option<StatsdConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slack: Option<SlackConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<EmailConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pagerduty: Option<PagerDutyConfig>,
+    /// Commands run for each component that changes status, in order. Arguments are split
+    /// like a shell does, without expanding anything.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub on_status_change: Vec<String>,
+    #[serde(default = "default_hook_timeout_seconds")]
+    pub on_status_change_timeout_seconds: u64,
+    /// Pinged after every run, with `/fail` appended when the run failed or the overall status
+    /// is FAILED, such as a healthchecks.io check.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub heartbeat_url: Option<String>,
+    #[serde(default = "default_heartbeat_timeout_seconds")]
+    pub heartbeat_timeout_seconds: u64,
+    #[serde(default)]
+    pub events: EventsConfig,
+    #[serde(default)]
+    pub incidents: Vec<Incident>,
+    /// Show an overall status of OK or DEGRADED as WARNING while an incident is unresolved.
+    #[serde(default)]
+    pub incidents_warn: bool,
+    /// The components that get an SVG badge, such as `eessi`, `stratum1` or
+    /// `repo-software.eessi.io`.
+    #[serde(default)]
+    pub badges: Vec<String>,
+    /// The components that get a shields.io endpoint, named like `badges`.
+    #[serde(default)]
+    pub badge_endpoints: Vec<String>,
+    /// The CSS classes of statuses, replacing the built-in Font Awesome ones, such as for the
+    /// icons of a custom theme.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub status_classes: BTreeMap<Status, String>,
+    #[serde(default)]
+    pub history: HistoryConfig,
+    #[serde(default)]
+    pub privacy: PrivacyConfig,
+    #[serde(default)]
+    pub check: CheckConfig,
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    #[serde(default)]
+    pub output: OutputConfig,
+    #[serde(default)]
+    pub precompress: PrecompressConfig,
+    /// The pages rendered after every run, instead of only the status page.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pages: Vec<PageConfig>,
     pub rules: Vec<Rule>,
 }
 
+/// An empty configuration, with the defaults of the optional fields. It stands in for the
+/// configuration of a status.json that doesn't embed it.
+impl Default for ConfigFile {
+    fn default() -> Self {
+        serde_json::from_value(serde_json::json!({
+            "meta": {
+                "title": "",
+                "logging_level": "info",
+                "contact_email": "",
+                "repo_url": "",
+                "repo_url_text": "",
+            },
+            "servers": [],
+            "repositories": [],
+            "ignored_repositories": [],
+            "rules": [],
+        }))
+        .expect("The empty configuration is valid")
+    }
+}
+
+impl ConfigFile {
+    /// The class of every status, the built-in one unless it is in `status_classes`.
+    pub fn effective_status_classes(&self) -> BTreeMap<Status, String> {
+        Status::all()
+            .into_iter()
+            .map(|status| (status, status.class_with_overrides(self).to_string()))
+            .collect()
+    }
+
+    /// Scrape only the given repositories, regardless of the configured ones. An empty list
+    /// leaves the configuration untouched.
+    ///
+    /// Repositories that aren't configured are accepted, with a warning.
+    pub fn restrict_repositories(&mut self, repositories: &[String]) {
+        if repositories.is_empty() {
+            return;
+        }
+
+        for repository in repositories
+            .iter()
+            .filter(|r| !self.repositories.contains(r))
+        {
+            warn!(
+                "Repository {} is not in the configured repositories",
+                repository
+            );
+        }
+
+        self.repositories = repositories.to_vec();
+        self.limit_scraping_to_repositories = true;
+    }
+
+    /// Keep only the servers matching the given hostnames and server types. An empty filter
+    /// matches every server.
+    ///
+    /// Hostnames that aren't configured are an error, which lists the configured hostnames.
+    pub fn retain_servers(
+        &mut self,
+        hostnames: &[String],
+        server_types: &[ServerType],
+    ) -> Result<(), String> {
+        let unknown: Vec<&String> = hostnames
+            .iter()
+            .filter(|h| {
+                !self
+                    .servers
+                    .iter()
+                    .any(|s| s.server.hostname.to_str() == h.as_str())
+            })
+            .collect();
+
+        if !unknown.is_empty() {
+            return Err(format!(
+                "Unknown server(s): {}. Configured servers: {}",
+                unknown
+                    .iter()
+                    .map(|h| h.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                self.servers
+                    .iter()
+                    .map(|s| s.server.hostname.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+
+        self.servers.retain(|s| {
+            (hostnames.is_empty() || hostnames.iter().any(|h| s.server.hostname.to_str() == h))
+                && (server_types.is_empty() || server_types.contains(&s.server.server_type))
+        });
+        Ok(())
+    }
+
+    /// Should the given repository be deep checked?
+    pub fn deep_check_enabled_for(&self, repository: &str) -> bool {
+        self.repository_settings
+            .get(repository)
+            .and_then(|s| s.deep_check)
+            .unwrap_or(self.deep_check.enabled)
+    }
+
+    /// Find the servers (by hostname, in any case), repositories, ignored repositories and
+    /// rules that are listed more than once, and the repositories that are also ignored, all at
+    /// once. With `strict_validation` these are an error, otherwise a warning, and the first of
+    /// each is kept, with a repository that is also ignored kept scraped.
+    pub fn check_duplicates(&mut self) -> Result<(), String> {
+        let mut problems: Vec<String> = Vec::new();
+        for hostname in remove_duplicates(&mut self.servers, |s| {
+            s.server.hostname.to_str().to_lowercase()
+        }) {
+            problems.push(format!("duplicate server {}", hostname));
+        }
+        for repository in remove_duplicates(&mut self.repositories, |r| r.clone()) {
+            problems.push(format!("duplicate repository {}", repository));
+        }
+        for repository in remove_duplicates(&mut self.ignored_repositories, |r| r.clone()) {
+            problems.push(format!("duplicate ignored repository {}", repository));
+        }
+        let repositories = &self.repositories;
+        self.ignored_repositories.retain(|repository| {
+            let both = repositories.contains(repository);
+            if both {
+                problems.push(format!(
+                    "repository {} is both in repositories and ignored_repositories",
+                    repository
+                ));
+            }
+            !both
+        });
+        for id in remove_duplicates(&mut self.rules, |r| r.id.clone()) {
+            problems.push(format!("duplicate rule {}", id));
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else if self.strict_validation {
+            Err(format!("Invalid configuration: {}", problems.join(", ")))
+        } else {
+            for problem in &problems {
+                warn!("Ignoring {} in the configuration", problem);
+            }
+            Ok(())
+        }
+    }
+
+    /// The certificate fingerprints the given repository is pinned to, empty if it isn't.
+    pub fn accepted_cert_fingerprints(&self, repository: &str) -> &[String] {
+        self.repository_settings
+            .get(repository)
+            .map_or(&[], |s| s.expected_cert_fingerprint.as_slice())
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Rule {
     pub id: String,
@@ -50,46 +1399,211 @@ pub struct Condition {
     pub when: String,
 }
 
+/// Keep the first of the `items` with the same `key`, returning the keys of the ones removed,
+/// each once.
+fn remove_duplicates<T>(items: &mut Vec<T>, key: impl Fn(&T) -> String) -> Vec<String> {
+    let mut seen = std::collections::HashSet::new();
+    let mut duplicates: Vec<String> = Vec::new();
+    items.retain(|item| {
+        let key = key(item);
+        if seen.insert(key.clone()) {
+            return true;
+        }
+        if !duplicates.contains(&key) {
+            duplicates.push(key);
+        }
+        false
+    });
+    duplicates
+}
+
+/// Split a command into the program and its arguments, like a shell does.
+pub fn split_command(command: &str) -> Result<Vec<String>, String> {
+    match shlex::split(command) {
+        Some(args) if !args.is_empty() => Ok(args),
+        Some(_) => Err("empty command".to_string()),
+        None => Err(format!("unbalanced quotes in {}", command)),
+    }
+}
+
 impl ConfigManager {
-    pub fn new(filename: &str) -> Self {
+    pub fn new(filename: &str, overrides: &[String]) -> Self {
         ConfigManager {
-            config: read_config(filename),
+            config: read_config(filename, overrides),
         }
         .validate_config()
     }
 
+    /// Load a configuration, apply the `key=value` overrides of --set and validate the result,
+    /// returning an error instead of panicking.
+    pub fn load(filename: &str, overrides: &[String]) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(filename)
+            .map_err(|e| format!("Failed to open configuration file {}: {}", filename, e))?;
+        let config: ConfigFile = ConfigFormat::from_path(filename)
+            .parse(&contents)
+            .map_err(|e| format!("Unable to parse configuration file {}: {}", filename, e))?;
+        let mut config = apply_overrides(config, overrides)?;
+        config.check_duplicates()?;
+        let manager = ConfigManager {
+            config: RwLock::new(Arc::new(config)),
+        };
+        manager.check_config()?;
+        Ok(manager)
+    }
+
+    /// The configuration as JSON, with the class of every status.
     pub fn as_json(&self) -> String {
-        serde_json::to_string_pretty(&*self.config.read().unwrap()).unwrap()
+        let mut config = ConfigFile::clone(&self.get_config());
+        config.status_classes = config.effective_status_classes();
+        serde_json::to_string_pretty(&config).unwrap()
     }
 
-    fn validate_config(self) -> Self {
-        // Clone or copy the necessary data while holding the lock
-        let config_data = {
-            let config = self.config.read().unwrap();
-            config.clone()
-        };
+    fn validate_config(self) -> Self {
+        if let Err(e) = self.check_config() {
+            panic!("{}", e);
+        }
+        self
+    }
+
+    fn check_config(&self) -> Result<(), String> {
+        let config_data = self.get_config();
+
+        let s3_servers: Vec<&Server> = config_data
+            .servers
+            .iter()
+            .map(|s| &s.server)
+            .filter(|s| s.backend_type == ServerBackendType::S3)
+            .collect();
+
+        if !s3_servers.is_empty() && config_data.repositories.is_empty() {
+            return Err(format!(
+                "{} uses S3 as backend, but no repositories are explicitly provided to scrape",
+                s3_servers
+                    .iter()
+                    .map(|s| s.hostname.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ));
+        }
+
+        let invalid_addresses: Vec<String> = config_data
+            .servers
+            .iter()
+            .filter_map(|s| {
+                s.validate_addresses()
+                    .err()
+                    .map(|e| format!("{} ({})", s.server.hostname, e))
+            })
+            .collect();
+
+        if !invalid_addresses.is_empty() {
+            return Err(format!(
+                "Invalid server addresses for {}",
+                invalid_addresses.join(", ")
+            ));
+        }
+
+        config_data
+            .metrics
+            .validate()
+            .map_err(|e| format!("Invalid metrics configuration: {}", e))?;
+
+        for incident in &config_data.incidents {
+            incident.validate()?;
+        }
+        let mut ids: Vec<&str> = config_data
+            .incidents
+            .iter()
+            .map(|i| i.id.as_str())
+            .collect();
+        ids.sort();
+        if let Some(id) = ids.windows(2).find(|w| w[0] == w[1]) {
+            return Err(format!("Duplicate incident id {}", id[0]));
+        }
+
+        if let Some(name) = config_data
+            .badges
+            .iter()
+            .chain(&config_data.badge_endpoints)
+            .find(|name| !crate::badges::is_valid_name(name))
+        {
+            return Err(format!(
+                "Invalid badge {}, expected one of {} or repo-<repository>",
+                name,
+                crate::badges::COMPONENTS.join(", ")
+            ));
+        }
+
+        if config_data.scrape.timeout_seconds == 0 {
+            return Err("scrape.timeout_seconds must be at least 1".to_string());
+        }
+        if config_data.scrape.deadline_seconds == 0 {
+            return Err("scrape.deadline_seconds must be at least 1".to_string());
+        }
+
+        if config_data.meta.refresh_seconds == Some(0) {
+            return Err("meta.refresh_seconds must be at least 1".to_string());
+        }
+        if config_data.meta.stale_after_seconds == Some(0) {
+            return Err("meta.stale_after_seconds must be at least 1".to_string());
+        }
+
+        config_data.meta.validate_open_graph()?;
+        crate::dependencies::exclude_set(&config_data.meta.exclude_resources)
+            .map_err(|e| format!("Invalid meta.exclude_resources: {}", e))?;
+        if !(1..=9).contains(&config_data.precompress.level) {
+            return Err("precompress.level must be from 1 to 9".to_string());
+        }
+        if config_data.precompress.brotli_level > 11 {
+            return Err("precompress.brotli_level must be from 0 to 11".to_string());
+        }
+
+        for link in &config_data.meta.links {
+            link.validate()
+                .map_err(|e| format!("Invalid meta.links: {}", e))?;
+        }
+
+        for page in &config_data.pages {
+            page.validate()
+                .map_err(|e| format!("Invalid pages: {}", e))?;
+        }
+        let mut output_files: Vec<&PathBuf> =
+            config_data.pages.iter().map(|p| &p.output_file).collect();
+        output_files.sort();
+        if let Some(file) = output_files.windows(2).find(|w| w[0] == w[1]) {
+            return Err(format!("Duplicate page output_file {:?}", file[0]));
+        }
 
-        let s3_servers: Vec<&Server> = config_data
-            .servers
-            .iter()
-            .filter(|s| s.backend_type == ServerBackendType::S3)
-            .collect();
+        if let Some(hostname) = config_data.privacy.hidden_servers.iter().find(|h| {
+            !config_data
+                .servers
+                .iter()
+                .any(|s| s.server.hostname.to_str() == h.as_str())
+        }) {
+            return Err(format!("Unknown hidden server {}", hostname));
+        }
 
-        if !s3_servers.is_empty() && config_data.repositories.is_empty() {
-            panic!(
-                "{} uses S3 as backend, but no repositories are explicitly provided to scrape",
-                s3_servers
-                    .iter()
-                    .map(|s| s.hostname.to_string())
-                    .collect::<Vec<String>>()
-                    .join(", ")
-            );
+        for command in &config_data.on_status_change {
+            split_command(command).map_err(|e| format!("Invalid on_status_change: {}", e))?;
         }
 
-        self
+        if proxy_configured() && config_data.servers.iter().any(|s| s.resolve_to.is_some()) {
+            warn!("A proxy is configured in the environment, resolve_to addresses will be contacted through the proxy");
+        }
+
+        Ok(())
+    }
+
+    /// Replace the configuration with the contents of `filename`, with the same `overrides`.
+    /// The current configuration is kept if the new one fails to load or validate.
+    pub fn reload(&self, filename: &str, overrides: &[String]) -> Result<(), String> {
+        let reloaded = ConfigManager::load(filename, overrides)?;
+        *self.config.write().unwrap() = reloaded.config.into_inner().unwrap();
+        Ok(())
     }
 
-    pub fn get_config(&self) -> ConfigFile {
+    /// The current configuration, which a reload doesn't change.
+    pub fn get_config(&self) -> Arc<ConfigFile> {
         self.config.read().unwrap().clone()
     }
 
@@ -104,31 +1618,172 @@ impl ConfigManager {
     }
 }
 
-static CONFIG_MANAGER: OnceCell<ConfigManager> = OnceCell::new();
+/// The logging settings of a configuration file, read before the configuration is loaded.
+#[derive(Debug, Default, PartialEq)]
+pub struct EarlyLogging {
+    pub format: Option<LogFormat>,
+    pub level: Option<String>,
+}
+
+/// Read only the logging settings from a configuration file, so logging can be set up before
+/// the configuration is loaded (and validated, which may log). Problems with the file are left
+/// for the full load to report.
+pub fn read_logging_settings(filename: &str) -> EarlyLogging {
+    let Some(config) = std::fs::read_to_string(filename).ok().and_then(|contents| {
+        ConfigFormat::from_path(filename)
+            .parse::<serde_json::Value>(&contents)
+            .ok()
+    }) else {
+        return EarlyLogging::default();
+    };
+
+    EarlyLogging {
+        format: config
+            .pointer("/logging/format")
+            .and_then(|f| serde_json::from_value(f.clone()).ok()),
+        level: config
+            .pointer("/meta/logging_level")
+            .and_then(|l| l.as_str())
+            .map(|l| l.to_string()),
+    }
+}
+
+/// Parse the value of a --set override: JSON if it is valid JSON, a list of the comma
+/// separated items if it is in brackets, and a string otherwise.
+fn parse_override_value(value: &str) -> serde_json::Value {
+    if let Ok(value) = serde_json::from_str(value) {
+        return value;
+    }
+    match value
+        .strip_prefix('[')
+        .and_then(|value| value.strip_suffix(']'))
+    {
+        Some(items) => serde_json::Value::Array(
+            items
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(parse_override_value)
+                .collect(),
+        ),
+        None => serde_json::Value::String(value.to_string()),
+    }
+}
+
+/// The value at the dotted `path`, where numbers index into lists. With `create`, a missing
+/// last key of an object is added.
+fn value_at<'a>(
+    value: &'a mut serde_json::Value,
+    path: &str,
+    create: bool,
+) -> Option<&'a mut serde_json::Value> {
+    let keys: Vec<&str> = path.split('.').collect();
+    let mut current = value;
+    for (i, key) in keys.iter().enumerate() {
+        let last = i == keys.len() - 1;
+        current = match current {
+            serde_json::Value::Object(map) => {
+                if create && last {
+                    map.entry(key.to_string())
+                        .or_insert(serde_json::Value::Null)
+                } else {
+                    map.get_mut(*key)?
+                }
+            }
+            serde_json::Value::Array(items) => items.get_mut(key.parse::<usize>().ok()?)?,
+            _ => return None,
+        };
+    }
+    Some(current)
+}
+
+/// Apply `key=value` overrides, where the key is a dotted path such as `meta.title` or
+/// `servers.0.hostname`. Unknown keys and values of the wrong type are an error naming the key.
+pub fn apply_overrides(config: ConfigFile, overrides: &[String]) -> Result<ConfigFile, String> {
+    let mut config = config;
+    for assignment in overrides {
+        let (key, value) = assignment
+            .split_once('=')
+            .ok_or_else(|| format!("Invalid override {}, expected key=value", assignment))?;
+        let value = parse_override_value(value);
+
+        let mut json = serde_json::to_value(&config).map_err(|e| e.to_string())?;
+        *value_at(&mut json, key, true)
+            .ok_or_else(|| format!("Invalid override {}: no such key", key))? = value.clone();
+        config =
+            serde_json::from_value(json).map_err(|e| format!("Invalid override {}: {}", key, e))?;
+
+        // Unknown keys are ignored when deserializing, so they are gone when serialized again.
+        let mut json = serde_json::to_value(&config).map_err(|e| e.to_string())?;
+        if value_at(&mut json, key, false).is_none() {
+            return Err(format!("Invalid override {}: no such key", key));
+        }
+    }
+    Ok(config)
+}
 
-pub fn init_config(filename: &str) {
-    let manager = ConfigManager::new(filename);
-    CONFIG_MANAGER
-        .set(manager)
-        .expect("Configuration already initialized");
+fn read_config(filename: &str, overrides: &[String]) -> RwLock<Arc<ConfigFile>> {
+    let contents = std::fs::read_to_string(filename).expect("Failed to open configuration file");
+    let config: ConfigFile = ConfigFormat::from_path(filename)
+        .parse(&contents)
+        .unwrap_or_else(|e| panic!("Unable to parse configuration file: {}", e));
+    let mut config = apply_overrides(config, overrides).unwrap_or_else(|e| panic!("{}", e));
+    config
+        .check_duplicates()
+        .unwrap_or_else(|e| panic!("{}", e));
+    RwLock::new(Arc::new(config))
 }
 
-pub fn get_config_manager() -> &'static ConfigManager {
-    CONFIG_MANAGER
-        .get()
-        .expect("Configuration not initialized, use `init_config` first")
+/// The formats a configuration file can be written in, told apart by the extension. Anything
+/// other than `.yaml`, `.yml` or `.toml` is JSON.
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum ConfigFormat {
+    Json,
+    Yaml,
+    Toml,
 }
 
-fn read_config(filename: &str) -> RwLock<ConfigFile> {
-    let file = File::open(filename).expect("Failed to open configuration file");
-    let reader = BufReader::new(file);
-    serde_json::from_reader(reader).expect("Unable to parse configuration file")
+impl ConfigFormat {
+    pub fn from_path(path: impl AsRef<std::path::Path>) -> Self {
+        match path.as_ref().extension().and_then(|e| e.to_str()) {
+            Some("yaml" | "yml") => ConfigFormat::Yaml,
+            Some("toml") => ConfigFormat::Toml,
+            _ => ConfigFormat::Json,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ConfigFormat::Json => "json",
+            ConfigFormat::Yaml => "yaml",
+            ConfigFormat::Toml => "toml",
+        }
+    }
+
+    pub fn parse<T: serde::de::DeserializeOwned>(&self, contents: &str) -> Result<T, String> {
+        match self {
+            ConfigFormat::Json => serde_json::from_str(contents).map_err(|e| e.to_string()),
+            ConfigFormat::Yaml => serde_yaml::from_str(contents).map_err(|e| e.to_string()),
+            ConfigFormat::Toml => toml::from_str(contents).map_err(|e| e.to_string()),
+        }
+    }
+
+    pub fn render<T: Serialize>(&self, value: &T) -> Result<String, String> {
+        match self {
+            ConfigFormat::Json => serde_json::to_string_pretty(value)
+                .map(|json| json + "\n")
+                .map_err(|e| e.to_string()),
+            ConfigFormat::Yaml => serde_yaml::to_string(value).map_err(|e| e.to_string()),
+            ConfigFormat::Toml => toml::to_string_pretty(value).map_err(|e| e.to_string()),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use cvmfs_server_scraper::{Hostname, ServerType};
+    use std::fs;
+    use yare::parameterized;
 
     #[test]
     fn test_config_validation_cvmfs_without_repos() {
@@ -139,20 +1794,84 @@ mod tests {
                 contact_email: "contact@bar.com".to_string(),
                 repo_url: "https://example.com".to_string(),
                 repo_url_text: "example.com".to_string(),
+                announcement: None,
+                announcement_severity: AnnouncementSeverity::default(),
+                template_dir: None,
+                strict_templates: false,
+                extra: BTreeMap::new(),
+                links: vec![],
+                locale: "en".to_string(),
+                refresh_seconds: None,
+                stale_after_seconds: None,
+                embed_data: false,
+                embed_data_warn_bytes: default_embed_data_warn_bytes(),
+                minify_html: false,
+                hashed_assets: false,
+                hashed_assets_keep_runs: default_hashed_assets_keep_runs(),
+                base_url: None,
+                description: None,
+                og_image: None,
+                resources_dir: None,
+                resources_link: false,
+                exclude_resources: vec![],
+                durability: Durability::Full,
+                output_file_mode: 0o644,
             },
-            servers: vec![Server {
-                hostname: Hostname::try_from("example.com".to_string()).unwrap(),
-                backend_type: ServerBackendType::CVMFS,
-                server_type: ServerType::Stratum1,
+            servers: vec![ServerConfig {
+                server: Server {
+                    hostname: Hostname::try_from("example.com".to_string()).unwrap(),
+                    backend_type: ServerBackendType::CVMFS,
+                    server_type: ServerType::Stratum1,
+                },
+                https: false,
+                resolve_to: None,
+                members: vec![],
+                quorum: None,
             }],
             repositories: vec![],
             ignored_repositories: vec![],
             rules: vec![],
             limit_scraping_to_repositories: false,
+            geodb_max_age_days: default_geodb_max_age_days(),
+            certificate_expiry: CertificateExpiryConfig::default(),
+            scrape: ScrapeConfig::default(),
+            deep_check: DeepCheckConfig::default(),
+            repository_settings: HashMap::new(),
+            server_status_from_repos: ServerStatusFromRepos::default(),
+            maintenance_aggregation: MaintenanceAggregation::default(),
+            missing_reference_status: default_missing_reference_status(),
+            revision_regression_exempt: vec![],
+            use_stale_data_minutes: None,
+            strict_validation: true,
+            logging: LoggingConfig::default(),
+            push_gateway: None,
+            influx: None,
+            graphite: None,
+            statsd: None,
+            slack: None,
+            email: None,
+            pagerduty: None,
+            on_status_change: vec![],
+            on_status_change_timeout_seconds: default_hook_timeout_seconds(),
+            heartbeat_url: None,
+            heartbeat_timeout_seconds: default_heartbeat_timeout_seconds(),
+            events: EventsConfig::default(),
+            history: HistoryConfig::default(),
+            incidents: vec![],
+            incidents_warn: false,
+            badges: vec![],
+            badge_endpoints: vec![],
+            privacy: PrivacyConfig::default(),
+            check: CheckConfig::default(),
+            metrics: MetricsConfig::default(),
+            output: OutputConfig::default(),
+            precompress: PrecompressConfig::default(),
+            pages: vec![],
+            status_classes: BTreeMap::new(),
         };
 
         let manager = ConfigManager {
-            config: RwLock::new(config),
+            config: RwLock::new(Arc::new(config)),
         };
 
         assert!(manager
@@ -176,22 +1895,956 @@ mod tests {
                 contact_email: "contact@bar.com".to_string(),
                 repo_url: "https://example.com".to_string(),
                 repo_url_text: "example.com".to_string(),
+                announcement: None,
+                announcement_severity: AnnouncementSeverity::default(),
+                template_dir: None,
+                strict_templates: false,
+                extra: BTreeMap::new(),
+                links: vec![],
+                locale: "en".to_string(),
+                refresh_seconds: None,
+                stale_after_seconds: None,
+                embed_data: false,
+                embed_data_warn_bytes: default_embed_data_warn_bytes(),
+                minify_html: false,
+                hashed_assets: false,
+                hashed_assets_keep_runs: default_hashed_assets_keep_runs(),
+                base_url: None,
+                description: None,
+                og_image: None,
+                resources_dir: None,
+                resources_link: false,
+                exclude_resources: vec![],
+                durability: Durability::Full,
+                output_file_mode: 0o644,
             },
-            servers: vec![Server {
-                hostname: Hostname::try_from("example.com".to_string()).unwrap(),
-                backend_type: ServerBackendType::S3,
-                server_type: ServerType::Stratum1,
+            servers: vec![ServerConfig {
+                server: Server {
+                    hostname: Hostname::try_from("example.com".to_string()).unwrap(),
+                    backend_type: ServerBackendType::S3,
+                    server_type: ServerType::Stratum1,
+                },
+                https: false,
+                resolve_to: None,
+                members: vec![],
+                quorum: None,
             }],
             repositories: vec![],
             ignored_repositories: vec![],
             rules: vec![],
             limit_scraping_to_repositories: false,
+            geodb_max_age_days: default_geodb_max_age_days(),
+            certificate_expiry: CertificateExpiryConfig::default(),
+            scrape: ScrapeConfig::default(),
+            deep_check: DeepCheckConfig::default(),
+            repository_settings: HashMap::new(),
+            server_status_from_repos: ServerStatusFromRepos::default(),
+            maintenance_aggregation: MaintenanceAggregation::default(),
+            missing_reference_status: default_missing_reference_status(),
+            revision_regression_exempt: vec![],
+            use_stale_data_minutes: None,
+            strict_validation: true,
+            logging: LoggingConfig::default(),
+            push_gateway: None,
+            influx: None,
+            graphite: None,
+            statsd: None,
+            slack: None,
+            email: None,
+            pagerduty: None,
+            on_status_change: vec![],
+            on_status_change_timeout_seconds: default_hook_timeout_seconds(),
+            heartbeat_url: None,
+            heartbeat_timeout_seconds: default_heartbeat_timeout_seconds(),
+            events: EventsConfig::default(),
+            history: HistoryConfig::default(),
+            incidents: vec![],
+            incidents_warn: false,
+            badges: vec![],
+            badge_endpoints: vec![],
+            privacy: PrivacyConfig::default(),
+            check: CheckConfig::default(),
+            metrics: MetricsConfig::default(),
+            output: OutputConfig::default(),
+            precompress: PrecompressConfig::default(),
+            pages: vec![],
+            status_classes: BTreeMap::new(),
         };
 
         let manager = ConfigManager {
-            config: RwLock::new(config),
+            config: RwLock::new(Arc::new(config)),
         };
 
         manager.validate_config();
     }
+
+    #[parameterized(
+        global_off = { false, None, false },
+        global_on = { true, None, true },
+        repo_enables = { false, Some(true), true },
+        repo_disables = { true, Some(false), false },
+    )]
+    fn test_deep_check_enabled_for(global: bool, repo_override: Option<bool>, expected: bool) {
+        let mut settings = serde_json::json!({});
+        if let Some(enabled) = repo_override {
+            settings = serde_json::json!({ "software.eessi.io": { "deep_check": enabled } });
+        }
+        let config: ConfigFile = serde_json::from_value(serde_json::json!({
+            "meta": {
+                "title": "Test",
+                "logging_level": "info",
+                "contact_email": "contact@bar.com",
+                "repo_url": "https://example.com",
+                "repo_url_text": "example.com"
+            },
+            "servers": [],
+            "repositories": ["software.eessi.io"],
+            "ignored_repositories": [],
+            "deep_check": { "enabled": global },
+            "repository_settings": settings,
+            "rules": []
+        }))
+        .unwrap();
+
+        assert_eq!(config.deep_check_enabled_for("software.eessi.io"), expected);
+        assert_eq!(config.deep_check_enabled_for("dev.eessi.io"), global);
+    }
+
+    #[parameterized(
+        unpinned = { serde_json::json!({ "deep_check": true }), &[] },
+        one = { serde_json::json!({ "expected_cert_fingerprint": "AB:CD" }), &["AB:CD"] },
+        many = { serde_json::json!({ "expected_cert_fingerprint": ["ab:cd", "ef01"] }), &["ab:cd", "ef01"] },
+    )]
+    fn test_accepted_cert_fingerprints(settings: serde_json::Value, expected: &[&str]) {
+        let config: ConfigFile = serde_json::from_value(serde_json::json!({
+            "meta": {
+                "title": "Test",
+                "logging_level": "info",
+                "contact_email": "contact@bar.com",
+                "repo_url": "https://example.com",
+                "repo_url_text": "example.com"
+            },
+            "servers": [],
+            "repositories": ["software.eessi.io"],
+            "ignored_repositories": [],
+            "repository_settings": { "software.eessi.io": settings },
+            "rules": []
+        }))
+        .unwrap();
+
+        assert_eq!(
+            config.accepted_cert_fingerprints("software.eessi.io"),
+            expected
+        );
+        assert!(config.accepted_cert_fingerprints("dev.eessi.io").is_empty());
+    }
+
+    /// A configuration with the given servers, repositories, ignored repositories and rules.
+    fn config_with_lists(
+        hostnames: &[&str],
+        repositories: &[&str],
+        ignored: &[&str],
+        rules: &[&str],
+        strict: bool,
+    ) -> ConfigFile {
+        let servers: Vec<serde_json::Value> = hostnames
+            .iter()
+            .map(|h| serde_json::json!({ "hostname": h, "server_type": "Stratum1", "backend_type": "CVMFS" }))
+            .collect();
+        let rules: Vec<serde_json::Value> = rules
+            .iter()
+            .map(|id| serde_json::json!({ "id": id, "description": "", "conditions": [] }))
+            .collect();
+        serde_json::from_value(serde_json::json!({
+            "meta": {
+                "title": "Test",
+                "logging_level": "info",
+                "contact_email": "contact@bar.com",
+                "repo_url": "https://example.com",
+                "repo_url_text": "example.com"
+            },
+            "servers": servers,
+            "repositories": repositories,
+            "ignored_repositories": ignored,
+            "strict_validation": strict,
+            "rules": rules
+        }))
+        .unwrap()
+    }
+
+    #[parameterized(
+        none = { &["s1.example.com", "s2.example.com"], &["a.eessi.io"], &["b.eessi.io"], &["eessi_status"], None },
+        server = { &["s1.example.com", "s2.example.com", "s1.example.com"], &[], &[], &[], Some("duplicate server s1.example.com") },
+        server_case = { &["s1.example.com", "S1.Example.com"], &[], &[], &[], Some("duplicate server s1.example.com") },
+        repository = { &[], &["a.eessi.io", "b.eessi.io", "a.eessi.io"], &[], &[], Some("duplicate repository a.eessi.io") },
+        ignored = { &[], &[], &["b.eessi.io", "b.eessi.io"], &[], Some("duplicate ignored repository b.eessi.io") },
+        both_lists = { &[], &["a.eessi.io"], &["a.eessi.io"], &[], Some("repository a.eessi.io is both in repositories and ignored_repositories") },
+        rule = { &[], &[], &[], &["eessi_status", "stratum1_servers", "eessi_status"], Some("duplicate rule eessi_status") },
+    )]
+    fn test_check_duplicates(
+        hostnames: &[&str],
+        repositories: &[&str],
+        ignored: &[&str],
+        rules: &[&str],
+        expected: Option<&str>,
+    ) {
+        let mut strict = config_with_lists(hostnames, repositories, ignored, rules, true);
+        assert_eq!(
+            strict.check_duplicates().err(),
+            expected.map(|problem| format!("Invalid configuration: {}", problem))
+        );
+
+        let mut lenient = config_with_lists(hostnames, repositories, ignored, rules, false);
+        assert_eq!(lenient.check_duplicates(), Ok(()));
+        let hostnames: Vec<String> = lenient
+            .servers
+            .iter()
+            .map(|s| s.server.hostname.to_str().to_lowercase())
+            .collect();
+        let rules: Vec<&str> = lenient.rules.iter().map(|r| r.id.as_str()).collect();
+        assert!((1..hostnames.len()).all(|i| !hostnames[..i].contains(&hostnames[i])));
+        assert!((1..rules.len()).all(|i| !rules[..i].contains(&rules[i])));
+        assert!(repositories.iter().all(|r| lenient
+            .repositories
+            .iter()
+            .filter(|l| l == r)
+            .count()
+            == 1));
+        assert!(lenient
+            .ignored_repositories
+            .iter()
+            .all(|r| !lenient.repositories.contains(r)));
+    }
+
+    #[test]
+    fn test_check_duplicates_reports_all() {
+        let mut config = config_with_lists(
+            &["s1.example.com", "s1.example.com"],
+            &["a.eessi.io", "a.eessi.io"],
+            &["a.eessi.io"],
+            &["eessi_status", "eessi_status"],
+            true,
+        );
+
+        assert_eq!(
+            config.check_duplicates(),
+            Err("Invalid configuration: duplicate server s1.example.com, \
+                 duplicate repository a.eessi.io, \
+                 repository a.eessi.io is both in repositories and ignored_repositories, \
+                 duplicate rule eessi_status"
+                .to_string())
+        );
+    }
+
+    #[parameterized(
+        lowercase = { "abcd01", "abcd01" },
+        uppercase = { "ABCD01", "abcd01" },
+        colons = { "AB:CD:01", "abcd01" },
+    )]
+    fn test_normalize_fingerprint(fingerprint: &str, expected: &str) {
+        assert_eq!(normalize_fingerprint(fingerprint), expected);
+    }
+
+    #[parameterized(
+        unset = { None, true },
+        ipv4 = { Some("10.0.0.1"), true },
+        hostname = { Some("lb1.internal"), true },
+        octet_out_of_range = { Some("10.0.0.300"), false },
+        too_few_octets = { Some("10.0.1"), false },
+        ipv6 = { Some("::1"), true },
+        ipv6_bracketed = { Some("[2001:db8::1]"), true },
+        ipv6_invalid = { Some("2001:db8::g"), false },
+        garbage = { Some("not a host"), false },
+    )]
+    fn test_resolve_to_address(resolve_to: Option<&str>, valid: bool) {
+        let server = ServerConfig {
+            server: Server {
+                hostname: Hostname::try_from("example.com".to_string()).unwrap(),
+                backend_type: ServerBackendType::CVMFS,
+                server_type: ServerType::Stratum1,
+            },
+            https: false,
+            resolve_to: resolve_to.map(|s| s.to_string()),
+            members: vec![],
+            quorum: None,
+        };
+
+        assert_eq!(server.resolve_to_address().is_ok(), valid);
+    }
+
+    #[parameterized(
+        plain = { &[], None, None, true },
+        members = { &["10.0.0.1", "10.0.0.2"], None, None, true },
+        members_with_quorum = { &["10.0.0.1", "10.0.0.2"], Some(1), None, true },
+        quorum_all = { &["10.0.0.1", "10.0.0.2"], Some(2), None, true },
+        quorum_too_large = { &["10.0.0.1", "10.0.0.2"], Some(3), None, false },
+        quorum_zero = { &["10.0.0.1"], Some(0), None, false },
+        quorum_without_members = { &[], Some(1), None, false },
+        invalid_member = { &["10.0.0.1", "10.0.0.300"], None, None, false },
+        ipv6_member = { &["10.0.0.1", "::1"], None, None, true },
+        members_and_resolve_to = { &["10.0.0.1"], None, Some("10.0.0.2"), false },
+    )]
+    fn test_validate_addresses(
+        members: &[&str],
+        quorum: Option<usize>,
+        resolve_to: Option<&str>,
+        valid: bool,
+    ) {
+        let server = ServerConfig {
+            server: Server {
+                hostname: Hostname::try_from("example.com".to_string()).unwrap(),
+                backend_type: ServerBackendType::CVMFS,
+                server_type: ServerType::Stratum1,
+            },
+            https: false,
+            resolve_to: resolve_to.map(|s| s.to_string()),
+            members: members.iter().map(|m| m.to_string()).collect(),
+            quorum,
+        };
+
+        assert_eq!(server.validate_addresses().is_ok(), valid);
+    }
+
+    fn config_with_servers() -> ConfigFile {
+        serde_json::from_value(serde_json::json!({
+            "meta": {
+                "title": "EESSI Status",
+                "logging_level": "info",
+                "contact_email": "support@eessi.io",
+                "repo_url": "https://example.com",
+                "repo_url_text": "Example"
+            },
+            "servers": [
+                { "hostname": "s0.example.com", "server_type": "Stratum0" },
+                { "hostname": "s1a.example.com", "server_type": "Stratum1" },
+                { "hostname": "s1b.example.com", "server_type": "Stratum1" },
+                { "hostname": "sync.example.com", "server_type": "SyncServer" }
+            ],
+            "repositories": [],
+            "ignored_repositories": [],
+            "rules": []
+        }))
+        .unwrap()
+    }
+
+    #[parameterized(
+        no_filter = { &[], &[], Some(4) },
+        by_hostname = { &["s1a.example.com"], &[], Some(1) },
+        by_type = { &[], &[ServerType::Stratum1], Some(2) },
+        by_hostname_and_type = { &["s0.example.com", "s1a.example.com"], &[ServerType::Stratum1], Some(1) },
+        unknown_hostname = { &["nope.example.com"], &[], None },
+    )]
+    fn test_retain_servers(
+        hostnames: &[&str],
+        server_types: &[ServerType],
+        expected: Option<usize>,
+    ) {
+        let mut config = config_with_servers();
+        let hostnames: Vec<String> = hostnames.iter().map(|h| h.to_string()).collect();
+
+        match config.retain_servers(&hostnames, server_types) {
+            Ok(()) => assert_eq!(Some(config.servers.len()), expected),
+            Err(e) => {
+                assert_eq!(expected, None);
+                assert!(e.contains("nope.example.com"));
+                assert!(e.contains("s1b.example.com"));
+            }
+        }
+    }
+
+    #[parameterized(
+        no_filter = { &[], &["software.eessi.io"], false },
+        configured = { &["software.eessi.io"], &["software.eessi.io"], true },
+        not_configured = { &["riscv.eessi.io"], &["riscv.eessi.io"], true },
+    )]
+    fn test_restrict_repositories(repositories: &[&str], expected: &[&str], limited: bool) {
+        let mut config = config_with_servers();
+        config.repositories = vec!["software.eessi.io".to_string()];
+        let repositories: Vec<String> = repositories.iter().map(|r| r.to_string()).collect();
+
+        config.restrict_repositories(&repositories);
+
+        assert_eq!(config.repositories, expected);
+        assert_eq!(config.limit_scraping_to_repositories, limited);
+    }
+
+    #[parameterized(
+        valid = { ServerBackendType::CVMFS, true },
+        s3_without_repositories = { ServerBackendType::S3, false },
+    )]
+    fn test_load(backend_type: ServerBackendType, valid: bool) {
+        let mut config = config_with_servers();
+        config.servers[0].server.backend_type = backend_type;
+        let file = tempfile::NamedTempFile::new().unwrap();
+        serde_json::to_writer(&file, &config).unwrap();
+
+        let loaded = ConfigManager::load(file.path().to_str().unwrap(), &[]);
+
+        match loaded {
+            Ok(manager) => {
+                assert!(valid);
+                assert_eq!(manager.get_config().servers.len(), 4);
+            }
+            Err(e) => {
+                assert!(!valid);
+                assert!(e.contains("s0.example.com uses S3 as backend"));
+            }
+        }
+        assert!(ConfigManager::load("/nonexistent/config.json", &[]).is_err());
+    }
+
+    /// A configuration file of `config_with_servers` with `title`.
+    fn config_file(title: &str) -> tempfile::NamedTempFile {
+        let mut config = config_with_servers();
+        config.meta.title = title.to_string();
+        let file = tempfile::NamedTempFile::new().unwrap();
+        serde_json::to_writer(&file, &config).unwrap();
+        file
+    }
+
+    #[test]
+    fn test_load_two_configurations() {
+        let first = config_file("First");
+        let second = config_file("Second");
+
+        let first = ConfigManager::load(first.path().to_str().unwrap(), &[]).unwrap();
+        let second = ConfigManager::load(second.path().to_str().unwrap(), &[]).unwrap();
+
+        assert_eq!(first.get_config().meta.title, "First");
+        assert_eq!(second.get_config().meta.title, "Second");
+    }
+
+    #[test]
+    fn test_reload() {
+        let file = config_file("First");
+        let path = file.path().to_str().unwrap();
+        let manager = ConfigManager::load(path, &[]).unwrap();
+
+        serde_json::to_writer(fs::File::create(path).unwrap(), &{
+            let mut config = ConfigFile::clone(&manager.get_config());
+            config.meta.title = "Second".to_string();
+            config
+        })
+        .unwrap();
+        manager.reload(path, &[]).unwrap();
+        assert_eq!(manager.get_config().meta.title, "Second");
+
+        // An invalid configuration keeps the current one.
+        fs::write(path, "{").unwrap();
+        assert!(manager.reload(path, &[]).is_err());
+        assert_eq!(manager.get_config().meta.title, "Second");
+
+        fs::write(path, serde_json::to_string(&config_with_servers()).unwrap()).unwrap();
+        manager
+            .reload(path, &["meta.title=Third".to_string()])
+            .unwrap();
+        assert_eq!(manager.get_config().meta.title, "Third");
+    }
+
+    #[test]
+    fn test_get_config_shared() {
+        let mut config = config_with_servers();
+        config.servers = serde_json::from_value(serde_json::Value::Array(
+            (0..5000)
+                .map(|i| {
+                    serde_json::json!({
+                        "hostname": format!("s1-{}.example.com", i),
+                        "server_type": "Stratum1"
+                    })
+                })
+                .collect(),
+        ))
+        .unwrap();
+        let file = tempfile::NamedTempFile::new().unwrap();
+        serde_json::to_writer(&file, &config).unwrap();
+        let path = file.path().to_str().unwrap();
+        let manager = ConfigManager::load(path, &[]).unwrap();
+
+        let first = manager.get_config();
+        let second = manager.get_config();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(
+            serde_json::to_value(&*first).unwrap(),
+            serde_json::to_value(&config).unwrap()
+        );
+
+        // A reload leaves the configuration of a run that already got it as it was.
+        manager
+            .reload(path, &["meta.title=Reloaded".to_string()])
+            .unwrap();
+        assert_eq!(first.meta.title, "EESSI Status");
+        assert_eq!(first.servers.len(), 5000);
+        assert_eq!(manager.get_config().meta.title, "Reloaded");
+    }
+
+    #[parameterized(
+        empty = { "", &[], true },
+        prefix = { "eessi_statuspage_", &["site"], true },
+        namespaced = { "eessi:", &[], true },
+        leading_digit = { "1eessi_", &[], false },
+        dash = { "eessi-", &[], false },
+        reserved_label = { "", &["__name__"], false },
+        invalid_label = { "", &["site-name"], false },
+    )]
+    fn test_metrics_config_validate(prefix: &str, labels: &[&str], valid: bool) {
+        let config = MetricsConfig {
+            prefix: prefix.to_string(),
+            constant_labels: labels
+                .iter()
+                .map(|l| (l.to_string(), "value".to_string()))
+                .collect(),
+            ..MetricsConfig::default()
+        };
+
+        assert_eq!(config.validate().is_ok(), valid);
+    }
+
+    #[parameterized(
+        open = { &[("aws", "2024-05-01T08:00:00Z", None)], None },
+        resolved = { &[("aws", "2024-05-01T08:00:00Z", Some("2024-05-01T10:00:00Z"))], None },
+        resolved_before_start = { &[("aws", "2024-05-01T08:00:00Z", Some("2024-05-01T07:00:00Z"))], Some("incident aws is resolved at") },
+        duplicate_id = { &[("aws", "2024-05-01T08:00:00Z", None), ("aws", "2024-05-02T08:00:00Z", None)], Some("Duplicate incident id aws") },
+    )]
+    fn test_load_incidents(incidents: &[(&str, &str, Option<&str>)], error: Option<&str>) {
+        let mut config = config_with_servers();
+        config.incidents = incidents
+            .iter()
+            .map(|&(id, started_at, resolved_at)| Incident {
+                id: id.to_string(),
+                title: "Stratum 1 outage at AWS".to_string(),
+                body: String::new(),
+                status: IncidentStatus::Investigating,
+                started_at: started_at.parse().unwrap(),
+                resolved_at: resolved_at.map(|r| r.parse().unwrap()),
+                components: vec!["stratum1".to_string()],
+            })
+            .collect();
+        let file = tempfile::NamedTempFile::new().unwrap();
+        serde_json::to_writer(&file, &config).unwrap();
+
+        let loaded = ConfigManager::load(file.path().to_str().unwrap(), &[]);
+
+        match error {
+            None => assert!(loaded.is_ok(), "{:?}", loaded.err()),
+            Some(error) => assert!(
+                loaded.as_ref().is_err_and(|e| e.contains(error)),
+                "{:?}",
+                loaded.err()
+            ),
+        }
+    }
+
+    #[parameterized(
+        default = { None, Some(Status::WARNING) },
+        ok = { Some("OK"), Some(Status::OK) },
+        failed = { Some("FAILED"), Some(Status::FAILED) },
+        unknown = { Some("UNKNOWN"), None },
+    )]
+    fn test_missing_reference_status(status: Option<&str>, expected: Option<Status>) {
+        let mut config = serde_json::to_value(config_with_servers()).unwrap();
+        match status {
+            Some(status) => config["missing_reference_status"] = serde_json::json!(status),
+            None => {
+                config
+                    .as_object_mut()
+                    .unwrap()
+                    .remove("missing_reference_status");
+            }
+        }
+
+        let parsed = serde_json::from_value::<ConfigFile>(config);
+
+        assert_eq!(parsed.ok().map(|c| c.missing_reference_status), expected);
+    }
+
+    #[parameterized(
+        octal = { "0644", Some(0o644) },
+        no_leading_zero = { "640", Some(0o640) },
+        setgid = { "2775", Some(0o2775) },
+        decimal = { "0649", None },
+        too_large = { "10000", None },
+        symbolic = { "rw-r--r--", None },
+    )]
+    fn test_output_file_mode(mode: &str, expected: Option<u32>) {
+        let mut config = serde_json::to_value(config_with_servers()).unwrap();
+        config["meta"]["output_file_mode"] = serde_json::json!(mode);
+
+        let parsed = serde_json::from_value::<ConfigFile>(config);
+
+        assert_eq!(parsed.ok().map(|c| c.meta.output_file_mode), expected);
+    }
+
+    #[parameterized(
+        none = { &[], None },
+        public_and_internal = { &[("status.html", "index.html", "public"), ("status.html", "ops/index.html", "internal")], None },
+        no_template = { &[("", "index.html", "public")], Some("page \"index.html\" has no template") },
+        absolute = { &[("status.html", "/var/www/index.html", "public")], Some("which must be a path in the destination") },
+        parent = { &[("status.html", "../index.html", "public")], Some("which must be a path in the destination") },
+        duplicate = { &[("status.html", "index.html", "public"), ("ops.html", "index.html", "internal")], Some("Duplicate page output_file \"index.html\"") },
+    )]
+    fn test_load_pages(pages: &[(&str, &str, &str)], error: Option<&str>) {
+        let mut config = serde_json::to_value(config_with_servers()).unwrap();
+        config["pages"] = pages
+            .iter()
+            .map(|&(template, output_file, visibility)| {
+                serde_json::json!({
+                    "template": template,
+                    "output_file": output_file,
+                    "visibility": visibility,
+                })
+            })
+            .collect();
+        let file = tempfile::NamedTempFile::new().unwrap();
+        serde_json::to_writer(&file, &config).unwrap();
+
+        let loaded = ConfigManager::load(file.path().to_str().unwrap(), &[]);
+
+        match error {
+            None => assert_eq!(loaded.unwrap().get_config().pages.len(), pages.len()),
+            Some(error) => assert!(
+                loaded.as_ref().is_err_and(|e| e.contains(error)),
+                "{:?}",
+                loaded.err()
+            ),
+        }
+    }
+
+    #[parameterized(
+        none = { serde_json::json!([]), None },
+        links = { serde_json::json!([{"text": "Documentation", "url": "https://www.eessi.io/docs/", "icon": "fas fa-book"}, {"text": "Dashboards", "url": "https://grafana.example.com"}]), None },
+        no_text = { serde_json::json!([{"text": " ", "url": "https://www.eessi.io/docs/"}]), Some("link to \"https://www.eessi.io/docs/\" has no text") },
+        no_url = { serde_json::json!([{"text": "Documentation", "url": ""}]), Some("link \"Documentation\" has no url") },
+        empty_icon = { serde_json::json!([{"text": "Documentation", "url": "https://www.eessi.io/docs/", "icon": ""}]), Some("link \"Documentation\" has an empty icon") },
+    )]
+    fn test_load_links(links: serde_json::Value, error: Option<&str>) {
+        let mut config = serde_json::to_value(config_with_servers()).unwrap();
+        config["meta"]["links"] = links.clone();
+        let file = tempfile::NamedTempFile::new().unwrap();
+        serde_json::to_writer(&file, &config).unwrap();
+
+        let loaded = ConfigManager::load(file.path().to_str().unwrap(), &[]);
+
+        match error {
+            None => assert_eq!(
+                serde_json::to_value(&loaded.unwrap().get_config().meta.links).unwrap(),
+                links
+            ),
+            Some(error) => assert!(
+                loaded.as_ref().is_err_and(|e| e.contains(error)),
+                "{:?}",
+                loaded.err()
+            ),
+        }
+    }
+
+    #[parameterized(
+        none = { &[], None },
+        valid = { &["webfonts/**", "*.map"], None },
+        invalid = { &["webfonts/["], Some("Invalid meta.exclude_resources: ") },
+    )]
+    fn test_load_exclude_resources(patterns: &[&str], error: Option<&str>) {
+        let mut config = serde_json::to_value(config_with_servers()).unwrap();
+        config["meta"]["exclude_resources"] = serde_json::json!(patterns);
+        let file = tempfile::NamedTempFile::new().unwrap();
+        serde_json::to_writer(&file, &config).unwrap();
+
+        let loaded = ConfigManager::load(file.path().to_str().unwrap(), &[]);
+
+        match error {
+            None => assert_eq!(
+                loaded.unwrap().get_config().meta.exclude_resources,
+                patterns
+            ),
+            Some(error) => assert!(
+                loaded.as_ref().is_err_and(|e| e.starts_with(error)),
+                "{:?}",
+                loaded.err()
+            ),
+        }
+    }
+
+    #[parameterized(
+        default = { None, Ok(6) },
+        fastest = { Some(1), Ok(1) },
+        zero = { Some(0), Err("precompress.level must be from 1 to 9") },
+        too_high = { Some(10), Err("precompress.level must be from 1 to 9") },
+    )]
+    fn test_load_precompress(level: Option<u32>, expected: Result<u32, &str>) {
+        let mut config = serde_json::to_value(config_with_servers()).unwrap();
+        config["precompress"] = match level {
+            Some(level) => serde_json::json!({ "enabled": true, "level": level }),
+            None => serde_json::json!({ "enabled": true }),
+        };
+        let file = tempfile::NamedTempFile::new().unwrap();
+        serde_json::to_writer(&file, &config).unwrap();
+
+        let loaded = ConfigManager::load(file.path().to_str().unwrap(), &[]);
+
+        match expected {
+            Ok(expected) => assert_eq!(loaded.unwrap().get_config().precompress.level, expected),
+            Err(error) => assert_eq!(loaded.err().as_deref(), Some(error)),
+        }
+    }
+
+    #[parameterized(
+        default = { None, Ok(11) },
+        fastest = { Some(0), Ok(0) },
+        too_high = { Some(12), Err("precompress.brotli_level must be from 0 to 11") },
+    )]
+    fn test_load_precompress_brotli_level(level: Option<u32>, expected: Result<u32, &str>) {
+        let mut config = serde_json::to_value(config_with_servers()).unwrap();
+        config["precompress"] = match level {
+            Some(level) => serde_json::json!({ "enabled": true, "brotli_level": level }),
+            None => serde_json::json!({ "enabled": true }),
+        };
+        let file = tempfile::NamedTempFile::new().unwrap();
+        serde_json::to_writer(&file, &config).unwrap();
+
+        let loaded = ConfigManager::load(file.path().to_str().unwrap(), &[]);
+
+        match expected {
+            Ok(expected) => assert_eq!(
+                loaded.unwrap().get_config().precompress.brotli_level,
+                expected
+            ),
+            Err(error) => assert_eq!(loaded.err().as_deref(), Some(error)),
+        }
+    }
+
+    #[parameterized(
+        default = { serde_json::json!({}), Ok((10, 120)) },
+        configured = { serde_json::json!({ "timeout_seconds": 5, "deadline_seconds": 60 }), Ok((5, 60)) },
+        zero_timeout = { serde_json::json!({ "timeout_seconds": 0 }), Err("scrape.timeout_seconds must be at least 1") },
+        zero_deadline = { serde_json::json!({ "deadline_seconds": 0 }), Err("scrape.deadline_seconds must be at least 1") },
+    )]
+    fn test_load_scrape(scrape: serde_json::Value, expected: Result<(u64, u64), &str>) {
+        let mut config = serde_json::to_value(config_with_servers()).unwrap();
+        config["scrape"] = scrape;
+        let file = tempfile::NamedTempFile::new().unwrap();
+        serde_json::to_writer(&file, &config).unwrap();
+
+        let loaded = ConfigManager::load(file.path().to_str().unwrap(), &[]);
+
+        match expected {
+            Ok(expected) => {
+                let config = loaded.unwrap().get_config();
+                assert_eq!(
+                    (
+                        config.scrape.timeout_seconds,
+                        config.scrape.deadline_seconds
+                    ),
+                    expected
+                );
+            }
+            Err(error) => assert_eq!(loaded.err().as_deref(), Some(error)),
+        }
+    }
+
+    #[parameterized(
+        none = { None, None, Ok(None) },
+        refresh = { Some(300), None, Ok(Some(900)) },
+        stale_after = { Some(300), Some(600), Ok(Some(600)) },
+        stale_after_only = { None, Some(3600), Ok(Some(3600)) },
+        zero_refresh = { Some(0), None, Err("meta.refresh_seconds must be at least 1") },
+        zero_stale_after = { Some(300), Some(0), Err("meta.stale_after_seconds must be at least 1") },
+    )]
+    fn test_load_refresh(
+        refresh_seconds: Option<u64>,
+        stale_after_seconds: Option<u64>,
+        expected: Result<Option<u64>, &str>,
+    ) {
+        let mut config = serde_json::to_value(config_with_servers()).unwrap();
+        config["meta"]["refresh_seconds"] = refresh_seconds.into();
+        config["meta"]["stale_after_seconds"] = stale_after_seconds.into();
+        let file = tempfile::NamedTempFile::new().unwrap();
+        serde_json::to_writer(&file, &config).unwrap();
+
+        let loaded = ConfigManager::load(file.path().to_str().unwrap(), &[]);
+
+        match expected {
+            Ok(expected) => assert_eq!(
+                loaded.unwrap().get_config().meta.stale_after_seconds(),
+                expected
+            ),
+            Err(error) => assert_eq!(loaded.err().as_deref(), Some(error)),
+        }
+    }
+
+    #[parameterized(
+        none = { None, None, None, Ok(None) },
+        base_url_only = { Some("https://status.eessi.io/"), None, None, Ok(None) },
+        relative_image = { Some("https://status.eessi.io/"), Some("EESSI status"), Some("eessi-512px.png"), Ok(Some("https://status.eessi.io/eessi-512px.png")) },
+        absolute_image = { Some("https://status.eessi.io/"), None, Some("https://www.eessi.io/logo.png"), Ok(Some("https://www.eessi.io/logo.png")) },
+        no_base_url = { None, Some("EESSI status"), None, Err("meta.base_url is required with meta.description or meta.og_image") },
+        relative_base_url = { Some("status.eessi.io"), None, None, Err("meta.base_url must be an absolute http(s) URL, got \"status.eessi.io\"") },
+        file_base_url = { Some("file:///var/www"), None, Some("eessi-512px.png"), Err("meta.base_url must be an absolute http(s) URL, got \"file:///var/www\"") },
+    )]
+    fn test_load_open_graph(
+        base_url: Option<&str>,
+        description: Option<&str>,
+        og_image: Option<&str>,
+        expected: Result<Option<&str>, &str>,
+    ) {
+        let mut config = serde_json::to_value(config_with_servers()).unwrap();
+        config["meta"]["base_url"] = base_url.into();
+        config["meta"]["description"] = description.into();
+        config["meta"]["og_image"] = og_image.into();
+        let file = tempfile::NamedTempFile::new().unwrap();
+        serde_json::to_writer(&file, &config).unwrap();
+
+        let loaded = ConfigManager::load(file.path().to_str().unwrap(), &[]);
+
+        match expected {
+            Ok(expected) => assert_eq!(
+                loaded.unwrap().get_config().meta.og_image_url().as_deref(),
+                expected
+            ),
+            Err(error) => assert_eq!(loaded.err().as_deref(), Some(error)),
+        }
+    }
+
+    #[parameterized(
+        none = { serde_json::json!({}), None },
+        warning = { serde_json::json!({"WARNING": "theme-warning"}), None },
+        unknown_status = { serde_json::json!({"BROKEN": "theme-broken"}), Some("unknown variant `BROKEN`") },
+    )]
+    fn test_load_status_classes(classes: serde_json::Value, error: Option<&str>) {
+        let mut config = serde_json::to_value(config_with_servers()).unwrap();
+        config["status_classes"] = classes;
+        let file = tempfile::NamedTempFile::new().unwrap();
+        serde_json::to_writer(&file, &config).unwrap();
+
+        let loaded = ConfigManager::load(file.path().to_str().unwrap(), &[]);
+
+        match error {
+            None => {
+                let shown: serde_json::Value =
+                    serde_json::from_str(&loaded.unwrap().as_json()).unwrap();
+                let expected = if config["status_classes"]["WARNING"].is_string() {
+                    "theme-warning"
+                } else {
+                    Status::WARNING.class()
+                };
+                assert_eq!(shown["status_classes"]["WARNING"], expected);
+                assert_eq!(shown["status_classes"]["OK"], Status::OK.class());
+            }
+            Some(error) => assert!(
+                loaded.as_ref().is_err_and(|e| e.contains(error)),
+                "{:?}",
+                loaded.err()
+            ),
+        }
+    }
+
+    #[parameterized(
+        none = { serde_json::Value::Null, true },
+        nested = { serde_json::json!({ "grafana_url": "https://grafana.example.com", "matomo": { "site_id": 3 } }), true },
+        empty = { serde_json::json!({}), true },
+        string = { serde_json::json!("https://grafana.example.com"), false },
+        list = { serde_json::json!(["https://grafana.example.com"]), false },
+    )]
+    fn test_load_extra(extra: serde_json::Value, ok: bool) {
+        let mut config = serde_json::to_value(config_with_servers()).unwrap();
+        if !extra.is_null() {
+            config["meta"]["extra"] = extra.clone();
+        }
+        let file = tempfile::NamedTempFile::new().unwrap();
+        serde_json::to_writer(&file, &config).unwrap();
+
+        let loaded = ConfigManager::load(file.path().to_str().unwrap(), &[]);
+
+        if ok {
+            let loaded = serde_json::to_value(&loaded.unwrap().get_config().meta.extra).unwrap();
+            if extra.is_null() {
+                assert_eq!(loaded, serde_json::json!({}));
+            } else {
+                assert_eq!(loaded, extra);
+            }
+        } else {
+            assert!(
+                loaded.as_ref().is_err_and(|e| e.contains("expected a map")),
+                "{:?}",
+                loaded.err()
+            );
+        }
+    }
+
+    #[parameterized(
+        none = { &[], true },
+        configured = { &["sync.example.com"], true },
+        unknown = { &["sync.internal.example.com"], false },
+    )]
+    fn test_load_hidden_servers(hidden_servers: &[&str], valid: bool) {
+        let mut config = config_with_servers();
+        config.privacy.hidden_servers = hidden_servers.iter().map(|h| h.to_string()).collect();
+        let file = tempfile::NamedTempFile::new().unwrap();
+        serde_json::to_writer(&file, &config).unwrap();
+
+        let loaded = ConfigManager::load(file.path().to_str().unwrap(), &[]);
+
+        assert_eq!(loaded.is_ok(), valid, "{:?}", loaded.err());
+    }
+
+    #[parameterized(
+        string = { "meta.title=Test page", "/meta/title", Some(serde_json::json!("Test page")) },
+        quoted = { r#"meta.title="Test page""#, "/meta/title", Some(serde_json::json!("Test page")) },
+        boolean = { "limit_scraping_to_repositories=true", "/limit_scraping_to_repositories", Some(serde_json::json!(true)) },
+        number = { "geodb_max_age_days=7", "/geodb_max_age_days", Some(serde_json::json!(7)) },
+        list = { "repositories=[a.eessi.io, b.eessi.io]", "/repositories", Some(serde_json::json!(["a.eessi.io", "b.eessi.io"])) },
+        empty_list = { "ignored_repositories=[]", "/ignored_repositories", Some(serde_json::json!([])) },
+        index = { "servers.1.hostname=s1c.example.com", "/servers/1/hostname", Some(serde_json::json!("s1c.example.com")) },
+        optional = { "heartbeat_url=https://example.com/ping", "/heartbeat_url", Some(serde_json::json!("https://example.com/ping")) },
+        unknown = { "meta.colour=red", "", None },
+        unknown_nested = { "nope.title=x", "", None },
+        wrong_type = { "geodb_max_age_days=soon", "", None },
+        out_of_range = { "servers.9.hostname=x", "", None },
+        no_value = { "meta.title", "", None },
+    )]
+    fn test_apply_overrides(assignment: &str, pointer: &str, expected: Option<serde_json::Value>) {
+        let result = apply_overrides(config_with_servers(), &[assignment.to_string()]);
+
+        match expected {
+            Some(expected) => {
+                let config = serde_json::to_value(result.unwrap()).unwrap();
+                assert_eq!(config.pointer(pointer), Some(&expected));
+            }
+            None => {
+                let key = assignment.split('=').next().unwrap();
+                assert!(
+                    result.as_ref().is_err_and(|e| e.contains(key)),
+                    "{:?}",
+                    result.err()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_load_with_overrides() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        serde_json::to_writer(&file, &config_with_servers()).unwrap();
+        let overrides = [
+            "meta.title=Test page".to_string(),
+            "meta.title=Second".to_string(),
+        ];
+
+        let loaded = ConfigManager::load(file.path().to_str().unwrap(), &overrides).unwrap();
+
+        assert_eq!(loaded.get_config().meta.title, "Second");
+        assert!(loaded.as_json().contains("\"Second\""));
+    }
+
+    #[parameterized(
+        program = { "/usr/local/bin/notify-oncall", Some(&["/usr/local/bin/notify-oncall"][..]) },
+        arguments = { "notify --team eessi", Some(&["notify", "--team", "eessi"][..]) },
+        quoted = { r#"notify "EESSI ops" 'a b'"#, Some(&["notify", "EESSI ops", "a b"][..]) },
+        escaped_space = { r"notify EESSI\ ops", Some(&["notify", "EESSI ops"][..]) },
+        no_expansion = { "notify $HOME *", Some(&["notify", "$HOME", "*"][..]) },
+        unbalanced = { "notify \"EESSI", None },
+        empty = { "  ", None },
+    )]
+    fn test_split_command(command: &str, expected: Option<&[&str]>) {
+        let expected = expected.map(|args| args.iter().map(|a| a.to_string()).collect());
+        assert_eq!(split_command(command).ok(), expected);
+    }
 }
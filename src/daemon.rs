@@ -0,0 +1,146 @@
+use log::info;
+use rand::Rng;
+use std::time::Duration;
+use tokio::signal::unix::{signal, Signal, SignalKind};
+
+/// Parse a duration such as `90`, `90s`, `5m` or `1h`. A bare number is in seconds.
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => value.split_at(index),
+        None => (value, "s"),
+    };
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration '{}'", value))?;
+
+    let seconds = match unit {
+        "s" => number,
+        "m" => number * 60,
+        "h" => number * 60 * 60,
+        _ => {
+            return Err(format!(
+                "invalid duration '{}', use a number with s, m or h",
+                value
+            ))
+        }
+    };
+    Ok(Duration::from_secs(seconds))
+}
+
+/// When to run the next iteration.
+#[derive(Debug, Clone, Copy)]
+pub struct Schedule {
+    pub interval: Duration,
+    /// Up to this much is added to each interval at random, so instances started together
+    /// don't all scrape the servers at the same time.
+    pub jitter: Duration,
+}
+
+impl Schedule {
+    /// The delay before the next iteration, given how long the last one took.
+    pub fn next_delay(&self, elapsed: Duration) -> Duration {
+        let jitter = if self.jitter.is_zero() {
+            Duration::ZERO
+        } else {
+            rand::thread_rng().gen_range(Duration::ZERO..=self.jitter)
+        };
+        self.interval.saturating_sub(elapsed) + jitter
+    }
+}
+
+/// Why the daemon woke up.
+#[derive(Debug, PartialEq)]
+pub enum Wake {
+    /// The interval passed.
+    Tick,
+    /// SIGHUP, reload the configuration.
+    Reload,
+    /// SIGTERM or SIGINT, exit.
+    Shutdown,
+}
+
+/// The signals the daemon reacts to. Signals received while an iteration runs are kept, and
+/// handled once it has finished.
+pub struct Signals {
+    terminate: Signal,
+    interrupt: Signal,
+    hangup: Signal,
+}
+
+impl Signals {
+    pub fn new() -> std::io::Result<Self> {
+        Ok(Signals {
+            terminate: signal(SignalKind::terminate())?,
+            interrupt: signal(SignalKind::interrupt())?,
+            hangup: signal(SignalKind::hangup())?,
+        })
+    }
+
+    /// Sleep for `delay`, or until a signal arrives.
+    pub async fn wait(&mut self, delay: Duration) -> Wake {
+        tokio::select! {
+            biased;
+            _ = self.terminate.recv() => {
+                info!("Received SIGTERM");
+                Wake::Shutdown
+            }
+            _ = self.interrupt.recv() => {
+                info!("Received SIGINT");
+                Wake::Shutdown
+            }
+            _ = self.hangup.recv() => {
+                info!("Received SIGHUP");
+                Wake::Reload
+            }
+            _ = tokio::time::sleep(delay) => Wake::Tick,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yare::parameterized;
+
+    #[parameterized(
+        bare = { "90", Some(90) },
+        seconds = { "60s", Some(60) },
+        minutes = { "5m", Some(300) },
+        hours = { "1h", Some(3600) },
+        zero = { "0s", Some(0) },
+        unknown_unit = { "5d", None },
+        no_number = { "s", None },
+        empty = { "", None },
+    )]
+    fn test_parse_duration(value: &str, expected: Option<u64>) {
+        assert_eq!(
+            parse_duration(value).ok(),
+            expected.map(Duration::from_secs)
+        );
+    }
+
+    #[parameterized(
+        no_jitter = { 60, 0, 10, 50, 50 },
+        with_jitter = { 60, 10, 10, 50, 60 },
+        overran = { 60, 0, 90, 0, 0 },
+    )]
+    fn test_next_delay(interval: u64, jitter: u64, elapsed: u64, min: u64, max: u64) {
+        let schedule = Schedule {
+            interval: Duration::from_secs(interval),
+            jitter: Duration::from_secs(jitter),
+        };
+
+        for _ in 0..20 {
+            let delay = schedule.next_delay(Duration::from_secs(elapsed));
+            assert!(delay >= Duration::from_secs(min), "{:?} < {}s", delay, min);
+            assert!(delay <= Duration::from_secs(max), "{:?} > {}s", delay, max);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_wait_ticks() {
+        let mut signals = Signals::new().unwrap();
+        assert_eq!(signals.wait(Duration::from_millis(10)).await, Wake::Tick);
+    }
+}
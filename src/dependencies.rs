@@ -1,131 +1,860 @@
 use anyhow::{Context, Result};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
 use include_dir::{include_dir, Dir};
-use log::{debug, info, trace};
-use once_cell::sync::Lazy;
+use log::{debug, info, trace, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs;
 use std::io::Write;
-use std::path::Path;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
 use tempfile::NamedTempFile;
 
+use crate::templating::BUILTIN_TEMPLATES;
+
 const RESOURCES_DIR: Dir = include_dir!("resources");
-const STATUS_TEMPLATE: &str = include_str!("../templates/status.html");
 
-pub struct Stats {
-    files_checked: AtomicUsize,
-    files_written: AtomicUsize,
-    files_skipped: AtomicUsize,
+/// The extensions of the resources the pages load with a Subresource Integrity hash, and with
+/// a hashed name with `hashed_assets`.
+const ASSET_EXTENSIONS: [&str; 2] = ["css", "js"];
+
+/// The file in the destination with the hashed names of the stylesheets and scripts.
+pub(crate) const ASSET_MANIFEST_FILE: &str = "asset-manifest.json";
+
+/// The hidden file in the destination with the hashes of the resources and templates as they
+/// were written, to tell the ones modified locally from those of an older version, and to
+/// delete the ones no longer built in.
+pub(crate) const RESOURCES_MANIFEST_FILE: &str = ".resources-manifest.json";
+
+/// The resource files and templates checked, written, updated, skipped and pruned by a call of
+/// `populate`. Written ones were missing, updated ones differed from the built-in ones, and
+/// pruned ones were deleted as they are no longer built in or excluded. Excluded ones are
+/// neither checked nor written.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct FileCounts {
+    pub checked: usize,
+    pub written: usize,
+    #[serde(default)]
+    pub updated: usize,
+    pub skipped: usize,
+    #[serde(default)]
+    pub pruned: usize,
+    #[serde(default)]
+    pub excluded: usize,
 }
 
-impl Stats {
-    fn new() -> Self {
-        Stats {
-            files_checked: AtomicUsize::new(0),
-            files_written: AtomicUsize::new(0),
-            files_skipped: AtomicUsize::new(0),
-        }
+/// What `populate` did, with a warning for each file modified locally that was replaced or,
+/// with `keep_local_modifications`, kept, and the integrity hashes and, with `hashed_assets`,
+/// the hashed names of the stylesheets and scripts in the destination.
+#[derive(Debug, Default)]
+pub(crate) struct Populated {
+    pub files: FileCounts,
+    pub warnings: Vec<String>,
+    pub integrity: BTreeMap<String, String>,
+    pub assets: BTreeMap<String, String>,
+}
+
+/// The hashed names of the stylesheets and scripts, as `asset-manifest.json`.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
+pub(crate) struct AssetManifest {
+    /// The hashed name of every stylesheet and script, by its plain name.
+    pub assets: BTreeMap<String, String>,
+    /// The hashed names no longer in use, with the number of runs since they were, kept for
+    /// pages that are still cached.
+    #[serde(default)]
+    pub replaced: BTreeMap<String, usize>,
+}
+
+impl AssetManifest {
+    /// Read the manifest in `output_dir`. A missing or corrupt one is empty, which only means
+    /// the hashed files it named aren't deleted.
+    pub(crate) fn load(output_dir: &Path, sink: &OutputSink) -> Self {
+        let path = output_dir.join(ASSET_MANIFEST_FILE);
+        let Ok(contents) = sink.read(&path) else {
+            return AssetManifest::default();
+        };
+        serde_json::from_slice(&contents).unwrap_or_else(|e| {
+            warn!("Ignoring {:?}, failed to parse it: {}", path, e);
+            AssetManifest::default()
+        })
     }
 }
 
-// clippy gets this one wrong, we need the closure.
-#[allow(clippy::redundant_closure)]
-static STATS: Lazy<Stats> = Lazy::new(|| Stats::new());
+/// How `populate` treats the resources and templates in the destination.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct PopulateOptions<'a> {
+    /// A directory to copy the resources from, with its subdirectories, rather than write the
+    /// built-in ones.
+    pub resources_dir: Option<&'a Path>,
+    /// Link to the files in `resources_dir`, rather than copy them.
+    pub link_resources: bool,
+    /// Glob patterns of the resources not to write, such as `webfonts/**`.
+    pub exclude_resources: &'a [String],
+    /// Write every file, even those that are up to date or modified locally.
+    pub force: bool,
+    /// Keep the files that were modified since they were written, with a warning, rather than
+    /// replace them with the built-in ones.
+    pub keep_local_modifications: bool,
+    /// Also write the stylesheets and scripts under hashed names, and delete those replaced
+    /// this many runs ago.
+    pub hashed_assets_keep_runs: Option<usize>,
+    /// Only report the files that are no longer built in, rather than delete them.
+    pub prune_dry_run: bool,
+}
 
-pub fn populate(path: &str, force: bool) -> Result<()> {
+/// Write the resources and templates to `path` that aren't there or differ from the built-in
+/// ones, or those in `resources_dir`, and delete those written by a previous call that are no
+/// longer built in. With `hashed_assets_keep_runs`, the stylesheets and scripts are also
+/// written under hashed names.
+pub(crate) fn populate(
+    path: &str,
+    options: PopulateOptions,
+    sink: &OutputSink,
+) -> Result<Populated> {
     trace!("Contents of resources directory: {:?}", RESOURCES_DIR);
     let output_dir = Path::new(path);
     info!("Ensuring resources exist under: {:?}", output_dir);
-    fs::create_dir_all(output_dir).context("Failed to create output directory")?;
+    sink.create_dir_all(output_dir)?;
+
+    let has_manifest = sink.exists(&output_dir.join(RESOURCES_MANIFEST_FILE));
+    let (previous, corrupt) = match load_resources_manifest(output_dir, sink) {
+        Ok(previous) => (previous, None),
+        Err(reason) => (BTreeMap::new(), Some(reason)),
+    };
+    let mut population = Population {
+        output_dir,
+        options,
+        sink,
+        has_manifest,
+        exclude: exclude_set(options.exclude_resources)
+            .context("Invalid meta.exclude_resources")?,
+        previous,
+        written: BTreeMap::new(),
+        warnings: vec![],
+        files: FileCounts::default(),
+    };
+    match options.resources_dir {
+        Some(dir) => population.copy_dir(dir, Path::new(""))?,
+        None => population.write_dir(&RESOURCES_DIR)?,
+    }
+    for (name, contents) in BUILTIN_TEMPLATES {
+        population.write(
+            &Path::new("templates").join(name),
+            contents.as_bytes(),
+            Source::Builtin,
+        )?;
+    }
+    match corrupt {
+        // Any file could be listed, so none is deleted until it is written again.
+        Some(reason) => population.warnings.push(format!(
+            "Not deleting resources that are no longer built in, {:?} looks corrupt: {}",
+            output_dir.join(RESOURCES_MANIFEST_FILE),
+            reason
+        )),
+        None => population.prune(),
+    }
+    let json = serde_json::to_string_pretty(&population.written)?;
+    sink.atomic_write(&output_dir.join(RESOURCES_MANIFEST_FILE), json.as_bytes())?;
 
-    populate_dirs_and_files(&RESOURCES_DIR, output_dir, force)?;
-    populate_root_files(output_dir, force)?;
-    create_status_template(output_dir, force)?;
+    let integrity = asset_integrity(output_dir, sink)?;
+    let assets = match options.hashed_assets_keep_runs {
+        Some(keep_runs) => {
+            write_hashed_assets(
+                output_dir,
+                options.force,
+                keep_runs,
+                &mut population.files,
+                sink,
+            )?
+            .assets
+        }
+        None => BTreeMap::new(),
+    };
 
+    let files = population.files;
     debug!(
-        "Population of resource files complete. Files checked: {}, written: {}, skipped: {}",
-        STATS.files_checked.load(Ordering::Relaxed),
-        STATS.files_written.load(Ordering::Relaxed),
-        STATS.files_skipped.load(Ordering::Relaxed)
+        files_checked = files.checked,
+        files_written = files.written,
+        files_updated = files.updated,
+        files_skipped = files.skipped,
+        files_pruned = files.pruned,
+        files_excluded = files.excluded;
+        "Population of resource files complete. Files checked: {}, written: {}, updated: {}, skipped: {}, pruned: {}, excluded: {}",
+        files.checked,
+        files.written,
+        files.updated,
+        files.skipped,
+        files.pruned,
+        files.excluded
     );
 
-    Ok(())
+    Ok(Populated {
+        files,
+        warnings: population.warnings,
+        integrity,
+        assets,
+    })
 }
 
-fn populate_dirs_and_files(dir: &Dir, output_dir: &Path, force: bool) -> Result<()> {
-    for entry in dir.entries() {
-        match entry {
-            include_dir::DirEntry::Dir(subdir) => {
-                let subdir_path = output_dir.join(subdir.path());
-                trace!("Ensuring directory: {:?}", subdir_path);
-                fs::create_dir_all(&subdir_path)
-                    .context(format!("Failed to create directory: {:?}", subdir_path))?;
-                populate_dirs_and_files(subdir, &subdir_path, force)?;
-            }
-            include_dir::DirEntry::File(file) => {
-                write_file(file, output_dir, force)?;
-            }
+/// The stylesheets and scripts of the resources in `output_dir`, those in its root written by
+/// `populate`, either the built-in ones or those of `resources_dir`.
+fn asset_names(output_dir: &Path, sink: &OutputSink) -> Vec<String> {
+    load_resources_manifest(output_dir, sink)
+        .unwrap_or_default()
+        .into_keys()
+        .filter(|name| {
+            let path = Path::new(name);
+            path.parent() == Some(Path::new(""))
+                && path
+                    .extension()
+                    .and_then(|extension| extension.to_str())
+                    .is_some_and(|extension| ASSET_EXTENSIONS.contains(&extension))
+        })
+        .collect()
+}
+
+/// `name` with the start of the SHA-256 hash of `contents` before its extension, such as
+/// `status.1a2b3c4d.css`.
+fn hashed_name(name: &str, contents: &[u8]) -> String {
+    let hash: String = openssl::sha::sha256(contents)[..4]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    match name.rsplit_once('.') {
+        Some((stem, extension)) => format!("{}.{}.{}", stem, hash, extension),
+        None => format!("{}.{}", name, hash),
+    }
+}
+
+/// Write every stylesheet and script in `output_dir` again under its hashed name, which only
+/// changes with its contents, so a file that exists is up to date. The hashed names replaced
+/// `keep_runs` runs ago are deleted, and the new names written to the manifest. The files are
+/// counted in `files`.
+fn write_hashed_assets(
+    output_dir: &Path,
+    force: bool,
+    keep_runs: usize,
+    files: &mut FileCounts,
+    sink: &OutputSink,
+) -> Result<AssetManifest> {
+    let previous = AssetManifest::load(output_dir, sink);
+    let mut manifest = AssetManifest::default();
+    for name in asset_names(output_dir, sink) {
+        let path = output_dir.join(&name);
+        let contents = sink
+            .read(&path)
+            .context(format!("Failed to read {:?}", path))?;
+        let hashed = hashed_name(&name, &contents);
+        let hashed_path = output_dir.join(&hashed);
+        files.checked += 1;
+        if should_skip_file(&hashed_path, force, sink) {
+            files.skipped += 1;
+            trace!("Skipping existing hashed asset {:?}", hashed_path);
+        } else {
+            sink.atomic_write(&hashed_path, &contents)
+                .context(format!("Failed to write file: {:?}", hashed_path))?;
+            files.written += 1;
         }
+        manifest.assets.insert(name, hashed);
     }
-    Ok(())
+
+    let in_use: BTreeSet<&String> = manifest.assets.values().collect();
+    let replaced = previous
+        .assets
+        .into_values()
+        .map(|hashed| (hashed, 0))
+        .chain(previous.replaced);
+    for (hashed, runs) in replaced {
+        if in_use.contains(&hashed) {
+            continue;
+        }
+        if runs < keep_runs {
+            manifest.replaced.insert(hashed, runs + 1);
+            continue;
+        }
+        // The manifest may have been edited, only files directly in the destination are deleted.
+        if Path::new(&hashed).file_name() != Some(hashed.as_ref()) {
+            warn!("Not deleting {:?} from {}", hashed, ASSET_MANIFEST_FILE);
+            continue;
+        }
+        let path = output_dir.join(&hashed);
+        match sink.remove_file(&path) {
+            Ok(()) => info!(
+                "Deleted hashed asset replaced {} runs ago: {:?}",
+                runs, path
+            ),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!("Failed to delete hashed asset {:?}: {}", path, e),
+        }
+    }
+
+    let json = serde_json::to_string_pretty(&manifest)?;
+    sink.atomic_write(&output_dir.join(ASSET_MANIFEST_FILE), json.as_bytes())?;
+    Ok(manifest)
+}
+
+/// The Subresource Integrity hash of every stylesheet and script of the resources, by path
+/// relative to `output_dir`. They are of the files in `output_dir`, rather than the built-in
+/// ones, as those are what browsers load.
+pub(crate) fn asset_integrity(
+    output_dir: &Path,
+    sink: &OutputSink,
+) -> Result<BTreeMap<String, String>> {
+    asset_names(output_dir, sink)
+        .into_iter()
+        .map(|name| {
+            let path = output_dir.join(&name);
+            let contents = sink
+                .read(&path)
+                .context(format!("Failed to read {:?}", path))?;
+            Ok((name, integrity(&contents)))
+        })
+        .collect()
+}
+
+/// The value of an `integrity` attribute for `contents`.
+fn integrity(contents: &[u8]) -> String {
+    format!(
+        "sha384-{}",
+        openssl::base64::encode_block(&openssl::sha::sha384(contents))
+    )
 }
 
-fn populate_root_files(output_dir: &Path, force: bool) -> Result<()> {
-    for file in RESOURCES_DIR.files() {
-        write_file(file, output_dir, force)?;
+/// The matcher of the `exclude_resources` patterns, against the paths of the resources
+/// relative to the destination. `*` doesn't match a `/`, so `*.map` only matches the files in
+/// the root, and `**/*.map` all of them.
+pub(crate) fn exclude_set(patterns: &[String]) -> Result<GlobSet, globset::Error> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(GlobBuilder::new(pattern).literal_separator(true).build()?);
     }
-    Ok(())
+    builder.build()
+}
+
+/// The SHA-256 hashes of the files written by the previous call of `populate`, by path, empty
+/// if there was none. A manifest that doesn't parse, or names a path outside the destination or
+/// a hash that isn't SHA-256, is corrupt, as it may have been edited or truncated.
+fn load_resources_manifest(
+    output_dir: &Path,
+    sink: &OutputSink,
+) -> Result<BTreeMap<String, String>, String> {
+    let Ok(contents) = sink.read(&output_dir.join(RESOURCES_MANIFEST_FILE)) else {
+        return Ok(BTreeMap::new());
+    };
+    let manifest: BTreeMap<String, String> =
+        serde_json::from_slice(&contents).map_err(|e| e.to_string())?;
+    for (path, hash) in &manifest {
+        let relative = Path::new(path)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+        if path.is_empty() || !relative {
+            return Err(format!("{:?} is not a path in the destination", path));
+        }
+        if hash.len() != 64 || !hash.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')) {
+            return Err(format!("{:?} is not the SHA-256 hash of {:?}", hash, path));
+        }
+    }
+    Ok(manifest)
+}
+
+fn sha256_hex(contents: &[u8]) -> String {
+    openssl::sha::sha256(contents)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// How `populate` writes a file: with the built-in contents, as a copy of a file in
+/// `resources_dir` with its permissions, such as the executable bits, or as a link to it.
+#[derive(Clone, Copy)]
+enum Source<'a> {
+    Builtin,
+    Copy(u32),
+    Link(&'a Path),
+}
+
+impl Source<'_> {
+    /// Whether the file at `path`, which has the contents of the source, was written from it,
+    /// rather than from another source, such as a copy of a file it now links to.
+    fn wrote(&self, path: &Path) -> bool {
+        let Ok(metadata) = fs::symlink_metadata(path) else {
+            return false;
+        };
+        match self {
+            Source::Builtin => !metadata.is_symlink(),
+            Source::Copy(mode) => !metadata.is_symlink() && metadata.permissions().mode() == *mode,
+            Source::Link(target) => fs::read_link(path).is_ok_and(|link| link == *target),
+        }
+    }
+}
+
+/// A call of `populate`, with the hashes of the files as the previous call wrote them, to tell
+/// a file of an older version from one modified since.
+struct Population<'a> {
+    output_dir: &'a Path,
+    options: PopulateOptions<'a>,
+    sink: &'a OutputSink,
+    /// Whether the destination has a manifest. Without one, the files were written by a
+    /// version that didn't keep it, so those that differ are taken for older versions.
+    has_manifest: bool,
+    exclude: GlobSet,
+    previous: BTreeMap<String, String>,
+    written: BTreeMap<String, String>,
+    warnings: Vec<String>,
+    files: FileCounts,
 }
 
-fn write_file(file: &include_dir::File, output_dir: &Path, force: bool) -> Result<()> {
-    let output_path = output_dir.join(file.path());
-    STATS.files_checked.fetch_add(1, Ordering::Relaxed);
-    trace!("Checking resource file: {:?}", file.path());
-    if should_skip_file(&output_path, force) {
-        STATS.files_skipped.fetch_add(1, Ordering::Relaxed);
-        trace!("Skipping existing file {:?}", output_path);
-        return Ok(());
+impl Population<'_> {
+    fn write_dir(&mut self, dir: &Dir) -> Result<()> {
+        for entry in dir.entries() {
+            match entry {
+                include_dir::DirEntry::Dir(subdir) => self.write_dir(subdir)?,
+                include_dir::DirEntry::File(file) if self.is_excluded(file.path()) => {}
+                include_dir::DirEntry::File(file) => {
+                    self.write(file.path(), file.contents(), Source::Builtin)?
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the files in `path` under `dir`, and its subdirectories, following links.
+    fn copy_dir(&mut self, dir: &Path, path: &Path) -> Result<()> {
+        let source_dir = dir.join(path);
+        let mut entries = fs::read_dir(&source_dir)
+            .context(format!(
+                "Failed to read resources directory {:?}",
+                source_dir
+            ))?
+            .collect::<std::io::Result<Vec<_>>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+        for entry in entries {
+            let source = entry.path();
+            let path = path.join(entry.file_name());
+            let metadata = fs::metadata(&source).context(format!("Failed to read {:?}", source))?;
+            if metadata.is_dir() {
+                self.copy_dir(dir, &path)?;
+                continue;
+            }
+            if self.is_excluded(&path) {
+                continue;
+            }
+            let contents = fs::read(&source).context(format!("Failed to read {:?}", source))?;
+            if self.options.link_resources {
+                // Absolute, as a relative link is relative to the link.
+                let target =
+                    fs::canonicalize(&source).context(format!("Failed to resolve {:?}", source))?;
+                self.write(&path, &contents, Source::Link(&target))?;
+            } else {
+                let mode = metadata.permissions().mode();
+                self.write(&path, &contents, Source::Copy(mode))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the resource at `path` is excluded, which counts it.
+    fn is_excluded(&mut self, path: &Path) -> bool {
+        let excluded = self.exclude.is_match(path);
+        if excluded {
+            self.files.excluded += 1;
+            trace!("Excluding resource file {:?}", path);
+        }
+        excluded
     }
-    trace!("Writing file {:?}", file.path());
-    ensure_parent_dir(&output_path)?;
-    atomic_write(&output_path, file.contents())
+
+    /// Write `contents` to `path` in the destination, unless it is there already. A file that
+    /// differs is updated if it is as the previous call wrote it, an older version, or only
+    /// differs in how it was written, such as a copy that is now linked. Without a manifest,
+    /// every file that differs is taken for an older version. One that was modified since is
+    /// replaced too, unless modifications are kept.
+    fn write(&mut self, path: &Path, contents: &[u8], source: Source) -> Result<()> {
+        let output_path = self.output_dir.join(path);
+        let key = path.to_str().context("Invalid resource path")?.to_string();
+        let hash = sha256_hex(contents);
+        self.files.checked += 1;
+        trace!("Checking resource file: {:?}", output_path);
+
+        let existing = self
+            .sink
+            .read(&output_path)
+            .ok()
+            .map(|existing| sha256_hex(&existing));
+        let updated = match existing {
+            None => false,
+            Some(_) if self.options.force => false,
+            Some(existing) if existing == hash && source.wrote(&output_path) => {
+                self.files.skipped += 1;
+                trace!("Skipping up to date file {:?}", output_path);
+                self.written.insert(key, hash);
+                return Ok(());
+            }
+            Some(existing)
+                if existing == hash
+                    || !self.has_manifest
+                    || self.previous.get(&key) == Some(&existing) =>
+            {
+                true
+            }
+            Some(_) if self.options.keep_local_modifications => {
+                self.files.skipped += 1;
+                self.warnings.push(format!(
+                    "Kept {:?}, which was modified locally and differs from the built-in one",
+                    output_path
+                ));
+                // Still modified locally the next time, unless it is edited back, and listed
+                // with the stylesheets and scripts.
+                let previous = self.previous.get(&key).cloned();
+                self.written.insert(key, previous.unwrap_or(hash));
+                return Ok(());
+            }
+            Some(_) => {
+                self.warnings.push(format!(
+                    "Replaced {:?}, which was modified locally, with the built-in one, use --keep-local-modifications to keep it",
+                    output_path
+                ));
+                true
+            }
+        };
+        trace!("Writing file {:?}", output_path);
+        if let Some(parent) = output_path.parent() {
+            self.sink.create_dir_all(parent)?;
+        }
+        match source {
+            Source::Builtin => self.sink.atomic_write(&output_path, contents),
+            Source::Copy(mode) => self
+                .sink
+                .atomic_write(&output_path, contents)
+                .and_then(|()| self.sink.set_mode(&output_path, mode)),
+            Source::Link(target) => self.sink.symlink(target, &output_path, contents),
+        }
         .context(format!("Failed to write file: {:?}", output_path))?;
-    STATS.files_written.fetch_add(1, Ordering::Relaxed);
-    Ok(())
+        if updated {
+            self.files.updated += 1;
+        } else {
+            self.files.written += 1;
+        }
+        self.written.insert(key, hash);
+        Ok(())
+    }
+
+    /// Delete the files the previous call wrote that are no longer built in or are excluded,
+    /// those not written by this one. One that was modified since is kept with
+    /// `keep_local_modifications`, and with `prune_dry_run` they are only reported. The kept
+    /// ones stay in the manifest.
+    fn prune(&mut self) {
+        let stale: Vec<(String, String)> = self
+            .previous
+            .iter()
+            .filter(|(path, _)| !self.written.contains_key(*path))
+            .map(|(path, hash)| (path.clone(), hash.clone()))
+            .collect();
+        for (key, hash) in stale {
+            let path = self.output_dir.join(&key);
+            let modified = match self.sink.read(&path) {
+                Ok(contents) => sha256_hex(&contents) != hash,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => {
+                    warn!("Failed to read {:?}: {}", path, e);
+                    self.written.insert(key, hash);
+                    continue;
+                }
+            };
+            let reason = if self.exclude.is_match(&key) {
+                "is excluded"
+            } else {
+                "is no longer a built-in resource"
+            };
+            if modified && self.options.keep_local_modifications {
+                self.warnings.push(format!(
+                    "Kept {:?}, which {}, as it was modified locally",
+                    path, reason
+                ));
+            } else if self.options.prune_dry_run {
+                self.warnings
+                    .push(format!("Would delete {:?}, which {}", path, reason));
+            } else {
+                match self.sink.remove_file(&path) {
+                    Ok(()) => {
+                        self.files.pruned += 1;
+                        info!("Deleted {:?}, which {}", path, reason);
+                        if modified {
+                            self.warnings.push(format!(
+                                "Deleted {:?}, which {}, though it was modified locally, use --keep-local-modifications to keep it",
+                                path, reason
+                            ));
+                        }
+                        self.remove_empty_parents(&path);
+                        continue;
+                    }
+                    Err(e) => warn!("Failed to delete {:?}: {}", path, e),
+                }
+            }
+            self.written.insert(key, hash);
+        }
+    }
+
+    /// Delete the directories above `path` left empty, such as those of a font that was
+    /// dropped, up to the destination.
+    fn remove_empty_parents(&self, path: &Path) {
+        for parent in path.ancestors().skip(1) {
+            if parent == self.output_dir
+                || self.sink.is_dry_run()
+                || fs::remove_dir(parent).is_err()
+            {
+                break;
+            }
+        }
+    }
+}
+
+fn should_skip_file(path: &Path, force: bool, sink: &OutputSink) -> bool {
+    sink.exists(path) && !force
+}
+
+/// What a write of a dry run would have done to its file: create it, update it, leave it as it
+/// is as the contents are the same, or delete it.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum WriteAction {
+    Create,
+    Update,
+    Skip,
+    Delete,
+}
+
+/// A write of a dry run, for the run report.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PlannedWrite {
+    pub path: PathBuf,
+    pub action: WriteAction,
+    pub bytes: u64,
+}
+
+/// The writes of a dry run, and the contents they would have left, so the files written
+/// earlier in the run are read as they would be, `None` for a deleted one.
+#[derive(Default)]
+struct DryRun {
+    writes: Vec<PlannedWrite>,
+    contents: HashMap<PathBuf, Option<Vec<u8>>>,
+}
+
+/// Whether a run writes its files, or only reports the writes it would make.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WriteMode {
+    #[default]
+    Write,
+    /// The writes, directories and deletions are only logged and listed.
+    DryRun,
 }
 
-fn should_skip_file(path: &Path, force: bool) -> bool {
-    path.exists() && !force
+/// Where the writers write the files of a run to: the destination, or in a dry run the list of
+/// the writes they would have made. Every writer is given the sink of the run, so nothing it
+/// writes escapes a dry run, from whichever thread or task it writes.
+#[derive(Default)]
+pub struct OutputSink {
+    options: WriteOptions,
+    dry_run: Option<Mutex<DryRun>>,
 }
 
-fn ensure_parent_dir(path: &Path) -> Result<()> {
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent)
-            .context(format!("Failed to create parent directory: {:?}", parent))?;
+impl OutputSink {
+    pub fn new(mode: WriteMode, options: WriteOptions) -> Self {
+        OutputSink {
+            options,
+            dry_run: (mode == WriteMode::DryRun).then(Mutex::default),
+        }
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run.is_some()
+    }
+
+    /// The writes of a dry run so far, in the order they were made, none when writing.
+    pub fn planned_writes(&self) -> Vec<PlannedWrite> {
+        self.dry_run
+            .as_ref()
+            .map(|dry_run| dry_run.lock().unwrap().writes.clone())
+            .unwrap_or_default()
+    }
+
+    /// Keep `contents` as written to `path`, or deleted if `None`, when in a dry run,
+    /// returning whether it was.
+    fn plan(&self, path: &Path, contents: Option<&[u8]>) -> bool {
+        let Some(dry_run) = &self.dry_run else {
+            return false;
+        };
+        let existing = self.read(path).ok();
+        let (action, bytes) = match (&existing, contents) {
+            (None, None) => return true,
+            (Some(existing), None) => (WriteAction::Delete, existing.len()),
+            (None, Some(contents)) => (WriteAction::Create, contents.len()),
+            (Some(existing), Some(contents)) if existing == contents => {
+                (WriteAction::Skip, contents.len())
+            }
+            (Some(_), Some(contents)) => (WriteAction::Update, contents.len()),
+        };
+        info!("Dry run, would {:?} {:?}, {} bytes", action, path, bytes);
+        let mut dry_run = dry_run.lock().unwrap();
+        dry_run.writes.push(PlannedWrite {
+            path: path.to_path_buf(),
+            action,
+            bytes: bytes as u64,
+        });
+        dry_run
+            .contents
+            .insert(path.to_path_buf(), contents.map(<[u8]>::to_vec));
+        true
+    }
+
+    /// The contents of `path`, as written earlier in a dry run if it was.
+    pub(crate) fn read(&self, path: &Path) -> std::io::Result<Vec<u8>> {
+        let planned = self
+            .dry_run
+            .as_ref()
+            .and_then(|dry_run| dry_run.lock().unwrap().contents.get(path).cloned());
+        match planned {
+            Some(Some(contents)) => Ok(contents),
+            Some(None) => Err(std::io::ErrorKind::NotFound.into()),
+            None => fs::read(path),
+        }
+    }
+
+    /// Whether there is a file at `path`, as the writes planned in a dry run would have left it.
+    pub(crate) fn exists(&self, path: &Path) -> bool {
+        let planned = self.dry_run.as_ref().and_then(|dry_run| {
+            dry_run
+                .lock()
+                .unwrap()
+                .contents
+                .get(path)
+                .map(Option::is_some)
+        });
+        planned.unwrap_or_else(|| path.exists())
+    }
+
+    /// Create `path` and its parents, which a dry run leaves to its writes.
+    pub fn create_dir_all(&self, path: &Path) -> Result<()> {
+        if self.is_dry_run() {
+            return Ok(());
+        }
+        fs::create_dir_all(path).context(format!("Failed to create directory: {:?}", path))
+    }
+
+    /// Delete the file at `path`, or plan to in a dry run.
+    pub(crate) fn remove_file(&self, path: &Path) -> std::io::Result<()> {
+        if self.is_dry_run() {
+            if self.read(path).is_err() {
+                return Err(std::io::ErrorKind::NotFound.into());
+            }
+            self.plan(path, None);
+            return Ok(());
+        }
+        fs::remove_file(path)
+    }
+
+    /// Add `contents` to the end of the file at `path`, creating it if needed. Unlike
+    /// `atomic_write`, the file is written in place, as only its end changes.
+    pub(crate) fn append(&self, path: &Path, contents: &[u8]) -> Result<()> {
+        if self.is_dry_run() {
+            let mut appended = self.read(path).unwrap_or_default();
+            appended.extend_from_slice(contents);
+            self.plan(path, Some(&appended));
+            return Ok(());
+        }
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .context(format!("Failed to open {:?}", path))?;
+        file.write_all(contents)
+            .context(format!("Failed to write {:?}", path))
+    }
+
+    /// Set the permissions of the file at `path` to `mode`, unless in a dry run.
+    fn set_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        if self.is_dry_run() {
+            return Ok(());
+        }
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))
+            .context(format!("Failed to set the permissions of {:?}", path))
+    }
+
+    /// Replace the file at `path` with a link to `target`, which has `contents`, atomically
+    /// like `atomic_write`.
+    fn symlink(&self, target: &Path, path: &Path, contents: &[u8]) -> Result<()> {
+        if self.plan(path, Some(contents)) {
+            return Ok(());
+        }
+        self.replace_link(target, path)
+    }
+
+    /// Replace the file or link at `path` with a link to `target`, atomically, and sync its
+    /// directory as `atomic_write` does.
+    pub(crate) fn replace_link(&self, target: &Path, path: &Path) -> Result<()> {
+        let dir = path.parent().context("Invalid path: no parent directory")?;
+        let link = tempfile::Builder::new()
+            .make_in(dir, |temp| std::os::unix::fs::symlink(target, temp))
+            .context(format!("Failed to create a link in {:?}", dir))?;
+        link.persist(path)
+            .map_err(|e| e.error)
+            .context(format!("Failed to persist link to {:?}", path))?;
+        if self.options.durability == Durability::Full {
+            sync_dir(dir)?;
+        }
+        Ok(())
+    }
+
+    pub fn atomic_write(&self, path: &Path, contents: &[u8]) -> Result<()> {
+        if self.plan(path, Some(contents)) {
+            return Ok(());
+        }
+        let dir = path.parent().context("Invalid path: no parent directory")?;
+        let temp_file = write_temp_file(dir, path, contents, self.options)?;
+        persist(temp_file, path, self.options)
     }
-    Ok(())
 }
 
-fn create_status_template(output_dir: &Path, force: bool) -> Result<()> {
-    let template_path = output_dir.join("templates").join("status.html");
-    STATS.files_checked.fetch_add(1, Ordering::Relaxed);
-    trace!("Checking status template: {:?}", template_path);
-    if should_skip_file(&template_path, force) {
-        STATS.files_skipped.fetch_add(1, Ordering::Relaxed);
-        trace!("Skipping existing status template");
-        return Ok(());
-    }
-    trace!("Creating status template: {:?}", template_path);
-    ensure_parent_dir(&template_path)?;
-    atomic_write(&template_path, STATUS_TEMPLATE.as_bytes()).context(format!(
-        "Failed to create status template: {:?}",
-        template_path
-    ))?;
-    STATS.files_written.fetch_add(1, Ordering::Relaxed);
-    Ok(())
+/// How hard `atomic_write` tries to have a file survive a crash of the machine.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Durability {
+    /// Sync the file before renaming it into place, and its directory after, so the file is
+    /// either the old or the new one.
+    #[default]
+    Full,
+    /// Only sync the file, so the rename may be lost, but never leaves an empty file.
+    File,
+    /// Leave both to the operating system, for filesystems where syncing is slow.
+    None,
 }
 
-pub fn atomic_write(path: &Path, contents: &[u8]) -> Result<()> {
-    let dir = path.parent().context("Invalid path: no parent directory")?;
+/// How `atomic_write` writes files, from the `meta` section.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WriteOptions {
+    pub durability: Durability,
+    /// The permissions of a new file. A replaced file keeps those of the old one.
+    pub file_mode: u32,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        WriteOptions {
+            durability: Durability::Full,
+            file_mode: 0o644,
+        }
+    }
+}
+
+/// Write `contents` to a temporary file in `dir`, with the permissions, and when running as
+/// root the owner and group, of the file at `path` it is to replace. `dir` is the directory of
+/// `path`, so `persist` can rename it without crossing filesystems.
+fn write_temp_file(
+    dir: &Path,
+    path: &Path,
+    contents: &[u8],
+    options: WriteOptions,
+) -> Result<NamedTempFile> {
     let mut temp_file = NamedTempFile::new_in(dir)
         .context(format!("Failed to create temporary file in {:?}", dir))?;
     trace!("Writing to temporary file {:?}", temp_file.path());
@@ -135,9 +864,809 @@ pub fn atomic_write(path: &Path, contents: &[u8]) -> Result<()> {
     temp_file
         .flush()
         .context("Failed to flush temporary file")?;
+    copy_permissions(temp_file.as_file(), path, options.file_mode)?;
+    if options.durability != Durability::None {
+        temp_file
+            .as_file()
+            .sync_all()
+            .context("Failed to sync temporary file")?;
+    }
+    Ok(temp_file)
+}
+
+/// Give `file` the permissions of the file at `path`, or `file_mode` if there is none yet.
+/// Only root can give a file away, so the owner and group are only copied when running as root,
+/// which the new file is owned by.
+fn copy_permissions(file: &fs::File, path: &Path, file_mode: u32) -> Result<()> {
+    let existing = match fs::metadata(path) {
+        Ok(existing) => existing,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return file
+                .set_permissions(fs::Permissions::from_mode(file_mode))
+                .context("Failed to set the permissions of temporary file");
+        }
+        Err(e) => return Err(e).context(format!("Failed to read the permissions of {:?}", path)),
+    };
+    let temp = file
+        .metadata()
+        .context("Failed to read the owner of temporary file")?;
+    if temp.uid() == 0 && (temp.uid(), temp.gid()) != (existing.uid(), existing.gid()) {
+        std::os::unix::fs::fchown(file, Some(existing.uid()), Some(existing.gid())).context(
+            format!("Failed to give temporary file the owner of {:?}", path),
+        )?;
+    }
+    file.set_permissions(existing.permissions())
+        .context("Failed to set the permissions of temporary file")
+}
+
+/// Rename `temp_file`, which was created in the directory of `path`, to `path`.
+///
+/// Both are entries of the same directory, even when it is a link to another mount, so the
+/// rename never fails with `EXDEV` and there is no need to fall back to copying the file.
+fn persist(temp_file: NamedTempFile, path: &Path, options: WriteOptions) -> Result<()> {
     trace!("Renaming temporary file to {:?}", path);
+    let dir = path.parent().context("Invalid path: no parent directory")?;
     temp_file
         .persist(path)
+        .map_err(|e| e.error)
         .context(format!("Failed to persist file to {:?}", path))?;
+    if options.durability == Durability::Full {
+        sync_dir(dir)?;
+    }
     Ok(())
 }
+
+/// Sync the entries of `dir`, so a file renamed into it is still there after a crash.
+fn sync_dir(dir: &Path) -> Result<()> {
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    fs::File::open(dir)
+        .and_then(|dir| dir.sync_all())
+        .context(format!("Failed to sync directory {:?}", dir))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yare::parameterized;
+
+    #[parameterized(
+        full = { Durability::Full },
+        file = { Durability::File },
+        none = { Durability::None },
+    )]
+    fn test_write_temp_file(durability: Durability) -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("status.json");
+
+        let options = WriteOptions {
+            durability,
+            ..Default::default()
+        };
+        let temp_file = write_temp_file(dir.path(), &path, b"{}", options)?;
+        persist(temp_file, &path, options)?;
+
+        assert_eq!(fs::read(&path)?, b"{}");
+        assert_eq!(fs::read_dir(dir.path())?.count(), 1);
+        Ok(())
+    }
+
+    #[parameterized(
+        new = { None, 0o644, 0o644 },
+        new_configured = { None, 0o600, 0o600 },
+        replaced = { Some(0o640), 0o644, 0o640 },
+        replaced_executable = { Some(0o755), 0o600, 0o755 },
+    )]
+    fn test_write_temp_file_mode(
+        existing: Option<u32>,
+        file_mode: u32,
+        expected: u32,
+    ) -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("index.html");
+        if let Some(mode) = existing {
+            fs::write(&path, "old")?;
+            fs::set_permissions(&path, fs::Permissions::from_mode(mode))?;
+        }
+        let options = WriteOptions {
+            file_mode,
+            ..Default::default()
+        };
+
+        let temp_file = write_temp_file(dir.path(), &path, b"new", options)?;
+        persist(temp_file, &path, options)?;
+
+        let metadata = fs::metadata(&path)?;
+        assert_eq!(metadata.permissions().mode() & 0o7777, expected);
+        // Running as someone else, the owner stays as it was; as root, the file was already
+        // root's.
+        assert_eq!(metadata.uid(), fs::metadata(dir.path())?.uid());
+        Ok(())
+    }
+
+    #[test]
+    fn test_persist_error() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        // The file is renamed into a directory that doesn't exist.
+        let path = dir.path().join("missing").join("status.json");
+
+        let options = WriteOptions::default();
+        let temp_file = write_temp_file(dir.path(), &path, b"new", options)?;
+        let persisted = persist(temp_file, &path, options);
+
+        assert!(format!("{:#}", persisted.unwrap_err()).starts_with("Failed to persist file"));
+        assert_eq!(fs::read_dir(dir.path())?.count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_integrity() {
+        assert_eq!(
+            integrity(b""),
+            "sha384-OLBgp1GsljhM2TJ+sbHjaiH9txEUvgdDTAzHv2P24donTt6/529l+9Ua0vFImLlb"
+        );
+    }
+
+    #[parameterized(
+        plain = { "status.css", b"body {}", "status.62368a1a.css" },
+        dots = { "fa.all.min.css", b"body {}", "fa.all.min.62368a1a.css" },
+        no_extension = { "LICENSE", b"", "LICENSE.e3b0c442" },
+    )]
+    fn test_hashed_name(name: &str, contents: &[u8], expected: &str) {
+        assert_eq!(hashed_name(name, contents), expected);
+    }
+
+    fn hashed_assets(keep_runs: usize) -> PopulateOptions<'static> {
+        PopulateOptions {
+            keep_local_modifications: true,
+            hashed_assets_keep_runs: Some(keep_runs),
+            ..Default::default()
+        }
+    }
+
+    fn keep_local_modifications() -> PopulateOptions<'static> {
+        PopulateOptions {
+            keep_local_modifications: true,
+            ..Default::default()
+        }
+    }
+
+    fn force() -> PopulateOptions<'static> {
+        PopulateOptions {
+            force: true,
+            ..Default::default()
+        }
+    }
+
+    #[parameterized(
+        directory = { "webfonts/**", "webfonts/fa-solid-900.woff2", true },
+        directory_other = { "webfonts/**", "status.css", false },
+        extension = { "*.map", "status.css.map", true },
+        extension_nested = { "*.map", "webfonts/fa.map", false },
+        extension_anywhere = { "**/*.map", "webfonts/fa.map", true },
+        extension_anywhere_root = { "**/*.map", "status.css.map", true },
+        file = { "eessi-512px.png", "eessi-512px.png", true },
+        alternatives = { "webfonts/*.{eot,ttf}", "webfonts/fa-solid-900.ttf", true },
+        alternatives_other = { "webfonts/*.{eot,ttf}", "webfonts/fa-solid-900.woff2", false },
+    )]
+    fn test_exclude_set(pattern: &str, path: &str, excluded: bool) {
+        let exclude = exclude_set(&[pattern.to_string()]).unwrap();
+        assert_eq!(exclude.is_match(path), excluded);
+    }
+
+    #[test]
+    fn test_exclude_set_invalid() {
+        assert!(exclude_set(&["webfonts/[".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_populate_exclude_resources() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let destination = dir.path().to_str().unwrap();
+        let all = populate(
+            destination,
+            PopulateOptions::default(),
+            &OutputSink::default(),
+        )?
+        .files;
+        let fonts = RESOURCES_DIR.get_dir("webfonts").unwrap().files().count();
+        let exclude = ["webfonts/**".to_string(), "*.map".to_string()];
+        let options = PopulateOptions {
+            exclude_resources: &exclude,
+            ..Default::default()
+        };
+
+        let populated = populate(destination, options, &OutputSink::default())?;
+
+        assert_eq!(populated.files.excluded, fonts);
+        assert_eq!(populated.files.checked, all.checked - fonts);
+        assert_eq!(populated.files.skipped, all.checked - fonts);
+        // The copies written before are deleted.
+        assert_eq!(populated.files.pruned, fonts);
+        assert!(!dir.path().join("webfonts").exists());
+        assert!(dir.path().join("status.css").exists());
+
+        // And not written in a fresh destination.
+        let fresh = tempfile::tempdir()?;
+        let populated = populate(
+            fresh.path().to_str().unwrap(),
+            options,
+            &OutputSink::default(),
+        )?;
+        assert_eq!(populated.files.pruned, 0);
+        assert!(!fresh.path().join("webfonts").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_populate_hashed_assets() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let destination = dir.path().to_str().unwrap();
+
+        let first = populate(destination, hashed_assets(1), &OutputSink::default())?.assets;
+        assert_eq!(
+            first.keys().collect::<Vec<_>>(),
+            ["fa.all.min.css", "status.css", "status.js"]
+        );
+        for (name, hashed) in &first {
+            assert_eq!(
+                fs::read(dir.path().join(hashed))?,
+                fs::read(dir.path().join(name))?
+            );
+        }
+        assert_eq!(
+            AssetManifest::load(dir.path(), &OutputSink::default()).assets,
+            first
+        );
+
+        // A changed stylesheet gets a new name, the old one is kept for a run.
+        fs::write(dir.path().join("status.css"), "body {}")?;
+        let second = populate(destination, hashed_assets(1), &OutputSink::default())?.assets;
+        assert_eq!(second["status.css"], "status.62368a1a.css");
+        assert_eq!(second["status.js"], first["status.js"]);
+        let manifest = AssetManifest::load(dir.path(), &OutputSink::default());
+        assert_eq!(manifest.assets, second);
+        assert_eq!(
+            manifest.replaced,
+            BTreeMap::from([(first["status.css"].clone(), 1)])
+        );
+        assert!(dir.path().join(&first["status.css"]).exists());
+
+        populate(destination, hashed_assets(1), &OutputSink::default())?;
+        assert!(!dir.path().join(&first["status.css"]).exists());
+        assert!(AssetManifest::load(dir.path(), &OutputSink::default())
+            .replaced
+            .is_empty());
+        assert!(dir.path().join(&second["status.css"]).exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_populate_hashed_assets_edited_manifest() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let outside = tempfile::NamedTempFile::new()?;
+        let manifest = AssetManifest {
+            assets: BTreeMap::from([(
+                "status.css".to_string(),
+                outside.path().to_str().unwrap().to_string(),
+            )]),
+            replaced: BTreeMap::new(),
+        };
+        fs::write(
+            dir.path().join(ASSET_MANIFEST_FILE),
+            serde_json::to_string(&manifest)?,
+        )?;
+
+        populate(
+            dir.path().to_str().unwrap(),
+            hashed_assets(0),
+            &OutputSink::default(),
+        )?;
+
+        assert!(outside.path().exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_populate_integrity() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let destination = dir.path().to_str().unwrap();
+        let builtin = integrity(RESOURCES_DIR.get_file("status.css").unwrap().contents());
+
+        let populated = populate(
+            destination,
+            PopulateOptions::default(),
+            &OutputSink::default(),
+        )?;
+        assert_eq!(
+            populated.integrity.keys().collect::<Vec<_>>(),
+            ["fa.all.min.css", "status.css", "status.js"]
+        );
+        assert_eq!(populated.integrity["status.css"], builtin);
+        assert!(populated.warnings.is_empty(), "{:?}", populated.warnings);
+
+        // A kept file is hashed as it is.
+        fs::write(dir.path().join("status.css"), "body {}")?;
+        let populated = populate(
+            destination,
+            keep_local_modifications(),
+            &OutputSink::default(),
+        )?;
+        assert_eq!(populated.integrity["status.css"], integrity(b"body {}"));
+
+        let populated = populate(destination, force(), &OutputSink::default())?;
+        assert_eq!(populated.integrity["status.css"], builtin);
+        assert!(populated.warnings.is_empty(), "{:?}", populated.warnings);
+        assert_eq!(
+            asset_integrity(dir.path(), &OutputSink::default())?,
+            populated.integrity
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_populate_unchanged() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let destination = dir.path().to_str().unwrap();
+        let first = populate(
+            destination,
+            PopulateOptions::default(),
+            &OutputSink::default(),
+        )?
+        .files;
+        assert_eq!(first.written, first.checked);
+
+        let second = populate(
+            destination,
+            PopulateOptions::default(),
+            &OutputSink::default(),
+        )?;
+
+        assert_eq!(
+            second.files,
+            FileCounts {
+                checked: first.checked,
+                written: 0,
+                updated: 0,
+                skipped: first.checked,
+                pruned: 0,
+                excluded: 0,
+            }
+        );
+        assert!(second.warnings.is_empty(), "{:?}", second.warnings);
+        // The fonts are where the stylesheet loads them from.
+        assert!(dir
+            .path()
+            .join("webfonts")
+            .join("fa-solid-900.woff2")
+            .exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_populate_counts_each_call() -> Result<()> {
+        let first = tempfile::tempdir()?;
+        let second = tempfile::tempdir()?;
+        let options = PopulateOptions {
+            hashed_assets_keep_runs: Some(1),
+            ..Default::default()
+        };
+
+        let first = populate(
+            first.path().to_str().unwrap(),
+            options,
+            &OutputSink::default(),
+        )?
+        .files;
+        let second = populate(
+            second.path().to_str().unwrap(),
+            options,
+            &OutputSink::default(),
+        )?
+        .files;
+
+        assert_eq!(second, first);
+        assert_eq!(
+            first,
+            FileCounts {
+                checked: first.checked,
+                written: first.checked,
+                ..Default::default()
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_populate_changed_upstream() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let destination = dir.path().to_str().unwrap();
+        populate(
+            destination,
+            PopulateOptions::default(),
+            &OutputSink::default(),
+        )?;
+        // As an older version wrote it.
+        fs::write(dir.path().join("status.css"), "body {}")?;
+        let mut written: BTreeMap<String, String> = serde_json::from_str(&fs::read_to_string(
+            dir.path().join(RESOURCES_MANIFEST_FILE),
+        )?)?;
+        written.insert("status.css".to_string(), sha256_hex(b"body {}"));
+        fs::write(
+            dir.path().join(RESOURCES_MANIFEST_FILE),
+            serde_json::to_string(&written)?,
+        )?;
+
+        let populated = populate(
+            destination,
+            keep_local_modifications(),
+            &OutputSink::default(),
+        )?;
+
+        assert_eq!(populated.files.updated, 1);
+        assert_eq!(populated.files.written, 0);
+        assert!(populated.warnings.is_empty(), "{:?}", populated.warnings);
+        assert_eq!(
+            fs::read(dir.path().join("status.css"))?,
+            RESOURCES_DIR.get_file("status.css").unwrap().contents()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_populate_without_manifest() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let destination = dir.path().to_str().unwrap();
+        populate(
+            destination,
+            PopulateOptions::default(),
+            &OutputSink::default(),
+        )?;
+        // As a version that didn't keep the manifest left the destination.
+        fs::remove_file(dir.path().join(RESOURCES_MANIFEST_FILE))?;
+        fs::write(dir.path().join("status.css"), "body {}")?;
+        fs::write(dir.path().join("templates/status.html"), "<html></html>")?;
+
+        let populated = populate(
+            destination,
+            keep_local_modifications(),
+            &OutputSink::default(),
+        )?;
+
+        assert_eq!(populated.files.updated, 2);
+        assert!(populated.warnings.is_empty(), "{:?}", populated.warnings);
+        assert_eq!(
+            fs::read(dir.path().join("status.css"))?,
+            RESOURCES_DIR.get_file("status.css").unwrap().contents()
+        );
+        assert!(dir.path().join(RESOURCES_MANIFEST_FILE).exists());
+        Ok(())
+    }
+
+    #[parameterized(
+        replaced = { false, "status.css", 1 },
+        kept = { true, "status.css", 0 },
+        template_replaced = { false, "templates/status.html", 1 },
+        template_kept = { true, "templates/status.html", 0 },
+    )]
+    fn test_populate_modified_locally(keep: bool, path: &str, updated: usize) -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let destination = dir.path().to_str().unwrap();
+        populate(
+            destination,
+            PopulateOptions::default(),
+            &OutputSink::default(),
+        )?;
+        fs::write(dir.path().join(path), "edited")?;
+        let options = PopulateOptions {
+            keep_local_modifications: keep,
+            ..Default::default()
+        };
+
+        let populated = populate(destination, options, &OutputSink::default())?;
+
+        assert_eq!(populated.files.updated, updated);
+        assert_eq!(populated.warnings.len(), 1);
+        assert!(
+            populated.warnings[0].contains(path),
+            "{:?}",
+            populated.warnings
+        );
+        assert_eq!(fs::read_to_string(dir.path().join(path))? == "edited", keep);
+        // Still modified locally, rather than taken for an older version, the next time.
+        let populated = populate(destination, options, &OutputSink::default())?;
+        assert_eq!(populated.warnings.len(), usize::from(keep));
+        Ok(())
+    }
+
+    /// Populate `dir`, with a manifest that also lists `extra`, as if an older version wrote
+    /// them.
+    fn populate_with_manifest(dir: &Path, extra: &[(&str, &str)]) -> Result<()> {
+        populate(
+            dir.to_str().unwrap(),
+            PopulateOptions::default(),
+            &OutputSink::default(),
+        )?;
+        let path = dir.join(RESOURCES_MANIFEST_FILE);
+        let mut manifest: BTreeMap<String, String> =
+            serde_json::from_str(&fs::read_to_string(&path)?)?;
+        for (name, hash) in extra {
+            manifest.insert(name.to_string(), hash.to_string());
+        }
+        fs::write(path, serde_json::to_string(&manifest)?)?;
+        Ok(())
+    }
+
+    #[parameterized(
+        deleted = { PopulateOptions::default(), false, 2, 1 },
+        kept = { keep_local_modifications(), true, 1, 1 },
+        dry_run = { PopulateOptions { prune_dry_run: true, ..Default::default() }, true, 0, 2 },
+    )]
+    fn test_populate_prune(
+        options: PopulateOptions,
+        modified_kept: bool,
+        pruned: usize,
+        warnings: usize,
+    ) -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        fs::create_dir(dir.path().join("fonts"))?;
+        fs::write(dir.path().join("fonts").join("old.woff2"), "font")?;
+        fs::write(dir.path().join("old.css"), "edited")?;
+        fs::write(dir.path().join("custom.css"), "body {}")?;
+        populate_with_manifest(
+            dir.path(),
+            &[
+                ("fonts/old.woff2", &sha256_hex(b"font")),
+                ("old.css", &sha256_hex(b"body {}")),
+                ("missing.js", &sha256_hex(b"")),
+            ],
+        )?;
+
+        let populated = populate(
+            dir.path().to_str().unwrap(),
+            options,
+            &OutputSink::default(),
+        )?;
+
+        assert_eq!(populated.files.pruned, pruned);
+        assert_eq!(
+            populated.warnings.len(),
+            warnings,
+            "{:?}",
+            populated.warnings
+        );
+        assert_eq!(
+            dir.path().join("fonts").exists(),
+            options.prune_dry_run,
+            "the emptied directory"
+        );
+        assert_eq!(dir.path().join("old.css").exists(), modified_kept);
+        assert!(dir.path().join("custom.css").exists());
+        // The files that are still there are still pruned later.
+        let manifest = load_resources_manifest(dir.path(), &OutputSink::default()).unwrap();
+        assert_eq!(manifest.contains_key("old.css"), modified_kept);
+        assert!(!manifest.contains_key("missing.js"));
+        Ok(())
+    }
+
+    #[parameterized(
+        not_json = { "{", "EOF while parsing an object at line 1 column 1" },
+        absolute = { r#"{"/etc/passwd": "0000000000000000000000000000000000000000000000000000000000000000"}"#, "\"/etc/passwd\" is not a path in the destination" },
+        parent = { r#"{"../old.css": "0000000000000000000000000000000000000000000000000000000000000000"}"#, "\"../old.css\" is not a path in the destination" },
+        hash = { r#"{"old.css": "md5"}"#, "\"md5\" is not the SHA-256 hash of \"old.css\"" },
+    )]
+    fn test_populate_prune_corrupt(manifest: &str, reason: &str) -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        fs::write(dir.path().join("old.css"), "body {}")?;
+        fs::write(dir.path().join(RESOURCES_MANIFEST_FILE), manifest)?;
+
+        let populated = populate(
+            dir.path().to_str().unwrap(),
+            PopulateOptions::default(),
+            &OutputSink::default(),
+        )?;
+
+        assert_eq!(populated.files.pruned, 0);
+        assert_eq!(populated.warnings.len(), 1);
+        assert!(
+            populated.warnings[0].ends_with(&format!("looks corrupt: {}", reason)),
+            "{:?}",
+            populated.warnings
+        );
+        assert!(dir.path().join("old.css").exists());
+        // Written again, so the next run prunes from it.
+        assert!(load_resources_manifest(dir.path(), &OutputSink::default()).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_dry_run() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        fs::write(dir.path().join("same.txt"), "same")?;
+        fs::write(dir.path().join("changed.txt"), "old")?;
+
+        let sink = OutputSink::new(WriteMode::DryRun, WriteOptions::default());
+        sink.atomic_write(&dir.path().join("same.txt"), b"same")?;
+        sink.atomic_write(&dir.path().join("changed.txt"), b"new")?;
+        sink.append(&dir.path().join("changed.txt"), b" line")?;
+        sink.create_dir_all(&dir.path().join("repos"))?;
+        sink.atomic_write(&dir.path().join("repos").join("new.json"), b"{}")?;
+        sink.remove_file(&dir.path().join("same.txt"))?;
+        // Read as the run would have left them.
+        assert_eq!(sink.read(&dir.path().join("changed.txt"))?, b"new line");
+        assert!(sink.read(&dir.path().join("same.txt")).is_err());
+        assert!(!sink.exists(&dir.path().join("same.txt")));
+        assert!(sink.exists(&dir.path().join("repos").join("new.json")));
+        assert!(sink.remove_file(&dir.path().join("same.txt")).is_err());
+        let writes = sink.planned_writes();
+
+        assert_eq!(
+            writes
+                .iter()
+                .map(|w| (
+                    w.path.strip_prefix(dir.path()).unwrap().to_str().unwrap(),
+                    w.action,
+                    w.bytes
+                ))
+                .collect::<Vec<_>>(),
+            [
+                ("same.txt", WriteAction::Skip, 4),
+                ("changed.txt", WriteAction::Update, 3),
+                ("changed.txt", WriteAction::Update, 8),
+                ("repos/new.json", WriteAction::Create, 2),
+                ("same.txt", WriteAction::Delete, 4),
+            ]
+        );
+        assert_eq!(fs::read_to_string(dir.path().join("same.txt"))?, "same");
+        assert_eq!(fs::read_to_string(dir.path().join("changed.txt"))?, "old");
+        assert!(!dir.path().join("repos").exists());
+        // Another sink of the same run writes.
+        OutputSink::default().atomic_write(&dir.path().join("changed.txt"), b"new")?;
+        assert_eq!(fs::read_to_string(dir.path().join("changed.txt"))?, "new");
+        Ok(())
+    }
+
+    #[test]
+    fn test_populate_dry_run() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let destination = dir.path().join("www");
+
+        let sink = OutputSink::new(WriteMode::DryRun, WriteOptions::default());
+        let populated = populate(
+            destination.to_str().unwrap(),
+            PopulateOptions {
+                hashed_assets_keep_runs: Some(10),
+                ..Default::default()
+            },
+            &sink,
+        )?;
+        let writes = sink.planned_writes();
+
+        assert!(!destination.exists());
+        assert_eq!(populated.files.written, populated.files.checked);
+        // The stylesheets are hashed as they would have been written.
+        assert_eq!(
+            populated.integrity["status.css"],
+            integrity(RESOURCES_DIR.get_file("status.css").unwrap().contents())
+        );
+        assert!(writes.iter().all(|w| w.action == WriteAction::Create));
+        assert!(writes
+            .iter()
+            .any(|w| w.path == destination.join(RESOURCES_MANIFEST_FILE)));
+        assert!(writes
+            .iter()
+            .any(|w| w.path == destination.join(&populated.assets["status.css"])));
+        Ok(())
+    }
+
+    /// A resources directory with a stylesheet, a script, a font in a subdirectory and an
+    /// executable.
+    fn resources_dir() -> Result<tempfile::TempDir> {
+        let dir = tempfile::tempdir()?;
+        fs::create_dir_all(dir.path().join("webfonts"))?;
+        fs::create_dir_all(dir.path().join("bin"))?;
+        fs::write(dir.path().join("status.css"), "body {}")?;
+        fs::write(dir.path().join("status.js"), "")?;
+        fs::write(dir.path().join("webfonts").join("icons.woff2"), "font")?;
+        fs::write(dir.path().join("bin").join("refresh.sh"), "#!/bin/sh\n")?;
+        fs::set_permissions(
+            dir.path().join("bin").join("refresh.sh"),
+            fs::Permissions::from_mode(0o755),
+        )?;
+        Ok(dir)
+    }
+
+    #[parameterized(
+        copy = { false },
+        link = { true },
+    )]
+    fn test_populate_resources_dir(link: bool) -> Result<()> {
+        let resources = resources_dir()?;
+        let dir = tempfile::tempdir()?;
+        let destination = dir.path().to_str().unwrap();
+        let options = PopulateOptions {
+            resources_dir: Some(resources.path()),
+            link_resources: link,
+            ..Default::default()
+        };
+
+        let populated = populate(destination, options, &OutputSink::default())?;
+
+        assert_eq!(
+            populated.files.written,
+            4 + BUILTIN_TEMPLATES.len(),
+            "{:?}",
+            populated.files
+        );
+        assert_eq!(
+            populated.integrity,
+            BTreeMap::from([
+                ("status.css".to_string(), integrity(b"body {}")),
+                ("status.js".to_string(), integrity(b"")),
+            ])
+        );
+        assert!(!dir.path().join("fa.all.min.css").exists());
+        assert_eq!(
+            fs::read(dir.path().join("webfonts").join("icons.woff2"))?,
+            b"font"
+        );
+        let script = dir.path().join("bin").join("refresh.sh");
+        assert_eq!(fs::metadata(&script)?.permissions().mode() & 0o777, 0o755);
+        assert_eq!(fs::symlink_metadata(&script)?.is_symlink(), link);
+
+        let populated = populate(destination, options, &OutputSink::default())?;
+        assert_eq!(populated.files.skipped, populated.files.checked);
+        Ok(())
+    }
+
+    #[test]
+    fn test_populate_resources_dir_copied_then_linked() -> Result<()> {
+        let resources = resources_dir()?;
+        let dir = tempfile::tempdir()?;
+        let destination = dir.path().to_str().unwrap();
+        let mut options = PopulateOptions {
+            resources_dir: Some(resources.path()),
+            ..Default::default()
+        };
+        populate(destination, options, &OutputSink::default())?;
+
+        options.link_resources = true;
+        let populated = populate(destination, options, &OutputSink::default())?;
+
+        assert_eq!(populated.files.updated, 4);
+        assert!(populated.warnings.is_empty(), "{:?}", populated.warnings);
+        assert_eq!(
+            fs::read_link(dir.path().join("status.css"))?,
+            fs::canonicalize(resources.path().join("status.css"))?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_populate_resources_dir_missing() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing");
+        let options = PopulateOptions {
+            resources_dir: Some(&missing),
+            ..Default::default()
+        };
+
+        let error = populate(
+            dir.path().to_str().unwrap(),
+            options,
+            &OutputSink::default(),
+        )
+        .unwrap_err();
+
+        assert!(
+            error
+                .to_string()
+                .starts_with("Failed to read resources directory"),
+            "{}",
+            error
+        );
+    }
+}
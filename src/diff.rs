@@ -0,0 +1,322 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use crate::hooks::HookRun;
+use crate::models::{Status, StatusPageData};
+use crate::templating::ServerStatus;
+
+/// The changes between a previous status.json and the current run.
+#[derive(Debug, Serialize, Default)]
+pub struct Changes {
+    /// When the previous status was generated, `None` on the first run.
+    pub previous_update: Option<String>,
+    pub current_update: String,
+    /// Components (overall, stratum0, ...) whose rule outcome changed.
+    pub components: Vec<StatusChange>,
+    pub servers: Vec<StatusChange>,
+    pub revisions: Vec<RevisionChange>,
+    /// The `on_status_change` commands run for the changed components.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub hooks: Vec<HookRun>,
+}
+
+/// A status that changed, `None` meaning the entry was added or removed.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct StatusChange {
+    pub name: String,
+    pub from: Option<Status>,
+    pub to: Option<Status>,
+}
+
+/// A repository revision on a server that changed.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct RevisionChange {
+    pub server: String,
+    pub repository: String,
+    pub from: Option<i32>,
+    pub to: Option<i32>,
+}
+
+impl RevisionChange {
+    /// How many revisions the repository moved, if it has a revision on both sides.
+    pub fn delta(&self) -> Option<i32> {
+        Some(self.to? - self.from?)
+    }
+}
+
+impl Changes {
+    /// Compare the current status with the previous one, if there is one.
+    pub fn between(previous: Option<&StatusPageData>, current: &StatusPageData) -> Self {
+        let Some(previous) = previous else {
+            return Changes {
+                current_update: current.last_update.clone(),
+                ..Default::default()
+            };
+        };
+
+        Changes {
+            previous_update: Some(previous.last_update.clone()),
+            current_update: current.last_update.clone(),
+            components: diff_statuses(&component_statuses(previous), &component_statuses(current)),
+            servers: diff_statuses(&server_statuses(previous), &server_statuses(current)),
+            revisions: diff_revisions(&revisions(previous), &revisions(current)),
+            hooks: vec![],
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.components.is_empty() && self.servers.is_empty() && self.revisions.is_empty()
+    }
+}
+
+fn component_statuses(data: &StatusPageData) -> BTreeMap<String, Status> {
+    [
+        ("overall", data.eessi_status.status),
+        ("stratum0", data.stratum0.status),
+        ("stratum1", data.stratum1.status),
+        ("syncservers", data.syncservers.status),
+        ("repositories", data.repositories_status.status),
+    ]
+    .into_iter()
+    .map(|(name, status)| (name.to_string(), status))
+    .collect()
+}
+
+fn server_statuses(data: &StatusPageData) -> BTreeMap<String, Status> {
+    data.servers
+        .iter()
+        .map(|server| (server.name.clone(), server.status))
+        .collect()
+}
+
+fn revisions(data: &StatusPageData) -> BTreeMap<(String, String), Option<i32>> {
+    data.servers
+        .iter()
+        .flat_map(|server: &ServerStatus| {
+            server
+                .repositories
+                .iter()
+                .map(move |repo| ((server.name.clone(), repo.name.clone()), repo.revision))
+        })
+        .collect()
+}
+
+fn diff_statuses(
+    previous: &BTreeMap<String, Status>,
+    current: &BTreeMap<String, Status>,
+) -> Vec<StatusChange> {
+    let names: BTreeSet<&String> = previous.keys().chain(current.keys()).collect();
+
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let from = previous.get(name).copied();
+            let to = current.get(name).copied();
+            (from != to).then(|| StatusChange {
+                name: name.clone(),
+                from,
+                to,
+            })
+        })
+        .collect()
+}
+
+fn diff_revisions(
+    previous: &BTreeMap<(String, String), Option<i32>>,
+    current: &BTreeMap<(String, String), Option<i32>>,
+) -> Vec<RevisionChange> {
+    let keys: BTreeSet<&(String, String)> = previous.keys().chain(current.keys()).collect();
+
+    keys.into_iter()
+        .filter_map(|key| {
+            let from = previous.get(key).copied().flatten();
+            let to = current.get(key).copied().flatten();
+            (from != to).then(|| RevisionChange {
+                server: key.0.clone(),
+                repository: key.1.clone(),
+                from,
+                to,
+            })
+        })
+        .collect()
+}
+
+fn describe<T: fmt::Display>(value: Option<T>) -> String {
+    value
+        .map(|v| v.to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+impl fmt::Display for StatusChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.name,
+            describe(self.from.as_ref().map(Status::text)),
+            describe(self.to.as_ref().map(Status::text))
+        )
+    }
+}
+
+impl fmt::Display for RevisionChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} on {}: {} -> {}",
+            self.repository,
+            self.server,
+            describe(self.from),
+            describe(self.to)
+        )?;
+        if let Some(delta) = self.delta() {
+            write!(f, " ({:+})", delta)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Changes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Some(previous_update) = &self.previous_update else {
+            return writeln!(f, "No previous status found, nothing to compare with.");
+        };
+
+        if self.is_empty() {
+            return writeln!(f, "No changes since {}.", previous_update);
+        }
+
+        writeln!(
+            f,
+            "Changes between {} and {}:",
+            previous_update, self.current_update
+        )?;
+        for (title, lines) in [
+            (
+                "Components",
+                self.components
+                    .iter()
+                    .map(|c| c.to_string())
+                    .collect::<Vec<_>>(),
+            ),
+            (
+                "Servers",
+                self.servers.iter().map(|c| c.to_string()).collect(),
+            ),
+            (
+                "Revisions",
+                self.revisions.iter().map(|c| c.to_string()).collect(),
+            ),
+        ] {
+            if lines.is_empty() {
+                continue;
+            }
+            writeln!(f, "\n{}:", title)?;
+            for line in lines {
+                writeln!(f, "  {}", line)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use yare::parameterized;
+
+    fn stratum(status: &str) -> serde_json::Value {
+        json!({ "status": status, "status_class": "", "details": [], "servers": [] })
+    }
+
+    fn status_page_data(stratum1: &str, server: &str, revision: Option<i32>) -> StatusPageData {
+        serde_json::from_value(json!({
+            "title": "EESSI",
+            "eessi_status": { "status": "OK", "class": "", "text": "", "description": "" },
+            "contact_email": "support@eessi.io",
+            "last_update": "2025-10-02T07:28:00Z",
+            "legend": [],
+            "stratum0": stratum("OK"),
+            "stratum1": stratum(stratum1),
+            "syncservers": stratum("OK"),
+            "repositories_status": {
+                "name": "", "status": "OK", "revision_class": "", "snapshot_class": ""
+            },
+            "repositories": [],
+            "config": {
+                "meta": {
+                    "title": "", "logging_level": "", "contact_email": "",
+                    "repo_url": "", "repo_url_text": ""
+                },
+                "servers": [],
+                "repositories": [],
+                "ignored_repositories": [],
+                "rules": []
+            },
+            "servers": [{
+                "name": "s1.example.com",
+                "status": server,
+                "geodb_status": "OK",
+                "update_class": "",
+                "geoapi_class": "",
+                "repositories": [
+                    { "name": "software.eessi.io", "revision": revision, "status": "OK" }
+                ]
+            }]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_first_run() {
+        let current = status_page_data("OK", "OK", Some(10));
+        let changes = Changes::between(None, &current);
+
+        assert!(changes.is_empty());
+        assert_eq!(changes.previous_update, None);
+        assert_eq!(
+            changes.to_string(),
+            "No previous status found, nothing to compare with.\n"
+        );
+    }
+
+    #[parameterized(
+        unchanged = { ("OK", "OK", Some(10)), ("OK", "OK", Some(10)), 0, 0, 0 },
+        revision_bumped = { ("OK", "OK", Some(10)), ("OK", "OK", Some(12)), 0, 0, 1 },
+        server_failed = { ("OK", "OK", Some(10)), ("DEGRADED", "FAILED", None), 1, 1, 1 },
+    )]
+    fn test_changes_between(
+        previous: (&str, &str, Option<i32>),
+        current: (&str, &str, Option<i32>),
+        components: usize,
+        servers: usize,
+        revisions: usize,
+    ) {
+        let previous = status_page_data(previous.0, previous.1, previous.2);
+        let current = status_page_data(current.0, current.1, current.2);
+        let changes = Changes::between(Some(&previous), &current);
+
+        assert_eq!(changes.components.len(), components);
+        assert_eq!(changes.servers.len(), servers);
+        assert_eq!(changes.revisions.len(), revisions);
+        assert_eq!(changes.is_empty(), components + servers + revisions == 0);
+    }
+
+    #[test]
+    fn test_revision_change_display() {
+        let change = RevisionChange {
+            server: "s1.example.com".to_string(),
+            repository: "software.eessi.io".to_string(),
+            from: Some(10),
+            to: Some(12),
+        };
+
+        assert_eq!(change.delta(), Some(2));
+        assert_eq!(
+            change.to_string(),
+            "software.eessi.io on s1.example.com: 10 -> 12 (+2)"
+        );
+    }
+}
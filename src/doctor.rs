@@ -0,0 +1,350 @@
+use anyhow::{bail, Context, Result};
+use rhai::Engine;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::config::{ConfigFile, ConfigManager, ServerConfig};
+use crate::dependencies::OutputSink;
+use crate::locale::Locale;
+use crate::models::{StatusPageData, RULE_IDS};
+use crate::report::RunReport;
+use crate::templating::Templates;
+
+/// The file written to the destination to see if it is writable, and removed again.
+const PROBE_FILE: &str = ".doctor-probe";
+
+/// How long a hostname may take to resolve.
+const DNS_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The outcome of one check: what was found if it passed, why it failed otherwise.
+pub struct Finding {
+    pub check: String,
+    pub result: Result<String>,
+}
+
+impl Finding {
+    pub fn new(check: impl Into<String>, result: Result<String>) -> Self {
+        Finding {
+            check: check.into(),
+            result,
+        }
+    }
+
+    pub fn passed(&self) -> bool {
+        self.result.is_ok()
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.result {
+            Ok(detail) => write!(f, "PASS {}: {}", self.check, detail),
+            Err(e) => write!(f, "FAIL {}: {:#}", self.check, e),
+        }
+    }
+}
+
+/// The destination exists or can be created, and a file can be written to it atomically.
+pub fn destination(path: &Path, sink: &OutputSink) -> Result<String> {
+    fs::create_dir_all(path).context(format!("Failed to create directory: {:?}", path))?;
+    let probe = path.join(PROBE_FILE);
+    sink.atomic_write(&probe, b"probe")?;
+    fs::remove_file(&probe).context(format!("Failed to remove {:?}", probe))?;
+    Ok(format!("{:?} is writable", path))
+}
+
+/// The most recent run in `destination` succeeded, and all its conditions evaluated.
+pub fn last_run(destination: &Path) -> Result<String> {
+    let Some(report) = RunReport::load(destination)? else {
+        return Ok("no run report yet".to_string());
+    };
+    if !report.succeeded() {
+        bail!(
+            "The run started at {} failed: {}",
+            report.started_at,
+            report.error.as_deref().unwrap_or("it didn't finish")
+        );
+    }
+    if let Some(error) = report.rule_errors.first() {
+        bail!(
+            "{} conditions failed to evaluate in the run started at {}, such as {} of rule {}: {}",
+            report.rule_errors.len(),
+            report.started_at,
+            error.condition,
+            error.rule,
+            error.error
+        );
+    }
+    Ok(format!(
+        "the run started at {} succeeded, with {} warnings",
+        report.started_at,
+        report.warnings.len()
+    ))
+}
+
+/// The configuration parses and is valid.
+pub fn configuration(filename: &str, overrides: &[String]) -> Result<ConfigManager> {
+    ConfigManager::load(filename, overrides).map_err(anyhow::Error::msg)
+}
+
+/// Every rule that is looked up exists, and every condition compiles.
+pub fn rules(config: &ConfigFile) -> Result<String> {
+    let missing: Vec<&str> = RULE_IDS
+        .into_iter()
+        .filter(|id| !config.rules.iter().any(|rule| rule.id == *id))
+        .collect();
+    if !missing.is_empty() {
+        bail!("No rules found for {}", missing.join(", "));
+    }
+
+    let engine = Engine::new();
+    let mut conditions = 0;
+    for rule in &config.rules {
+        for condition in &rule.conditions {
+            engine
+                .compile_expression(&condition.when)
+                .map_err(|e| anyhow::anyhow!("{}", e))
+                .context(format!(
+                    "Invalid condition in rule {}: {}",
+                    rule.id, condition.when
+                ))?;
+            conditions += 1;
+        }
+    }
+    Ok(format!(
+        "{} rules with {} conditions compile",
+        config.rules.len(),
+        conditions
+    ))
+}
+
+/// The templates in `dir` (or the built-in ones) parse, and the status page renders from
+/// `data` without referring to undefined variables.
+pub fn templates(dir: Option<&Path>, data: &StatusPageData) -> Result<String> {
+    let templates = Templates::load(dir)?
+        .strict(true)
+        .locale(Locale::load(&data.locale, dir)?);
+    let mut context = tera::Context::new();
+    context.insert("data", data);
+    templates.render("status.html", &context)?;
+    Ok(match dir {
+        Some(dir) => format!("the templates in {:?} parse, status.html renders", dir),
+        None => "the built-in templates parse, status.html renders".to_string(),
+    })
+}
+
+/// The directory of resources, if any, exists, as a run fails without it.
+pub fn resources(dir: Option<&Path>) -> Result<String> {
+    let Some(dir) = dir else {
+        return Ok("the built-in resources are used".to_string());
+    };
+    let files =
+        count_files(dir).context(format!("Failed to read resources directory {:?}", dir))?;
+    Ok(format!("{:?} has {} files", dir, files))
+}
+
+/// The files in `dir` and its subdirectories.
+fn count_files(dir: &Path) -> Result<usize> {
+    let mut files = 0;
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        files += if path.is_dir() {
+            count_files(&path)?
+        } else {
+            1
+        };
+    }
+    Ok(files)
+}
+
+/// The names the server is connected to: its members, the address it resolves to, or its
+/// hostname. IPv6 addresses lose their brackets.
+fn names(server: &ServerConfig) -> Vec<String> {
+    if !server.members.is_empty() {
+        server
+            .member_addresses()
+            .unwrap_or_else(|_| server.members.clone())
+    } else if let Some(resolve_to) = &server.resolve_to {
+        vec![server
+            .resolve_to_address()
+            .ok()
+            .flatten()
+            .unwrap_or_else(|| resolve_to.clone())]
+    } else {
+        vec![server.server.hostname.to_string()]
+    }
+}
+
+/// `name` resolves to at least one address.
+pub async fn resolves(name: &str) -> Result<String> {
+    let addresses = tokio::time::timeout(DNS_TIMEOUT, tokio::net::lookup_host((name, 0)))
+        .await
+        .context(format!("Timed out resolving {}", name))?
+        .context(format!("Failed to resolve {}", name))?;
+    let addresses: Vec<String> = addresses.map(|a| a.ip().to_string()).collect();
+    Ok(format!("resolves to {}", addresses.join(", ")))
+}
+
+/// A finding for each name the configured servers are connected to.
+pub async fn dns(config: &ConfigFile) -> Vec<Finding> {
+    let mut findings = vec![];
+    for name in config.servers.iter().flat_map(names) {
+        let result = resolves(&name).await;
+        findings.push(Finding::new(format!("dns {}", name), result));
+    }
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yare::parameterized;
+
+    fn data() -> StatusPageData {
+        serde_json::from_str(include_str!("testdata/status-schema-0.json")).unwrap()
+    }
+
+    #[test]
+    fn test_destination_writable() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("status");
+
+        destination(&path, &OutputSink::default())?;
+
+        assert_eq!(fs::read_dir(&path)?.count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_destination_not_a_directory() -> Result<()> {
+        let file = tempfile::NamedTempFile::new()?;
+        assert!(destination(file.path(), &OutputSink::default()).is_err());
+        Ok(())
+    }
+
+    #[parameterized(
+        none = { None, true },
+        succeeded = { Some(Ok(crate::models::Status::OK)), true },
+        failed = { Some(Err("Scraping failed for all servers".to_string())), false },
+    )]
+    fn test_last_run(
+        outcome: Option<Result<crate::models::Status, String>>,
+        ok: bool,
+    ) -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        if let Some(outcome) = outcome {
+            let mut report = RunReport::new(chrono::Utc::now());
+            report.finish(chrono::Utc::now(), outcome);
+            report.save(dir.path(), &OutputSink::default())?;
+        }
+
+        assert_eq!(last_run(dir.path()).is_ok(), ok);
+        Ok(())
+    }
+
+    #[test]
+    fn test_last_run_rule_errors() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut report = RunReport::new(chrono::Utc::now());
+        report.rule_errors.push(crate::models::RuleError {
+            rule: "eessi_status".to_string(),
+            condition: "stratum2_servers > 0".to_string(),
+            error: "Variable not found: stratum2_servers".to_string(),
+        });
+        report.finish(chrono::Utc::now(), Ok(crate::models::Status::OK));
+        report.save(dir.path(), &OutputSink::default())?;
+
+        let error = last_run(dir.path()).unwrap_err().to_string();
+
+        assert!(
+            error.contains("stratum2_servers > 0 of rule eessi_status"),
+            "{}",
+            error
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_resources() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        fs::create_dir(dir.path().join("webfonts"))?;
+        fs::write(dir.path().join("status.css"), "body {}")?;
+        fs::write(dir.path().join("webfonts").join("icons.woff2"), "")?;
+
+        assert!(resources(None).is_ok());
+        assert_eq!(
+            resources(Some(dir.path()))?,
+            format!("{:?} has 2 files", dir.path())
+        );
+        assert!(resources(Some(&dir.path().join("missing"))).is_err());
+        Ok(())
+    }
+
+    #[parameterized(
+        valid = { "stratum0_servers > 0", None },
+        syntax = { "stratum0_servers >", Some("Invalid condition in rule stratum0_servers") },
+    )]
+    fn test_rules(when: &str, error: Option<&str>) {
+        let mut config = crate::init::example();
+        config.rules[0].conditions[0].when = when.to_string();
+
+        let result = rules(&config);
+
+        match error {
+            None => assert!(result.is_ok(), "{:?}", result),
+            Some(error) => assert!(format!("{:#}", result.unwrap_err()).contains(error)),
+        }
+    }
+
+    #[test]
+    fn test_rules_missing() {
+        let mut config = crate::init::example();
+        config.rules.retain(|rule| rule.id != "sync_servers");
+
+        let error = rules(&config).unwrap_err();
+
+        assert_eq!(error.to_string(), "No rules found for sync_servers");
+    }
+
+    #[test]
+    fn test_templates() {
+        let result = templates(None, &data());
+        assert!(result.is_ok(), "{:#}", result.unwrap_err());
+    }
+
+    #[parameterized(
+        syntax = { "{% if data.title %}" },
+        unknown_filter = { "{{ data.title | nope }}" },
+        missing_field = { "{{ data.nope.title }}" },
+        undefined_condition = { "{% if data.contact_emial %}{{ data.title }}{% endif %}" },
+    )]
+    fn test_templates_broken(template: &str) -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        fs::write(dir.path().join("status.html"), template)?;
+
+        assert!(templates(Some(dir.path()), &data()).is_err());
+        Ok(())
+    }
+
+    #[parameterized(
+        localhost = { "localhost", true },
+        ip = { "127.0.0.1", true },
+        invalid = { "status-page.invalid", false },
+    )]
+    #[test_macro(tokio::test)]
+    async fn test_resolves(name: &str, ok: bool) {
+        assert_eq!(resolves(name).await.is_ok(), ok);
+    }
+
+    #[test]
+    fn test_display() {
+        let passed = Finding::new("destination", Ok("\"/srv\" is writable".to_string()));
+        let failed = Finding::new("dns s1.invalid", Err(anyhow::anyhow!("Failed to resolve")));
+
+        assert_eq!(passed.to_string(), "PASS destination: \"/srv\" is writable");
+        assert_eq!(failed.to_string(), "FAIL dns s1.invalid: Failed to resolve");
+        assert!(passed.passed() && !failed.passed());
+    }
+}
@@ -0,0 +1,290 @@
+use anyhow::{Context, Result};
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use log::{debug, info};
+use std::time::Duration;
+
+use crate::config::{EmailConfig, SmtpTls};
+use crate::diff::Changes;
+use crate::models::{Status, StatusPageData};
+use crate::notifications::Transition;
+use crate::pushgateway::env_value;
+
+/// A rendered email, with the same summary as plain text and HTML.
+#[derive(Debug, PartialEq)]
+pub struct Email {
+    pub subject: String,
+    pub text: String,
+    pub html: String,
+}
+
+/// Whether the transition is to or from a status at least as severe as `min_status`, so
+/// recoveries are reported as well as failures.
+pub fn wanted(config: &EmailConfig, transition: &Transition) -> bool {
+    transition.to >= config.min_status || transition.from.is_some_and(|f| f >= config.min_status)
+}
+
+fn describe(status: Option<Status>) -> String {
+    status.map_or("new".to_string(), |s| s.to_string())
+}
+
+/// Summarize the transitions, the servers that changed status and the repositories that
+/// aren't OK.
+pub fn render(transitions: &[&Transition], changes: &Changes, data: &StatusPageData) -> Email {
+    let summary: Vec<String> = transitions
+        .iter()
+        .map(|t| format!("{} is {}", t.component, t.to))
+        .collect();
+    let subject = format!("{}: {}", data.title, summary.join(", "));
+
+    let components: Vec<String> = transitions
+        .iter()
+        .map(|t| {
+            let mut line = format!("{}: {} -> {}", t.component, describe(t.from), t.to);
+            for detail in &t.details {
+                line.push_str(&format!("\n  {}", detail));
+            }
+            line
+        })
+        .collect();
+    let servers: Vec<String> = changes.servers.iter().map(|c| c.to_string()).collect();
+    let repositories: Vec<String> = data
+        .repositories
+        .iter()
+        .filter(|r| r.status != Status::OK)
+        .map(|r| format!("{}: {}", r.name, r.status))
+        .collect();
+    let sections = [
+        ("Components", components),
+        ("Servers", servers),
+        ("Repositories", repositories),
+    ];
+
+    let mut text = format!("Status changes at {}\n", changes.current_update);
+    let mut html = format!(
+        "<html><body>\n<p>Status changes at {}</p>\n",
+        escape(&changes.current_update)
+    );
+    for (title, lines) in sections.iter().filter(|(_, lines)| !lines.is_empty()) {
+        text.push_str(&format!("\n{}:\n", title));
+        html.push_str(&format!("<h3>{}</h3>\n<ul>\n", title));
+        for line in lines {
+            text.push_str(&format!("  {}\n", line.replace('\n', "\n  ")));
+            html.push_str(&format!(
+                "<li>{}</li>\n",
+                escape(line).replace('\n', "<br>\n")
+            ));
+        }
+        html.push_str("</ul>\n");
+    }
+    html.push_str("</body></html>\n");
+
+    Email {
+        subject,
+        text,
+        html,
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Send the email to every recipient, or only log it for a dry run.
+pub async fn send(config: &EmailConfig, email: &Email, dry_run: bool) -> Result<()> {
+    if dry_run {
+        info!(
+            "Dry run, not sending email to {}\nSubject: {}\n\n{}\n{}",
+            config.to.join(", "),
+            email.subject,
+            email.text,
+            email.html
+        );
+        return Ok(());
+    }
+
+    let mut message = Message::builder()
+        .from(parse_mailbox(&config.from)?)
+        .subject(&email.subject);
+    for to in &config.to {
+        message = message.to(parse_mailbox(to)?);
+    }
+    let message = message
+        .multipart(MultiPart::alternative_plain_html(
+            email.text.clone(),
+            email.html.clone(),
+        ))
+        .context("Failed to build the email")?;
+
+    let mut transport = match config.tls {
+        SmtpTls::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)
+            .context(format!("Invalid SMTP server {}", config.host))?,
+        SmtpTls::Implicit => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host)
+            .context(format!("Invalid SMTP server {}", config.host))?,
+        SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host),
+    }
+    .port(config.port)
+    .timeout(Some(Duration::from_secs(config.timeout_seconds)));
+    if let Some(username) = env_value(&config.username_env)? {
+        let password = env_value(&config.password_env)?.unwrap_or_default();
+        transport = transport.credentials(Credentials::new(username, password));
+    }
+
+    debug!("Sending email via {}:{}", config.host, config.port);
+    transport.build().send(message).await.context(format!(
+        "Failed to send email via {}:{}",
+        config.host, config.port
+    ))?;
+
+    info!("Email sent to: {}", config.to.join(", "));
+    Ok(())
+}
+
+fn parse_mailbox(address: &str) -> Result<Mailbox> {
+    address
+        .parse()
+        .context(format!("Invalid email address {}", address))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff::StatusChange;
+    use yare::parameterized;
+
+    fn config() -> EmailConfig {
+        EmailConfig {
+            host: "127.0.0.1".to_string(),
+            port: 25,
+            tls: SmtpTls::None,
+            username_env: None,
+            password_env: None,
+            from: "status@eessi.io".to_string(),
+            to: vec!["ops@eessi.io".to_string()],
+            min_status: Status::FAILED,
+            timeout_seconds: 1,
+        }
+    }
+
+    fn transition(from: Option<Status>, to: Status) -> Transition {
+        Transition {
+            component: "stratum1".to_string(),
+            from,
+            to,
+            conditions: vec![],
+            details: vec!["s1 <example> is down".to_string()],
+        }
+    }
+
+    #[parameterized(
+        failed = { Some(Status::OK), Status::FAILED, true },
+        recovered = { Some(Status::FAILED), Status::OK, true },
+        new_failed = { None, Status::FAILED, true },
+        warning = { Some(Status::OK), Status::WARNING, false },
+        maintenance = { Some(Status::OK), Status::MAINTENANCE, true },
+    )]
+    fn test_wanted(from: Option<Status>, to: Status, expected: bool) {
+        assert_eq!(wanted(&config(), &transition(from, to)), expected);
+    }
+
+    #[test]
+    fn test_render() {
+        let data: StatusPageData = serde_json::from_value(serde_json::json!({
+            "title": "EESSI",
+            "eessi_status": { "status": "FAILED", "class": "", "text": "", "description": "" },
+            "contact_email": "",
+            "last_update": "2025-10-02T07:28:00Z",
+            "legend": [],
+            "stratum0": { "status": "OK", "status_class": "", "details": [], "servers": [] },
+            "stratum1": { "status": "FAILED", "status_class": "", "details": [], "servers": [] },
+            "syncservers": { "status": "OK", "status_class": "", "details": [], "servers": [] },
+            "repositories_status": {
+                "name": "", "status": "OK", "revision_class": "", "snapshot_class": ""
+            },
+            "repositories": [
+                { "name": "software.eessi.io", "status": "WARNING", "revision_class": "", "snapshot_class": "" },
+                { "name": "dev.eessi.io", "status": "OK", "revision_class": "", "snapshot_class": "" }
+            ],
+            "config": {
+                "meta": {
+                    "title": "", "logging_level": "", "contact_email": "",
+                    "repo_url": "", "repo_url_text": ""
+                },
+                "servers": [],
+                "repositories": [],
+                "ignored_repositories": [],
+                "rules": []
+            },
+            "servers": []
+        }))
+        .unwrap();
+        let changes = Changes {
+            current_update: "2025-10-02T07:28:00Z".to_string(),
+            servers: vec![StatusChange {
+                name: "s1.example.com".to_string(),
+                from: Some(Status::OK),
+                to: Some(Status::FAILED),
+            }],
+            ..Default::default()
+        };
+        let transition = transition(Some(Status::OK), Status::FAILED);
+
+        assert_eq!(
+            render(&[&transition], &changes, &data),
+            Email {
+                subject: "EESSI: stratum1 is FAILED".to_string(),
+                text: "Status changes at 2025-10-02T07:28:00Z\n\
+                       \n\
+                       Components:\n  \
+                         stratum1: OK -> FAILED\n    \
+                           s1 <example> is down\n\
+                       \n\
+                       Servers:\n  \
+                         s1.example.com: Normal service -> Failed\n\
+                       \n\
+                       Repositories:\n  \
+                         software.eessi.io: WARNING\n"
+                    .to_string(),
+                html: "<html><body>\n\
+                       <p>Status changes at 2025-10-02T07:28:00Z</p>\n\
+                       <h3>Components</h3>\n<ul>\n\
+                       <li>stratum1: OK -&gt; FAILED<br>\n  s1 &lt;example&gt; is down</li>\n\
+                       </ul>\n\
+                       <h3>Servers</h3>\n<ul>\n\
+                       <li>s1.example.com: Normal service -&gt; Failed</li>\n\
+                       </ul>\n\
+                       <h3>Repositories</h3>\n<ul>\n\
+                       <li>software.eessi.io: WARNING</li>\n\
+                       </ul>\n\
+                       </body></html>\n"
+                    .to_string(),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_failure() {
+        // Nothing listens on the port of a dropped listener.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+        let config = EmailConfig { port, ..config() };
+        let email = Email {
+            subject: "EESSI: stratum1 is FAILED".to_string(),
+            text: String::new(),
+            html: String::new(),
+        };
+
+        assert!(send(&config, &email, true).await.is_ok());
+        let error = send(&config, &email, false).await.unwrap_err();
+        assert!(
+            error.to_string().starts_with("Failed to send email via"),
+            "{}",
+            error
+        );
+    }
+}
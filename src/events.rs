@@ -0,0 +1,334 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::config::EventsConfig;
+use crate::dependencies::OutputSink;
+use crate::diff::Changes;
+use crate::models::{Status, StatusPageData};
+use crate::notifications::Transition;
+
+/// The file, in the destination directory, with one event per line.
+pub const EVENTS_FILE: &str = "events.jsonl";
+
+/// A component that changed status, as recorded in the history.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Event {
+    pub timestamp: DateTime<Utc>,
+    pub component: String,
+    pub from: Option<Status>,
+    pub to: Status,
+    /// The conditions of the rule of the component that lead to the new status.
+    #[serde(default)]
+    pub conditions: Vec<String>,
+    /// Servers that changed to a status other than OK in the same run.
+    #[serde(default)]
+    pub servers: Vec<String>,
+    /// Repositories that aren't OK.
+    #[serde(default)]
+    pub repositories: Vec<String>,
+    /// For the event of a repository whose revision went backwards, where and by how much.
+    #[serde(default)]
+    pub regression: Option<Regression>,
+}
+
+/// A repository whose revision on a server went below the highest one seen there before.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Regression {
+    pub repository: String,
+    pub server: String,
+    pub from: i32,
+    pub to: i32,
+}
+
+/// The events for the transitions of a run at `timestamp`.
+pub fn from_transitions(
+    transitions: &[Transition],
+    changes: &Changes,
+    data: &StatusPageData,
+    timestamp: DateTime<Utc>,
+) -> Vec<Event> {
+    let servers: Vec<String> = changes
+        .servers
+        .iter()
+        .filter(|c| c.to.is_some_and(|to| to != Status::OK))
+        .map(|c| c.name.clone())
+        .collect();
+    let repositories: Vec<String> = data
+        .repositories
+        .iter()
+        .filter(|r| r.status != Status::OK)
+        .map(|r| r.name.clone())
+        .collect();
+    transitions
+        .iter()
+        .map(|t| Event {
+            timestamp,
+            component: t.component.clone(),
+            from: t.from,
+            to: t.to,
+            conditions: t.conditions.clone(),
+            servers: servers.clone(),
+            repositories: repositories.clone(),
+            regression: None,
+        })
+        .collect()
+}
+
+/// An event for each repository whose revision went backwards on a server in the run at
+/// `timestamp`, unless it already had in the `previous` run, as a regression lasts until it is
+/// acknowledged.
+pub fn from_regressions(
+    previous: Option<&StatusPageData>,
+    data: &StatusPageData,
+    timestamp: DateTime<Utc>,
+) -> Vec<Event> {
+    let previous_repository = |server: &str, repository: &str| {
+        previous?
+            .servers
+            .iter()
+            .find(|s| s.name == server)?
+            .repositories
+            .iter()
+            .find(|r| r.name == repository)
+            .cloned()
+    };
+
+    data.servers
+        .iter()
+        .flat_map(|server| {
+            server.repositories.iter().filter_map(move |repo| {
+                let from = repo.regressed_from?;
+                let before = previous_repository(&server.name, &repo.name);
+                if before.as_ref().is_some_and(|r| r.regressed_from.is_some()) {
+                    return None;
+                }
+                Some(Event {
+                    timestamp,
+                    component: repo.name.clone(),
+                    from: before.map(|r| r.status),
+                    to: repo.status,
+                    conditions: vec![],
+                    servers: vec![server.name.clone()],
+                    repositories: vec![repo.name.clone()],
+                    regression: Some(Regression {
+                        repository: repo.name.clone(),
+                        server: server.name.clone(),
+                        from,
+                        to: repo.revision.unwrap_or_default(),
+                    }),
+                })
+            })
+        })
+        .collect()
+}
+
+/// Read the events from `path`, oldest first. Lines that can't be parsed are skipped with a
+/// warning, and a missing file has no events.
+pub fn load(path: &Path) -> Result<Vec<Event>> {
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+
+    let contents = fs::read_to_string(path).context(format!("Failed to read {:?}", path))?;
+    Ok(contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .filter_map(|(i, line)| {
+            serde_json::from_str(line)
+                .map_err(|e| warn!("Skipping line {} of {:?}: {}", i + 1, path, e))
+                .ok()
+        })
+        .collect())
+}
+
+/// Drop the events older than `max_age_days`, and then the oldest until at most `max_events`
+/// are left.
+pub fn prune(events: &mut Vec<Event>, config: &EventsConfig, now: DateTime<Utc>) {
+    let oldest = now - Duration::days(config.max_age_days);
+    events.retain(|e| e.timestamp >= oldest);
+    if events.len() > config.max_events {
+        events.drain(..events.len() - config.max_events);
+    }
+}
+
+/// Append the events to the history at `path` and prune it, returning the history. The file is
+/// replaced as a whole, so a crash never leaves a partial line behind.
+pub fn record(
+    path: &Path,
+    new: Vec<Event>,
+    config: &EventsConfig,
+    now: DateTime<Utc>,
+    sink: &OutputSink,
+) -> Result<Vec<Event>> {
+    let mut events = load(path)?;
+    let loaded = events.len();
+    let added = new.len();
+    events.extend(new);
+    prune(&mut events, config, now);
+
+    if added > 0 || events.len() != loaded {
+        let mut contents = String::new();
+        for event in &events {
+            contents.push_str(&serde_json::to_string(event)?);
+            contents.push('\n');
+        }
+        sink.atomic_write(path, contents.as_bytes())?;
+        info!("{} events written to: {:?}", added, path);
+    }
+    Ok(events)
+}
+
+/// The most recent events, newest first.
+pub fn recent(events: &[Event], count: usize) -> Vec<Event> {
+    events.iter().rev().take(count).cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::templating::ServerRepository;
+    use chrono::TimeZone;
+    use yare::parameterized;
+
+    fn event(component: &str, timestamp: DateTime<Utc>) -> Event {
+        Event {
+            timestamp,
+            component: component.to_string(),
+            from: Some(Status::OK),
+            to: Status::FAILED,
+            conditions: vec!["stratum1_servers == 0".to_string()],
+            servers: vec!["s1.example.com".to_string()],
+            repositories: vec![],
+            regression: None,
+        }
+    }
+
+    fn config(max_events: usize, max_age_days: i64) -> EventsConfig {
+        EventsConfig {
+            max_events,
+            max_age_days,
+            ..EventsConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_record_appends() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join(EVENTS_FILE);
+        let now = Utc.with_ymd_and_hms(2025, 10, 2, 7, 28, 0).unwrap();
+
+        record(
+            &path,
+            vec![event("overall", now)],
+            &config(10, 30),
+            now,
+            &OutputSink::default(),
+        )?;
+        let events = record(
+            &path,
+            vec![event("stratum1", now)],
+            &config(10, 30),
+            now,
+            &OutputSink::default(),
+        )?;
+
+        assert_eq!(events, vec![event("overall", now), event("stratum1", now)]);
+        assert_eq!(load(&path)?, events);
+        assert_eq!(fs::read_to_string(&path)?.lines().count(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_skips_invalid_lines() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join(EVENTS_FILE);
+        let now = Utc.with_ymd_and_hms(2025, 10, 2, 7, 28, 0).unwrap();
+        let line = serde_json::to_string(&event("overall", now))?;
+        fs::write(&path, format!("{}\n{{\"truncated\n\n{}\n", line, line))?;
+
+        assert_eq!(load(&path)?.len(), 2);
+        assert_eq!(load(&dir.path().join("missing.jsonl"))?, vec![]);
+        Ok(())
+    }
+
+    #[parameterized(
+        nothing_to_prune = { 10, 30, &["a", "b", "c"] },
+        max_events = { 2, 30, &["b", "c"] },
+        max_age = { 10, 5, &["c"] },
+        both = { 1, 15, &["c"] },
+    )]
+    fn test_prune(max_events: usize, max_age_days: i64, expected: &[&str]) {
+        let now = Utc.with_ymd_and_hms(2025, 10, 2, 7, 28, 0).unwrap();
+        let mut events = vec![
+            event("a", now - Duration::days(20)),
+            event("b", now - Duration::days(10)),
+            event("c", now),
+        ];
+
+        prune(&mut events, &config(max_events, max_age_days), now);
+
+        let components: Vec<&str> = events.iter().map(|e| e.component.as_str()).collect();
+        assert_eq!(components, expected);
+    }
+
+    #[test]
+    fn test_from_regressions() {
+        let now = Utc.with_ymd_and_hms(2025, 10, 2, 7, 28, 0).unwrap();
+        let mut previous: StatusPageData =
+            serde_json::from_str(include_str!("testdata/status-schema-0.json")).unwrap();
+        let repository = ServerRepository {
+            name: "software.eessi.io".to_string(),
+            revision: Some(4200),
+            status: Status::OK,
+            regressed_from: None,
+        };
+        previous.servers[0].repositories = vec![repository.clone()];
+        let mut data: StatusPageData =
+            serde_json::from_str(include_str!("testdata/status-schema-0.json")).unwrap();
+        data.servers[0].repositories = vec![ServerRepository {
+            revision: Some(4000),
+            status: Status::FAILED,
+            regressed_from: Some(4200),
+            ..repository
+        }];
+        let server = data.servers[0].name.clone();
+        let name = "software.eessi.io".to_string();
+
+        let events = from_regressions(Some(&previous), &data, now);
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].component, name);
+        assert_eq!(events[0].from, Some(Status::OK));
+        assert_eq!(events[0].to, Status::FAILED);
+        assert_eq!(events[0].servers, vec![server.clone()]);
+        assert_eq!(
+            events[0].regression,
+            Some(Regression {
+                repository: name,
+                server,
+                from: 4200,
+                to: 4000,
+            })
+        );
+        // Only recorded in the run it went backwards in.
+        previous.servers[0].repositories[0].regressed_from = Some(4200);
+        assert!(from_regressions(Some(&previous), &data, now).is_empty());
+    }
+
+    #[test]
+    fn test_recent() {
+        let now = Utc.with_ymd_and_hms(2025, 10, 2, 7, 28, 0).unwrap();
+        let events = [event("a", now), event("b", now), event("c", now)];
+
+        let components: Vec<String> = recent(&events, 2)
+            .into_iter()
+            .map(|e| e.component)
+            .collect();
+        assert_eq!(components, ["c", "b"]);
+    }
+}
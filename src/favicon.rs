@@ -0,0 +1,196 @@
+use anyhow::Result;
+use log::info;
+use std::path::{Path, PathBuf};
+
+use crate::badges::color;
+use crate::dependencies::OutputSink;
+use crate::models::Status;
+
+/// The favicon, written to the destination on every run.
+pub const SVG_FILE: &str = "favicon.svg";
+
+/// The favicon for browsers without SVG favicons.
+pub const PNG_FILE: &str = "favicon.png";
+
+/// The width and height of the PNG favicon, in pixels.
+const SIZE: usize = 32;
+
+/// Samples per pixel in each direction, to smooth the edge of the circle in the PNG.
+const SUBPIXELS: usize = 4;
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+/// A circle in the color of the status.
+pub fn svg(status: Status) -> String {
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{size}" height="{size}" viewBox="0 0 {size} {size}"><circle cx="16" cy="16" r="14" fill="#{color}"/></svg>
+"##,
+        size = SIZE,
+        color = color(status),
+    )
+}
+
+/// The same circle as the SVG favicon, as a PNG.
+pub fn png(status: Status) -> Vec<u8> {
+    let rgb = u32::from_str_radix(color(status), 16).expect("the colors are hex");
+    let [_, r, g, b] = rgb.to_be_bytes();
+
+    // Each row starts with the filter type, none.
+    let mut pixels = Vec::with_capacity(SIZE * (SIZE * 4 + 1));
+    for y in 0..SIZE {
+        pixels.push(0);
+        for x in 0..SIZE {
+            pixels.extend([r, g, b, coverage(x, y)]);
+        }
+    }
+
+    let mut header = Vec::with_capacity(13);
+    header.extend((SIZE as u32).to_be_bytes());
+    header.extend((SIZE as u32).to_be_bytes());
+    // 8 bits per channel, RGBA, the only compression, filter and interlace methods.
+    header.extend([8, 6, 0, 0, 0]);
+
+    let mut png = PNG_SIGNATURE.to_vec();
+    chunk(&mut png, b"IHDR", &header);
+    chunk(&mut png, b"IDAT", &zlib_stored(&pixels));
+    chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+/// How much of the pixel at `x`, `y` the circle covers, as its alpha.
+fn coverage(x: usize, y: usize) -> u8 {
+    let inside = (0..SUBPIXELS * SUBPIXELS)
+        .filter(|sample| {
+            let dx = x as f64 + (sample % SUBPIXELS) as f64 / SUBPIXELS as f64 + 0.125 - 16.0;
+            let dy = y as f64 + (sample / SUBPIXELS) as f64 / SUBPIXELS as f64 + 0.125 - 16.0;
+            dx * dx + dy * dy <= 14.0 * 14.0
+        })
+        .count();
+    (inside * 255 / (SUBPIXELS * SUBPIXELS)) as u8
+}
+
+fn chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend((data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend(kind);
+    png.extend(data);
+    let crc = crc32(&png[start..]);
+    png.extend(crc.to_be_bytes());
+}
+
+/// `data` as a zlib stream of uncompressed blocks, which is small enough for a favicon and
+/// needs no compressor.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut zlib = vec![0x78, 0x01];
+    let blocks: Vec<&[u8]> = data.chunks(u16::MAX as usize).collect();
+    for (i, block) in blocks.iter().enumerate() {
+        zlib.push(u8::from(i + 1 == blocks.len()));
+        let len = block.len() as u16;
+        zlib.extend(len.to_le_bytes());
+        zlib.extend((!len).to_le_bytes());
+        zlib.extend(*block);
+    }
+    zlib.extend(adler32(data).to_be_bytes());
+    zlib
+}
+
+/// The CRC-32 of a PNG chunk.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = flate2::Crc::new();
+    crc.update(data);
+    crc.sum()
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+/// Write the favicons for the overall status to `destination`, returning the paths written.
+pub fn write(destination: &Path, status: Status, sink: &OutputSink) -> Result<Vec<PathBuf>> {
+    let svg_path = destination.join(SVG_FILE);
+    sink.atomic_write(&svg_path, svg(status).as_bytes())?;
+    let png_path = destination.join(PNG_FILE);
+    sink.atomic_write(&png_path, &png(status))?;
+    info!(
+        "Favicons for {} written to: {:?}",
+        status.as_ref(),
+        destination
+    );
+    Ok(vec![svg_path, png_path])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yare::parameterized;
+
+    #[parameterized(
+        ok = { Status::OK, "#2ecc71" },
+        warning = { Status::WARNING, "#e67e22" },
+        failed = { Status::FAILED, "#e74c3c" },
+    )]
+    fn test_svg(status: Status, fill: &str) {
+        assert!(svg(status).contains(&format!(r#"fill="{}""#, fill)));
+    }
+
+    #[test]
+    fn test_adler32() {
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+
+    #[test]
+    fn test_zlib_stored_blocks() {
+        let data = vec![7; 70000];
+
+        let zlib = zlib_stored(&data);
+
+        // Two blocks, each with a 5 byte header, between the zlib header and the checksum.
+        assert_eq!(zlib.len(), 2 + 5 + 65535 + 5 + 4465 + 4);
+        assert_eq!(zlib[2], 0);
+        assert_eq!(zlib[2 + 5 + 65535], 1);
+    }
+
+    #[test]
+    fn test_png() {
+        let png = png(Status::FAILED);
+
+        assert_eq!(png[..8], PNG_SIGNATURE);
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(png[16..20], 32u32.to_be_bytes());
+        assert_eq!(png[20..24], 32u32.to_be_bytes());
+        assert_eq!(&png[png.len() - 8..png.len() - 4], b"IEND");
+        // The IHDR checksum covers its type and data.
+        assert_eq!(png[29..33], crc32(&png[12..29]).to_be_bytes());
+    }
+
+    #[parameterized(
+        center = { 16, 16, 255 },
+        corner = { 0, 0, 0 },
+        edge = { 2, 16, 255 },
+        outside = { 1, 16, 0 },
+        partial = { 5, 6, 79 },
+    )]
+    fn test_coverage(x: usize, y: usize, expected: u8) {
+        assert_eq!(coverage(x, y), expected);
+    }
+
+    #[test]
+    fn test_write() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+
+        let written = write(dir.path(), Status::WARNING, &OutputSink::default())?;
+
+        assert_eq!(
+            written,
+            vec![dir.path().join(SVG_FILE), dir.path().join(PNG_FILE)]
+        );
+        assert_eq!(std::fs::read_to_string(&written[0])?, svg(Status::WARNING));
+        assert_eq!(std::fs::read(&written[1])?, png(Status::WARNING));
+        Ok(())
+    }
+}
@@ -0,0 +1,216 @@
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use tera::{Filter, Tera, Value};
+
+/// The units of `human_bytes`, in steps of 1000.
+const BYTE_UNITS: [&str; 7] = ["B", "kB", "MB", "GB", "TB", "PB", "EB"];
+
+/// Register the filters for human readable values. `now` is the time `time_ago` is relative to.
+pub fn register(tera: &mut Tera, now: DateTime<Utc>) {
+    tera.register_filter("human_bytes", human_bytes);
+    tera.register_filter("human_duration", human_duration);
+    tera.register_filter("time_ago", TimeAgo { now });
+    tera.register_filter("short_hash", short_hash);
+}
+
+fn number(filter: &str, value: &Value) -> tera::Result<f64> {
+    value.as_f64().ok_or_else(|| {
+        tera::Error::msg(format!(
+            "Filter `{}` expects a number, got {}",
+            filter, value
+        ))
+    })
+}
+
+/// A size in bytes with a decimal unit, such as `70.4 MB`.
+fn human_bytes(value: &Value, _: &HashMap<String, Value>) -> tera::Result<Value> {
+    let bytes = number("human_bytes", value)?;
+    let sign = if bytes < 0.0 { "-" } else { "" };
+    let mut size = bytes.abs();
+    let mut unit = 0;
+    // Past 999.95, the size would be rounded to 1000.0 of the unit.
+    while unit + 1 < BYTE_UNITS.len() && size >= if unit == 0 { 1000.0 } else { 999.95 } {
+        size /= 1000.0;
+        unit += 1;
+    }
+    Ok(Value::String(if unit == 0 {
+        format!("{}{} B", sign, size)
+    } else {
+        format!("{}{:.1} {}", sign, size, BYTE_UNITS[unit])
+    }))
+}
+
+/// A duration in seconds as its two largest units, such as `2h 15m`. Units that are zero are left
+/// out, and so are seconds once it is over a day.
+fn human_duration(value: &Value, _: &HashMap<String, Value>) -> tera::Result<Value> {
+    let seconds = number("human_duration", value)?.round() as i64;
+    let sign = if seconds < 0 { "-" } else { "" };
+    let seconds = seconds.unsigned_abs();
+    let parts = [
+        (seconds / 86400, "d"),
+        (seconds % 86400 / 3600, "h"),
+        (seconds % 3600 / 60, "m"),
+        (seconds % 60, "s"),
+    ];
+    let Some(largest) = parts.iter().position(|(n, _)| *n > 0) else {
+        return Ok(Value::String("0s".to_string()));
+    };
+    let text = parts[largest..(largest + 2).min(parts.len())]
+        .iter()
+        .filter(|(n, _)| *n > 0)
+        .map(|(n, unit)| format!("{}{}", n, unit))
+        .collect::<Vec<_>>()
+        .join(" ");
+    Ok(Value::String(format!("{}{}", sign, text)))
+}
+
+/// How long before `now` a time was, such as `7 minutes ago`, or `in 5 minutes` for a time after
+/// it. The time is an RFC 3339 string or a unix timestamp.
+struct TimeAgo {
+    now: DateTime<Utc>,
+}
+
+impl Filter for TimeAgo {
+    fn filter(&self, value: &Value, _: &HashMap<String, Value>) -> tera::Result<Value> {
+        let time = match value {
+            Value::String(text) => DateTime::parse_from_rfc3339(text)
+                .map(|time| time.with_timezone(&Utc))
+                .map_err(|e| {
+                    tera::Error::msg(format!("Filter `time_ago` can't parse {:?}: {}", text, e))
+                })?,
+            Value::Number(number) => number
+                .as_i64()
+                .and_then(|timestamp| DateTime::from_timestamp(timestamp, 0))
+                .ok_or_else(|| {
+                    tera::Error::msg(format!(
+                        "Filter `time_ago` got an invalid timestamp {}",
+                        number
+                    ))
+                })?,
+            _ => {
+                return Err(tera::Error::msg(format!(
+                    "Filter `time_ago` expects a time or a timestamp, got {}",
+                    value
+                )))
+            }
+        };
+
+        let seconds = (self.now - time).num_seconds();
+        let elapsed = seconds.unsigned_abs();
+        let (count, unit) = match elapsed {
+            0 => return Ok(Value::String("just now".to_string())),
+            1..=59 => (elapsed, "second"),
+            60..=3599 => (elapsed / 60, "minute"),
+            3600..=86399 => (elapsed / 3600, "hour"),
+            _ => (elapsed / 86400, "day"),
+        };
+        let amount = format!("{} {}{}", count, unit, if count == 1 { "" } else { "s" });
+        Ok(Value::String(if seconds > 0 {
+            format!("{} ago", amount)
+        } else {
+            format!("in {}", amount)
+        }))
+    }
+}
+
+/// The first 8 characters of a hash, to show next to the full hash in a `title`.
+fn short_hash(value: &Value, _: &HashMap<String, Value>) -> tera::Result<Value> {
+    let hash = value.as_str().ok_or_else(|| {
+        tera::Error::msg(format!(
+            "Filter `short_hash` expects a string, got {}",
+            value
+        ))
+    })?;
+    Ok(Value::String(hash.chars().take(8).collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use yare::parameterized;
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 10, 2, 7, 28, 0).unwrap()
+    }
+
+    fn render(template: &str, value: Value) -> tera::Result<String> {
+        let mut tera = Tera::default();
+        register(&mut tera, now());
+        let mut context = tera::Context::new();
+        context.insert("value", &value);
+        tera.render_str(template, &context)
+    }
+
+    #[parameterized(
+        zero = { 0, "0 B" },
+        bytes = { 999, "999 B" },
+        kilobytes = { 1000, "1.0 kB" },
+        catalog = { 70412288, "70.4 MB" },
+        rounds_up = { 999_950, "1.0 MB" },
+        negative = { -1500, "-1.5 kB" },
+        largest = { i64::MAX, "9.2 EB" },
+    )]
+    fn test_human_bytes(bytes: i64, expected: &str) {
+        assert_eq!(
+            render("{{ value | human_bytes }}", bytes.into()).unwrap(),
+            expected
+        );
+    }
+
+    #[parameterized(
+        zero = { 0, "0s" },
+        seconds = { 45, "45s" },
+        minutes = { 240, "4m" },
+        minutes_and_seconds = { 330, "5m 30s" },
+        hours = { 8100, "2h 15m" },
+        hours_and_seconds = { 7205, "2h" },
+        days = { 90061, "1d 1h" },
+        negative = { -330, "-5m 30s" },
+        large = { 1_000_000_000, "11574d 1h" },
+    )]
+    fn test_human_duration(seconds: i64, expected: &str) {
+        assert_eq!(
+            render("{{ value | human_duration }}", seconds.into()).unwrap(),
+            expected
+        );
+    }
+
+    #[parameterized(
+        now = { "2025-10-02T07:28:00Z".into(), "just now" },
+        second = { "2025-10-02T07:27:59Z".into(), "1 second ago" },
+        minutes = { "2025-10-02T07:21:00Z".into(), "7 minutes ago" },
+        offset = { "2025-10-02T09:21:00+02:00".into(), "7 minutes ago" },
+        hours = { "2025-10-02T04:28:00Z".into(), "3 hours ago" },
+        days = { "2025-09-01T07:28:00Z".into(), "31 days ago" },
+        future = { "2025-10-02T07:33:00Z".into(), "in 5 minutes" },
+        timestamp = { 1759389660.into(), "7 minutes ago" },
+        epoch = { 0.into(), "20363 days ago" },
+    )]
+    fn test_time_ago(time: Value, expected: &str) {
+        assert_eq!(render("{{ value | time_ago }}", time).unwrap(), expected);
+    }
+
+    #[parameterized(
+        human_bytes = { "{{ value | human_bytes }}", "big".into() },
+        human_duration = { "{{ value | human_duration }}", Value::Null },
+        time_ago = { "{{ value | time_ago }}", "yesterday".into() },
+        time_ago_float = { "{{ value | time_ago }}", 1.5.into() },
+        short_hash = { "{{ value | short_hash }}", 42.into() },
+    )]
+    fn test_invalid(template: &str, value: Value) {
+        assert!(render(template, value).is_err());
+    }
+
+    #[parameterized(
+        long = { "1a2b3c4d5e6f7a8b9c0d", "1a2b3c4d" },
+        short = { "aa11", "aa11" },
+        empty = { "", "" },
+    )]
+    fn test_short_hash(hash: &str, expected: &str) {
+        assert_eq!(
+            render("{{ value | short_hash }}", hash.into()).unwrap(),
+            expected
+        );
+    }
+}
@@ -0,0 +1,167 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::{debug, info};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpStream, UdpSocket};
+
+use crate::config::{GraphiteConfig, GraphiteProtocol};
+use crate::prometheus::FlatSample;
+
+/// Render the samples as Graphite plaintext lines, `<path> <value> <timestamp>`, all at
+/// `timestamp` in seconds.
+///
+/// The path is the prefix, the label values in order and the metric name, such as
+/// `eessi.stratum1.s1_example_org.software_eessi_io.repo_revision`. Values that aren't finite
+/// are left out.
+pub fn render(samples: &[FlatSample], prefix: &str, timestamp: &DateTime<Utc>) -> String {
+    let mut out = String::new();
+    for sample in samples.iter().filter(|s| s.value.is_finite()) {
+        let path: Vec<String> = std::iter::once(prefix)
+            .filter(|p| !p.is_empty())
+            .map(|p| p.trim_end_matches('.').to_string())
+            .chain(sample.labels.iter().map(|(_, v)| sanitize(v)))
+            .chain(std::iter::once(sanitize(&sample.name)))
+            .collect();
+        out.push_str(&format!(
+            "{} {} {}\n",
+            path.join("."),
+            sample.value,
+            timestamp.timestamp()
+        ));
+    }
+    out
+}
+
+/// A path component, with dots, spaces and other characters Graphite treats specially
+/// replaced by underscores.
+fn sanitize(s: &str) -> String {
+    let sanitized: String = s
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == ':' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if sanitized.is_empty() {
+        "_".to_string()
+    } else {
+        sanitized
+    }
+}
+
+/// Send the lines to the Graphite server, over TCP or as one UDP datagram per line.
+pub async fn send(config: &GraphiteConfig, lines: &str) -> Result<()> {
+    let address = format!("{}:{}", config.host, config.port);
+    let timeout = Duration::from_secs(config.timeout_seconds);
+    debug!("Sending metrics to Graphite at {}", address);
+
+    let sent = async {
+        match config.protocol {
+            GraphiteProtocol::Tcp => {
+                let mut stream = TcpStream::connect(&address).await?;
+                stream.write_all(lines.as_bytes()).await?;
+                stream.shutdown().await
+            }
+            GraphiteProtocol::Udp => {
+                let socket = UdpSocket::bind("0.0.0.0:0").await?;
+                socket.connect(&address).await?;
+                for line in lines.lines() {
+                    socket.send(format!("{}\n", line).as_bytes()).await?;
+                }
+                Ok(())
+            }
+        }
+    };
+    tokio::time::timeout(timeout, sent)
+        .await
+        .context(format!(
+            "Timed out sending metrics to Graphite at {}",
+            address
+        ))?
+        .context(format!("Failed to send metrics to Graphite at {}", address))?;
+
+    info!("Metrics sent to Graphite at: {}", address);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use tokio::io::AsyncReadExt;
+    use yare::parameterized;
+
+    fn sample(name: &str, labels: &[(&str, &str)], value: f64) -> FlatSample {
+        FlatSample {
+            name: name.to_string(),
+            labels: labels
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            value,
+        }
+    }
+
+    #[parameterized(
+        repository = { "software.eessi.io", "software_eessi_io" },
+        hostname_with_port = { "cvmfs-ext.gridpp.rl.ac.uk:8000", "cvmfs-ext_gridpp_rl_ac_uk:8000" },
+        space = { "two words", "two_words" },
+        empty = { "", "_" },
+    )]
+    fn test_sanitize(value: &str, expected: &str) {
+        assert_eq!(sanitize(value), expected);
+    }
+
+    #[test]
+    fn test_render() {
+        let timestamp = Utc.with_ymd_and_hms(2025, 10, 2, 7, 28, 0).unwrap();
+        let samples = [
+            sample("eessi_status", &[], 0.0),
+            sample(
+                "repo_revision",
+                &[
+                    ("type", "stratum1"),
+                    ("server", "s1.example.org"),
+                    ("repository", "software.eessi.io"),
+                ],
+                12.0,
+            ),
+            sample("repo_revision_lag", &[("server", "s2")], f64::NAN),
+        ];
+
+        assert_eq!(
+            render(&samples, "eessi.", &timestamp),
+            "eessi.eessi_status 0 1759390080\n\
+             eessi.stratum1.s1_example_org.software_eessi_io.repo_revision 12 1759390080\n"
+        );
+        assert_eq!(
+            render(&samples[..1], "", &timestamp),
+            "eessi_status 0 1759390080\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_tcp() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let config = GraphiteConfig {
+            port: listener.local_addr().unwrap().port(),
+            ..GraphiteConfig::with_host("127.0.0.1")
+        };
+        let received = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut received = String::new();
+            stream.read_to_string(&mut received).await.unwrap();
+            received
+        });
+
+        send(&config, "eessi.eessi_status 0 1759390080\n")
+            .await
+            .unwrap();
+
+        assert_eq!(received.await.unwrap(), "eessi.eessi_status 0 1759390080\n");
+    }
+}
@@ -0,0 +1,69 @@
+use anyhow::{Context, Result};
+use log::{debug, info};
+use std::time::Duration;
+
+/// The URL to ping, with `/fail` appended for a failed run as healthchecks.io expects.
+pub fn ping_url(url: &str, failed: bool) -> String {
+    if failed {
+        format!("{}/fail", url.trim_end_matches('/'))
+    } else {
+        url.to_string()
+    }
+}
+
+/// Ping the heartbeat URL, failing on a connection error or a non-success response.
+pub async fn ping(url: &str, failed: bool, timeout: Duration) -> Result<()> {
+    let url = ping_url(url, failed);
+    let client = reqwest::Client::builder()
+        .timeout(timeout)
+        .build()
+        .context("Failed to build the heartbeat client")?;
+
+    debug!("Pinging heartbeat {}", url);
+    let response = client
+        .get(&url)
+        .send()
+        .await
+        .context(format!("Failed to ping heartbeat {}", url))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        anyhow::bail!("Heartbeat {} responded with {}", url, status);
+    }
+
+    info!("Heartbeat pinged: {}", url);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yare::parameterized;
+
+    #[parameterized(
+        ok = { "https://hc-ping.com/uuid", false, "https://hc-ping.com/uuid" },
+        failed = { "https://hc-ping.com/uuid", true, "https://hc-ping.com/uuid/fail" },
+        failed_trailing_slash = { "https://hc-ping.com/uuid/", true, "https://hc-ping.com/uuid/fail" },
+    )]
+    fn test_ping_url(url: &str, failed: bool, expected: &str) {
+        assert_eq!(ping_url(url, failed), expected);
+    }
+
+    #[tokio::test]
+    async fn test_ping() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/uuid", listener.local_addr().unwrap());
+        let router = axum::Router::new()
+            .route("/uuid", axum::routing::get(|| async { "OK" }))
+            .route(
+                "/uuid/fail",
+                axum::routing::get(|| async { axum::http::StatusCode::NOT_FOUND }),
+            );
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        let timeout = Duration::from_secs(5);
+
+        ping(&url, false, timeout).await.unwrap();
+        let error = ping(&url, true, timeout).await.unwrap_err();
+        assert!(error.to_string().contains("404 Not Found"), "{}", error);
+    }
+}
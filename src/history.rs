@@ -0,0 +1,431 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::config::{HistoryConfig, MaintenanceAggregation};
+use crate::dependencies::OutputSink;
+use crate::models::{Status, StatusPageData};
+
+/// The file, in the destination directory, with one run per line.
+pub const HISTORY_FILE: &str = "history.jsonl";
+
+/// The components that uptime is computed for, in the order they are shown.
+pub const COMPONENTS: [&str; 4] = ["overall", "stratum0", "stratum1", "syncservers"];
+
+/// The outcome of a run, as recorded in the history.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Run {
+    pub timestamp: DateTime<Utc>,
+    pub components: BTreeMap<String, Status>,
+    #[serde(default)]
+    pub repositories: BTreeMap<String, Status>,
+}
+
+impl Run {
+    pub fn from_data(data: &StatusPageData, timestamp: DateTime<Utc>) -> Self {
+        let statuses = [
+            data.eessi_status.status,
+            data.stratum0.status,
+            data.stratum1.status,
+            data.syncservers.status,
+        ];
+        Run {
+            timestamp,
+            components: COMPONENTS
+                .iter()
+                .zip(statuses)
+                .map(|(component, status)| (component.to_string(), status))
+                .collect(),
+            repositories: data
+                .repositories
+                .iter()
+                .map(|r| (r.name.clone(), r.status))
+                .collect(),
+        }
+    }
+}
+
+/// The uptime of a component over the configured windows.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ComponentUptime {
+    pub component: String,
+    pub windows: Vec<UptimeWindow>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct UptimeWindow {
+    pub days: i64,
+    /// `None` when nothing is known about the window.
+    pub percentage: Option<f64>,
+}
+
+/// The worst status of a component on a day, for the bars on the page.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DailyStatus {
+    pub date: NaiveDate,
+    /// `None` for a day without any runs.
+    pub status: Option<Status>,
+    pub class: String,
+}
+
+impl DailyStatus {
+    fn new(date: NaiveDate, status: Option<Status>) -> Self {
+        let class = match status {
+            Some(status) => format!("day-{}", status.as_ref().to_lowercase()),
+            None => "day-no-data".to_string(),
+        };
+        DailyStatus {
+            date,
+            status,
+            class,
+        }
+    }
+}
+
+/// The daily statuses of a component, oldest first.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ComponentDays {
+    pub component: String,
+    pub days: Vec<DailyStatus>,
+}
+
+/// Read the runs from `path`, oldest first. Lines that can't be parsed are skipped with a
+/// warning, and a missing file has no runs.
+pub fn load(path: &Path) -> Result<Vec<Run>> {
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+
+    let contents = fs::read_to_string(path).context(format!("Failed to read {:?}", path))?;
+    Ok(contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .filter_map(|(i, line)| {
+            serde_json::from_str(line)
+                .map_err(|e| warn!("Skipping line {} of {:?}: {}", i + 1, path, e))
+                .ok()
+        })
+        .collect())
+}
+
+/// Append the run to the history at `path`, returning the history without the runs older than
+/// `max_age_days`. The file is only rewritten when runs were pruned, as it grows with every run.
+pub fn record(
+    path: &Path,
+    run: Run,
+    config: &HistoryConfig,
+    now: DateTime<Utc>,
+    sink: &OutputSink,
+) -> Result<Vec<Run>> {
+    let mut runs = load(path)?;
+    let loaded = runs.len();
+    let oldest = now - Duration::days(config.max_age_days);
+    runs.retain(|r| r.timestamp >= oldest);
+    let pruned = loaded - runs.len();
+    let line = serde_json::to_string(&run)?;
+    runs.push(run);
+
+    if pruned > 0 {
+        let mut contents = String::new();
+        for run in &runs {
+            contents.push_str(&serde_json::to_string(run)?);
+            contents.push('\n');
+        }
+        sink.atomic_write(path, contents.as_bytes())?;
+        debug!("Pruned {} runs from: {:?}", pruned, path);
+    } else {
+        sink.append(path, format!("{}\n", line).as_bytes())?;
+    }
+    Ok(runs)
+}
+
+/// The share of the time that the status was known in which the component wasn't FAILED.
+///
+/// A status holds from its run until the next one, but for at most `max_gap_minutes`, so the
+/// time the tool wasn't running counts as neither up nor down. MAINTENANCE counts as down,
+/// unless `exclude_maintenance` is set, in which case it counts as unknown.
+pub fn uptime(
+    runs: &[Run],
+    component: &str,
+    days: i64,
+    config: &HistoryConfig,
+    now: DateTime<Utc>,
+) -> Option<f64> {
+    let start = now - Duration::days(days);
+    let max_gap = Duration::minutes(config.max_gap_minutes);
+    let mut known = Duration::zero();
+    let mut up = Duration::zero();
+
+    for (run, next) in runs.iter().zip(runs.iter().skip(1)) {
+        let Some(status) = run.components.get(component) else {
+            continue;
+        };
+        if *status == Status::MAINTENANCE && config.exclude_maintenance {
+            continue;
+        }
+
+        let from = run.timestamp.max(start);
+        let until = next.timestamp.min(run.timestamp + max_gap).min(now);
+        if until <= from {
+            continue;
+        }
+        known += until - from;
+        if !matches!(status, Status::FAILED | Status::MAINTENANCE) {
+            up += until - from;
+        }
+    }
+
+    (known > Duration::zero())
+        .then(|| 100.0 * up.num_milliseconds() as f64 / known.num_milliseconds() as f64)
+}
+
+/// The uptime of every component over every configured window.
+pub fn uptimes(runs: &[Run], config: &HistoryConfig, now: DateTime<Utc>) -> Vec<ComponentUptime> {
+    COMPONENTS
+        .iter()
+        .map(|component| ComponentUptime {
+            component: component.to_string(),
+            windows: config
+                .uptime_days
+                .iter()
+                .map(|&days| UptimeWindow {
+                    days,
+                    percentage: uptime(runs, component, days, config, now),
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// The worst status of the component on each of the last `days` days in UTC, up to and
+/// including the day of `now`.
+///
+/// A status counts for every day it holds on, the same way as for [`uptime`], so a failure just
+/// before midnight that lasts until the next run also marks the next day. Statuses on the same day
+/// are combined with [`Status::combine`] under `maintenance`.
+pub fn daily(
+    runs: &[Run],
+    component: &str,
+    days: i64,
+    config: &HistoryConfig,
+    maintenance: MaintenanceAggregation,
+    now: DateTime<Utc>,
+) -> Vec<DailyStatus> {
+    let today = now.date_naive();
+    let first = today - Duration::days(days - 1);
+    let max_gap = Duration::minutes(config.max_gap_minutes);
+    let mut worst: BTreeMap<NaiveDate, Status> = BTreeMap::new();
+
+    let nexts = runs.iter().skip(1).map(Some).chain([None]);
+    for (run, next) in runs.iter().zip(nexts) {
+        let Some(&status) = run.components.get(component) else {
+            continue;
+        };
+        let until = next.map_or(run.timestamp, |next| {
+            next.timestamp.min(run.timestamp + max_gap)
+        });
+        // The end is exclusive, so a status until midnight doesn't mark the next day.
+        let last = if until > run.timestamp {
+            (until - Duration::nanoseconds(1)).date_naive()
+        } else {
+            run.timestamp.date_naive()
+        };
+
+        let mut date = run.timestamp.date_naive().max(first);
+        while date <= last.min(today) {
+            worst
+                .entry(date)
+                .and_modify(|w| *w = w.combine(status, maintenance))
+                .or_insert(status);
+            date += Duration::days(1);
+        }
+    }
+
+    first
+        .iter_days()
+        .take(days as usize)
+        .map(|date| DailyStatus::new(date, worst.get(&date).copied()))
+        .collect()
+}
+
+/// The daily statuses of every component over the last `daily_days`.
+pub fn dailies(
+    runs: &[Run],
+    config: &HistoryConfig,
+    maintenance: MaintenanceAggregation,
+    now: DateTime<Utc>,
+) -> Vec<ComponentDays> {
+    COMPONENTS
+        .iter()
+        .map(|component| ComponentDays {
+            component: component.to_string(),
+            days: daily(runs, component, config.daily_days, config, maintenance, now),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use yare::parameterized;
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 10, 2, 12, 0, 0).unwrap()
+    }
+
+    /// A run `hours_ago` with the given status of the overall component.
+    fn run(hours_ago: i64, status: Status) -> Run {
+        Run {
+            timestamp: now() - Duration::hours(hours_ago),
+            components: BTreeMap::from([("overall".to_string(), status)]),
+            repositories: BTreeMap::new(),
+        }
+    }
+
+    fn config(max_gap_minutes: i64, exclude_maintenance: bool) -> HistoryConfig {
+        HistoryConfig {
+            max_gap_minutes,
+            exclude_maintenance,
+            ..HistoryConfig::default()
+        }
+    }
+
+    #[parameterized(
+        all_up = { &[(4, Status::OK), (2, Status::WARNING), (0, Status::OK)], 120, false, Some(100.0) },
+        half_down = { &[(4, Status::OK), (2, Status::FAILED), (0, Status::OK)], 120, false, Some(50.0) },
+        degraded_is_up = { &[(4, Status::DEGRADED), (0, Status::OK)], 240, false, Some(100.0) },
+        gap_is_unknown = { &[(4, Status::OK), (3, Status::FAILED), (0, Status::OK)], 60, false, Some(50.0) },
+        maintenance_is_down = { &[(4, Status::OK), (2, Status::MAINTENANCE), (0, Status::OK)], 120, false, Some(50.0) },
+        maintenance_excluded = { &[(4, Status::OK), (2, Status::MAINTENANCE), (0, Status::OK)], 120, true, Some(100.0) },
+        single_run = { &[(0, Status::OK)], 60, false, None },
+        outside_window = { &[(50, Status::FAILED), (1, Status::OK), (0, Status::OK)], 60, false, Some(100.0) },
+    )]
+    fn test_uptime(
+        runs: &[(i64, Status)],
+        max_gap_minutes: i64,
+        exclude_maintenance: bool,
+        expected: Option<f64>,
+    ) {
+        let runs: Vec<Run> = runs.iter().map(|&(h, s)| run(h, s)).collect();
+        let config = config(max_gap_minutes, exclude_maintenance);
+
+        assert_eq!(uptime(&runs, "overall", 1, &config, now()), expected);
+    }
+
+    #[test]
+    fn test_uptime_clips_to_window() {
+        // Up since before the window of a day starts, and down for the last 6 of its 24 hours.
+        let runs = vec![
+            run(36, Status::OK),
+            run(18, Status::OK),
+            run(6, Status::FAILED),
+            run(0, Status::OK),
+        ];
+
+        assert_eq!(
+            uptime(&runs, "overall", 1, &config(24 * 60, false), now()),
+            Some(75.0)
+        );
+        assert_eq!(
+            uptime(&runs, "stratum1", 1, &config(60, false), now()),
+            None
+        );
+    }
+
+    #[parameterized(
+        worst_of_the_day = { &[("2025-10-01T08:00:00Z", Status::OK), ("2025-10-01T09:00:00Z", Status::FAILED), ("2025-10-01T09:30:00Z", Status::WARNING)], &[None, Some(Status::FAILED), None] },
+        across_midnight = { &[("2025-10-01T23:50:00Z", Status::FAILED), ("2025-10-02T00:10:00Z", Status::OK)], &[None, Some(Status::FAILED), Some(Status::FAILED)] },
+        until_midnight = { &[("2025-10-01T23:00:00Z", Status::FAILED), ("2025-10-02T00:00:00Z", Status::OK)], &[None, Some(Status::FAILED), Some(Status::OK)] },
+        gap_is_no_data = { &[("2025-09-30T22:30:00Z", Status::WARNING), ("2025-10-02T06:00:00Z", Status::OK)], &[Some(Status::WARNING), None, Some(Status::OK)] },
+        before_window = { &[("2025-09-29T23:30:00Z", Status::FAILED), ("2025-10-01T12:00:00Z", Status::OK)], &[Some(Status::FAILED), Some(Status::OK), None] },
+        timezone = { &[("2025-10-02T01:00:00+02:00", Status::FAILED)], &[None, Some(Status::FAILED), None] },
+    )]
+    fn test_daily(runs: &[(&str, Status)], expected: &[Option<Status>]) {
+        let runs: Vec<Run> = runs
+            .iter()
+            .map(|&(timestamp, status)| Run {
+                timestamp: DateTime::parse_from_rfc3339(timestamp).unwrap().into(),
+                ..run(0, status)
+            })
+            .collect();
+
+        let days = daily(
+            &runs,
+            "overall",
+            3,
+            &config(60, false),
+            MaintenanceAggregation::default(),
+            now(),
+        );
+
+        let dates: Vec<String> = days.iter().map(|d| d.date.to_string()).collect();
+        assert_eq!(dates, ["2025-09-30", "2025-10-01", "2025-10-02"]);
+        let statuses: Vec<Option<Status>> = days.iter().map(|d| d.status).collect();
+        assert_eq!(statuses, expected);
+    }
+
+    #[parameterized(
+        masks = { MaintenanceAggregation::Masks, Status::MAINTENANCE },
+        masked = { MaintenanceAggregation::Masked, Status::FAILED },
+    )]
+    fn test_daily_maintenance_with_failed(maintenance: MaintenanceAggregation, expected: Status) {
+        let runs = [run(2, Status::FAILED), run(1, Status::MAINTENANCE)];
+
+        let days = daily(&runs, "overall", 1, &config(60, false), maintenance, now());
+
+        assert_eq!(days[0].status, Some(expected));
+    }
+
+    #[test]
+    fn test_daily_status_class() {
+        let date = NaiveDate::from_ymd_opt(2025, 10, 2).unwrap();
+        assert_eq!(
+            DailyStatus::new(date, Some(Status::FAILED)).class,
+            "day-failed"
+        );
+        assert_eq!(DailyStatus::new(date, None).class, "day-no-data");
+    }
+
+    #[test]
+    fn test_record_appends_and_prunes() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join(HISTORY_FILE);
+        let config = HistoryConfig {
+            max_age_days: 2,
+            ..HistoryConfig::default()
+        };
+
+        record(
+            &path,
+            run(50, Status::OK),
+            &config,
+            now() - Duration::hours(50),
+            &OutputSink::default(),
+        )?;
+        record(
+            &path,
+            run(2, Status::OK),
+            &config,
+            now() - Duration::hours(2),
+            &OutputSink::default(),
+        )?;
+        assert_eq!(load(&path)?.len(), 2);
+
+        let runs = record(
+            &path,
+            run(0, Status::FAILED),
+            &config,
+            now(),
+            &OutputSink::default(),
+        )?;
+        assert_eq!(runs, vec![run(2, Status::OK), run(0, Status::FAILED)]);
+        assert_eq!(load(&path)?, runs);
+        Ok(())
+    }
+}
@@ -0,0 +1,150 @@
+use log::{info, warn};
+use serde::Serialize;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::config::split_command;
+use crate::notifications::Transition;
+
+/// A command run for a transition, for the run report.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct HookRun {
+    pub command: String,
+    pub component: String,
+    /// `None` when the command failed to start, timed out or was killed by a signal.
+    pub exit_code: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Run `command` for the transition, with the transition in the environment and `status_json`
+/// on stdin. Its output is logged, stdout at info and stderr at warn. The command is killed
+/// after `timeout`.
+pub async fn run(
+    command: &str,
+    transition: &Transition,
+    status_json: &[u8],
+    timeout: Duration,
+) -> HookRun {
+    let mut hook_run = HookRun {
+        command: command.to_string(),
+        component: transition.component.clone(),
+        exit_code: None,
+        error: None,
+    };
+    match execute(command, transition, status_json, timeout).await {
+        Ok(code) => hook_run.exit_code = code,
+        Err(e) => {
+            warn!("Hook {} for {}: {}", command, transition.component, e);
+            hook_run.error = Some(e);
+        }
+    }
+    hook_run
+}
+
+async fn execute(
+    command: &str,
+    transition: &Transition,
+    status_json: &[u8],
+    timeout: Duration,
+) -> Result<Option<i32>, String> {
+    let args = split_command(command)?;
+    let mut child = Command::new(&args[0])
+        .args(&args[1..])
+        .env("COMPONENT", &transition.component)
+        .env(
+            "OLD_STATUS",
+            transition.from.map(|s| s.to_string()).unwrap_or_default(),
+        )
+        .env("NEW_STATUS", transition.to.to_string())
+        .env("DETAILS", transition.details.join("\n"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("failed to start: {}", e))?;
+
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let status_json = status_json.to_vec();
+    // A command that doesn't read its stdin shouldn't block on a full pipe, nor fail the hook.
+    tokio::spawn(async move {
+        let _ = stdin.write_all(&status_json).await;
+    });
+
+    let output = tokio::time::timeout(timeout, child.wait_with_output())
+        .await
+        .map_err(|_| format!("timed out after {} seconds", timeout.as_secs_f64()))?
+        .map_err(|e| format!("failed to wait for the command: {}", e))?;
+
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        info!("Hook {}: {}", command, line);
+    }
+    for line in String::from_utf8_lossy(&output.stderr).lines() {
+        warn!("Hook {}: {}", command, line);
+    }
+    if !output.status.success() {
+        warn!(
+            "Hook {} for {} exited with {}",
+            command, transition.component, output.status
+        );
+    }
+    Ok(output.status.code())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Status;
+    use yare::parameterized;
+
+    fn transition() -> Transition {
+        Transition {
+            component: "stratum1".to_string(),
+            from: Some(Status::OK),
+            to: Status::FAILED,
+            conditions: vec![],
+            details: vec!["s1 is down".to_string(), "s2 is down".to_string()],
+        }
+    }
+
+    #[parameterized(
+        success = { "true", Some(0), None },
+        failure = { "sh -c 'exit 3'", Some(3), None },
+        environment = { r#"sh -c 'test "$COMPONENT $OLD_STATUS $NEW_STATUS" = "stratum1 OK FAILED"'"#, Some(0), None },
+        details = { r#"sh -c 'test "$DETAILS" = "s1 is down
+s2 is down"'"#, Some(0), None },
+        stdin = { r#"sh -c 'test "$(cat)" = "{\"title\": \"EESSI\"}"'"#, Some(0), None },
+        ignores_stdin = { "sh -c 'exit 0'", Some(0), None },
+        missing = { "/nonexistent/notify-oncall", None, Some("failed to start") },
+        unbalanced = { "sh -c 'exit 0", None, Some("unbalanced quotes") },
+        timeout = { "sleep 10", None, Some("timed out after 0.2 seconds") },
+    )]
+    #[test_macro(tokio::test)]
+    async fn test_run(command: &str, exit_code: Option<i32>, error: Option<&str>) {
+        let hook_run = run(
+            command,
+            &transition(),
+            br#"{"title": "EESSI"}"#,
+            Duration::from_millis(200),
+        )
+        .await;
+
+        assert_eq!(hook_run.component, "stratum1");
+        assert_eq!(hook_run.exit_code, exit_code);
+        match error {
+            Some(error) => assert!(
+                hook_run
+                    .error
+                    .as_deref()
+                    .unwrap_or_default()
+                    .contains(error),
+                "{:?}",
+                hook_run
+            ),
+            None => assert_eq!(hook_run.error, None),
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use pulldown_cmark::{html, Options, Parser};
+use serde::{Deserialize, Serialize};
+
+use crate::config::Incident;
+use crate::models::Status;
+
+/// An incident with its body rendered to HTML, for the page.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RenderedIncident {
+    #[serde(flatten)]
+    pub incident: Incident,
+    pub body_html: String,
+    pub resolved: bool,
+}
+
+/// Render markdown to HTML. Raw HTML in the markdown is escaped rather than passed through.
+pub fn markdown_to_html(markdown: &str) -> String {
+    let parser = Parser::new_ext(
+        markdown,
+        Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TABLES,
+    )
+    .map(|event| match event {
+        pulldown_cmark::Event::Html(raw) | pulldown_cmark::Event::InlineHtml(raw) => {
+            pulldown_cmark::Event::Text(raw)
+        }
+        event => event,
+    });
+    let mut rendered = String::new();
+    html::push_html(&mut rendered, parser);
+    rendered
+}
+
+/// The incidents, newest first, with their bodies rendered.
+pub fn render(incidents: &[Incident]) -> Vec<RenderedIncident> {
+    let mut rendered: Vec<RenderedIncident> = incidents
+        .iter()
+        .map(|incident| RenderedIncident {
+            incident: incident.clone(),
+            body_html: markdown_to_html(&incident.body),
+            resolved: incident.is_resolved(),
+        })
+        .collect();
+    rendered.sort_by_key(|r| std::cmp::Reverse(r.incident.started_at));
+    rendered
+}
+
+/// The overall status to show: OK and DEGRADED become WARNING while an incident is unresolved,
+/// if `warn` is set.
+pub fn overall_status(status: Status, incidents: &[Incident], warn: bool) -> Status {
+    if warn && status < Status::WARNING && incidents.iter().any(|i| !i.is_resolved()) {
+        Status::WARNING
+    } else {
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::IncidentStatus;
+    use yare::parameterized;
+
+    fn incident(id: &str, started_at: &str, status: IncidentStatus) -> Incident {
+        Incident {
+            id: id.to_string(),
+            title: "Stratum 1 outage at AWS".to_string(),
+            body: "The **AWS** Stratum 1 is down.".to_string(),
+            status,
+            started_at: started_at.parse().unwrap(),
+            resolved_at: None,
+            components: vec!["stratum1".to_string()],
+        }
+    }
+
+    #[parameterized(
+        emphasis = { "The **AWS** Stratum 1 is down.", "<p>The <strong>AWS</strong> Stratum 1 is down.</p>\n" },
+        list = { "- one\n- two", "<ul>\n<li>one</li>\n<li>two</li>\n</ul>\n" },
+        link = { "[EESSI](https://eessi.io)", "<p><a href=\"https://eessi.io\">EESSI</a></p>\n" },
+        inline_html = { "a <script>alert(1)</script>", "<p>a &lt;script&gt;alert(1)&lt;/script&gt;</p>\n" },
+        block_html = { "<div>x</div>", "&lt;div&gt;x&lt;/div&gt;" },
+        empty = { "", "" },
+    )]
+    fn test_markdown_to_html(markdown: &str, expected: &str) {
+        assert_eq!(markdown_to_html(markdown), expected);
+    }
+
+    #[test]
+    fn test_render_newest_first() {
+        let mut resolved = incident("old", "2024-05-01T08:00:00Z", IncidentStatus::Monitoring);
+        resolved.resolved_at = Some("2024-05-01T10:00:00Z".parse().unwrap());
+        let incidents = [
+            resolved,
+            incident("new", "2024-06-01T08:00:00Z", IncidentStatus::Identified),
+        ];
+
+        let rendered = render(&incidents);
+
+        let ids: Vec<(&str, bool)> = rendered
+            .iter()
+            .map(|r| (r.incident.id.as_str(), r.resolved))
+            .collect();
+        assert_eq!(ids, [("new", false), ("old", true)]);
+        assert_eq!(
+            rendered[0].body_html,
+            "<p>The <strong>AWS</strong> Stratum 1 is down.</p>\n"
+        );
+    }
+
+    #[parameterized(
+        ok_unresolved = { Status::OK, IncidentStatus::Investigating, true, Status::WARNING },
+        degraded_unresolved = { Status::DEGRADED, IncidentStatus::Monitoring, true, Status::WARNING },
+        failed_unresolved = { Status::FAILED, IncidentStatus::Identified, true, Status::FAILED },
+        ok_resolved = { Status::OK, IncidentStatus::Resolved, true, Status::OK },
+        not_enabled = { Status::OK, IncidentStatus::Investigating, false, Status::OK },
+    )]
+    fn test_overall_status(status: Status, stage: IncidentStatus, warn: bool, expected: Status) {
+        let incidents = [incident("aws", "2024-05-01T08:00:00Z", stage)];
+        assert_eq!(overall_status(status, &incidents, warn), expected);
+    }
+}
@@ -0,0 +1,196 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::{debug, info};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::config::InfluxConfig;
+use crate::prometheus::FlatSample;
+
+/// The measurement every line is written to.
+pub const MEASUREMENT: &str = "eessi_status";
+
+/// The tags of a line, sorted by key.
+type Tags<'a> = Vec<(&'a str, &'a str)>;
+
+/// Render the samples as InfluxDB line protocol, one line per label set with the metrics as
+/// fields, all at `timestamp` in nanoseconds.
+///
+/// Labels become tags, except empty ones which line protocol doesn't allow. Values that aren't
+/// finite, such as the lag of a server missing a repository, are left out for the same reason.
+pub fn render(samples: &[FlatSample], timestamp: &DateTime<Utc>) -> String {
+    let mut lines: BTreeMap<Tags, Vec<(&str, f64)>> = BTreeMap::new();
+    for sample in samples.iter().filter(|s| s.value.is_finite()) {
+        let mut tags: Tags = sample
+            .labels
+            .iter()
+            .filter(|(_, v)| !v.is_empty())
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        tags.sort();
+        lines
+            .entry(tags)
+            .or_default()
+            .push((sample.name.as_str(), sample.value));
+    }
+
+    let timestamp = timestamp.timestamp_nanos_opt().unwrap_or_default();
+    let mut out = String::new();
+    for (tags, fields) in lines {
+        out.push_str(&escape_measurement(MEASUREMENT));
+        for (key, value) in tags {
+            out.push_str(&format!(",{}={}", escape_key(key), escape_key(value)));
+        }
+        for (i, (key, value)) in fields.iter().enumerate() {
+            let separator = if i == 0 { ' ' } else { ',' };
+            out.push_str(&format!("{}{}={}", separator, escape_key(key), value));
+        }
+        out.push_str(&format!(" {}\n", timestamp));
+    }
+    out
+}
+
+/// Write the lines to the HTTP write endpoint.
+///
+/// Fails on a connection error or a non-success response, including the response body.
+pub async fn write(config: &InfluxConfig, url: &str, lines: &str) -> Result<()> {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(config.timeout_seconds))
+        .build()
+        .context("Failed to build the InfluxDB client")?;
+
+    let mut request = client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "text/plain; charset=utf-8")
+        .body(lines.to_string());
+    if let Some(name) = &config.token_env {
+        let token =
+            std::env::var(name).context(format!("Environment variable {} is not set", name))?;
+        request = request.header(reqwest::header::AUTHORIZATION, format!("Token {}", token));
+    }
+
+    debug!("Writing metrics to {}", url);
+    let response = request
+        .send()
+        .await
+        .context(format!("Failed to write metrics to {}", url))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        anyhow::bail!(
+            "InfluxDB at {} responded with {}: {}",
+            url,
+            status,
+            body.trim()
+        );
+    }
+
+    info!("Metrics written to: {}", url);
+    Ok(())
+}
+
+/// Measurements escape commas and spaces.
+fn escape_measurement(s: &str) -> String {
+    escape(s, &[',', ' '])
+}
+
+/// Tag keys, tag values and field keys escape commas, equals signs and spaces.
+fn escape_key(s: &str) -> String {
+    escape(s, &[',', '=', ' '])
+}
+
+fn escape(s: &str, special: &[char]) -> String {
+    let mut out = String::with_capacity(s.len());
+    for ch in s.chars() {
+        if special.contains(&ch) {
+            out.push('\\');
+        }
+        out.push(ch);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prometheus::MetricsBuilder;
+    use chrono::TimeZone;
+    use yare::parameterized;
+
+    fn sample(name: &str, labels: &[(&str, &str)], value: f64) -> FlatSample {
+        FlatSample {
+            name: name.to_string(),
+            labels: labels
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            value,
+        }
+    }
+
+    #[parameterized(
+        plain = { "software.eessi.io", "software.eessi.io" },
+        comma = { "a,b", r"a\,b" },
+        equals = { "a=b", r"a\=b" },
+        space = { "a b", r"a\ b" },
+    )]
+    fn test_escape_key(value: &str, expected: &str) {
+        assert_eq!(escape_key(value), expected);
+    }
+
+    #[test]
+    fn test_escape_measurement() {
+        assert_eq!(
+            escape_measurement("eessi status,x=y"),
+            r"eessi\ status\,x=y"
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let timestamp = Utc.with_ymd_and_hms(2025, 10, 2, 7, 28, 0).unwrap();
+        let labels = [
+            ("type", "stratum1"),
+            ("server", "s1.example.com"),
+            ("repository", "software.eessi.io"),
+        ];
+        let samples = [
+            sample("eessi_status", &[], 0.0),
+            sample("repo_revision", &labels, 12.0),
+            sample("repo_revision_lag", &labels, 1.0),
+            sample(
+                "repo_revision_lag",
+                &[("server", "s2"), ("member", "")],
+                f64::NAN,
+            ),
+            sample("repo_publish_age_seconds", &labels, 90.5),
+        ];
+
+        assert_eq!(
+            render(&samples, &timestamp),
+            "eessi_status eessi_status=0 1759390080000000000\n\
+             eessi_status,repository=software.eessi.io,server=s1.example.com,type=stratum1 \
+             repo_revision=12,repo_revision_lag=1,repo_publish_age_seconds=90.5 \
+             1759390080000000000\n"
+        );
+    }
+
+    #[test]
+    fn test_render_matches_prometheus_samples() {
+        let timestamp = Utc.with_ymd_and_hms(2025, 10, 2, 7, 28, 0).unwrap();
+        let mut b = MetricsBuilder::new().with_constant_labels([("site", "ams")]);
+        b.add_gauge(
+            "server_status",
+            "Status",
+            2.0,
+            &[("server", "s 1")],
+            Some(5),
+        );
+
+        assert_eq!(
+            render(&b.flatten().unwrap(), &timestamp),
+            "eessi_status,server=s\\ 1,site=ams server_status=2 1759390080000000000\n"
+        );
+    }
+}
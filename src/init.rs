@@ -0,0 +1,159 @@
+use anyhow::{bail, Context, Result};
+use serde_json::json;
+use std::path::Path;
+
+use crate::config::{ConfigFile, ConfigFormat};
+use crate::dependencies::OutputSink;
+
+/// The comment at the top of the example, in the formats that have comments.
+const HEADER: &str = "\
+# An example configuration for the EESSI status page, written by `init`.
+# Replace the servers and repositories with your own. The rules are looked up by
+# their id: stratum0_servers, stratum1_servers, sync_servers and eessi_status.
+# Every other section is optional, and shown here with its defaults.
+";
+
+/// A complete configuration for EESSI: a stratum0, two stratum1s, a sync server, the
+/// repositories and a rule for each rule id that is looked up.
+pub fn example() -> ConfigFile {
+    serde_json::from_value(json!({
+        "meta": {
+            "title": "EESSI status",
+            "logging_level": "info",
+            "contact_email": "support@eessi.io",
+            "repo_url": "https://www.eessi.io/docs/repositories/",
+            "repo_url_text": "EESSI repositories"
+        },
+        "servers": [
+            { "hostname": "rug-nl-s0.eessi.science", "server_type": "Stratum0", "backend_type": "CVMFS" },
+            { "hostname": "aws-eu-central-s1.eessi.science", "server_type": "Stratum1", "backend_type": "CVMFS" },
+            { "hostname": "azure-us-east-s1.eessi.science", "server_type": "Stratum1", "backend_type": "CVMFS" },
+            { "hostname": "aws-eu-west-s1-sync.eessi.science", "server_type": "SyncServer", "backend_type": "S3" }
+        ],
+        "repositories": ["software.eessi.io", "dev.eessi.io", "riscv.eessi.io"],
+        "limit_scraping_to_repositories": false,
+        "ignored_repositories": [],
+        "rules": [
+            {
+                "id": "stratum0_servers",
+                "description": "Stratum0 servers",
+                "conditions": [
+                    { "status": "FAILED", "when": "stratum0_servers == 0" },
+                    { "status": "OK", "when": "stratum0_servers > 0" }
+                ]
+            },
+            {
+                "id": "stratum1_servers",
+                "description": "Stratum1 servers",
+                "conditions": [
+                    { "status": "FAILED", "when": "stratum1_servers == 0" },
+                    { "status": "WARNING", "when": "stratum1_servers == 1" },
+                    { "status": "OK", "when": "stratum1_servers >= 2" }
+                ]
+            },
+            {
+                "id": "sync_servers",
+                "description": "Sync servers",
+                "conditions": [
+                    { "status": "FAILED", "when": "sync_servers == 0" },
+                    { "status": "OK", "when": "sync_servers > 0" }
+                ]
+            },
+            {
+                "id": "eessi_status",
+                "description": "EESSI status",
+                "conditions": [
+                    { "status": "FAILED", "when": "stratum1_servers == 0" },
+                    { "status": "WARNING", "when": "stratum0_servers == 0 || sync_servers == 0" },
+                    { "status": "DEGRADED", "when": "stratum1_servers == 1" },
+                    { "status": "DEGRADED", "when": "repos_out_of_sync > 0" },
+                    { "status": "OK", "when": "stratum0_servers > 0 && stratum1_servers > 1 && sync_servers > 0" }
+                ]
+            }
+        ]
+    }))
+    .expect("The example configuration is valid")
+}
+
+/// The example in `format`, with a comment on top where the format has them.
+pub fn render(format: ConfigFormat) -> Result<String> {
+    let rendered = format.render(&example()).map_err(anyhow::Error::msg)?;
+    Ok(match format {
+        ConfigFormat::Json => rendered,
+        ConfigFormat::Yaml | ConfigFormat::Toml => format!("{}\n{}", HEADER, rendered),
+    })
+}
+
+/// Write the example to `path`, which is only overwritten with `force`.
+pub fn write(path: &Path, format: ConfigFormat, force: bool, sink: &OutputSink) -> Result<()> {
+    if path.exists() && !force {
+        bail!("{:?} already exists, use --force to overwrite it", path);
+    }
+    sink.atomic_write(path, render(format)?.as_bytes())
+        .context(format!(
+            "Failed to write the example configuration to {:?}",
+            path
+        ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ConfigManager;
+    use yare::parameterized;
+
+    #[parameterized(
+        json = { ConfigFormat::Json },
+        yaml = { ConfigFormat::Yaml },
+        toml = { ConfigFormat::Toml },
+    )]
+    fn test_example_round_trips(format: ConfigFormat) -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join(format!("config.{}", format.extension()));
+
+        write(&path, format, false, &OutputSink::default())?;
+        let manager = ConfigManager::new(path.to_str().unwrap(), &[]);
+
+        let config = manager.get_config();
+        assert_eq!(config.servers.len(), 4);
+        for rule in [
+            "stratum0_servers",
+            "stratum1_servers",
+            "sync_servers",
+            "eessi_status",
+        ] {
+            assert!(manager.get_conditions_for_rule(rule).is_some(), "{}", rule);
+        }
+        assert_eq!(
+            serde_json::to_value(&config)?,
+            serde_json::to_value(example())?
+        );
+        Ok(())
+    }
+
+    #[parameterized(
+        json = { ConfigFormat::Json, false },
+        yaml = { ConfigFormat::Yaml, true },
+        toml = { ConfigFormat::Toml, true },
+    )]
+    fn test_render_header(format: ConfigFormat, commented: bool) -> Result<()> {
+        assert_eq!(render(format)?.starts_with('#'), commented);
+        Ok(())
+    }
+
+    #[parameterized(
+        refused = { false, false },
+        forced = { true, true },
+    )]
+    fn test_write_existing(force: bool, overwritten: bool) -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("config.json");
+        std::fs::write(&path, "{}")?;
+
+        let result = write(&path, ConfigFormat::Json, force, &OutputSink::default());
+
+        assert_eq!(result.is_ok(), overwritten);
+        assert_eq!(std::fs::read_to_string(&path)? != "{}", overwritten);
+        Ok(())
+    }
+}
@@ -0,0 +1,1808 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use cvmfs_server_scraper::ServerType;
+use log::{error, info, trace, warn};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+mod announcement;
+mod badges;
+pub mod cache;
+pub mod check;
+mod client_scrape;
+pub mod config;
+pub mod daemon;
+pub mod dependencies;
+pub mod diff;
+pub mod doctor;
+pub mod email;
+pub mod events;
+mod favicon;
+mod filters;
+pub mod graphite;
+pub mod heartbeat;
+pub mod history;
+pub mod hooks;
+mod incidents;
+pub mod influx;
+pub mod init;
+pub mod listing;
+pub mod locale;
+pub mod lock;
+pub mod logging;
+mod markdown;
+mod minify;
+pub mod models;
+pub mod notifications;
+pub mod pagerduty;
+mod plaintext;
+pub mod precompress;
+pub mod privacy;
+pub mod prometheus;
+pub mod publish;
+pub mod pushgateway;
+pub mod report;
+mod scraping;
+pub mod server;
+pub mod slack;
+pub mod state;
+pub mod statsd;
+mod summary;
+pub mod templating;
+pub mod terminal;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+mod tls;
+mod undefined;
+
+pub use config::{ConfigFile, ConfigManager};
+pub use models::{Status, StatusManager, StatusPageData};
+pub use prometheus::MetricsBuilder;
+
+use dependencies::{populate, OutputSink, PopulateOptions};
+use models::{
+    EESSIStatus, Generator, HighestRevisions, StratumStatus, ToEESSILabel, SCHEMA_VERSION,
+};
+use templating::{render_template_to_file, RepoStatus, StatusInfo, TemplateError, Templates};
+
+/// Load the configuration at `path`, with the keys in `overrides`, such as `meta.title=Test`,
+/// set over those of the file.
+pub fn load_config(path: &str, overrides: &[String]) -> Result<ConfigManager> {
+    ConfigManager::load(path, overrides).map_err(anyhow::Error::msg)
+}
+
+/// Where and what `render` writes.
+#[derive(Debug, Clone)]
+pub struct OutputOptions {
+    /// The directory the files are written to, and relative to which the other paths are.
+    pub destination: PathBuf,
+    /// The status page, unless the configuration has pages.
+    pub output_file: PathBuf,
+    pub json_output_file: PathBuf,
+    pub summary_output_file: PathBuf,
+    pub text_output_file: Option<PathBuf>,
+    pub markdown_output_file: Option<PathBuf>,
+    /// The announcement to show on the page. [default: `<destination>/announcement.json`]
+    pub announcement_file: Option<PathBuf>,
+    /// The directory of templates, overriding `template_dir` in the `meta` section.
+    pub template_dir: Option<PathBuf>,
+    /// The directory to copy the resources from, overriding `resources_dir` in the `meta` section.
+    pub resources_dir: Option<PathBuf>,
+    /// Whether the pages and the JSON documents are written.
+    pub outputs: config::OutputConfig,
+    pub strict_templates: bool,
+    pub minify_html: bool,
+    pub hashed_assets: bool,
+    /// Write every badge, rather than only those in the configuration.
+    pub badges: bool,
+    pub force_resource_creation: bool,
+    pub keep_local_modifications: bool,
+    pub prune_dry_run: bool,
+}
+
+impl OutputOptions {
+    /// The files of the command line defaults in `destination`.
+    pub fn new(destination: impl Into<PathBuf>) -> Self {
+        OutputOptions {
+            destination: destination.into(),
+            output_file: PathBuf::from("index.html"),
+            json_output_file: PathBuf::from("status.json"),
+            summary_output_file: PathBuf::from("summary.json"),
+            text_output_file: None,
+            markdown_output_file: None,
+            announcement_file: None,
+            template_dir: None,
+            resources_dir: None,
+            outputs: config::OutputConfig::default(),
+            strict_templates: false,
+            minify_html: false,
+            hashed_assets: false,
+            badges: false,
+            force_resource_creation: false,
+            keep_local_modifications: false,
+            prune_dry_run: false,
+        }
+    }
+}
+
+/// How `generate` and `render_from_json` run, besides where and what `render` writes.
+#[derive(Debug, Clone)]
+pub struct RunOptions {
+    /// Where and what `render` writes. The directories that aren't set are taken from the `meta`
+    /// section, and the outputs that are off here are skipped even if configured.
+    pub output: OutputOptions,
+    /// The directory of the configuration file, which the directories of the `meta` section are
+    /// relative to.
+    pub config_dir: PathBuf,
+    /// Only scrape these servers, if any.
+    pub servers: Vec<String>,
+    /// Only scrape servers of these types, if any.
+    pub server_types: Vec<ServerType>,
+    /// Only scrape these repositories, if any.
+    pub repositories: Vec<String>,
+    /// The file keeping the state across runs. [default: `<destination>/.state.json`]
+    pub state_file: Option<PathBuf>,
+    /// The repositories and the servers whose revision is accepted to have gone backwards.
+    pub acknowledged_regressions: Vec<(String, String)>,
+    /// Write the changes since the previous status to changes.json.
+    pub diff: bool,
+    /// Only plan the writes, and send no notifications, metrics or heartbeat.
+    pub dry_run: bool,
+    pub email_dry_run: bool,
+    /// Compress the written files, even if the `precompress` section doesn't enable it.
+    pub precompress: bool,
+    /// The metrics file, relative to the destination or absolute, overriding `output` in the
+    /// `metrics` section.
+    pub prometheus_output: Option<PathBuf>,
+    /// Write the metrics file, to `metrics` in the destination unless another file is set.
+    pub prometheus_metrics: bool,
+    pub metrics_format: prometheus::MetricsFormat,
+    pub metrics_json_output: Option<PathBuf>,
+    /// Overrides `url` in the `push_gateway` section.
+    pub push_gateway_url: Option<String>,
+    /// Overrides `output` in the `influx` section.
+    pub influx_output: Option<PathBuf>,
+    pub publish_mode: publish::PublishMode,
+    /// How many releases to keep with `PublishMode::Symlink`, including the current one.
+    pub keep_releases: usize,
+}
+
+impl RunOptions {
+    /// The command line defaults, writing to `destination`.
+    pub fn new(destination: impl Into<PathBuf>) -> Self {
+        RunOptions {
+            output: OutputOptions::new(destination),
+            config_dir: PathBuf::new(),
+            servers: vec![],
+            server_types: vec![],
+            repositories: vec![],
+            state_file: None,
+            acknowledged_regressions: vec![],
+            diff: false,
+            dry_run: false,
+            email_dry_run: false,
+            precompress: false,
+            prometheus_output: None,
+            prometheus_metrics: false,
+            metrics_format: prometheus::MetricsFormat::default(),
+            metrics_json_output: None,
+            push_gateway_url: None,
+            influx_output: None,
+            publish_mode: publish::PublishMode::default(),
+            keep_releases: 5,
+        }
+    }
+
+    /// A description of each restriction put on the run, empty for a full run.
+    pub fn scope(&self) -> Vec<String> {
+        let mut scope = vec![];
+        if !self.servers.is_empty() {
+            scope.push(format!("servers: {}", self.servers.join(", ")));
+        }
+        if !self.server_types.is_empty() {
+            scope.push(format!(
+                "server types: {}",
+                self.server_types
+                    .iter()
+                    .map(|t| t.to_label())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        if !self.repositories.is_empty() {
+            scope.push(format!("repositories: {}", self.repositories.join(", ")));
+        }
+        scope
+    }
+
+    /// The configuration, restricted by the server and repository filters.
+    pub fn scoped_config(&self, config_manager: &ConfigManager) -> Result<ConfigFile> {
+        let mut config = ConfigFile::clone(&config_manager.get_config());
+        config
+            .retain_servers(&self.servers, &self.server_types)
+            .map_err(anyhow::Error::msg)?;
+        config.restrict_repositories(&self.repositories);
+        Ok(config)
+    }
+
+    /// The file keeping the state across runs.
+    pub fn state_file(&self) -> PathBuf {
+        self.state_file
+            .clone()
+            .unwrap_or_else(|| self.output.destination.join(state::STATE_FILE_NAME))
+    }
+
+    /// The directory of templates, if any: the one of the output options, or `template_dir` in
+    /// the `meta` section.
+    pub fn template_dir(&self, meta: &config::ConfigSection) -> Option<PathBuf> {
+        self.output.template_dir.clone().or_else(|| {
+            let dir = meta.template_dir.as_ref()?;
+            Some(self.config_dir.join(dir))
+        })
+    }
+
+    /// The directory to copy the resources from, if any: the one of the output options, or
+    /// `resources_dir` in the `meta` section.
+    pub fn resources_dir(&self, meta: &config::ConfigSection) -> Option<PathBuf> {
+        self.output.resources_dir.clone().or_else(|| {
+            let dir = meta.resources_dir.as_ref()?;
+            Some(self.config_dir.join(dir))
+        })
+    }
+
+    /// Where the files of a run are written: only reported in a dry run, and written as set by
+    /// `durability` and `output_file_mode` in the `meta` section.
+    pub fn output_sink(&self, meta: &config::ConfigSection) -> OutputSink {
+        let mode = match self.dry_run {
+            true => dependencies::WriteMode::DryRun,
+            false => dependencies::WriteMode::Write,
+        };
+        OutputSink::new(
+            mode,
+            dependencies::WriteOptions {
+                durability: meta.durability,
+                file_mode: meta.output_file_mode,
+            },
+        )
+    }
+
+    /// Where and what `render` writes, with the directories of the `meta` section.
+    pub fn output_options(
+        &self,
+        meta: &config::ConfigSection,
+        outputs: &config::OutputConfig,
+    ) -> OutputOptions {
+        OutputOptions {
+            template_dir: self.template_dir(meta),
+            resources_dir: self.resources_dir(meta),
+            outputs: outputs.clone(),
+            ..self.output.clone()
+        }
+    }
+
+    /// The outputs that are written, with those that are off in the output options turned off.
+    pub fn output_config(&self, config: &config::OutputConfig) -> config::OutputConfig {
+        config::OutputConfig {
+            html: config.html && self.output.outputs.html,
+            json: config.json && self.output.outputs.json,
+        }
+    }
+
+    /// Refuse a run that would write nothing, and a diff against a status.json that isn't
+    /// written.
+    pub fn check_outputs(&self, outputs: &config::OutputConfig, wants_metrics: bool) -> Result<()> {
+        let writes_other =
+            self.output.text_output_file.is_some() || self.output.markdown_output_file.is_some();
+        if !outputs.html && !outputs.json && !wants_metrics && !writes_other {
+            anyhow::bail!(
+                "Both the HTML and the JSON output are skipped, so nothing would be written. \
+                 Enable metrics with --prometheus-metrics, --prometheus-output or \
+                 --metrics-json-output, or drop --no-html or --no-json"
+            );
+        }
+        if self.diff && !outputs.json {
+            anyhow::bail!("--diff compares with the previous status.json, which isn't written with the JSON output skipped");
+        }
+        Ok(())
+    }
+
+    /// Where to write the metrics file, `None` if it isn't wanted: `prometheus_output`, or
+    /// `output` in the `metrics` section, or `metrics` with `prometheus_metrics`. A relative
+    /// path is relative to the destination.
+    pub fn prometheus_output(&self, config: &config::MetricsConfig) -> Option<PathBuf> {
+        let output = self.prometheus_output.as_ref().or(config.output.as_ref());
+        if output.is_none() && !self.prometheus_metrics {
+            return None;
+        }
+        Some(
+            self.output
+                .destination
+                .join(output.map_or(Path::new("metrics"), PathBuf::as_path)),
+        )
+    }
+
+    /// The Pushgateway to push to, with the URL of the options if given.
+    fn push_gateway_config(&self, config: &ConfigFile) -> Option<config::PushGatewayConfig> {
+        match (&self.push_gateway_url, &config.push_gateway) {
+            (Some(url), Some(push_gateway)) => Some(config::PushGatewayConfig {
+                url: url.clone(),
+                ..push_gateway.clone()
+            }),
+            (Some(url), None) => Some(config::PushGatewayConfig::with_url(url)),
+            (None, push_gateway) => push_gateway.clone(),
+        }
+    }
+
+    fn influx_config(&self, config: &ConfigFile) -> Option<config::InfluxConfig> {
+        match (&self.influx_output, &config.influx) {
+            (Some(output), Some(influx)) => Some(config::InfluxConfig {
+                output: Some(output.clone()),
+                ..influx.clone()
+            }),
+            (Some(output), None) => Some(config::InfluxConfig::with_output(output.clone())),
+            (None, influx) => influx.clone(),
+        }
+    }
+}
+
+/// Scrape the servers of `config`, with their data paths and certificates. With
+/// `use_stale_data_minutes`, the servers that fail are taken from `last_scrape`, and the
+/// repositories whose revision is below the one in `highest_revisions` fail.
+pub async fn scrape(
+    config: &config::ConfigFile,
+    last_scrape: Option<&cache::LastScrape>,
+    highest_revisions: &HighestRevisions,
+) -> Result<StatusManager> {
+    let mut servers = vec![];
+
+    for server in config.servers.iter() {
+        // Addresses are validated when the configuration was loaded.
+        let members = server.member_addresses().unwrap_or_default();
+        let addresses = if members.is_empty() {
+            vec![server.resolve_to_address().unwrap_or(None)]
+        } else {
+            // Each member of a load balanced service is scraped individually.
+            members.into_iter().map(Some).collect()
+        };
+
+        for resolve_to in addresses {
+            servers.push(scraping::ScrapeTarget {
+                server: cvmfs_server_scraper::Server::new(
+                    server.server.server_type,
+                    server.server.backend_type,
+                    server.server.hostname.clone(),
+                ),
+                resolve_to,
+            });
+        }
+    }
+
+    let repolist = config.repositories.clone();
+    let ignored_repos = config.ignored_repositories.clone();
+
+    // Scrape all servers in parallel, timing each of them.
+    let scrape = scraping::scrape_servers(
+        servers,
+        repolist,
+        ignored_repos,
+        config.limit_scraping_to_repositories,
+        &config.scrape,
+    );
+
+    // Check the certificates of HTTPS servers while the scrape is running.
+    let https_servers: Vec<_> = config.servers.iter().filter(|s| s.https).collect();
+    let resolve_to: Vec<_> = https_servers
+        .iter()
+        .map(|s| s.resolve_to_address().unwrap_or(None))
+        .collect();
+    let cert_checks =
+        futures::future::join_all(https_servers.iter().zip(&resolve_to).map(|(s, address)| {
+            tls::fetch_certificate_expiry(&s.server.hostname, address.as_deref())
+        }));
+
+    let (scrapes, cert_expiries) = tokio::join!(scrape, cert_checks);
+    let scrapes = scrapes?;
+
+    let mut status_manager = StatusManager::new(scrapes, config);
+    if let (Some(max_age_minutes), Some(last_scrape)) = (config.use_stale_data_minutes, last_scrape)
+    {
+        status_manager.apply_stale_data(last_scrape, max_age_minutes, chrono::Utc::now());
+    }
+    status_manager.apply_revision_regressions(highest_revisions, config);
+    status_manager.apply_cert_fingerprints(config);
+
+    let data_checks = scraping::check_data_paths(
+        status_manager.data_check_targets(config),
+        &config.deep_check,
+    )
+    .await;
+    for check in data_checks.iter() {
+        status_manager.apply_data_check(check);
+    }
+    let now = chrono::Utc::now();
+    for (server, expiry) in https_servers.iter().zip(cert_expiries) {
+        match expiry {
+            Ok(expiry) => status_manager.apply_certificate_expiry(
+                &server.server.hostname,
+                expiry,
+                &config.certificate_expiry,
+                now,
+            ),
+            Err(e) => warn!("Certificate check failed: {:#}", e),
+        }
+    }
+
+    Ok(status_manager)
+}
+
+/// The status of the page, of the servers scraped into `status_manager` by the rules of
+/// `config_manager`, in `locale`.
+pub fn build_page_data(
+    config_manager: &config::ConfigManager,
+    status_manager: &StatusManager,
+    locale: &locale::Locale,
+    run_start_time: &DateTime<Utc>,
+) -> Result<StatusPageData> {
+    let config = config_manager.get_config();
+    let s0status = get_status(
+        config_manager,
+        status_manager,
+        "stratum0_servers",
+        |sm, c| sm.status_stratum0(c),
+    )?;
+    let s1status = get_status(
+        config_manager,
+        status_manager,
+        "stratum1_servers",
+        |sm, c| sm.status_stratum1(c),
+    )?;
+    let syncstatus = get_status(config_manager, status_manager, "sync_servers", |sm, c| {
+        sm.status_syncserver(c)
+    })?;
+    let eessi_status = get_status(config_manager, status_manager, "eessi_status", |sm, c| {
+        sm.status_overall(c)
+    })?;
+    let eessi_status =
+        incidents::overall_status(eessi_status, &config.incidents, config.incidents_warn);
+
+    let now = chrono::Utc::now();
+    let mut data = StatusPageData {
+        schema_version: SCHEMA_VERSION,
+        generator: Generator::current(),
+        title: config.meta.title.clone(),
+        eessi_status: create_eessi_status(eessi_status, locale),
+        maintenance_failing_servers: status_manager.maintenance_failing_servers(eessi_status),
+        contact_email: config.meta.contact_email.clone(),
+        last_update: now.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        generated_at: now.timestamp(),
+        legend: StatusInfo::all(locale),
+        stratum0: create_stratum_status(s0status, status_manager, ServerType::Stratum0, locale),
+        stratum1: create_stratum_status(s1status, status_manager, ServerType::Stratum1, locale),
+        syncservers: create_stratum_status(
+            syncstatus,
+            status_manager,
+            ServerType::SyncServer,
+            locale,
+        ),
+        repositories_status: create_repo_status(),
+        repositories: status_manager.details_repositories(),
+        repository_matrix: status_manager.repository_matrix(),
+        config: config_manager.get_config(),
+        servers: status_manager.get_server_status_for_all(),
+        run_duration_seconds: (chrono::Utc::now() - *run_start_time).num_milliseconds() as f64
+            / 1000.0,
+        rendered_from_cache: false,
+        partial_run: false,
+        scope: vec![],
+        recent_events: vec![],
+        uptime: vec![],
+        daily: vec![],
+        incidents: incidents::render(&config.incidents),
+        announcement: None,
+        extra: config.meta.extra.clone(),
+        links: config.meta.links.clone(),
+        locale: locale.name().to_string(),
+        refresh_seconds: config.meta.refresh_seconds,
+        stale_after_seconds: config.meta.stale_after_seconds(),
+        base_url: config.meta.base_url.clone(),
+        description: config.meta.description.clone(),
+        og_image: config.meta.og_image_url(),
+    };
+    models::apply_status_classes(&mut data, &config);
+    Ok(data)
+}
+
+fn get_status<F>(
+    config_manager: &config::ConfigManager,
+    status_manager: &StatusManager,
+    rule: &str,
+    status_fn: F,
+) -> Result<Status>
+where
+    F: FnOnce(&StatusManager, Vec<config::Condition>) -> Status,
+{
+    let conditions = config_manager
+        .get_conditions_for_rule(rule)
+        .context(format!("No rules found for '{}'", rule))?;
+    Ok(status_fn(status_manager, conditions))
+}
+
+fn create_eessi_status(status: Status, locale: &locale::Locale) -> EESSIStatus {
+    EESSIStatus {
+        status,
+        class: status.class().to_string(),
+        text: status.text_localized(locale).to_string(),
+        description: status.description_localized(locale).to_string(),
+    }
+}
+
+fn create_stratum_status(
+    status: Status,
+    status_manager: &StatusManager,
+    server_type: ServerType,
+    locale: &locale::Locale,
+) -> StratumStatus {
+    StratumStatus {
+        status,
+        status_class: status.class().to_string(),
+        details: if status == Status::FAILED && server_type == ServerType::Stratum0 {
+            vec![locale
+                .get("stratum0.unreachable")
+                .unwrap_or_default()
+                .to_string()]
+        } else {
+            status_manager.details_stratum0()
+        },
+        servers: status_manager.get_server_status_for_all_by_type(server_type),
+    }
+}
+
+fn create_repo_status() -> RepoStatus {
+    RepoStatus {
+        name: "Repositories".to_string(),
+        status: Status::OK,
+        revision_class: Status::OK.class().to_string(),
+        snapshot_class: Status::OK.class().to_string(),
+        details: vec![],
+        href: String::new(),
+        revision: None,
+        published_at: None,
+    }
+}
+
+/// The locale of the page. One that is missing or invalid is a template error.
+pub fn load_locale(name: &str, template_dir: Option<&Path>) -> Result<locale::Locale> {
+    locale::Locale::load(name, template_dir)
+        .context(TemplateError(format!("Failed to load locale {}", name)))
+}
+
+/// The templates to render the pages with, strict with `strict_templates` in the
+/// options or the `meta` section, and minifying the pages with `minify_html` in either.
+fn load_templates(
+    options: &OutputOptions,
+    meta: &config::ConfigSection,
+    locale: &str,
+) -> Result<Templates> {
+    let dir = options.template_dir.as_deref();
+    Ok(Templates::load(dir)?
+        .strict(options.strict_templates || meta.strict_templates)
+        .minify(options.minify_html || meta.minify_html)
+        .locale(load_locale(locale, dir)?))
+}
+
+/// How many runs to keep replaced hashed stylesheets and scripts for, with `hashed_assets` in the
+/// options or the `meta` section, `None` without hashed assets.
+fn hashed_assets_keep_runs(options: &OutputOptions, meta: &config::ConfigSection) -> Option<usize> {
+    (options.hashed_assets || meta.hashed_assets).then_some(meta.hashed_assets_keep_runs)
+}
+
+/// Write the page and the documents of the status to `sink`, keeping what was written in the
+/// report.
+pub fn render(
+    status_page_data: &mut StatusPageData,
+    unredacted: &privacy::Unredacted,
+    options: &OutputOptions,
+    sink: &OutputSink,
+    report: &mut report::RunReport,
+) -> Result<()> {
+    let announcement_file = options
+        .announcement_file
+        .clone()
+        .unwrap_or_else(|| options.destination.join(announcement::ANNOUNCEMENT_FILE));
+    status_page_data.announcement =
+        announcement::load(&announcement_file, &status_page_data.config.meta)?;
+
+    let destination = options
+        .destination
+        .to_str()
+        .context("Invalid destination path")?;
+
+    // Without the page, the resources would only litter the destination.
+    if options.outputs.html {
+        let templates = load_templates(
+            options,
+            &status_page_data.config.meta,
+            &status_page_data.locale,
+        )?;
+        let meta = &status_page_data.config.meta;
+        let populated = populate(
+            destination,
+            PopulateOptions {
+                resources_dir: options.resources_dir.as_deref(),
+                link_resources: meta.resources_link,
+                exclude_resources: &meta.exclude_resources,
+                force: options.force_resource_creation,
+                keep_local_modifications: options.keep_local_modifications,
+                prune_dry_run: options.prune_dry_run,
+                hashed_assets_keep_runs: hashed_assets_keep_runs(options, meta),
+            },
+            sink,
+        )?;
+        report.files = populated.files;
+        let templates = templates.assets(populated.assets.clone());
+        for warning in &populated.warnings {
+            report.warn(warning.clone());
+        }
+        let status = status_page_data.eessi_status.status;
+        for path in favicon::write(&options.destination, status, sink)? {
+            report.artifact("favicon", path);
+        }
+        render_pages(
+            options,
+            &templates,
+            status_page_data,
+            unredacted,
+            &populated.integrity,
+            sink,
+            report,
+        )?;
+    }
+    if options.outputs.json {
+        generate_json_output(
+            status_page_data,
+            &options.destination,
+            &options.json_output_file,
+            sink,
+        )?;
+        generate_summary_output(
+            status_page_data,
+            &options.destination,
+            &options.summary_output_file,
+            sink,
+        )?;
+        report.artifact("JSON", options.destination.join(&options.json_output_file));
+        report.artifact(
+            "summary",
+            options.destination.join(&options.summary_output_file),
+        );
+    }
+    if let Some(filename) = &options.text_output_file {
+        let path = options.destination.join(filename);
+        sink.atomic_write(&path, plaintext::render(status_page_data).as_bytes())?;
+        info!("Plain text status written to: {:?}", path);
+        report.artifact("text", path);
+    }
+    if let Some(filename) = &options.markdown_output_file {
+        let path = options.destination.join(filename);
+        sink.atomic_write(&path, markdown::render(status_page_data).as_bytes())?;
+        info!("Markdown status written to: {:?}", path);
+        report.artifact("markdown", path);
+    }
+
+    let wanted = &status_page_data.config.badges;
+    if options.badges || !wanted.is_empty() {
+        let badges = badges::badges(status_page_data, (!options.badges).then_some(wanted));
+        badges::write(&options.destination, &badges, sink)?;
+        report.artifact("badges", options.destination.join(badges::BADGES_DIR));
+    }
+
+    let wanted = &status_page_data.config.badge_endpoints;
+    if !wanted.is_empty() {
+        let badges = badges::badges(status_page_data, Some(wanted));
+        for path in badges::write_endpoints(&options.destination, &badges, sink)? {
+            info!("Badge endpoint written to: {:?}", path);
+        }
+        report.artifact(
+            "badge endpoints",
+            options.destination.join(badges::ENDPOINTS_DIR),
+        );
+    }
+
+    Ok(())
+}
+
+/// The pages to render: those in the configuration, or the status page to `output_file`.
+pub fn pages(options: &OutputOptions, config: &config::ConfigFile) -> Vec<config::PageConfig> {
+    if !config.pages.is_empty() {
+        return config.pages.clone();
+    }
+    vec![config::PageConfig {
+        template: "status.html".to_string(),
+        output_file: options.output_file.clone(),
+        visibility: config::Visibility::Public,
+    }]
+}
+
+/// Render a page to its file in the destination, with the data of the page added to the
+/// `context` shared by the pages, returning the path.
+fn render_page(
+    options: &OutputOptions,
+    templates: &Templates,
+    page: &config::PageConfig,
+    status_page_data: &StatusPageData,
+    unredacted: &privacy::Unredacted,
+    context: &tera::Context,
+    sink: &OutputSink,
+) -> Result<PathBuf> {
+    let mut context = context.clone();
+    match page.visibility {
+        config::Visibility::Public => context.insert("data", status_page_data),
+        config::Visibility::Internal => {
+            context.insert("data", &unredacted.restore(status_page_data)?);
+        }
+    }
+    let path = options.destination.join(&page.output_file);
+    let dir = path.parent().context("Invalid output file path")?;
+    sink.create_dir_all(dir)?;
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .context("Invalid output file path")?;
+    let dir = dir.to_str().context("Invalid destination path")?;
+    render_template_to_file(templates, &page.template, &context, dir, file_name, sink)?;
+    Ok(path)
+}
+
+/// The status to embed in the pages with `meta.embed_data`, the same as status.json, also in
+/// internal pages.
+fn embedded_data(
+    status_page_data: &StatusPageData,
+    report: &mut report::RunReport,
+) -> Result<Option<String>> {
+    let meta = &status_page_data.config.meta;
+    if !meta.embed_data {
+        return Ok(None);
+    }
+    let json = templating::json_island(status_page_data)?;
+    info!("Embedding {} bytes of status data in the pages", json.len());
+    if json.len() > meta.embed_data_warn_bytes {
+        report.warn(format!(
+            "The status data embedded in the pages is {} bytes, more than meta.embed_data_warn_bytes ({})",
+            json.len(),
+            meta.embed_data_warn_bytes
+        ));
+    }
+    Ok(Some(json))
+}
+
+/// Render every page, each to its own file. A page that fails doesn't keep the others from being
+/// written, the run fails once all of them were tried.
+fn render_pages(
+    options: &OutputOptions,
+    templates: &Templates,
+    status_page_data: &StatusPageData,
+    unredacted: &privacy::Unredacted,
+    asset_integrity: &BTreeMap<String, String>,
+    sink: &OutputSink,
+    report: &mut report::RunReport,
+) -> Result<()> {
+    let pages = pages(options, &status_page_data.config);
+    let mut context = tera::Context::new();
+    if let Some(embedded) = embedded_data(status_page_data, report)? {
+        context.insert("embedded_data", &embedded);
+    }
+    context.insert("asset_integrity", asset_integrity);
+    let mut failed = vec![];
+    for page in &pages {
+        match render_page(
+            options,
+            templates,
+            page,
+            status_page_data,
+            unredacted,
+            &context,
+            sink,
+        ) {
+            Ok(path) => report.artifact("HTML", path),
+            Err(e) => {
+                error!("Failed to render page {:?}: {:#}", page.output_file, e);
+                failed.push((page.output_file.display().to_string(), e));
+            }
+        }
+    }
+    if failed.is_empty() {
+        return Ok(());
+    }
+    let names = failed
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let (_, first) = failed.remove(0);
+    Err(first.context(format!(
+        "Failed to render {} of {} pages: {}",
+        failed.len() + 1,
+        pages.len(),
+        names
+    )))
+}
+
+/// Render a detail page, and a JSON document with the same data, for every repository.
+pub fn render_repo_details(
+    options: &OutputOptions,
+    status_manager: &StatusManager,
+    status_page_data: &StatusPageData,
+    run_start_time: DateTime<Utc>,
+    sink: &OutputSink,
+) -> Result<Option<PathBuf>> {
+    if !options.outputs.html && !options.outputs.json {
+        return Ok(None);
+    }
+    let dir = options.destination.join(templating::REPOS_DIR);
+    sink.create_dir_all(&dir)?;
+    let dir_str = dir.to_str().context("Invalid destination path")?;
+    // The resources were written with the status page.
+    let templates = if options.outputs.html {
+        let meta = &status_page_data.config.meta;
+        let assets = match hashed_assets_keep_runs(options, meta) {
+            Some(_) => dependencies::AssetManifest::load(&options.destination, sink).assets,
+            None => BTreeMap::new(),
+        };
+        let templates = load_templates(options, meta, &status_page_data.locale)?.assets(assets);
+        Some((
+            templates,
+            dependencies::asset_integrity(&options.destination, sink)?,
+        ))
+    } else {
+        None
+    };
+
+    for repo in &status_page_data.repositories {
+        let mut detail =
+            status_manager.build_repo_detail(&status_page_data.title, &repo.name, run_start_time);
+        models::apply_repo_detail_status_classes(&mut detail, &status_page_data.config);
+        privacy::redact_repo_detail(&mut detail, &status_page_data.config.privacy);
+        let file_name = templating::repo_file_name(&repo.name);
+
+        if let Some((templates, asset_integrity)) = &templates {
+            let mut context = tera::Context::new();
+            context.insert("data", &detail);
+            context.insert("asset_integrity", asset_integrity);
+            render_template_to_file(
+                templates,
+                "repo_detail.html",
+                &context,
+                dir_str,
+                &format!("{}.html", file_name),
+                sink,
+            )?;
+        }
+        if options.outputs.json {
+            let json = serde_json::to_string_pretty(&detail)?;
+            sink.atomic_write(&dir.join(format!("{}.json", file_name)), json.as_bytes())?;
+        }
+    }
+    Ok(Some(dir))
+}
+
+fn generate_json_output(
+    data: &StatusPageData,
+    destination: &Path,
+    filename: &PathBuf,
+    sink: &OutputSink,
+) -> Result<()> {
+    let fqfn = destination.join(filename);
+    trace!("Generating JSON output file: {:?}", fqfn);
+
+    let json = serde_json::to_string_pretty(data)?;
+    sink.atomic_write(&fqfn, json.as_bytes())?;
+    info!("JSON output file written to: {:?}", fqfn);
+    Ok(())
+}
+
+fn generate_summary_output(
+    data: &StatusPageData,
+    destination: &Path,
+    filename: &PathBuf,
+    sink: &OutputSink,
+) -> Result<()> {
+    let fqfn = destination.join(filename);
+    let json = serde_json::to_string_pretty(&summary::Summary::new(data))?;
+    sink.atomic_write(&fqfn, json.as_bytes())?;
+    info!("Summary written to: {:?}", fqfn);
+    Ok(())
+}
+
+/// What `generate` wrote the pages from.
+pub struct Generated {
+    pub data: StatusPageData,
+    /// The changes since the previous status, with `diff`.
+    pub changes: Option<diff::Changes>,
+}
+
+/// Scrape the servers and write the status page, the JSON status and the metrics once, keeping
+/// what the run did in `report`. The report is written to the destination, or in a dry run gets
+/// the writes the run would have made.
+///
+/// The metrics are also stored in `metrics`, if given, for serving over HTTP. The heartbeat is
+/// pinged once everything is written, as failed if the run failed or the overall status is
+/// FAILED.
+pub async fn generate(
+    options: &RunOptions,
+    config_manager: &ConfigManager,
+    run_start_time: DateTime<Utc>,
+    metrics: Option<&server::Metrics>,
+    report: &mut report::RunReport,
+) -> Result<Generated> {
+    let staged = stage(options, run_start_time)?;
+    let options = staged.as_ref().map_or(options, |(options, _)| options);
+    let sink = options.output_sink(&config_manager.get_config().meta);
+    let mut outcome = generate_files(
+        options,
+        config_manager,
+        run_start_time,
+        metrics,
+        &sink,
+        report,
+    )
+    .await;
+    if let (Ok(_), Some((options, release))) = (&outcome, &staged) {
+        if let Err(e) = publish(options, release, &sink) {
+            outcome = Err(e);
+        }
+    }
+    report.finish(
+        Utc::now(),
+        outcome
+            .as_ref()
+            .map(|generated| generated.data.eessi_status.status)
+            .map_err(|e| format!("{:#}", e)),
+    );
+
+    let config = config_manager.get_config();
+    if let Some(url) = config.heartbeat_url.as_ref().filter(|_| !options.dry_run) {
+        let failed = !report.succeeded() || report.status == Some(Status::FAILED);
+        let timeout = std::time::Duration::from_secs(config.heartbeat_timeout_seconds);
+        let pinged = heartbeat::ping(url, failed, timeout).await;
+        if let Err(e) = report.deliver("heartbeat", "", pinged) {
+            error!("{:#}", e);
+            count_heartbeat_failure(options, &sink);
+        }
+    }
+    if let Err(e) = save_report(options, report, &sink) {
+        warn!("{:#}", e);
+    }
+
+    outcome
+}
+
+/// Count a failed heartbeat ping in the state file, reported in the metrics of the next run.
+fn count_heartbeat_failure(options: &RunOptions, sink: &OutputSink) {
+    let path = options.state_file();
+    let mut run_state = state::RunState::load(&path);
+    run_state.heartbeat_failures_total += 1;
+    if let Err(e) = run_state.save(&path, sink) {
+        warn!("Failed to count the failed heartbeat: {:#}", e);
+    }
+}
+
+/// `generate`, without staging and publishing the release, and the report.
+async fn generate_files(
+    options: &RunOptions,
+    config_manager: &ConfigManager,
+    run_start_time: DateTime<Utc>,
+    metrics: Option<&server::Metrics>,
+    sink: &OutputSink,
+    report: &mut report::RunReport,
+) -> Result<Generated> {
+    let config = options.scoped_config(config_manager)?;
+    let scope = options.scope();
+    let destination = &options.output.destination;
+
+    let push_gateway = options.push_gateway_config(&config);
+    let influx = options.influx_config(&config);
+    let prometheus_output = options.prometheus_output(&config.metrics);
+    let wants_metrics = prometheus_output.is_some()
+        || options.metrics_json_output.is_some()
+        || metrics.is_some()
+        || push_gateway.is_some()
+        || influx.is_some()
+        || config.graphite.is_some()
+        || config.statsd.is_some();
+    let outputs = options.output_config(&config.output);
+    options.check_outputs(&outputs, wants_metrics)?;
+
+    let last_scrape = match cache::LastScrape::load(destination) {
+        Ok(last_scrape) => last_scrape,
+        Err(e) => {
+            report.warn(format!("Ignoring the last scrape: {:#}", e));
+            None
+        }
+    };
+    let state_file = options.state_file();
+    let mut run_state = state::RunState::load(&state_file);
+    for (repository, hostname) in &options.acknowledged_regressions {
+        if run_state.acknowledge_regression(repository, hostname) {
+            info!(
+                "Accepting the revision of {} on {} as the highest one",
+                repository, hostname
+            );
+        } else {
+            report.warn(format!(
+                "No revision of {} on {} was seen before, nothing to acknowledge",
+                repository, hostname
+            ));
+        }
+    }
+
+    // Load the previous status before it is overwritten by this run. Only `diff` needs it to be
+    // readable, otherwise the changes are merely unknown this once. Without the JSON output,
+    // the previous run kept it in the state.
+    let path = destination.join(&options.output.json_output_file);
+    let previous_status = if !outputs.json {
+        run_state.previous_status()
+    } else {
+        match load_status_page_data(&path) {
+            Ok(previous_status) => Some(previous_status),
+            Err(_) if !path.exists() => None,
+            Err(e) if !options.diff => {
+                report.warn(format!("Ignoring the previous status: {:#}", e));
+                None
+            }
+            Err(e) => return Err(e),
+        }
+    };
+
+    let status_manager =
+        scrape(&config, last_scrape.as_ref(), &run_state.highest_revisions).await?;
+    report.record_servers(&status_manager, &config.privacy.hidden_servers);
+    let locale = load_locale(
+        &config.meta.locale,
+        options.template_dir(&config.meta).as_deref(),
+    )?;
+    let mut status_page_data =
+        build_page_data(config_manager, &status_manager, &locale, &run_start_time)?;
+    record_rule_errors(config_manager, &status_manager, report);
+    let output = options.output_options(&config.meta, &outputs);
+    let wants_internal = pages(&output, &config)
+        .iter()
+        .any(|page| page.visibility == config::Visibility::Internal);
+    let mut unredacted = if wants_internal && output.outputs.html {
+        privacy::Unredacted::keep(&status_page_data)?
+    } else {
+        privacy::Unredacted::default()
+    };
+    // Before the comparison with the previous status, which was written without them.
+    privacy::redact(&mut status_page_data, &config.privacy);
+    status_page_data.partial_run = !scope.is_empty();
+    status_page_data.scope = scope;
+
+    let mut changes = diff::Changes::between(previous_status.as_ref(), &status_page_data);
+    let transitions = notifications::transitions(&changes, &status_page_data);
+    let recorded_events = events::record(
+        &destination.join(events::EVENTS_FILE),
+        events::from_transitions(&transitions, &changes, &status_page_data, run_start_time)
+            .into_iter()
+            .chain(events::from_regressions(
+                previous_status.as_ref(),
+                &status_page_data,
+                run_start_time,
+            ))
+            .collect(),
+        &config.events,
+        run_start_time,
+        sink,
+    )?;
+    status_page_data.recent_events = events::recent(&recorded_events, config.events.recent);
+    if wants_internal && output.outputs.html {
+        unredacted.keep_events(&status_page_data.recent_events)?;
+    }
+    privacy::redact_events(&mut status_page_data.recent_events, &config.privacy);
+
+    // A partial run doesn't cover every server, so its statuses would skew the uptime.
+    let history_path = destination.join(history::HISTORY_FILE);
+    let runs = if status_page_data.partial_run {
+        history::load(&history_path)?
+    } else {
+        history::record(
+            &history_path,
+            history::Run::from_data(&status_page_data, run_start_time),
+            &config.history,
+            run_start_time,
+            sink,
+        )?
+    };
+    status_page_data.uptime = history::uptimes(&runs, &config.history, run_start_time);
+    status_page_data.daily = history::dailies(
+        &runs,
+        &config.history,
+        config.maintenance_aggregation,
+        run_start_time,
+    );
+
+    render(&mut status_page_data, &unredacted, &output, sink, report)?;
+    if let Some(dir) = render_repo_details(
+        &output,
+        &status_manager,
+        &status_page_data,
+        run_start_time,
+        sink,
+    )? {
+        report.artifact("repositories", dir);
+    }
+
+    cache::LastScrape::updated(
+        last_scrape.as_ref(),
+        &status_manager.servers,
+        run_start_time,
+    )
+    .save(destination, sink)?;
+
+    run_state.record(
+        &status_manager.servers,
+        &status_page_data.config.metrics.scrape_duration_buckets,
+    );
+    run_state.record_revisions(&status_manager.servers);
+    run_state.previous_status = match outputs.json {
+        true => None,
+        false => Some(serde_json::to_value(&status_page_data)?),
+    };
+    run_state.save(&state_file, sink)?;
+
+    if wants_metrics {
+        // Failures are only reported for the sinks that are configured.
+        let sink_failures: Vec<(&str, &str, u64)> = [
+            push_gateway.as_ref().map(|_| {
+                (
+                    "pushgateway_push_failures_total",
+                    "Failed pushes to the Pushgateway",
+                    run_state.push_failures_total,
+                )
+            }),
+            config.graphite.as_ref().map(|_| {
+                (
+                    "graphite_send_failures_total",
+                    "Failed sends to Graphite",
+                    run_state.graphite_failures_total,
+                )
+            }),
+            config.statsd.as_ref().map(|_| {
+                (
+                    "statsd_send_failures_total",
+                    "Failed sends to StatsD",
+                    run_state.statsd_failures_total,
+                )
+            }),
+            config.heartbeat_url.as_ref().map(|_| {
+                (
+                    "heartbeat_ping_failures_total",
+                    "Failed heartbeat pings",
+                    run_state.heartbeat_failures_total,
+                )
+            }),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        let mut builder = prometheus::status_metrics(
+            &status_page_data,
+            Some(&status_manager),
+            Some(&run_state),
+            &run_start_time,
+            &sink_failures,
+        );
+        let include_timestamps = status_page_data.config.metrics.include_timestamps;
+        builder.suppress_timestamps(include_timestamps == Some(false));
+        if let Some(path) = &prometheus_output {
+            let text = builder
+                .render(options.metrics_format)
+                .map_err(anyhow::Error::msg)?;
+            prometheus::write_prometheus_metrics(path, &text, sink)?;
+            report.artifact("metrics", path.clone());
+        }
+        if let Some(path) = &options.metrics_json_output {
+            prometheus::write_metrics_json(path, &builder, sink)?;
+            report.artifact("metrics JSON", path.clone());
+        }
+
+        // A failed push is counted, and reported in the metrics of the next run.
+        if let Some(push_gateway) = push_gateway.as_ref().filter(|_| !options.dry_run) {
+            let pushed = pushgateway::push(push_gateway, &mut builder).await;
+            if let Err(e) = report.deliver("pushgateway", "", pushed) {
+                error!("{:#}", e);
+                run_state.push_failures_total += 1;
+                run_state.save(&state_file, sink)?;
+            }
+        }
+
+        if let Some(influx) = &influx {
+            write_influx(
+                influx,
+                &builder,
+                &run_start_time,
+                !options.dry_run,
+                sink,
+                report,
+            )
+            .await?;
+        }
+
+        // Like a failed push, a failed send is counted and reported in the next run.
+        if let Some(graphite) = config.graphite.as_ref().filter(|_| !options.dry_run) {
+            let samples = builder.flatten().map_err(anyhow::Error::msg)?;
+            let lines = graphite::render(&samples, &graphite.prefix, &run_start_time);
+            let sent = graphite::send(graphite, &lines).await;
+            if let Err(e) = report.deliver("graphite", "", sent) {
+                error!("{:#}", e);
+                run_state.graphite_failures_total += 1;
+                run_state.save(&state_file, sink)?;
+            }
+        }
+
+        // StatsD is fire and forget, so a failed send only warns, but is counted all the same.
+        if let Some(statsd) = config.statsd.as_ref().filter(|_| !options.dry_run) {
+            let samples = builder.flatten().map_err(anyhow::Error::msg)?;
+            let lines = statsd::render(&samples, &statsd.prefix, statsd.tag_style);
+            let sent = statsd::send(statsd, &lines).await;
+            if let Err(e) = report.deliver("statsd", "", sent) {
+                warn!("{:#}", e);
+                run_state.statsd_failures_total += 1;
+                run_state.save(&state_file, sink)?;
+            }
+        }
+
+        // Served metrics are scraped right away, so the scrape time is accurate enough.
+        if let Some(metrics) = metrics {
+            builder.suppress_timestamps(include_timestamps != Some(true));
+            metrics.set(
+                builder
+                    .render(options.metrics_format)
+                    .map_err(anyhow::Error::msg)?,
+            );
+        }
+    }
+
+    log_rendered(&status_page_data, &report.artifacts);
+
+    if options.dry_run {
+        info!("Dry run, not notifying of the changes");
+    } else {
+        changes.hooks = notify(
+            options,
+            &config,
+            &changes,
+            &transitions,
+            &status_page_data,
+            report,
+        )
+        .await?;
+    }
+
+    let changes = if options.diff {
+        let filename = destination.join("changes.json");
+        sink.atomic_write(
+            &filename,
+            serde_json::to_string_pretty(&changes)?.as_bytes(),
+        )?;
+        info!("Changes written to: {:?}", filename);
+        Some(changes)
+    } else {
+        None
+    };
+
+    // Last, so every file the run wrote to the destination is compressed as it was written.
+    precompress(options, &config.precompress, sink, report)?;
+
+    Ok(Generated {
+        data: status_page_data,
+        changes,
+    })
+}
+
+/// Notify the configured targets of the components that changed status, returning the
+/// `on_status_change` commands that were run. A failed notification is logged, and doesn't
+/// fail the run.
+async fn notify(
+    options: &RunOptions,
+    config: &ConfigFile,
+    changes: &diff::Changes,
+    transitions: &[notifications::Transition],
+    status_page_data: &StatusPageData,
+    report: &mut report::RunReport,
+) -> Result<Vec<hooks::HookRun>> {
+    if let Some(slack) = &config.slack {
+        for transition in transitions.iter().filter(|t| slack::wanted(slack, t)) {
+            let sent = slack::send(slack, &slack::payload(slack, transition)).await;
+            if let Err(e) = report.deliver("slack", &transition.component, sent) {
+                error!("{:#}", e);
+            }
+        }
+    }
+
+    if let Some(pagerduty_config) = &config.pagerduty {
+        match pagerduty::events(pagerduty_config, &status_page_data.title, transitions) {
+            Ok(events) => {
+                for event in &events {
+                    let sent = pagerduty::send(pagerduty_config, event).await;
+                    let target = event["dedup_key"].as_str().unwrap_or_default();
+                    if let Err(e) = report.deliver("pagerduty", target, sent) {
+                        error!("{:#}", e);
+                    }
+                }
+            }
+            Err(e) => {
+                if let Err(e) = report.deliver("pagerduty", "", Err(e)) {
+                    error!("{:#}", e);
+                }
+            }
+        }
+    }
+
+    if let Some(email_config) = &config.email {
+        let wanted: Vec<&notifications::Transition> = transitions
+            .iter()
+            .filter(|t| email::wanted(email_config, t))
+            .collect();
+        if !wanted.is_empty() {
+            let message = email::render(&wanted, changes, status_page_data);
+            let sent = email::send(email_config, &message, options.email_dry_run).await;
+            let components: Vec<&str> = wanted.iter().map(|t| t.component.as_str()).collect();
+            if let Err(e) = report.deliver("email", &components.join(", "), sent) {
+                error!("{:#}", e);
+            }
+        }
+    }
+
+    let mut hook_runs = vec![];
+    if !config.on_status_change.is_empty() && !transitions.is_empty() {
+        let status_json = serde_json::to_string_pretty(status_page_data)?;
+        let timeout = std::time::Duration::from_secs(config.on_status_change_timeout_seconds);
+        for transition in transitions {
+            for command in &config.on_status_change {
+                hook_runs
+                    .push(hooks::run(command, transition, status_json.as_bytes(), timeout).await);
+            }
+        }
+    }
+    for run in &hook_runs {
+        let ran = match (run.exit_code, &run.error) {
+            (Some(0), _) => Ok(()),
+            (_, Some(error)) => Err(anyhow::anyhow!("{}", error)),
+            (exit_code, None) => Err(anyhow::anyhow!("Exited with {:?}", exit_code)),
+        };
+        // Failed commands are already logged by `hooks::run`.
+        let _ = report.deliver("on_status_change", &run.command, ran);
+    }
+    Ok(hook_runs)
+}
+
+/// Write the metrics as line protocol to the file and, if `send`, the write endpoint of
+/// `influx`. Like a failed push, a failed write to the endpoint is logged without failing the
+/// run.
+async fn write_influx(
+    influx: &config::InfluxConfig,
+    builder: &MetricsBuilder,
+    run_start_time: &DateTime<Utc>,
+    send: bool,
+    sink: &OutputSink,
+    report: &mut report::RunReport,
+) -> Result<()> {
+    let samples = builder.flatten().map_err(anyhow::Error::msg)?;
+    let lines = influx::render(&samples, run_start_time);
+
+    if let Some(output) = &influx.output {
+        sink.atomic_write(output, lines.as_bytes())?;
+        info!("InfluxDB line protocol written to: {:?}", output);
+        report.artifact("InfluxDB", output.clone());
+    }
+    if let Some(url) = influx.url.as_ref().filter(|_| send) {
+        let written = influx::write(influx, url, &lines).await;
+        if let Err(e) = report.deliver("influx", "", written) {
+            error!("{:#}", e);
+        }
+    }
+    Ok(())
+}
+
+/// Render the page (and optionally the overview metrics) from a saved status.json, keeping what
+/// the run did in `report` like `generate`.
+///
+/// The last update time is kept from the file, and the output is flagged as rendered from cache.
+pub fn render_from_json(
+    options: &RunOptions,
+    path: &Path,
+    run_start_time: DateTime<Utc>,
+    report: &mut report::RunReport,
+) -> Result<StatusPageData> {
+    let staged = stage(options, run_start_time)?;
+    let options = staged.as_ref().map_or(options, |(options, _)| options);
+    info!("Rendering from saved status: {:?}", path);
+    let mut status_page_data = load_status_page_data(path)?;
+    status_page_data.rendered_from_cache = true;
+
+    let prometheus_output = options.prometheus_output(&status_page_data.config.metrics);
+    let wants_metrics = prometheus_output.is_some() || options.metrics_json_output.is_some();
+    let outputs = options.output_config(&status_page_data.config.output);
+    options.check_outputs(&outputs, wants_metrics)?;
+
+    // The hidden servers were already left out of the saved status.
+    let unredacted = privacy::Unredacted::default();
+    let output = options.output_options(&status_page_data.config.meta, &outputs);
+    let sink = options.output_sink(&status_page_data.config.meta);
+    render(&mut status_page_data, &unredacted, &output, &sink, report)?;
+
+    if wants_metrics {
+        let last_update = DateTime::parse_from_rfc3339(&status_page_data.last_update)
+            .map(|t| t.with_timezone(&Utc))
+            .unwrap_or_else(|_| Utc::now());
+        let mut builder =
+            prometheus::status_metrics(&status_page_data, None, None, &last_update, &[]);
+        builder
+            .suppress_timestamps(status_page_data.config.metrics.include_timestamps == Some(false));
+        if let Some(path) = &prometheus_output {
+            let text = builder
+                .render(options.metrics_format)
+                .map_err(anyhow::Error::msg)?;
+            prometheus::write_prometheus_metrics(path, &text, &sink)?;
+            report.artifact("metrics", path.clone());
+        }
+        if let Some(path) = &options.metrics_json_output {
+            prometheus::write_metrics_json(path, &builder, &sink)?;
+            report.artifact("metrics JSON", path.clone());
+        }
+    }
+    precompress(options, &status_page_data.config.precompress, &sink, report)?;
+    log_rendered(&status_page_data, &report.artifacts);
+
+    if let Some((options, release)) = &staged {
+        publish(options, release, &sink)?;
+    }
+    report.finish(Utc::now(), Ok(status_page_data.eessi_status.status));
+    save_report(options, report, &sink)?;
+    Ok(status_page_data)
+}
+
+/// With `PublishMode::Symlink`, stage a new release for the run, returning the options writing
+/// to it instead of the destination, and the release. A dry run writes nothing, so it runs
+/// against the current release.
+fn stage(
+    options: &RunOptions,
+    run_start_time: DateTime<Utc>,
+) -> Result<Option<(RunOptions, PathBuf)>> {
+    if options.publish_mode == publish::PublishMode::Direct {
+        return Ok(None);
+    }
+    let destination = &options.output.destination;
+    let release = if options.dry_run {
+        publish::served_dir(destination, options.publish_mode)
+    } else {
+        publish::stage(destination, run_start_time)?
+    };
+    let mut staged = options.clone();
+    staged.output.destination = release.clone();
+    Ok(Some((staged, release)))
+}
+
+/// Point the current link at the release the run wrote, unless in a dry run, and delete the
+/// releases beyond `keep_releases`.
+fn publish(options: &RunOptions, release: &Path, sink: &OutputSink) -> Result<()> {
+    if options.dry_run {
+        return Ok(());
+    }
+    let destination = release
+        .parent()
+        .and_then(Path::parent)
+        .context("Invalid release: not in a destination")?;
+    publish::publish(destination, release, sink)?;
+    match publish::prune(destination, options.keep_releases) {
+        Ok(pruned) if !pruned.is_empty() => info!("Deleted {} old releases", pruned.len()),
+        Ok(_) => {}
+        Err(e) => warn!("Failed to delete old releases: {:#}", e),
+    }
+    Ok(())
+}
+
+/// Write the run report to the destination, or in a dry run keep the writes the run would have
+/// made in it.
+fn save_report(
+    options: &RunOptions,
+    report: &mut report::RunReport,
+    sink: &OutputSink,
+) -> Result<()> {
+    if !sink.is_dry_run() {
+        return report.save(&options.output.destination, sink);
+    }
+    report.planned_writes = sink.planned_writes();
+    Ok(())
+}
+
+fn load_status_page_data(path: &Path) -> Result<StatusPageData> {
+    let contents = std::fs::read_to_string(path).context(format!("Failed to read {:?}", path))?;
+    serde_json::from_str(&contents).context(format!("Failed to parse {:?}", path))
+}
+
+/// Keep the conditions of the rules that failed to evaluate, which were taken as false.
+fn record_rule_errors(
+    config_manager: &ConfigManager,
+    status_manager: &StatusManager,
+    report: &mut report::RunReport,
+) {
+    for rule in models::RULE_IDS {
+        let Some(conditions) = config_manager.get_conditions_for_rule(rule) else {
+            continue;
+        };
+        for error in status_manager.rule_errors(rule, &conditions) {
+            warn!(
+                "Condition {} of rule {} failed to evaluate: {}",
+                error.condition, error.rule, error.error
+            );
+            report.rule_errors.push(error);
+        }
+    }
+}
+
+/// The summary of a run, with what it wrote.
+fn log_rendered(status_page_data: &StatusPageData, artifacts: &[report::Artifact]) {
+    let artifacts = artifacts
+        .iter()
+        .map(|artifact| artifact.kind.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    info!(
+        servers = status_page_data.servers.len(),
+        repositories = status_page_data.repositories.len(),
+        status = status_page_data.eessi_status.status.as_ref(),
+        artifacts = artifacts.as_str();
+        "Rendered status of {} servers and {} repositories, wrote {}",
+        status_page_data.servers.len(),
+        status_page_data.repositories.len(),
+        artifacts
+    );
+}
+
+/// Compress the files in the destination with `precompress` in the options or `enabled` in the
+/// `precompress` section, once every other file of the run was written. The run report is left
+/// out, as it is written after this and its copy would be outdated right away, and so is the
+/// last scrape, which is only read by the next run rather than served.
+fn precompress(
+    options: &RunOptions,
+    config: &config::PrecompressConfig,
+    sink: &OutputSink,
+    report: &mut report::RunReport,
+) -> Result<()> {
+    if !(options.precompress || config.enabled) {
+        return Ok(());
+    }
+    // The copies would be of the files as they are, rather than as the run would write them.
+    if sink.is_dry_run() {
+        info!("Dry run, not compressing the files in the destination");
+        return Ok(());
+    }
+    report.compressed = precompress::precompress(
+        &options.output.destination,
+        config,
+        &[report::REPORT_FILE, cache::LAST_SCRAPE_FILE],
+        sink,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::fs;
+    use std::io::Read;
+    use std::sync::Arc;
+    use yare::parameterized;
+
+    #[parameterized(
+        default = { None, false, &["index.html"] },
+        output_file = { Some("status.html"), false, &["status.html"] },
+        configured = { None, true, &["index.html", "ops/index.html"] },
+        configured_over_output_file = { Some("status.html"), true, &["index.html", "ops/index.html"] },
+    )]
+    fn test_pages(output_file: Option<&str>, configured: bool, expected: &[&str]) {
+        let mut options = OutputOptions::new(".");
+        if let Some(output_file) = output_file {
+            options.output_file = PathBuf::from(output_file);
+        }
+        let mut config = init::example();
+        if configured {
+            config.pages = serde_json::from_str(
+                r#"[
+                    {"template": "status.html", "output_file": "index.html"},
+                    {"template": "status.html", "output_file": "ops/index.html", "visibility": "internal"}
+                ]"#,
+            )
+            .unwrap();
+        }
+
+        let pages = pages(&options, &config);
+
+        assert_eq!(
+            pages
+                .iter()
+                .map(|page| page.output_file.clone())
+                .collect::<Vec<_>>(),
+            expected.iter().map(PathBuf::from).collect::<Vec<_>>()
+        );
+        assert!(pages.iter().all(|page| page.template == "status.html"));
+    }
+
+    #[parameterized(
+        disabled = { false, 1_000_000, false, false },
+        embedded = { true, 1_000_000, true, false },
+        too_large = { true, 10, true, true },
+    )]
+    fn test_embedded_data(embed: bool, warn_bytes: usize, embedded: bool, warned: bool) {
+        let mut data: StatusPageData =
+            serde_json::from_str(include_str!("testdata/status-schema-0.json")).unwrap();
+        let config = Arc::make_mut(&mut data.config);
+        config.meta.embed_data = embed;
+        config.meta.embed_data_warn_bytes = warn_bytes;
+        let mut report = report::RunReport::new(Utc::now());
+
+        let json = embedded_data(&data, &mut report).unwrap();
+
+        assert_eq!(json.is_some(), embedded);
+        assert_eq!(!report.warnings.is_empty(), warned);
+    }
+
+    #[test]
+    fn test_render_from_json_dry_run() -> Result<()> {
+        let destination = tempfile::tempdir()?;
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/src/testdata/status-schema-1.json"
+        );
+        let mut options = RunOptions::new(destination.path());
+        options.dry_run = true;
+        options.output.text_output_file = Some(PathBuf::from("status.txt"));
+        options.metrics_json_output = Some(destination.path().join("metrics.json"));
+        let mut report = report::RunReport::new(Utc::now());
+
+        render_from_json(&options, Path::new(path), Utc::now(), &mut report)?;
+
+        assert_eq!(fs::read_dir(destination.path())?.count(), 0);
+        let planned: Vec<PathBuf> = report
+            .planned_writes
+            .iter()
+            .filter(|w| w.action == dependencies::WriteAction::Create)
+            .map(|w| {
+                w.path
+                    .strip_prefix(destination.path())
+                    .unwrap()
+                    .to_path_buf()
+            })
+            .collect();
+        for file in [
+            "index.html",
+            "status.json",
+            "summary.json",
+            "status.txt",
+            "status.css",
+            "metrics.json",
+        ] {
+            assert!(planned.contains(&PathBuf::from(file)), "{}", file);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_from_json_publish_symlink() -> Result<()> {
+        let destination = tempfile::tempdir()?;
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/src/testdata/status-schema-1.json"
+        );
+        let mut options = RunOptions::new(destination.path());
+        options.publish_mode = publish::PublishMode::Symlink;
+        options.keep_releases = 1;
+        let time = |second| Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, second).unwrap();
+        let run = |second| {
+            let mut report = report::RunReport::new(time(second));
+            render_from_json(&options, Path::new(path), time(second), &mut report)
+        };
+
+        run(0)?;
+        let first = fs::read_link(destination.path().join(publish::CURRENT_LINK))?;
+        run(1)?;
+
+        let current = destination.path().join(publish::CURRENT_LINK);
+        assert_ne!(fs::read_link(&current)?, first);
+        assert!(current.join("index.html").exists());
+        assert!(current.join("status.css").exists());
+        assert!(!destination.path().join("index.html").exists());
+        // The first release was deleted once the second was published.
+        let releases = destination.path().join(publish::RELEASES_DIR);
+        assert_eq!(fs::read_dir(releases)?.count(), 1);
+        Ok(())
+    }
+
+    /// The example configuration in a file, with every server scraped from an address that
+    /// refuses the connection.
+    fn unreachable_config(
+        precompress: Option<serde_json::Value>,
+    ) -> Result<tempfile::NamedTempFile> {
+        let mut config = serde_json::to_value(init::example())?;
+        for server in config["servers"].as_array_mut().unwrap() {
+            server["resolve_to"] = "127.0.0.1".into();
+        }
+        if let Some(precompress) = precompress {
+            config["precompress"] = precompress;
+        }
+        let config_file = tempfile::NamedTempFile::with_suffix(".json")?;
+        fs::write(config_file.path(), config.to_string())?;
+        Ok(config_file)
+    }
+
+    #[tokio::test]
+    async fn test_generate_dry_run() -> Result<()> {
+        let destination = tempfile::tempdir()?;
+        let config_file = unreachable_config(None)?;
+        let config_manager = load_config(config_file.path().to_str().unwrap(), &[])?;
+        let mut options = RunOptions::new(destination.path());
+        options.dry_run = true;
+        options.prometheus_metrics = true;
+        options.output.badges = true;
+        let mut report = report::RunReport::new(Utc::now());
+
+        generate(&options, &config_manager, Utc::now(), None, &mut report).await?;
+
+        assert_eq!(fs::read_dir(destination.path())?.count(), 0);
+        let planned: Vec<PathBuf> = report
+            .planned_writes
+            .iter()
+            .map(|w| {
+                w.path
+                    .strip_prefix(destination.path())
+                    .unwrap()
+                    .to_path_buf()
+            })
+            .collect();
+        for file in [
+            "index.html",
+            "status.json",
+            "metrics",
+            "history.jsonl",
+            "badges/badge-eessi.svg",
+            cache::LAST_SCRAPE_FILE,
+            state::STATE_FILE_NAME,
+        ] {
+            assert!(planned.contains(&PathBuf::from(file)), "{}", file);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_precompresses_last() -> Result<()> {
+        let destination = tempfile::tempdir()?;
+        // The previous status has other servers, so there are changes to write.
+        fs::copy(
+            concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/src/testdata/status-schema-1.json"
+            ),
+            destination.path().join("status.json"),
+        )?;
+        let config_file =
+            unreachable_config(Some(serde_json::json!({ "enabled": true, "min_bytes": 1 })))?;
+        let config_manager = load_config(config_file.path().to_str().unwrap(), &[])?;
+        let mut options = RunOptions::new(destination.path());
+        options.diff = true;
+        options.metrics_json_output = Some(destination.path().join("metrics.json"));
+        let mut report = report::RunReport::new(Utc::now());
+
+        generate(&options, &config_manager, Utc::now(), None, &mut report).await?;
+
+        for file in ["changes.json", "metrics.json", "status.json"] {
+            let path = destination.path().join(file);
+            let mut decompressed = vec![];
+            flate2::read::GzDecoder::new(fs::File::open(path.with_extension("json.gz"))?)
+                .read_to_end(&mut decompressed)?;
+            assert_eq!(decompressed, fs::read(&path)?, "{}", file);
+        }
+        Ok(())
+    }
+}
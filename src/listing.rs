@@ -0,0 +1,210 @@
+use clap::ValueEnum;
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::models::{StatusManager, ToEESSILabel};
+
+/// Output format for the `list` subcommands.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Format {
+    Table,
+    Csv,
+    Json,
+}
+
+/// A row in a listing, with the headers and cells used for the table and CSV formats.
+pub trait Row: Serialize {
+    fn headers() -> &'static [&'static str];
+    fn cells(&self) -> Vec<String>;
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct ServerRow {
+    pub hostname: String,
+    pub server_type: String,
+    pub backend: String,
+    pub status: String,
+    pub repositories: usize,
+}
+
+impl Row for ServerRow {
+    fn headers() -> &'static [&'static str] {
+        &["HOSTNAME", "TYPE", "BACKEND", "STATUS", "REPOS"]
+    }
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.hostname.clone(),
+            self.server_type.clone(),
+            self.backend.clone(),
+            self.status.clone(),
+            self.repositories.to_string(),
+        ]
+    }
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct RepoRow {
+    pub repository: String,
+    pub server: String,
+    pub revision: Option<i32>,
+    /// How many revisions the server is behind the newest revision seen on any server.
+    pub lag: Option<i32>,
+}
+
+impl Row for RepoRow {
+    fn headers() -> &'static [&'static str] {
+        &["REPOSITORY", "SERVER", "REVISION", "LAG"]
+    }
+
+    fn cells(&self) -> Vec<String> {
+        let optional = |v: Option<i32>| v.map(|v| v.to_string()).unwrap_or_default();
+        vec![
+            self.repository.clone(),
+            self.server.clone(),
+            optional(self.revision),
+            optional(self.lag),
+        ]
+    }
+}
+
+pub fn server_rows(status_manager: &StatusManager) -> Vec<ServerRow> {
+    status_manager
+        .servers
+        .iter()
+        .map(|server| ServerRow {
+            hostname: server.hostname.to_string(),
+            server_type: server.server_type.to_label().to_string(),
+            backend: format!(
+                "{:?}",
+                server.backend_detected.unwrap_or(server.backend_type)
+            ),
+            status: server.status.text().to_string(),
+            repositories: server.repositories.len(),
+        })
+        .collect()
+}
+
+pub fn repo_rows(status_manager: &StatusManager) -> Vec<RepoRow> {
+    let mut newest: HashMap<&str, i32> = HashMap::new();
+    for server in status_manager.servers.iter() {
+        for repo in server.repositories.iter().filter(|r| r.manifest.is_some()) {
+            let entry = newest.entry(repo.name.as_str()).or_insert(repo.revision);
+            *entry = (*entry).max(repo.revision);
+        }
+    }
+
+    let mut rows: Vec<RepoRow> = status_manager
+        .servers
+        .iter()
+        .flat_map(|server| {
+            let newest = &newest;
+            server.repositories.iter().map(move |repo| {
+                let revision = repo.manifest.as_ref().map(|_| repo.revision);
+                RepoRow {
+                    repository: repo.name.clone(),
+                    server: server.hostname.to_string(),
+                    revision,
+                    lag: revision
+                        .and_then(|r| newest.get(repo.name.as_str()).map(|newest| newest - r)),
+                }
+            })
+        })
+        .collect();
+
+    rows.sort_by(|a, b| (&a.repository, &a.server).cmp(&(&b.repository, &b.server)));
+    rows
+}
+
+/// Render rows in the given format.
+pub fn render<R: Row>(rows: &[R], format: Format) -> serde_json::Result<String> {
+    let headers: Vec<String> = R::headers().iter().map(|h| h.to_string()).collect();
+    let cells: Vec<Vec<String>> = rows.iter().map(Row::cells).collect();
+
+    Ok(match format {
+        Format::Json => serde_json::to_string_pretty(rows)? + "\n",
+        Format::Csv => std::iter::once(&headers)
+            .chain(cells.iter())
+            .map(|row| {
+                row.iter()
+                    .map(|cell| csv_escape(cell))
+                    .collect::<Vec<_>>()
+                    .join(",")
+                    + "\n"
+            })
+            .collect(),
+        Format::Table => {
+            let widths: Vec<usize> = (0..headers.len())
+                .map(|i| {
+                    std::iter::once(&headers)
+                        .chain(cells.iter())
+                        .map(|row| row[i].len())
+                        .max()
+                        .unwrap_or(0)
+                })
+                .collect();
+
+            std::iter::once(&headers)
+                .chain(cells.iter())
+                .map(|row| {
+                    row.iter()
+                        .zip(widths.iter())
+                        .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                        .collect::<Vec<_>>()
+                        .join("  ")
+                        .trim_end()
+                        .to_string()
+                        + "\n"
+                })
+                .collect()
+        }
+    })
+}
+
+fn csv_escape(cell: &str) -> String {
+    if cell.contains([',', '"', '\n']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yare::parameterized;
+
+    fn rows() -> Vec<RepoRow> {
+        vec![
+            RepoRow {
+                repository: "software.eessi.io".to_string(),
+                server: "s1.example.com".to_string(),
+                revision: Some(12),
+                lag: Some(0),
+            },
+            RepoRow {
+                repository: "software.eessi.io".to_string(),
+                server: "s2, the slow one".to_string(),
+                revision: None,
+                lag: None,
+            },
+        ]
+    }
+
+    #[parameterized(
+        table = { Format::Table, "REPOSITORY         SERVER            REVISION  LAG\nsoftware.eessi.io  s1.example.com    12        0\nsoftware.eessi.io  s2, the slow one\n" },
+        csv = { Format::Csv, "REPOSITORY,SERVER,REVISION,LAG\nsoftware.eessi.io,s1.example.com,12,0\nsoftware.eessi.io,\"s2, the slow one\",,\n" },
+    )]
+    fn test_render(format: Format, expected: &str) {
+        assert_eq!(render(&rows(), format).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_render_json() {
+        let json: serde_json::Value =
+            serde_json::from_str(&render(&rows(), Format::Json).unwrap()).unwrap();
+
+        assert_eq!(json[0]["revision"], 12);
+        assert!(json[1]["lag"].is_null());
+    }
+}
@@ -0,0 +1,218 @@
+use anyhow::{Context, Result};
+use log::warn;
+use once_cell::sync::Lazy;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use tera::{Function, Value};
+
+/// The locale of the page without `meta.locale`, which every other locale falls back to.
+pub const DEFAULT_LOCALE: &str = "en";
+
+/// The locales built into the binary, by name.
+pub const BUILTIN_LOCALES: [(&str, &str); 1] = [("en", include_str!("../locales/en.json"))];
+
+/// The directory, in the template directory, with locales that add to or replace the built-in
+/// ones, as `<name>.json`.
+pub const LOCALES_DIR: &str = "locales";
+
+/// The built-in English locale, for the texts outside the page, such as the badges.
+pub static ENGLISH: Lazy<Locale> = Lazy::new(Locale::english);
+
+pub fn default_locale() -> String {
+    DEFAULT_LOCALE.to_string()
+}
+
+/// The translations of the status texts and the strings of the templates, by key. Keys the
+/// locale doesn't have are the English ones.
+#[derive(Debug, Clone)]
+pub struct Locale {
+    name: String,
+    strings: BTreeMap<String, String>,
+}
+
+fn parse(name: &str, json: &str) -> Result<BTreeMap<String, String>> {
+    serde_json::from_str(json).context(format!(
+        "Locale {} must be an object of strings by key",
+        name
+    ))
+}
+
+impl Locale {
+    pub fn english() -> Self {
+        Locale {
+            name: DEFAULT_LOCALE.to_string(),
+            strings: parse(DEFAULT_LOCALE, BUILTIN_LOCALES[0].1)
+                .expect("the built-in English locale is valid"),
+        }
+    }
+
+    /// Load the locale `name` from the locales directory of `template_dir`, or the built-in
+    /// one. The keys it is missing are the English ones, with a warning.
+    pub fn load(name: &str, template_dir: Option<&Path>) -> Result<Self> {
+        let mut locale = Locale::english();
+        let file = template_dir.map(|dir| dir.join(LOCALES_DIR).join(format!("{}.json", name)));
+        let strings = match file.filter(|file| file.is_file()) {
+            Some(file) => {
+                let json = std::fs::read_to_string(&file)
+                    .context(format!("Failed to read locale {:?}", file))?;
+                parse(name, &json).context(format!("Invalid locale {:?}", file))?
+            }
+            None => match BUILTIN_LOCALES.iter().find(|(builtin, _)| *builtin == name) {
+                Some((_, json)) => parse(name, json)?,
+                None => anyhow::bail!(
+                    "Unknown locale {}, expected one of {} or {}/<locale>.json in the template directory",
+                    name,
+                    BUILTIN_LOCALES.map(|(builtin, _)| builtin).join(", "),
+                    LOCALES_DIR
+                ),
+            },
+        };
+
+        let missing: Vec<&str> = locale
+            .strings
+            .keys()
+            .filter(|key| !strings.contains_key(*key))
+            .map(String::as_str)
+            .collect();
+        if !missing.is_empty() {
+            warn!(
+                "Locale {} has no translation for {}, using English",
+                name,
+                missing.join(", ")
+            );
+        }
+        locale.name = name.to_string();
+        locale.strings.extend(strings);
+        Ok(locale)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The string for `key`, `None` if not even English has it.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.strings.get(key).map(String::as_str)
+    }
+}
+
+/// `t(key="page.members", count=3)`: the string for the key, with `{count}` replaced by the
+/// argument of that name.
+impl Function for Locale {
+    fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+        let key = args
+            .get("key")
+            .and_then(Value::as_str)
+            .ok_or_else(|| tera::Error::msg("Function `t` expects a `key` string"))?;
+        let mut text = self
+            .strings
+            .get(key)
+            .ok_or_else(|| tera::Error::msg(format!("Function `t` got an unknown key {}", key)))?
+            .clone();
+        for (name, value) in args.iter().filter(|(name, _)| *name != "key") {
+            let value = match value {
+                Value::String(value) => value.clone(),
+                value => value.to_string(),
+            };
+            text = text.replace(&format!("{{{}}}", name), &value);
+        }
+        Ok(Value::String(text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yare::parameterized;
+
+    fn call(locale: &Locale, args: serde_json::Value) -> tera::Result<Value> {
+        locale.call(&serde_json::from_value(args).unwrap())
+    }
+
+    #[test]
+    fn test_load_partial() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::create_dir(dir.path().join(LOCALES_DIR))?;
+        std::fs::write(
+            dir.path().join(LOCALES_DIR).join("nl.json"),
+            r#"{"status.ok.text": "Normale werking", "page.members": "{count} leden"}"#,
+        )?;
+
+        let locale = Locale::load("nl", Some(dir.path()))?;
+
+        assert_eq!(locale.name(), "nl");
+        assert_eq!(locale.get("status.ok.text"), Some("Normale werking"));
+        assert_eq!(
+            locale.get("status.ok.description"),
+            Some("EESSI services operating without issues.")
+        );
+        assert_eq!(
+            call(
+                &locale,
+                serde_json::json!({"key": "page.members", "count": 3})
+            )
+            .unwrap(),
+            "3 leden"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_overrides_builtin() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::create_dir(dir.path().join(LOCALES_DIR))?;
+        std::fs::write(
+            dir.path().join(LOCALES_DIR).join("en.json"),
+            r#"{"status.ok.text": "All good"}"#,
+        )?;
+
+        assert_eq!(
+            Locale::load("en", Some(dir.path()))?.get("status.ok.text"),
+            Some("All good")
+        );
+        assert_eq!(
+            Locale::load("en", None)?.get("status.ok.text"),
+            Some("Normal service")
+        );
+        Ok(())
+    }
+
+    #[parameterized(
+        unknown = { "xx", None, "Unknown locale xx, expected one of en" },
+        not_an_object = { "nl", Some(r#"["Normale werking"]"#), "Invalid locale" },
+        not_a_string = { "nl", Some(r#"{"status.ok.text": 1}"#), "Invalid locale" },
+    )]
+    fn test_load_invalid(name: &str, file: Option<&str>, error: &str) -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::create_dir(dir.path().join(LOCALES_DIR))?;
+        if let Some(file) = file {
+            std::fs::write(dir.path().join(LOCALES_DIR).join("nl.json"), file)?;
+        }
+
+        let loaded = Locale::load(name, Some(dir.path()));
+
+        assert!(
+            loaded
+                .as_ref()
+                .is_err_and(|e| e.to_string().contains(error)),
+            "{:?}",
+            loaded.err()
+        );
+        Ok(())
+    }
+
+    #[parameterized(
+        plain = { serde_json::json!({"key": "page.incidents"}), Some("Incidents") },
+        arguments = { serde_json::json!({"key": "page.revision_published", "revision": 42, "time": "2025-10-02T07:18:00Z"}), Some("Revision 42, published 2025-10-02T07:18:00Z") },
+        unknown_key = { serde_json::json!({"key": "page.nope"}), None },
+        no_key = { serde_json::json!({"count": 3}), None },
+    )]
+    fn test_t(args: serde_json::Value, expected: Option<&str>) {
+        let result = call(&Locale::english(), args);
+
+        match expected {
+            Some(expected) => assert_eq!(result.unwrap(), expected),
+            None => assert!(result.is_err()),
+        }
+    }
+}
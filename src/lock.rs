@@ -0,0 +1,197 @@
+use log::{debug, warn};
+use std::fmt;
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{self, Read, Seek, Write};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// The name of the lock file in the destination directory, unless --lock-file is given.
+pub const LOCK_FILE_NAME: &str = ".status-page.lock";
+
+/// The exit code used when another run holds the lock.
+pub const LOCK_HELD_EXIT_CODE: i32 = 75;
+
+const RETRY_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug)]
+pub enum LockError {
+    /// Another live process holds the lock.
+    Held {
+        path: PathBuf,
+        pid: Option<u32>,
+    },
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+}
+
+impl fmt::Display for LockError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LockError::Held {
+                path,
+                pid: Some(pid),
+            } => write!(f, "{:?} is held by process {}", path, pid),
+            LockError::Held { path, pid: None } => {
+                write!(f, "{:?} is held by another process", path)
+            }
+            LockError::Io { path, error } => write!(f, "Failed to lock {:?}: {}", path, error),
+        }
+    }
+}
+
+impl std::error::Error for LockError {}
+
+/// An advisory lock on a file, holding the PID of the process that owns it.
+///
+/// The lock is released and the file removed when the guard is dropped, which includes
+/// unwinding from a panic. If the process dies, the operating system releases the lock.
+#[derive(Debug)]
+pub struct LockGuard {
+    file: File,
+    path: PathBuf,
+}
+
+impl Drop for LockGuard {
+    fn drop(&mut self) {
+        // Remove the file while still holding the lock, so a waiting process notices the file
+        // it locked is gone and retries with a new one.
+        if let Err(e) = fs::remove_file(&self.path) {
+            warn!("Failed to remove lock file {:?}: {}", self.path, e);
+        }
+        if let Err(e) = self.file.unlock() {
+            warn!("Failed to release lock {:?}: {}", self.path, e);
+        }
+        debug!("Released lock {:?}", self.path);
+    }
+}
+
+/// Acquire the lock, waiting up to `timeout` for another run to release it.
+///
+/// The operating system releases the lock of a process that dies, so a lock that is held
+/// always has a live holder, and a file left behind by a crash is simply locked again.
+pub async fn acquire(path: &Path, timeout: Duration) -> Result<LockGuard, LockError> {
+    let io_error = |error| LockError::Io {
+        path: path.to_path_buf(),
+        error,
+    };
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent).map_err(io_error)?;
+    }
+
+    let deadline = Instant::now() + timeout;
+    loop {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .map_err(io_error)?;
+
+        match file.try_lock() {
+            Ok(()) => {
+                if !is_same_file(&file, path) {
+                    // The previous holder removed the file after we opened it.
+                    continue;
+                }
+                file.set_len(0).map_err(io_error)?;
+                file.rewind().map_err(io_error)?;
+                write!(file, "{}", std::process::id()).map_err(io_error)?;
+                file.flush().map_err(io_error)?;
+                debug!("Acquired lock {:?}", path);
+                return Ok(LockGuard {
+                    file,
+                    path: path.to_path_buf(),
+                });
+            }
+            Err(TryLockError::WouldBlock) => {
+                let pid = read_pid(&mut file);
+                if Instant::now() >= deadline {
+                    return Err(LockError::Held {
+                        path: path.to_path_buf(),
+                        pid,
+                    });
+                }
+                tokio::time::sleep(RETRY_INTERVAL.min(deadline - Instant::now())).await;
+            }
+            Err(TryLockError::Error(error)) => return Err(io_error(error)),
+        }
+    }
+}
+
+fn is_same_file(file: &File, path: &Path) -> bool {
+    match (file.metadata(), fs::metadata(path)) {
+        (Ok(locked), Ok(current)) => locked.dev() == current.dev() && locked.ino() == current.ino(),
+        _ => false,
+    }
+}
+
+fn read_pid(file: &mut File) -> Option<u32> {
+    let mut contents = String::new();
+    file.rewind().ok()?;
+    file.read_to_string(&mut contents).ok()?;
+    contents.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_lock_is_exclusive_until_dropped() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(LOCK_FILE_NAME);
+
+        let guard = acquire(&path, Duration::ZERO).await.unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            std::process::id().to_string()
+        );
+
+        match acquire(&path, Duration::from_millis(300)).await {
+            Err(LockError::Held { pid, .. }) => assert_eq!(pid, Some(std::process::id())),
+            other => panic!("expected the lock to be held, got {:?}", other),
+        }
+
+        drop(guard);
+        assert!(!path.exists());
+        assert!(acquire(&path, Duration::ZERO).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_leftover_lock_file_is_locked_again() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("nested").join(LOCK_FILE_NAME);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+
+        // A file left behind by a process that crashed, which released its lock.
+        fs::write(&path, u32::MAX.to_string()).unwrap();
+
+        let guard = acquire(&path, Duration::ZERO).await.unwrap();
+        assert_eq!(
+            fs::read_to_string(&guard.path).unwrap(),
+            std::process::id().to_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_held_lock_is_never_replaced() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(LOCK_FILE_NAME);
+
+        // A lock that is held, whatever process its file names.
+        let mut holder = File::create(&path).unwrap();
+        holder.try_lock().unwrap();
+        write!(holder, "{}", u32::MAX).unwrap();
+
+        match acquire(&path, Duration::ZERO).await {
+            Err(LockError::Held { pid, .. }) => assert_eq!(pid, Some(u32::MAX)),
+            other => panic!("expected the lock to be held, got {:?}", other),
+        }
+        assert!(path.exists());
+    }
+}
@@ -0,0 +1,279 @@
+use chrono::{SecondsFormat, Utc};
+use clap::ValueEnum;
+use log::kv::{Error, Key, Value, VisitSource};
+use log::{debug, info, Record};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value as JsonValue};
+use std::io::Write;
+
+/// How log lines are written to stderr.
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// env_logger's human readable format.
+    #[default]
+    Text,
+    /// One JSON object per line.
+    Json,
+}
+
+/// The level used when neither flags, `RUST_LOG` nor the configuration set one.
+pub const DEFAULT_LEVEL: &str = "error";
+
+/// Where the log level came from, in order of precedence.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LevelSource {
+    /// `-v`, `-vv` or `-q` on the command line.
+    Flags,
+    /// The `RUST_LOG` environment variable.
+    Environment,
+    /// `meta.logging_level` in the configuration.
+    Config,
+    Default,
+}
+
+impl LevelSource {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            LevelSource::Flags => "command line flags",
+            LevelSource::Environment => "RUST_LOG",
+            LevelSource::Config => "the configuration",
+            LevelSource::Default => "the default",
+        }
+    }
+}
+
+/// The log filter to use, and the sources that set one.
+///
+/// Flags take precedence over `RUST_LOG`, which takes precedence over the configuration.
+pub fn resolve_filter(
+    verbose: u8,
+    quiet: bool,
+    env: Option<&str>,
+    config: Option<&str>,
+) -> (String, Vec<LevelSource>) {
+    let flags = match (quiet, verbose) {
+        (true, _) => Some("warn"),
+        (false, 0) => None,
+        (false, 1) => Some("info"),
+        (false, _) => Some("debug"),
+    };
+    let env = env.filter(|e| !e.is_empty());
+    let config = config.filter(|c| !c.is_empty());
+
+    let sources: Vec<(LevelSource, &str)> = [
+        (LevelSource::Flags, flags),
+        (LevelSource::Environment, env),
+        (LevelSource::Config, config),
+    ]
+    .into_iter()
+    .filter_map(|(source, filter)| filter.map(|f| (source, f)))
+    .collect();
+
+    match sources.first() {
+        Some((_, filter)) => (
+            filter.to_string(),
+            sources.iter().map(|(source, _)| *source).collect(),
+        ),
+        None => (DEFAULT_LEVEL.to_string(), vec![LevelSource::Default]),
+    }
+}
+
+/// Build a logger with the given format and filter (in `RUST_LOG` syntax).
+pub fn builder(format: LogFormat, filter: &str) -> env_logger::Builder {
+    let mut builder = env_logger::Builder::new();
+    builder.parse_filters(filter);
+    if format == LogFormat::Json {
+        builder.format(|buf, record| {
+            let timestamp = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+            write_json_record(buf, record, &timestamp)
+        });
+    }
+    builder
+}
+
+/// Install the logger, and log where its level came from.
+pub fn init(format: LogFormat, filter: &str, sources: &[LevelSource]) {
+    builder(format, filter).init();
+
+    if let [used, overridden @ ..] = sources {
+        if overridden.is_empty() {
+            debug!("Log level '{}' from {}", filter, used.describe());
+        } else {
+            info!(
+                "Log level '{}' from {}, overriding {}",
+                filter,
+                used.describe(),
+                overridden
+                    .iter()
+                    .map(|s| s.describe())
+                    .collect::<Vec<_>>()
+                    .join(" and ")
+            );
+        }
+    }
+}
+
+/// Write a record as a single line of JSON, with its key-values as fields of their own.
+pub fn write_json_record<W: Write>(
+    writer: &mut W,
+    record: &Record,
+    timestamp: &str,
+) -> std::io::Result<()> {
+    let mut fields = Map::new();
+    fields.insert("timestamp".to_string(), timestamp.into());
+    fields.insert("level".to_string(), record.level().as_str().into());
+    fields.insert("target".to_string(), record.target().into());
+    fields.insert("message".to_string(), record.args().to_string().into());
+
+    let mut visitor = JsonFields(&mut fields);
+    record
+        .key_values()
+        .visit(&mut visitor)
+        .map_err(std::io::Error::other)?;
+
+    serde_json::to_writer(&mut *writer, &JsonValue::Object(fields))?;
+    writeln!(writer)
+}
+
+struct JsonFields<'a>(&'a mut Map<String, JsonValue>);
+
+impl<'kvs> VisitSource<'kvs> for JsonFields<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), Error> {
+        let value = if let Some(v) = value.to_u64() {
+            v.into()
+        } else if let Some(v) = value.to_i64() {
+            v.into()
+        } else if let Some(v) = value.to_f64() {
+            v.into()
+        } else if let Some(v) = value.to_bool() {
+            v.into()
+        } else {
+            value.to_string().into()
+        };
+        self.0.insert(key.as_str().to_string(), value);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::{Level, Log};
+    use std::sync::{Arc, Mutex};
+    use yare::parameterized;
+
+    #[test]
+    fn test_json_lines() {
+        let mut output: Vec<u8> = Vec::new();
+        let kvs: [(&str, Value); 3] = [
+            ("hostname", Value::from("s1.example.com")),
+            ("servers", Value::from(4u64)),
+            ("succeeded", Value::from(3u64)),
+        ];
+
+        write_json_record(
+            &mut output,
+            &Record::builder()
+                .args(format_args!("Scraped {} servers", 4))
+                .level(Level::Info)
+                .target("cvmfs_status_page_rust::scraping")
+                .key_values(&kvs)
+                .build(),
+            "2025-10-02T07:28:00.000Z",
+        )
+        .unwrap();
+        write_json_record(
+            &mut output,
+            &Record::builder()
+                .args(format_args!("a \"quoted\"\nmultiline message"))
+                .level(Level::Warn)
+                .target("cvmfs_status_page_rust")
+                .build(),
+            "2025-10-02T07:28:01.000Z",
+        )
+        .unwrap();
+
+        let lines: Vec<JsonValue> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("each line is JSON"))
+            .collect();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["level"], "INFO");
+        assert_eq!(lines[0]["message"], "Scraped 4 servers");
+        assert_eq!(lines[0]["hostname"], "s1.example.com");
+        assert_eq!(lines[0]["servers"], 4);
+        assert_eq!(lines[0]["succeeded"], 3);
+        assert_eq!(lines[1]["target"], "cvmfs_status_page_rust");
+        assert_eq!(lines[1]["message"], "a \"quoted\"\nmultiline message");
+    }
+
+    #[parameterized(
+        nothing = { 0, false, None, None, "error", &[LevelSource::Default] },
+        config_only = { 0, false, None, Some("debug"), "debug", &[LevelSource::Config] },
+        env_wins_over_config = { 0, false, Some("warn"), Some("debug"), "warn", &[LevelSource::Environment, LevelSource::Config] },
+        verbose = { 1, false, Some("warn"), None, "info", &[LevelSource::Flags, LevelSource::Environment] },
+        very_verbose = { 2, false, None, Some("info"), "debug", &[LevelSource::Flags, LevelSource::Config] },
+        quiet = { 0, true, None, Some("info"), "warn", &[LevelSource::Flags, LevelSource::Config] },
+        empty_env_ignored = { 0, false, Some(""), Some("info"), "info", &[LevelSource::Config] },
+    )]
+    fn test_resolve_filter(
+        verbose: u8,
+        quiet: bool,
+        env: Option<&str>,
+        config: Option<&str>,
+        expected: &str,
+        sources: &[LevelSource],
+    ) {
+        let (filter, used) = resolve_filter(verbose, quiet, env, config);
+        assert_eq!(filter, expected);
+        assert_eq!(used, sources);
+    }
+
+    #[derive(Clone, Default)]
+    struct Capture(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_config_debug_level_without_rust_log() {
+        let (filter, _) = resolve_filter(0, false, None, Some("debug"));
+        let capture = Capture::default();
+        let logger = builder(LogFormat::Json, &filter)
+            .target(env_logger::Target::Pipe(Box::new(capture.clone())))
+            .build();
+
+        logger.log(
+            &Record::builder()
+                .args(format_args!("debug output"))
+                .level(Level::Debug)
+                .target("cvmfs_status_page_rust")
+                .build(),
+        );
+        logger.log(
+            &Record::builder()
+                .args(format_args!("trace output"))
+                .level(Level::Trace)
+                .target("cvmfs_status_page_rust")
+                .build(),
+        );
+
+        let output = String::from_utf8(capture.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<JsonValue> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0]["message"], "debug output");
+    }
+}
@@ -1,23 +1,25 @@
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
-use clap::Parser;
-use log::{debug, info, trace};
+use clap::{parser::ValueSource, CommandFactory, FromArgMatches, Parser, Subcommand, ValueHint};
+use futures::FutureExt;
+use log::{debug, error, info, warn};
+use std::io::IsTerminal;
+use std::panic::AssertUnwindSafe;
 use std::path::{Path, PathBuf};
 
-mod config;
-mod dependencies;
-mod models;
-mod prometheus;
-mod templating;
+use cvmfs_status_page_rust::{
+    cache, check, config, daemon, dependencies, doctor, init, listing, locale, lock, logging,
+    models, prometheus, publish, report, server, state, templating, terminal,
+};
 
-use config::{get_config_manager, init_config};
-use cvmfs_server_scraper::{Scraper, ScraperCommon, ServerType};
-use dependencies::{atomic_write, populate};
-use models::{EESSIStatus, Status, StatusManager, StatusPageData, StratumStatus, ToEESSILabel};
-use prometheus::MetricsBuilder;
-use templating::{render_template_to_file, RepoStatus, StatusInfo};
+use cvmfs_server_scraper::ServerType;
+use cvmfs_status_page_rust::{
+    build_page_data, generate, render_from_json, scrape, OutputOptions, RunOptions,
+};
+use dependencies::OutputSink;
+use models::{Status, StatusManager, StatusPageData, ToEESSILabel};
 
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
 #[command(
     name = "status-page",
     about = "An EESSI status page generator.",
@@ -27,28 +29,79 @@ use templating::{render_template_to_file, RepoStatus, StatusInfo};
 )]
 struct Opt {
     #[arg(
+        global = true,
         short,
         long,
         default_value = ".",
+        value_hint = ValueHint::DirPath,
         help = "Destination directory for the generated status page."
     )]
     destination: PathBuf,
 
     #[arg(
+        global = true,
         short,
         long,
         default_value = "config.json",
+        value_hint = ValueHint::FilePath,
         help = "Configuration file."
     )]
     configuration: PathBuf,
 
-    #[arg(short, long, help = "Show the configuration and exit.")]
+    #[arg(short, long, global = true, help = "Show the configuration and exit.")]
     show_config: bool,
 
-    #[arg(short, long, help = "Force overwrite of existing files.")]
+    #[arg(
+        long,
+        global = true,
+        value_name = "KEY=VALUE",
+        help = "Override a key of the configuration, such as meta.title=Test or repositories=[a,b]. May be repeated."
+    )]
+    set: Vec<String>,
+
+    #[arg(
+        short,
+        long,
+        global = true,
+        help = "Force overwrite of existing files."
+    )]
     force_resource_creation: bool,
 
     #[arg(
+        long,
+        global = true,
+        help = "Keep the resources and templates modified in the destination, rather than replace them with the built-in ones."
+    )]
+    keep_local_modifications: bool,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Report the resources and templates that are no longer built in, rather than delete them from the destination."
+    )]
+    prune_dry_run: bool,
+
+    #[arg(
+        global = true,
+        long,
+        value_enum,
+        default_value_t = publish::PublishMode::Direct,
+        help = "How the files are published: direct writes each into the destination, symlink writes them into a new directory in <destination>/releases and points <destination>/current at it once the run succeeded."
+    )]
+    publish_mode: publish::PublishMode,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "N",
+        default_value_t = 5,
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "How many releases to keep with --publish-mode symlink, in total, including the current one."
+    )]
+    keep_releases: u64,
+
+    #[arg(
+        global = true,
         short,
         long,
         default_value = "index.html",
@@ -57,6 +110,7 @@ struct Opt {
     output_file: PathBuf,
 
     #[arg(
+        global = true,
         short,
         long,
         default_value = "status.json",
@@ -65,333 +119,1114 @@ struct Opt {
     json_output_file: PathBuf,
 
     #[arg(
+        global = true,
+        long,
+        default_value = "summary.json",
+        help = "Filename for the summary of the statuses, will be placed in the destination directory."
+    )]
+    summary_output_file: PathBuf,
+
+    #[arg(
+        global = true,
+        long,
+        help = "Write the status as plain text to this file, relative to the destination directory."
+    )]
+    text_output_file: Option<PathBuf>,
+
+    #[arg(
+        global = true,
+        long,
+        help = "Write the status as markdown to this file, relative to the destination directory."
+    )]
+    markdown_output_file: Option<PathBuf>,
+
+    #[arg(
+        global = true,
+        long,
+        help = "Don't write the status page, the repository pages and their resources."
+    )]
+    no_html: bool,
+
+    #[arg(
+        global = true,
+        long,
+        help = "Don't write status.json, summary.json and the repository JSON documents."
+    )]
+    no_json: bool,
+
+    #[arg(
+        global = true,
+        long,
+        help = "Print a table of the statuses and the written files after the run. [default: when stdout is a terminal]"
+    )]
+    summary: bool,
+
+    #[arg(
+        global = true,
         short,
         long,
-        help = "Generate a prometheus-style metrics/index.html in the destination directory."
+        help = "Write Prometheus metrics to the file metrics in the destination directory."
     )]
     prometheus_metrics: bool,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "PATH",
+        help = "Write Prometheus metrics to this file, relative to the destination directory or absolute. Overrides metrics.output in the configuration."
+    )]
+    prometheus_output: Option<PathBuf>,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "PATH",
+        help = "Lock file preventing overlapping runs. [default: <destination>/.status-page.lock]"
+    )]
+    lock_file: Option<PathBuf>,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "SECONDS",
+        default_value_t = 0,
+        help = "How long to wait for another run to release the lock. If it is still held, exit with code 75."
+    )]
+    lock_timeout: u64,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "PATH",
+        help = "File keeping the metrics counters, and without the JSON output the status, across runs. [default: <destination>/.state.json]"
+    )]
+    state_file: Option<PathBuf>,
+
+    #[arg(
+        global = true,
+        long,
+        conflicts_with_all = ["diff", "from_json"],
+        help = "Keep running, generating the status page every --interval. SIGHUP reloads the configuration."
+    )]
+    daemon: bool,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "DURATION",
+        default_value = "60s",
+        value_parser = daemon::parse_duration,
+        help = "Time between the start of two runs in daemon mode, such as 90s, 5m or 1h."
+    )]
+    interval: std::time::Duration,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "DURATION",
+        default_value = "0s",
+        value_parser = daemon::parse_duration,
+        help = "Random delay of up to this much added to each interval in daemon mode."
+    )]
+    jitter: std::time::Duration,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "ADDRESS",
+        requires = "daemon",
+        help = "Serve the destination directory over HTTP on this address in daemon mode, such as 0.0.0.0:8080."
+    )]
+    listen: Option<std::net::SocketAddr>,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "URL",
+        help = "Push the metrics to this Pushgateway after each run, overrides push_gateway.url in the configuration."
+    )]
+    push_gateway_url: Option<String>,
+
+    #[arg(
+        global = true,
+        long,
+        value_enum,
+        default_value_t = prometheus::MetricsFormat::Prometheus,
+        help = "Format of the metrics file and the /metrics endpoint."
+    )]
+    metrics_format: prometheus::MetricsFormat,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "FILE",
+        help = "Write the metrics as InfluxDB line protocol to this file after each run, overrides influx.output in the configuration."
+    )]
+    influx_output: Option<PathBuf>,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "FILE",
+        help = "Also write the metrics as a JSON document to this file."
+    )]
+    metrics_json_output: Option<PathBuf>,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "FILE",
+        help = "Read the announcement banner from this markdown file instead of announcement.md in the destination."
+    )]
+    announcement_file: Option<PathBuf>,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "PATH",
+        help = "Render the pages with the templates in this directory, and the built-in templates for those it doesn't have."
+    )]
+    template_dir: Option<PathBuf>,
+
+    #[arg(
+        global = true,
+        long,
+        help = "Fail rendering when a template refers to undefined variables, rather than warning about them."
+    )]
+    strict_templates: bool,
+
+    #[arg(
+        global = true,
+        long,
+        help = "Collapse the whitespace and remove the comments of the rendered HTML pages."
+    )]
+    minify_html: bool,
+
+    #[arg(
+        global = true,
+        long,
+        help = "Also write the stylesheets and scripts under names with a hash of their contents, and load those in the pages."
+    )]
+    hashed_assets: bool,
+
+    #[arg(
+        global = true,
+        long,
+        help = "Write a gzipped copy, and a brotli one with the brotli feature, next to the pages, the JSON documents and the resources, for web servers that serve those."
+    )]
+    precompress: bool,
+
+    #[arg(
+        global = true,
+        long,
+        help = "Write an SVG badge for every component to the badges directory, rather than only for the badges in the configuration."
+    )]
+    badges: bool,
+
+    #[arg(
+        global = true,
+        long,
+        help = "Log the notification emails instead of sending them."
+    )]
+    email_dry_run: bool,
+
+    #[arg(
+        global = true,
+        long,
+        conflicts_with = "daemon",
+        help = "Scrape and render as usual, but only log the files that would be written, and send no notifications, metrics or heartbeat. The run report is printed instead of written."
+    )]
+    dry_run: bool,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "STATUS_JSON",
+        help = "Render from a previously saved status.json instead of scraping."
+    )]
+    from_json: Option<PathBuf>,
+
+    #[arg(
+        global = true,
+        long,
+        help = "Print the changes since the previous status.json, and write them to changes.json. Exits with 1 if anything changed."
+    )]
+    diff: bool,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "STATUS",
+        help = "Exit with 10 plus the level of the overall status (OK 0, DEGRADED 1, WARNING 2, FAILED 3, MAINTENANCE 9) if it is this status or worse, once everything is written."
+    )]
+    fail_on_status: Option<Status>,
+
+    #[arg(
+        global = true,
+        long = "server",
+        value_name = "HOSTNAME",
+        help = "Only scrape this server, may be repeated. Requires an explicit --destination."
+    )]
+    servers: Vec<String>,
+
+    #[arg(
+        global = true,
+        long = "server-type",
+        value_name = "TYPE",
+        value_parser = parse_server_type,
+        help = "Only scrape servers of this type (stratum0, stratum1, syncserver), may be repeated. Requires an explicit --destination."
+    )]
+    server_types: Vec<ServerType>,
+
+    #[arg(
+        global = true,
+        long = "repo",
+        value_name = "REPOSITORY",
+        help = "Only scrape this repository, may be repeated. Requires an explicit --destination."
+    )]
+    repositories: Vec<String>,
+
+    #[arg(
+        global = true,
+        long = "acknowledge-regression",
+        value_name = "REPOSITORY@HOSTNAME",
+        value_parser = parse_regression,
+        conflicts_with = "daemon",
+        help = "Accept that the revision of the repository on the server went backwards, so it is the highest revision seen from this run on, may be repeated."
+    )]
+    acknowledged_regressions: Vec<(String, String)>,
+
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        help = "Log format, overrides logging.format in the configuration. [default: text]"
+    )]
+    log_format: Option<logging::LogFormat>,
+
+    #[arg(
+        short,
+        long,
+        global = true,
+        action = clap::ArgAction::Count,
+        conflicts_with = "quiet",
+        help = "Log at info level, or debug with -vv. Overrides RUST_LOG and the configuration."
+    )]
+    verbose: u8,
+
+    #[arg(
+        short,
+        long,
+        global = true,
+        help = "Only log warnings and errors. Overrides RUST_LOG and the configuration."
+    )]
+    quiet: bool,
+
+    /// Set when --destination was given on the command line.
+    #[arg(skip)]
+    destination_given: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum Command {
+    /// Generate the status page. This is the default.
+    Generate,
+    /// Scrape the servers and print the status as a Nagios or Icinga check, with its state as
+    /// the exit code. Nothing is written to the destination.
+    Check,
+    /// Print a completion script for the shell to stdout.
+    Completions { shell: clap_complete::Shell },
+    /// Write an example configuration to start from.
+    Init {
+        #[arg(
+            long,
+            help = "Where to write the configuration. [default: config.<format>]"
+        )]
+        path: Option<PathBuf>,
+
+        #[arg(
+            long,
+            value_enum,
+            help = "Format of the configuration. [default: from the extension of --path, else json]"
+        )]
+        format: Option<config::ConfigFormat>,
+
+        #[arg(long, help = "Overwrite the file if it exists.")]
+        force: bool,
+    },
+    /// Check the destination, the configuration, the templates and the DNS of the servers
+    /// without scraping, exiting with 1 if any check fails.
+    Doctor,
+    /// Scrape the servers and list what was found.
+    List {
+        #[command(subcommand)]
+        what: ListCommand,
+
+        #[arg(long, global = true, value_enum, default_value_t = listing::Format::Table, help = "Output format.")]
+        format: listing::Format,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone, Copy)]
+enum ListCommand {
+    /// List servers with their type, backend, status and number of repositories.
+    Servers,
+    /// List the revision of each repository on each server, and how far it lags behind.
+    Repos,
+}
+
+fn parse_server_type(value: &str) -> Result<ServerType, String> {
+    [
+        ServerType::Stratum0,
+        ServerType::Stratum1,
+        ServerType::SyncServer,
+    ]
+    .into_iter()
+    .find(|t| t.to_label() == value)
+    .ok_or_else(|| format!("unknown server type '{}'", value))
+}
+
+/// Parse a repository on a server, given as `repository@hostname`.
+fn parse_regression(value: &str) -> Result<(String, String), String> {
+    match value.split_once('@') {
+        Some((repository, hostname)) if !repository.is_empty() && !hostname.is_empty() => {
+            Ok((repository.to_string(), hostname.to_string()))
+        }
+        _ => Err(format!("expected REPOSITORY@HOSTNAME, not '{}'", value)),
+    }
+}
+
+impl Opt {
+    /// How a run goes, from the command line.
+    fn run_options(&self) -> RunOptions {
+        RunOptions {
+            output: OutputOptions {
+                destination: self.destination.clone(),
+                output_file: self.output_file.clone(),
+                json_output_file: self.json_output_file.clone(),
+                summary_output_file: self.summary_output_file.clone(),
+                text_output_file: self.text_output_file.clone(),
+                markdown_output_file: self.markdown_output_file.clone(),
+                announcement_file: self.announcement_file.clone(),
+                template_dir: self.template_dir.clone(),
+                resources_dir: None,
+                outputs: config::OutputConfig {
+                    html: !self.no_html,
+                    json: !self.no_json,
+                },
+                strict_templates: self.strict_templates,
+                minify_html: self.minify_html,
+                hashed_assets: self.hashed_assets,
+                badges: self.badges,
+                force_resource_creation: self.force_resource_creation,
+                keep_local_modifications: self.keep_local_modifications,
+                prune_dry_run: self.prune_dry_run,
+            },
+            config_dir: self
+                .configuration
+                .parent()
+                .unwrap_or(Path::new(""))
+                .to_path_buf(),
+            servers: self.servers.clone(),
+            server_types: self.server_types.clone(),
+            repositories: self.repositories.clone(),
+            state_file: self.state_file.clone(),
+            acknowledged_regressions: self.acknowledged_regressions.clone(),
+            diff: self.diff,
+            dry_run: self.dry_run,
+            email_dry_run: self.email_dry_run,
+            precompress: self.precompress,
+            prometheus_output: self.prometheus_output.clone(),
+            prometheus_metrics: self.prometheus_metrics,
+            metrics_format: self.metrics_format,
+            metrics_json_output: self.metrics_json_output.clone(),
+            push_gateway_url: self.push_gateway_url.clone(),
+            influx_output: self.influx_output.clone(),
+            publish_mode: self.publish_mode,
+            keep_releases: self.keep_releases as usize,
+        }
+    }
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    env_logger::init();
     let run_start_time = chrono::Utc::now();
 
-    let args = Opt::parse();
+    let matches = Opt::command().get_matches();
+    let mut args = Opt::from_arg_matches(&matches)?;
+    args.destination_given = matches.value_source("destination") == Some(ValueSource::CommandLine);
+
+    let early_logging = args
+        .configuration
+        .to_str()
+        .map(config::read_logging_settings)
+        .unwrap_or_default();
+    let (log_filter, log_sources) = logging::resolve_filter(
+        args.verbose,
+        args.quiet,
+        std::env::var("RUST_LOG").ok().as_deref(),
+        early_logging.level.as_deref(),
+    );
+    logging::init(
+        args.log_format.or(early_logging.format).unwrap_or_default(),
+        &log_filter,
+        &log_sources,
+    );
     debug!("Running with the following options: {:?}", args);
 
-    let config_manager = init_and_get_config(&args)?;
+    if let Some(Command::Completions { shell }) = &args.command {
+        completions(*shell, &mut std::io::stdout());
+        return Ok(());
+    }
+
+    if let Some(Command::Init {
+        path,
+        format,
+        force,
+    }) = &args.command
+    {
+        let format = format
+            .or(path.as_ref().map(config::ConfigFormat::from_path))
+            .unwrap_or(config::ConfigFormat::Json);
+        let path = path
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(format!("config.{}", format.extension())));
+        init::write(&path, format, *force, &OutputSink::default())?;
+        println!("Example configuration written to {:?}", path);
+        return Ok(());
+    }
+
+    if let Some(Command::Doctor) = &args.command {
+        let findings = doctor(&args, &run_start_time).await;
+        for finding in &findings {
+            println!("{}", finding);
+        }
+        std::process::exit(if findings.iter().all(doctor::Finding::passed) {
+            0
+        } else {
+            1
+        });
+    }
+
+    if let Some(Command::Check) = &args.command {
+        let report = check(&args, &run_start_time).await;
+        println!("{}", report);
+        std::process::exit(report.exit_code());
+    }
+
+    let options = args.run_options();
+
+    if let Some(path) = &args.from_json {
+        let lock = acquire_lock(&args).await;
+        let mut report = report::RunReport::new(run_start_time);
+        let rendered = render_from_json(&options, path, run_start_time, &mut report);
+        drop(lock);
+        let status_page_data = rendered.map_err(exit_on_template_error)?;
+        print_summary(&args, &status_page_data, &report.artifacts);
+        return print_report(&args, &report);
+    }
+
+    let config_manager = load_config(&args)?;
 
     if args.show_config {
         println!("{}", config_manager.as_json());
         std::process::exit(0);
     }
 
-    let status_manager = create_status_manager(config_manager).await?;
-    let status_page_data = generate_status_page_data(config_manager, &status_manager)?;
+    let config = options.scoped_config(&config_manager)?;
+    if let Some(Command::List { what, format }) = &args.command {
+        return list(&config, *what, *format).await;
+    }
+    let scope = options.scope();
+    if !scope.is_empty() && !args.destination_given {
+        anyhow::bail!(
+            "Restricting the run to {} requires an explicit --destination, to avoid overwriting the full status",
+            scope.join("; ")
+        );
+    }
+
+    let lock = acquire_lock(&args).await;
+
+    if args.daemon {
+        // Unhealthy once two intervals pass without a successful run.
+        let health = server::Health::new(2 * (args.interval + args.jitter));
+        let metrics = server::Metrics::new(args.metrics_format);
+        let server = match args.listen {
+            Some(address) => {
+                Some(start_server(&args, address, health.clone(), metrics.clone()).await?)
+            }
+            None => None,
+        };
 
-    render_output(&args, &status_page_data)?;
+        let result = run_daemon(&args, &config_manager, &health, &metrics).await;
+
+        if let Some((stop, handle)) = server {
+            let _ = stop.send(());
+            handle.await?.context("HTTP server failed")?;
+        }
+        return result;
+    }
 
-    if args.prometheus_metrics {
-        generate_prometheus_metrics(&args, &status_page_data, &status_manager, &run_start_time)?;
+    let mut report = report::RunReport::new(run_start_time);
+    let generated = generate(&options, &config_manager, run_start_time, None, &mut report).await;
+    if let Ok(generated) = &generated {
+        print_summary(&args, &generated.data, &report.artifacts);
+        if let Some(changes) = &generated.changes {
+            print!("{}", changes);
+        }
+    }
+    print_report(&args, &report)?;
+    let generated = match generated {
+        Ok(generated) => generated,
+        Err(e) => {
+            drop(lock);
+            return Err(exit_on_template_error(e));
+        }
+    };
+    let status = generated.data.eessi_status.status;
+    if let Some(code) = fail_on_status_exit_code(args.fail_on_status, status) {
+        drop(lock);
+        std::process::exit(code);
+    }
+    if let Some(changes) = generated.changes {
+        if !changes.is_empty() {
+            drop(lock);
+            std::process::exit(1);
+        }
     }
 
     Ok(())
 }
 
-fn init_and_get_config(args: &Opt) -> Result<&config::ConfigManager> {
-    let config_path = args
-        .configuration
-        .to_str()
-        .context("Invalid configuration path")?;
-    init_config(config_path);
-    Ok(get_config_manager())
+/// Exit with `RENDER_FAILED_EXIT_CODE` if the run failed on a template, so a broken template is
+/// told apart from a failed scrape.
+fn exit_on_template_error(error: anyhow::Error) -> anyhow::Error {
+    if templating::is_template_error(&error) {
+        eprintln!("Error: {:?}", error);
+        std::process::exit(templating::RENDER_FAILED_EXIT_CODE);
+    }
+    error
 }
 
-async fn create_status_manager(config_manager: &config::ConfigManager) -> Result<StatusManager> {
-    let config = config_manager.get_config();
-    let mut servers = vec![];
-
-    for server in &config.servers {
-        let hostname = server.hostname.clone();
-        let backend = server.backend_type.clone();
-        let server_type = server.server_type.clone();
-        servers.push(cvmfs_server_scraper::Server::new(
-            server_type,
-            backend,
-            hostname,
-        ));
-    }
-
-    let repolist = config.repositories.clone();
-    let ignored_repos = config.ignored_repositories.clone();
-
-    // Build a Scraper and scrape all servers in parallel
-    let scraped_servers = Scraper::new()
-        .forced_repositories(repolist)
-        .ignored_repositories(ignored_repos)
-        .only_scrape_forced_repositories(config.limit_scraping_to_repositories)
-        .with_servers(servers) // Transitions to a WithServer state.
-        .validate()? // Transitions to a ValidatedAndReady state, now immutable.
-        .scrape()
-        .await; // Perform the scrape, return servers.
-
-    Ok(StatusManager::new(scraped_servers))
+/// Write the completion script of every option and subcommand for `shell`.
+fn completions(shell: clap_complete::Shell, out: &mut dyn std::io::Write) {
+    let mut command = Opt::command();
+    let name = command.get_name().to_string();
+    clap_complete::generate(shell, &mut command, name, out);
 }
 
-fn generate_status_page_data(
-    config_manager: &config::ConfigManager,
-    status_manager: &StatusManager,
-) -> Result<StatusPageData> {
-    let config = config_manager.get_config();
-    let s0status = get_status(
-        config_manager,
-        status_manager,
-        "stratum0_servers",
-        |sm, c| sm.status_stratum0(c),
-    )?;
-    let s1status = get_status(
-        config_manager,
-        status_manager,
-        "stratum1_servers",
-        |sm, c| sm.status_stratum1(c),
-    )?;
-    let syncstatus = get_status(config_manager, status_manager, "sync_servers", |sm, c| {
-        sm.status_syncserver(c)
-    })?;
-    let eessi_status = get_status(config_manager, status_manager, "eessi_status", |sm, c| {
-        sm.status_overall(c)
-    })?;
-
-    Ok(StatusPageData {
-        title: config.meta.title.clone(),
-        eessi_status: create_eessi_status(eessi_status),
-        contact_email: config.meta.contact_email.clone(),
-        last_update: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
-        legend: StatusInfo::all(),
-        stratum0: create_stratum_status(s0status, status_manager, ServerType::Stratum0),
-        stratum1: create_stratum_status(s1status, status_manager, ServerType::Stratum1),
-        syncservers: create_stratum_status(syncstatus, status_manager, ServerType::SyncServer),
-        repositories_status: create_repo_status(),
-        repositories: status_manager.details_repositories(),
-        config: config_manager.config.read().unwrap().clone(),
-        servers: status_manager.get_server_status_for_all(),
-    })
+/// Added to the level of the overall status for the exit code of --fail-on-status.
+const FAIL_ON_STATUS_EXIT_CODE_BASE: i32 = 10;
+
+/// The exit code of a run for --fail-on-status, if the overall status is `threshold` or worse.
+/// Distinct from the exit codes of failed runs, so a red page isn't mistaken for a broken run.
+fn fail_on_status_exit_code(threshold: Option<Status>, status: Status) -> Option<i32> {
+    use crate::models::StatusLevel;
+
+    threshold
+        .filter(|threshold| status >= *threshold)
+        .map(|_| FAIL_ON_STATUS_EXIT_CODE_BASE + status.level())
 }
 
-fn generate_prometheus_metrics(
+/// Generate the status page every interval until SIGTERM or SIGINT. A run in progress when
+/// the signal arrives is finished first. SIGHUP reloads the configuration.
+///
+/// A failing (or panicking) run is logged, and the next one is attempted as scheduled.
+async fn run_daemon(
     args: &Opt,
-    status_page_data: &StatusPageData,
-    status_manager: &StatusManager,
-    timestamp: &DateTime<Utc>,
+    config_manager: &config::ConfigManager,
+    health: &server::Health,
+    metrics: &server::Metrics,
 ) -> Result<()> {
-    use crate::models::StatusLevel;
-
-    let filename = args.destination.join("metrics");
-    trace!("Generating Prometheus metrics file: {:?}", filename);
-
-    let ts = timestamp.timestamp_millis();
-
-    let mut b = MetricsBuilder::new();
-    b.add_gauge(
-        "eessi_status",
-        "EESSI status",
-        status_page_data.eessi_status.level() as f64,
-        &[],
-        Some(ts),
-    )
-    .add_gauge(
-        "stratum0_status",
-        "Stratum0 status",
-        status_page_data.stratum0.level() as f64,
-        &[],
-        Some(ts),
-    )
-    .add_gauge(
-        "stratum1_status",
-        "Stratum1 status",
-        status_page_data.stratum1.level() as f64,
-        &[],
-        Some(ts),
-    )
-    .add_gauge(
-        "syncservers_status",
-        "SyncServers status",
-        status_page_data.syncservers.level() as f64,
-        &[],
-        Some(ts),
-    )
-    .add_gauge(
-        "repositories_status",
-        "Repositories status",
-        status_page_data.repositories_status.level() as f64,
-        &[],
-        Some(ts),
+    let config_path = args
+        .configuration
+        .to_str()
+        .context("Invalid configuration path")?;
+    let schedule = daemon::Schedule {
+        interval: args.interval,
+        jitter: args.jitter,
+    };
+    let mut signals = daemon::Signals::new().context("Failed to install signal handlers")?;
+    info!(
+        interval_seconds = schedule.interval.as_secs(),
+        jitter_seconds = schedule.jitter.as_secs();
+        "Running as a daemon, every {:?} with up to {:?} jitter", schedule.interval, schedule.jitter
     );
 
-    let maps = vec![
-        ("overall", status_page_data.eessi_status.level() as f64),
-        ("stratum0", status_page_data.stratum0.level() as f64),
-        ("stratum1", status_page_data.stratum1.level() as f64),
-        ("syncservers", status_page_data.syncservers.level() as f64),
-        (
-            "repositories",
-            status_page_data.repositories_status.level() as f64,
+    let options = args.run_options();
+    let mut iteration: u64 = 0;
+    loop {
+        iteration += 1;
+        let started = std::time::Instant::now();
+        let run_start_time = Utc::now();
+        let mut report = report::RunReport::new(run_start_time);
+        let outcome = AssertUnwindSafe(generate(
+            &options,
+            config_manager,
+            run_start_time,
+            Some(metrics),
+            &mut report,
+        ))
+        .catch_unwind()
+        .await;
+        let elapsed = started.elapsed();
+        let duration_ms = elapsed.as_millis() as u64;
+
+        match outcome {
+            Ok(Ok(generated)) => {
+                print_summary(args, &generated.data, &report.artifacts);
+                health.record_success();
+                info!(
+                    iteration, duration_ms, outcome = "ok";
+                    "Run {} finished in {:?}", iteration, elapsed
+                )
+            }
+            Ok(Err(e)) => error!(
+                iteration, duration_ms, outcome = "failed";
+                "Run {} failed after {:?}: {:#}", iteration, elapsed, e
+            ),
+            Err(_) => error!(
+                iteration, duration_ms, outcome = "panicked";
+                "Run {} panicked after {:?}", iteration, elapsed
+            ),
+        }
+
+        match signals.wait(schedule.next_delay(elapsed)).await {
+            daemon::Wake::Tick => {}
+            daemon::Wake::Reload => match config_manager.reload(config_path, &args.set) {
+                Ok(()) => info!("Configuration reloaded from {}", config_path),
+                Err(e) => error!("Keeping the current configuration: {}", e),
+            },
+            daemon::Wake::Shutdown => {
+                info!("Shutting down after {} runs", iteration);
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Load the configuration, scrape the configured servers and evaluate the rules, for a check of
+/// Nagios or Icinga. The check is UNKNOWN if any of that fails, such as with an invalid
+/// configuration.
+async fn check(args: &Opt, run_start_time: &DateTime<Utc>) -> check::CheckReport {
+    let report = async {
+        let config_manager = load_config(args)?;
+        let options = args.run_options();
+        let config = options.scoped_config(&config_manager)?;
+        // Read only, the check leaves the state to the runs that write the page.
+        let last_scrape = cache::LastScrape::load(&args.destination).unwrap_or_else(|e| {
+            warn!("Ignoring the last scrape: {:#}", e);
+            None
+        });
+        let run_state = state::RunState::load(&options.state_file());
+        let status_manager =
+            scrape(&config, last_scrape.as_ref(), &run_state.highest_revisions).await?;
+        let data = build_page_data(
+            &config_manager,
+            &status_manager,
+            &locale::ENGLISH,
+            run_start_time,
+        )?;
+        Ok(check::CheckReport::new(&data, &config.check))
+    };
+    report
+        .await
+        .unwrap_or_else(|e: anyhow::Error| check::CheckReport::unknown(&e))
+}
+
+/// Run the checks of the `doctor` subcommand. The last run is checked from its report. The
+/// templates are rendered from the status with no server scraped, and are only checked if the
+/// configuration is valid, like the resources directory.
+async fn doctor(args: &Opt, run_start_time: &DateTime<Utc>) -> Vec<doctor::Finding> {
+    let mut findings = vec![
+        doctor::Finding::new(
+            "destination",
+            doctor::destination(&args.destination, &OutputSink::default()),
         ),
+        doctor::Finding::new("last run", doctor::last_run(&last_run_dir(args))),
     ];
 
-    for (category, level) in maps {
-        b.add_gauge(
-            "status_overview",
-            "Status overview",
-            level,
-            &[("category", category)],
-            Some(ts),
-        );
+    let config_path = args.configuration.to_str().unwrap_or_default();
+    let config_manager = match doctor::configuration(config_path, &args.set) {
+        Ok(config_manager) => {
+            findings.push(doctor::Finding::new(
+                "configuration",
+                Ok(format!("{} is valid", config_path)),
+            ));
+            config_manager
+        }
+        Err(e) => {
+            findings.push(doctor::Finding::new("configuration", Err(e)));
+            return findings;
+        }
+    };
+    let config = config_manager.get_config();
+
+    let rules = doctor::rules(&config);
+    let rules_passed = rules.is_ok();
+    findings.push(doctor::Finding::new("rules", rules));
+    if rules_passed {
+        let status_manager = StatusManager {
+            servers: vec![],
+            maintenance_aggregation: config.maintenance_aggregation,
+        };
+        let dir = args.run_options().template_dir(&config.meta);
+        let templates = locale::Locale::load(&config.meta.locale, dir.as_deref())
+            .and_then(|locale| {
+                build_page_data(&config_manager, &status_manager, &locale, run_start_time)
+            })
+            .and_then(|data| doctor::templates(dir.as_deref(), &data));
+        findings.push(doctor::Finding::new("templates", templates));
     }
+    findings.push(doctor::Finding::new(
+        "resources",
+        doctor::resources(args.run_options().resources_dir(&config.meta).as_deref()),
+    ));
 
-    for server in status_manager.get_all_servers() {
-        let ts_ms = Some(ts);
-
-        for repo in server.repositories.iter() {
-            let repo_labels: [(&str, &str); 3] = [
-                ("type", server.server_type.to_label()),
-                ("server", server.hostname.to_str()),
-                ("repository", repo.name.as_str()),
-            ];
-
-            // The fields are:
-            // - c: Cryptographic hash of the repository’s current root catalog
-            // - b: Size of the root file catalog in bytes
-            // - a: true if the catalog should be fetched under its alternative name
-            // - r: MD5 hash of the repository’s current root path (usually always d41d8cd98f00b204e9800998ecf8427e)
-            // - x: Cryptographic hash of the signing certificate
-            // - g: true if the repository is garbage-collectable
-            // - h: Cryptographic hash of the repository’s named tag history database
-            // - t: Unix timestamp of this particular revision
-            // - d: Time To Live (TTL) of the root catalog
-            // - s: Revision number of this published revision
-            // - n: The full name of the manifested repository
-            // - m: Cryptographic hash of the repository JSON metadata
-            // - y: Cryptographic hash of the reflog checksum
-            // - l: currently unused (reserved for micro catalogs)
-            b.add_gauge(
-                "repo_revision",
-                "Repository revision",
-                repo.revision as f64,
-                &repo_labels,
-                ts_ms,
-            )
-            .add_gauge(
-                "repo_timestamp",
-                "Repository timestamp",
-                repo.manifest.t as f64,
-                &repo_labels,
-                ts_ms,
-            )
-            .add_gauge(
-                "repo_ttl",
-                "Repository TTL",
-                repo.manifest.d as f64,
-                &repo_labels,
-                ts_ms,
-            )
-            .add_gauge(
-                "repo_catalogue_size",
-                "Repository catalogue size",
-                repo.manifest.b as f64,
-                &repo_labels,
-                ts_ms,
-            );
-        }
+    findings.extend(doctor::dns(&config).await);
+    findings
+}
+
+/// Scrape the configured servers and print a listing of the servers or repositories.
+///
+/// Fails if no server could be scraped at all.
+async fn list(
+    config: &config::ConfigFile,
+    what: ListCommand,
+    format: listing::Format,
+) -> Result<()> {
+    let status_manager = scrape(config, None, &Default::default()).await?;
+
+    let output = match what {
+        ListCommand::Servers => listing::render(&listing::server_rows(&status_manager), format)?,
+        ListCommand::Repos => listing::render(&listing::repo_rows(&status_manager), format)?,
+    };
+    print!("{}", output);
+
+    if status_manager
+        .servers
+        .iter()
+        .all(|s| s.backend_detected.is_none())
+    {
+        anyhow::bail!("Scraping failed for all servers");
     }
 
-    let text = b.build();
-    atomic_write(&filename, text.as_bytes())?;
-    info!("Prometheus metrics file written to: {:?}", filename);
     Ok(())
 }
 
-fn get_status<F>(
-    config_manager: &config::ConfigManager,
-    status_manager: &StatusManager,
-    rule: &str,
-    status_fn: F,
-) -> Result<Status>
-where
-    F: FnOnce(&StatusManager, Vec<config::Condition>) -> Status,
-{
-    let conditions = config_manager
-        .get_conditions_for_rule(rule)
-        .context(format!("No rules found for '{}'", rule))?;
-    Ok(status_fn(status_manager, conditions))
+/// Start serving the destination directory, returning a sender that stops the server.
+async fn start_server(
+    args: &Opt,
+    address: std::net::SocketAddr,
+    health: server::Health,
+    metrics: server::Metrics,
+) -> Result<(
+    tokio::sync::oneshot::Sender<()>,
+    tokio::task::JoinHandle<std::io::Result<()>>,
+)> {
+    let listener = tokio::net::TcpListener::bind(address)
+        .await
+        .context(format!("Failed to listen on {}", address))?;
+    let served_dir = publish::served_dir(&args.destination, args.publish_mode);
+    let router = server::router(
+        &served_dir,
+        &args.output_file,
+        &args.json_output_file,
+        health,
+        metrics,
+    );
+    let (stop, stopped) = tokio::sync::oneshot::channel();
+    info!("Serving {:?} on http://{}", served_dir, address);
+
+    let handle = tokio::spawn(server::serve(listener, router, async {
+        stopped.await.ok();
+    }));
+    Ok((stop, handle))
 }
 
-fn create_eessi_status(status: Status) -> EESSIStatus {
-    EESSIStatus {
-        status,
-        class: status.class().to_string(),
-        text: status.text().to_string(),
-        description: status.description().to_string(),
+/// The directory with the run report of the last run: the newest release with
+/// `--publish-mode symlink`, whether it was published or the run failed.
+fn last_run_dir(args: &Opt) -> PathBuf {
+    match args.publish_mode {
+        publish::PublishMode::Direct => args.destination.clone(),
+        publish::PublishMode::Symlink => {
+            publish::last_release(&args.destination).unwrap_or_else(|| args.destination.clone())
+        }
     }
 }
 
-fn create_stratum_status(
-    status: Status,
-    status_manager: &StatusManager,
-    server_type: ServerType,
-) -> StratumStatus {
-    StratumStatus {
-        status,
-        status_class: status.class().to_string(),
-        details: if status == Status::FAILED && server_type == ServerType::Stratum0 {
-            vec!["Stratum0 servers are not reachable!".to_string()]
-        } else {
-            status_manager.details_stratum0()
-        },
-        servers: status_manager.get_server_status_for_all_by_type(server_type),
+/// Take the lock for the destination, or `None` in a dry run, which doesn't write to it.
+/// Exits with `LOCK_HELD_EXIT_CODE` if another run holds the lock, and 1 if it can't be taken.
+async fn acquire_lock(args: &Opt) -> Option<lock::LockGuard> {
+    if args.dry_run {
+        return None;
     }
-}
+    let path = args
+        .lock_file
+        .clone()
+        .unwrap_or_else(|| args.destination.join(lock::LOCK_FILE_NAME));
 
-fn create_repo_status() -> RepoStatus {
-    RepoStatus {
-        name: "Repositories".to_string(),
-        status: Status::OK,
-        revision_class: Status::OK.class().to_string(),
-        snapshot_class: Status::OK.class().to_string(),
+    match lock::acquire(&path, std::time::Duration::from_secs(args.lock_timeout)).await {
+        Ok(guard) => Some(guard),
+        Err(e @ lock::LockError::Held { .. }) => {
+            error!("Another run is in progress, exiting: {}", e);
+            std::process::exit(lock::LOCK_HELD_EXIT_CODE);
+        }
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
     }
 }
 
-fn render_output(args: &Opt, status_page_data: &StatusPageData) -> Result<()> {
-    let mut context = tera::Context::new();
-    context.insert("data", status_page_data);
-
-    let destination = args
-        .destination
-        .to_str()
-        .context("Invalid destination path")?;
-    let output_file = args
-        .output_file
+fn load_config(args: &Opt) -> Result<config::ConfigManager> {
+    let config_path = args
+        .configuration
         .to_str()
-        .context("Invalid output file path")?;
+        .context("Invalid configuration path")?;
+    cvmfs_status_page_rust::load_config(config_path, &args.set)
+}
 
-    populate(destination, args.force_resource_creation)?;
-    render_template_to_file("status.html", &context, destination, output_file)?;
-    generate_json_output(status_page_data, &args.destination, &args.json_output_file)?;
+/// Print the table of statuses and written files, with `--summary` or when stdout is a
+/// terminal. Daemon runs only print it with `--summary`, as they would print it every interval.
+fn print_summary(args: &Opt, status_page_data: &StatusPageData, artifacts: &[report::Artifact]) {
+    let is_terminal = std::io::stdout().is_terminal();
+    if !args.summary && (args.daemon || !is_terminal) {
+        return;
+    }
+    let color = terminal::use_color(std::env::var("NO_COLOR").ok(), is_terminal);
+    print!("{}", terminal::render(status_page_data, artifacts, color));
+}
 
+/// In a dry run, print the run report with the writes the run would have made, as it isn't
+/// written.
+fn print_report(args: &Opt, report: &report::RunReport) -> Result<()> {
+    if args.dry_run {
+        println!("{}", serde_json::to_string_pretty(report)?);
+    }
     Ok(())
 }
 
-fn generate_json_output(
-    data: &StatusPageData,
-    destination: &Path,
-    filename: &PathBuf,
-) -> Result<()> {
-    let fqfn = destination.join(filename);
-    trace!("Generating JSON output file: {:?}", fqfn);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use yare::parameterized;
 
-    let json = serde_json::to_string_pretty(data)?;
-    atomic_write(&fqfn, json.as_bytes())?;
-    info!("JSON output file written to: {:?}", fqfn);
-    Ok(())
+    #[parameterized(
+        no_threshold = { None, Status::FAILED, None },
+        below = { Some(Status::WARNING), Status::DEGRADED, None },
+        at = { Some(Status::WARNING), Status::WARNING, Some(12) },
+        above = { Some(Status::WARNING), Status::FAILED, Some(13) },
+        ok_threshold = { Some(Status::OK), Status::OK, Some(10) },
+    )]
+    fn test_fail_on_status_exit_code(
+        threshold: Option<Status>,
+        status: Status,
+        expected: Option<i32>,
+    ) {
+        assert_eq!(fail_on_status_exit_code(threshold, status), expected);
+    }
+
+    #[parameterized(
+        upper = { "WARNING", Status::WARNING },
+        lower = { "warning", Status::WARNING },
+        mixed = { "Failed", Status::FAILED },
+    )]
+    fn test_parse_fail_on_status(value: &str, expected: Status) {
+        let args =
+            Opt::try_parse_from(["cvmfs-status-page-rust", "--fail-on-status", value]).unwrap();
+        assert_eq!(args.fail_on_status, Some(expected));
+    }
+
+    #[parameterized(
+        repository_on_server = { &["--acknowledge-regression", "software.eessi.io@s1.example.com"], Some(&[("software.eessi.io", "s1.example.com")]) },
+        repeated = { &["--acknowledge-regression", "a.eessi.io@s1", "--acknowledge-regression", "b.eessi.io@s2"], Some(&[("a.eessi.io", "s1"), ("b.eessi.io", "s2")]) },
+        no_server = { &["--acknowledge-regression", "software.eessi.io"], None },
+        no_repository = { &["--acknowledge-regression", "@s1.example.com"], None },
+        with_daemon = { &["--acknowledge-regression", "software.eessi.io@s1.example.com", "--daemon"], None },
+    )]
+    fn test_parse_acknowledge_regression(flags: &[&str], expected: Option<&[(&str, &str)]>) {
+        let args = Opt::try_parse_from(std::iter::once(&"cvmfs-status-page-rust").chain(flags));
+
+        let acknowledged = args.ok().map(|args| args.acknowledged_regressions);
+        let expected = expected.map(|pairs| {
+            pairs
+                .iter()
+                .map(|(repository, hostname)| (repository.to_string(), hostname.to_string()))
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(acknowledged, expected);
+    }
+
+    #[parameterized(
+        disabled = { &[], None, None },
+        default = { &["-p"], None, Some("/srv/status/metrics") },
+        flag = { &["--prometheus-output", "prom/eessi.prom"], None, Some("/srv/status/prom/eessi.prom") },
+        config = { &[], Some("eessi.prom"), Some("/srv/status/eessi.prom") },
+        flag_over_config = { &["--prometheus-output", "a.prom"], Some("b.prom"), Some("/srv/status/a.prom") },
+        absolute = { &["--prometheus-output", "/var/lib/node_exporter/eessi.prom"], None, Some("/var/lib/node_exporter/eessi.prom") },
+    )]
+    fn test_prometheus_output(flags: &[&str], output: Option<&str>, expected: Option<&str>) {
+        let args = Opt::try_parse_from(
+            ["cvmfs-status-page-rust", "-d", "/srv/status"]
+                .iter()
+                .chain(flags),
+        )
+        .unwrap();
+        let config = config::MetricsConfig {
+            output: output.map(PathBuf::from),
+            ..config::MetricsConfig::default()
+        };
+
+        assert_eq!(
+            args.run_options().prometheus_output(&config),
+            expected.map(PathBuf::from)
+        );
+    }
+
+    #[parameterized(
+        none = { &[], None, None },
+        flag = { &["--template-dir", "custom"], None, Some("custom") },
+        config = { &[], Some("templates"), Some("/etc/status/templates") },
+        absolute = { &[], Some("/srv/templates"), Some("/srv/templates") },
+        flag_over_config = { &["--template-dir", "custom"], Some("templates"), Some("custom") },
+    )]
+    fn test_template_dir(flags: &[&str], configured: Option<&str>, expected: Option<&str>) {
+        let args = Opt::try_parse_from(
+            ["cvmfs-status-page-rust", "-c", "/etc/status/config.json"]
+                .iter()
+                .chain(flags),
+        )
+        .unwrap();
+        let mut meta = init::example().meta;
+        meta.template_dir = configured.map(PathBuf::from);
+
+        assert_eq!(
+            args.run_options().template_dir(&meta),
+            expected.map(PathBuf::from)
+        );
+    }
+
+    #[parameterized(
+        none = { None, None },
+        relative = { Some("branding"), Some("/etc/status/branding") },
+        absolute = { Some("/srv/branding"), Some("/srv/branding") },
+    )]
+    fn test_resources_dir(configured: Option<&str>, expected: Option<&str>) {
+        let args = Opt::try_parse_from(["cvmfs-status-page-rust", "-c", "/etc/status/config.json"])
+            .unwrap();
+        let mut meta = init::example().meta;
+        meta.resources_dir = configured.map(PathBuf::from);
+
+        assert_eq!(
+            args.run_options().resources_dir(&meta),
+            expected.map(PathBuf::from)
+        );
+    }
+
+    #[parameterized(
+        default = { &[], true, true, true },
+        no_html = { &["--no-html"], true, false, true },
+        no_json = { &["--no-json"], true, true, false },
+        both = { &["--no-html", "--no-json"], true, false, false },
+        configured = { &[], false, false, true },
+        configured_and_flag = { &["--no-json"], false, false, false },
+    )]
+    fn test_output_config(flags: &[&str], html_configured: bool, html: bool, json: bool) {
+        let args = Opt::try_parse_from(["cvmfs-status-page-rust"].iter().chain(flags)).unwrap();
+        let config = config::OutputConfig {
+            html: html_configured,
+            ..config::OutputConfig::default()
+        };
+
+        assert_eq!(
+            args.run_options().output_config(&config),
+            config::OutputConfig { html, json }
+        );
+    }
+
+    #[parameterized(
+        everything = { &[], false, true },
+        metrics_only = { &["--no-html", "--no-json"], true, true },
+        nothing = { &["--no-html", "--no-json"], false, false },
+        text_only = { &["--no-html", "--no-json", "--text-output-file", "status.txt"], false, true },
+        json_only = { &["--no-html"], false, true },
+        diff_without_json = { &["--no-json", "--diff"], true, false },
+    )]
+    fn test_check_outputs(flags: &[&str], wants_metrics: bool, ok: bool) {
+        let args = Opt::try_parse_from(["cvmfs-status-page-rust"].iter().chain(flags)).unwrap();
+        let options = args.run_options();
+        let outputs = options.output_config(&config::OutputConfig::default());
+
+        assert_eq!(options.check_outputs(&outputs, wants_metrics).is_ok(), ok);
+    }
+
+    #[test]
+    fn test_write_prometheus_metrics_outside_destination() -> Result<()> {
+        let destination = tempfile::tempdir()?;
+        let collector = tempfile::tempdir()?;
+        let args = Opt::try_parse_from([
+            "cvmfs-status-page-rust",
+            "-d",
+            destination.path().to_str().unwrap(),
+            "--prometheus-output",
+            collector
+                .path()
+                .join("textfile/eessi.prom")
+                .to_str()
+                .unwrap(),
+        ])?;
+        let path = args
+            .run_options()
+            .prometheus_output(&config::MetricsConfig::default())
+            .unwrap();
+
+        prometheus::write_prometheus_metrics(&path, "eessi_status 0\n", &OutputSink::default())?;
+
+        assert_eq!(fs::read_to_string(&path)?, "eessi_status 0\n");
+        assert_eq!(fs::read_dir(path.parent().unwrap())?.count(), 1);
+        assert_eq!(fs::read_dir(destination.path())?.count(), 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_check_invalid_configuration_is_unknown() -> Result<()> {
+        let config = tempfile::NamedTempFile::with_suffix(".json")?;
+        std::fs::write(config.path(), "{ not json")?;
+        let args = Opt::try_parse_from([
+            "cvmfs-status-page-rust",
+            "-c",
+            config.path().to_str().unwrap(),
+            "check",
+        ])?;
+
+        let report = check(&args, &Utc::now()).await;
+
+        assert_eq!(report.exit_code(), 3);
+        assert!(
+            report.to_string().starts_with("EESSI UNKNOWN"),
+            "{}",
+            report
+        );
+        Ok(())
+    }
+
+    #[parameterized(
+        bash = { clap_complete::Shell::Bash },
+        zsh = { clap_complete::Shell::Zsh },
+        fish = { clap_complete::Shell::Fish },
+    )]
+    fn test_completions(shell: clap_complete::Shell) {
+        let mut out = vec![];
+
+        completions(shell, &mut out);
+
+        let script = String::from_utf8(out).unwrap();
+        assert!(script.contains("fail-on-status"), "{}", script);
+        assert!(script.contains("completions"), "{}", script);
+    }
 }
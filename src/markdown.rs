@@ -0,0 +1,234 @@
+use std::collections::HashMap;
+
+use crate::models::{Status, StatusPageData};
+use crate::plaintext::server_reason;
+use crate::templating::ServerStatus;
+
+/// A table cell, which can't contain pipes or line breaks.
+fn cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+fn status_cell(status: Status) -> String {
+    format!("{} {}", status.emoji(), status.text())
+}
+
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map_or_else(|| "-".to_string(), |v| v.to_string())
+}
+
+fn table(header: &[&str], rows: Vec<Vec<String>>) -> Vec<String> {
+    let mut lines = vec![
+        format!("| {} |", header.join(" | ")),
+        format!("|{}", " --- |".repeat(header.len())),
+    ];
+    lines.extend(
+        rows.into_iter()
+            .map(|row| format!("| {} |", row.join(" | "))),
+    );
+    lines
+}
+
+/// The servers by their type, in the order of the page.
+fn servers(data: &StatusPageData) -> Vec<(&'static str, &ServerStatus)> {
+    [
+        ("Stratum 0", &data.stratum0),
+        ("Stratum 1", &data.stratum1),
+        ("Sync server", &data.syncservers),
+    ]
+    .into_iter()
+    .flat_map(|(label, stratum)| stratum.servers.iter().map(move |server| (label, server)))
+    .collect()
+}
+
+/// Render the status as a GitHub flavored markdown document, to paste into issues and chat.
+pub fn render(data: &StatusPageData) -> String {
+    let servers = servers(data);
+
+    // How many revisions each server is behind the newest one seen of each repository.
+    let mut newest: HashMap<&str, i32> = HashMap::new();
+    for (_, server) in &servers {
+        for repo in &server.repositories {
+            if let Some(revision) = repo.revision {
+                let entry = newest.entry(repo.name.as_str()).or_insert(revision);
+                *entry = (*entry).max(revision);
+            }
+        }
+    }
+    let lag = |server: &ServerStatus, repository: Option<&str>| {
+        server
+            .repositories
+            .iter()
+            .filter(|repo| repository.is_none_or(|name| repo.name == name))
+            .filter_map(|repo| Some(newest.get(repo.name.as_str())? - repo.revision?))
+            .max()
+    };
+
+    let mut lines = vec![
+        format!(
+            "# {} {} {}",
+            data.title,
+            data.eessi_status.status.emoji(),
+            data.eessi_status.status.text()
+        ),
+        String::new(),
+        format!("Updated {}", data.last_update),
+        String::new(),
+        "## Servers".to_string(),
+        String::new(),
+    ];
+    lines.extend(table(
+        &["Host", "Type", "Status", "Lag"],
+        servers
+            .iter()
+            .map(|(label, server)| {
+                vec![
+                    cell(&server.name),
+                    label.to_string(),
+                    status_cell(server.status),
+                    optional(lag(server, None)),
+                ]
+            })
+            .collect(),
+    ));
+    lines.extend([String::new(), "## Repositories".to_string(), String::new()]);
+    lines.extend(table(
+        &["Name", "Revision", "Lag", "Last publish"],
+        data.repositories
+            .iter()
+            .map(|repo| {
+                let repo_lag = servers
+                    .iter()
+                    .filter_map(|(_, server)| lag(server, Some(&repo.name)))
+                    .max();
+                vec![
+                    cell(&repo.name),
+                    optional(repo.revision),
+                    optional(repo_lag),
+                    optional(repo.published_at.as_deref()),
+                ]
+            })
+            .collect(),
+    ));
+
+    let components = [
+        ("Stratum 0", &data.stratum0),
+        ("Stratum 1", &data.stratum1),
+        ("Sync servers", &data.syncservers),
+    ];
+    let reasons: Vec<String> = components
+        .iter()
+        .filter(|(_, stratum)| stratum.status != Status::OK)
+        .flat_map(|(label, stratum)| {
+            stratum
+                .details
+                .iter()
+                .map(move |detail| format!("- **{}**: {}", label, detail))
+        })
+        .chain(
+            data.repositories
+                .iter()
+                .filter(|repo| repo.status != Status::OK)
+                .flat_map(|repo| {
+                    repo.details
+                        .iter()
+                        .map(move |detail| format!("- **{}**: {}", repo.name, detail))
+                }),
+        )
+        .chain(
+            servers
+                .iter()
+                .filter(|(_, server)| server.status != Status::OK)
+                .map(|(_, server)| (server, server_reason(server)))
+                .filter(|(_, reason)| !reason.is_empty())
+                .map(|(server, reason)| format!("- **{}**: {}", server.name, reason)),
+        )
+        .collect();
+    if !reasons.is_empty() {
+        lines.extend([
+            String::new(),
+            "<details>".to_string(),
+            "<summary>Failure reasons</summary>".to_string(),
+            String::new(),
+        ]);
+        lines.extend(reasons);
+        lines.extend([String::new(), "</details>".to_string()]);
+    }
+
+    lines.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::templating::ServerRepository;
+    use yare::parameterized;
+
+    fn repository(name: &str, revision: Option<i32>) -> ServerRepository {
+        ServerRepository {
+            name: name.to_string(),
+            revision,
+            status: if revision.is_some() {
+                Status::OK
+            } else {
+                Status::FAILED
+            },
+            regressed_from: None,
+        }
+    }
+
+    fn mixed_data() -> StatusPageData {
+        let mut data: StatusPageData =
+            serde_json::from_str(include_str!("testdata/status-schema-0.json")).unwrap();
+        data.eessi_status.status = Status::WARNING;
+        data.stratum0.status = Status::OK;
+        data.stratum0.servers[0].status = Status::OK;
+        data.stratum0.servers[0].repositories = vec![
+            repository("software.eessi.io", Some(1240)),
+            repository("dev.eessi.io", Some(87)),
+        ];
+        data.stratum1.status = Status::WARNING;
+        data.stratum1.details = vec!["1 of 1 stratum1 servers lag behind".to_string()];
+        data.stratum1.servers[0].status = Status::WARNING;
+        data.stratum1.servers[0].repositories = vec![
+            repository("software.eessi.io", Some(1238)),
+            repository("dev.eessi.io", Some(87)),
+        ];
+        data.syncservers.status = Status::OK;
+        data.syncservers.servers[0].status = Status::FAILED;
+        data.syncservers.servers[0].repositories = vec![repository("software.eessi.io", None)];
+        data.repositories[0].status = Status::WARNING;
+        data.repositories[0].revision = Some(1240);
+        data.repositories[0].published_at = Some("2025-10-02T07:00:00Z".to_string());
+        data.repositories[0].details =
+            vec!["Revision 1238 on aws-eu-central-s1.eessi.science | behind".to_string()];
+        let mut dev = serde_json::from_value::<crate::templating::RepoStatus>(
+            serde_json::to_value(&data.repositories[0]).unwrap(),
+        )
+        .unwrap();
+        dev.name = "dev.eessi.io".to_string();
+        dev.status = Status::OK;
+        dev.revision = Some(87);
+        dev.published_at = Some("2025-09-30T12:00:00Z".to_string());
+        dev.details = vec![];
+        data.repositories.push(dev);
+        data
+    }
+
+    #[test]
+    fn test_render_mixed() {
+        assert_eq!(
+            render(&mixed_data()),
+            include_str!("testdata/status-mixed.md")
+        );
+    }
+
+    #[parameterized(
+        plain = { "software.eessi.io", "software.eessi.io" },
+        pipe = { "a | b", "a \\| b" },
+        newline = { "a\nb", "a b" },
+    )]
+    fn test_cell(text: &str, expected: &str) {
+        assert_eq!(cell(text), expected);
+    }
+}
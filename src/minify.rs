@@ -0,0 +1,152 @@
+use anyhow::{bail, Result};
+
+/// Elements whose content is copied as is, as whitespace in them is significant or they are
+/// not HTML, such as the embedded status data.
+const RAW_ELEMENTS: [&str; 4] = ["pre", "textarea", "script", "style"];
+
+/// `html` without comments and with every run of whitespace between tags, and between the
+/// attributes of a tag, collapsed to a single space. Attribute values and the content of
+/// `<pre>`, `<textarea>`, `<script>` and `<style>` are kept as they are. HTML it can't follow,
+/// such as an unterminated comment or tag, is an error.
+pub fn html(html: &str) -> Result<String> {
+    let mut minified = String::with_capacity(html.len());
+    let mut rest = html.trim_start();
+    while !rest.is_empty() {
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            let Some(end) = comment.find("-->") else {
+                bail!("Unterminated comment");
+            };
+            rest = &comment[end + 3..];
+        } else if is_tag_start(rest) {
+            let end = tag_end(rest)?;
+            let tag = &rest[..end];
+            push_tag(&mut minified, tag);
+            rest = &rest[end..];
+            if let Some(name) = raw_element(tag) {
+                let close = format!("</{}", name);
+                let Some(content_end) = rest.to_ascii_lowercase().find(&close) else {
+                    bail!("Unterminated <{}>", name);
+                };
+                minified.push_str(&rest[..content_end]);
+                rest = &rest[content_end..];
+            }
+        } else {
+            let end = text_end(rest);
+            push_collapsed(&mut minified, &rest[..end]);
+            rest = &rest[end..];
+        }
+    }
+    Ok(minified.trim_end().to_string())
+}
+
+/// Whether `html` starts with a tag, rather than a `<` in text.
+fn is_tag_start(html: &str) -> bool {
+    let mut chars = html.chars();
+    chars.next() == Some('<')
+        && chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '/' || c == '!')
+}
+
+/// Where the text at the start of `html` ends: at the next tag or comment.
+fn text_end(html: &str) -> usize {
+    html.char_indices()
+        .skip(1)
+        .find(|(i, _)| is_tag_start(&html[*i..]))
+        .map_or(html.len(), |(i, _)| i)
+}
+
+/// The length of the tag at the start of `html`, up to the `>` outside the attribute values.
+fn tag_end(html: &str) -> Result<usize> {
+    let mut quote = None;
+    for (i, c) in html.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            (None, '>') => return Ok(i + 1),
+            _ => {}
+        }
+    }
+    bail!(
+        "Unterminated tag {}",
+        html.lines().next().unwrap_or_default()
+    )
+}
+
+/// The name of the element `tag` opens, if its content is kept as is.
+fn raw_element(tag: &str) -> Option<&'static str> {
+    let name: String = tag[1..]
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_ascii_lowercase();
+    RAW_ELEMENTS.into_iter().find(|raw| *raw == name)
+}
+
+/// `tag` with the whitespace between its attributes collapsed, and none before the `>`.
+fn push_tag(minified: &mut String, tag: &str) {
+    let mut quote = None;
+    let mut space = false;
+    for c in tag.chars() {
+        match quote {
+            Some(q) => {
+                minified.push(c);
+                if c == q {
+                    quote = None;
+                }
+            }
+            None if c.is_whitespace() => space = true,
+            None => {
+                if space && c != '>' {
+                    minified.push(' ');
+                }
+                space = false;
+                if c == '"' || c == '\'' {
+                    quote = Some(c);
+                }
+                minified.push(c);
+            }
+        }
+    }
+}
+
+/// `text` with every run of whitespace as a single space, and no space after another.
+fn push_collapsed(minified: &mut String, text: &str) {
+    for c in text.chars() {
+        if !c.is_whitespace() {
+            minified.push(c);
+        } else if !minified.ends_with(' ') {
+            minified.push(' ');
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yare::parameterized;
+
+    #[parameterized(
+        whitespace = { "<div>\n    <p>Normal   service</p>\n</div>\n", "<div> <p>Normal service</p> </div>" },
+        comments = { "<p>a<!-- removed\n --> b</p>", "<p>a b</p>" },
+        attributes = { "<div\n    class=\"links\"   data-text=\"Data is  {minutes} old\" >", "<div class=\"links\" data-text=\"Data is  {minutes} old\">" },
+        self_closing = { "<img src=\"eessi.png\" width=\"75px\" />", "<img src=\"eessi.png\" width=\"75px\" />" },
+        quoted_bracket = { "<a title='a > b'>x</a>", "<a title='a > b'>x</a>" },
+        pre = { "<pre>\n  two\n    lines\n</pre>", "<pre>\n  two\n    lines\n</pre>" },
+        script = { "<script type=\"application/json\">{\"a\":  \"<!-- b -->\"}</script>", "<script type=\"application/json\">{\"a\":  \"<!-- b -->\"}</script>" },
+        less_than = { "<p>1 < 2</p>", "<p>1 < 2</p>" },
+        doctype = { "<!DOCTYPE html>\n<html>", "<!DOCTYPE html> <html>" },
+    )]
+    fn test_html(input: &str, expected: &str) {
+        assert_eq!(html(input).unwrap(), expected);
+    }
+
+    #[parameterized(
+        comment = { "<p>a<!-- b</p>", "Unterminated comment" },
+        tag = { "<p class=\"a>b", "Unterminated tag <p class=\"a>b" },
+        pre = { "<pre>a", "Unterminated <pre>" },
+    )]
+    fn test_html_invalid(input: &str, error: &str) {
+        assert_eq!(html(input).unwrap_err().to_string(), error);
+    }
+}
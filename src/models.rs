@@ -1,22 +1,40 @@
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Arc;
 
-use log::{debug, info};
+use chrono::{DateTime, SecondsFormat, Utc};
+use log::{debug, info, warn};
 use rhai::{Engine, Scope};
 use serde::{Deserialize, Serialize};
 use strum::IntoEnumIterator;
-use strum_macros::{AsRefStr, EnumIter};
+use strum_macros::{AsRefStr, EnumIter, EnumString};
 
 use cvmfs_server_scraper::{
-    Hostname, Manifest, PopulatedRepositoryOrReplica, PopulatedServer, ScrapedServer,
-    ServerBackendType, ServerMetadata, ServerType,
+    Hostname, Manifest, MaybeRfc2822DateTime, ScrapedServer, ServerBackendType, ServerMetadata,
+    ServerType,
 };
 
-use crate::config::{Condition, ConfigFile};
-use crate::templating::{RepoStatus, ServerStatus, StatusInfo};
+use crate::announcement::Announcement;
+use crate::cache::LastScrape;
+use crate::config::{
+    normalize_fingerprint, CertificateExpiryConfig, Condition, ConfigFile, Link,
+    MaintenanceAggregation, ServerStatusFromRepos,
+};
+use crate::events::Event;
+use crate::history::{ComponentDays, ComponentUptime};
+use crate::incidents::RenderedIncident;
+use crate::locale::{Locale, ENGLISH};
+use crate::scraping::{DataCheckResult, DataCheckTarget, RepositoryError, TimedScrape};
+use crate::templating::{
+    repo_href, RepoDetail, RepoServerDetail, RepoStatus, RepositoryMatrixCell, RepositoryMatrixRow,
+    ServerRepository, ServerStatus, StatusInfo,
+};
+use crate::tls::certificate_status;
 
 #[allow(clippy::upper_case_acronyms)]
-#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy, Eq, EnumIter, AsRefStr)]
+#[derive(
+    Serialize, Deserialize, PartialEq, Debug, Clone, Copy, Eq, EnumIter, AsRefStr, EnumString,
+)]
 #[strum(ascii_case_insensitive)]
 pub enum Status {
     OK,
@@ -32,6 +50,8 @@ impl PartialOrd for Status {
     }
 }
 
+/// Orders the statuses for display, with MAINTENANCE last. Statuses are aggregated with
+/// `Status::combine`, which also decides how MAINTENANCE and FAILED combine.
 impl Ord for Status {
     fn cmp(&self, other: &Self) -> Ordering {
         use Status::*;
@@ -64,7 +84,19 @@ impl Status {
         Status::iter().collect()
     }
 
-    pub fn class(&self) -> &str {
+    /// The worse of two statuses, where `policy` decides whether MAINTENANCE is worse than
+    /// FAILED.
+    pub fn combine(self, other: Status, policy: MaintenanceAggregation) -> Status {
+        match (self, other, policy) {
+            (Status::MAINTENANCE, Status::FAILED, MaintenanceAggregation::Masked)
+            | (Status::FAILED, Status::MAINTENANCE, MaintenanceAggregation::Masked) => {
+                Status::FAILED
+            }
+            _ => self.max(other),
+        }
+    }
+
+    pub fn class(&self) -> &'static str {
         match self {
             Status::OK => "status-ok fas fa-check",
             Status::DEGRADED => "status-degraded fas fa-minus-square",
@@ -74,72 +106,245 @@ impl Status {
         }
     }
 
-    pub fn text(&self) -> &str {
+    /// The class of the status in `status_classes` of the configuration, or the built-in one.
+    pub fn class_with_overrides<'a>(&self, config: &'a ConfigFile) -> &'a str {
+        config
+            .status_classes
+            .get(self)
+            .map_or(self.class(), String::as_str)
+    }
+
+    /// The emoji for the status in markdown, where the icons of `class` aren't available.
+    pub fn emoji(&self) -> &str {
         match self {
-            Status::OK => "Normal service",
-            Status::DEGRADED => "Degraded",
-            Status::WARNING => "Warning",
-            Status::FAILED => "Failed",
-            Status::MAINTENANCE => "Maintenance",
+            Status::OK => "✅",
+            Status::DEGRADED => "🟡",
+            Status::WARNING => "⚠️",
+            Status::FAILED => "❌",
+            Status::MAINTENANCE => "🔧",
         }
     }
 
-    pub fn description(&self) -> &str {
+    /// The SGR parameters of the color of the status in a terminal, like the colors of the page.
+    pub fn ansi_color(&self) -> &str {
         match self {
-            Status::OK => "EESSI services operating without issues.",
-            Status::DEGRADED => "EESSI services are operational and may be used as expected, but performance may be affected.",
-            Status::WARNING => "EESSI services are operational, but some systems may be unavailable or out of sync.",
-            Status::FAILED => "EESSI services have failed.",
-            Status::MAINTENANCE => "EESSI services are unavailable due to scheduled maintenance.",
+            Status::OK => "32",
+            Status::DEGRADED => "33",
+            Status::WARNING => "38;5;208",
+            Status::FAILED => "31",
+            Status::MAINTENANCE => "34",
         }
     }
 
-    /// Check if the repository is in sync
-    ///
-    /// If we scraped a stratum0 check against its version of the repo with the same name.
-    /// If we did not scrape a stratum0, check against same repo on the other stratum1.
-    ///
-    /// If the revision is the same, return OK.
-    /// If the revision is off by 1, return WARNING.
-    /// If the revision is off by more than 1, return FAILED.
-    pub fn get_repo_revision_status(
-        repo: &PopulatedRepositoryOrReplica,
-        scraped_servers: &[ScrapedServer],
-    ) -> Self {
-        let good_servers: Vec<&PopulatedServer> = scraped_servers
-            .iter()
-            .filter_map(|s| match s {
-                ScrapedServer::Populated(server) => Some(server),
-                ScrapedServer::Failed(_) => None,
-            })
-            .collect();
+    pub fn text(&self) -> &'static str {
+        self.text_localized(&ENGLISH)
+    }
 
-        let stratum0 = good_servers
-            .iter()
-            .find(|s| s.server_type == ServerType::Stratum0);
+    /// The text of the status in the language of `locale`, such as `Normal service`.
+    pub fn text_localized<'a>(&self, locale: &'a Locale) -> &'a str {
+        self.localized(locale, "text")
+    }
 
-        if let Some(stratum0) = stratum0 {
-            compare_with_stratum0(repo, stratum0)
-        } else {
-            compare_with_other_stratum1s(repo, &good_servers)
+    pub fn description_localized<'a>(&self, locale: &'a Locale) -> &'a str {
+        self.localized(locale, "description")
+    }
+
+    /// The English locale has every status, which every locale falls back to.
+    fn localized<'a>(&self, locale: &'a Locale, field: &str) -> &'a str {
+        let key = format!("status.{}.{}", self.as_ref().to_lowercase(), field);
+        locale.get(&key).unwrap_or_default()
+    }
+}
+
+/// The revision of each repository that servers are compared against: the stratum0's revision
+/// if the stratum0 has the repository, otherwise the newest revision on any stratum1.
+///
+/// Takes the server type, repository name and revision of every scraped repository.
+pub fn reference_revisions<'a>(
+    revisions: impl IntoIterator<Item = (ServerType, &'a str, i32)>,
+) -> BTreeMap<String, i32> {
+    let mut stratum0: BTreeMap<String, i32> = BTreeMap::new();
+    let mut stratum1: BTreeMap<String, i32> = BTreeMap::new();
+    for (server_type, repository, revision) in revisions {
+        let newest = match server_type {
+            ServerType::Stratum0 => stratum0.entry(repository.to_string()),
+            ServerType::Stratum1 => stratum1.entry(repository.to_string()),
+            ServerType::SyncServer => continue,
         }
+        .or_insert(revision);
+        *newest = (*newest).max(revision);
     }
+
+    stratum1.extend(stratum0);
+    stratum1
 }
 
-#[derive(Serialize)]
+/// The status of a repository lagging `lag` revisions behind the reference revision.
+pub fn revision_status(lag: i32) -> Status {
+    match lag.abs() {
+        0 => Status::OK,
+        1 => Status::WARNING,
+        _ => Status::FAILED,
+    }
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct StatusPageData {
+    /// The shape of this document, see [`SCHEMA_VERSION`]. Documents from before it was
+    /// introduced have version 0.
+    #[serde(default)]
+    pub schema_version: u32,
+    #[serde(default)]
+    pub generator: Generator,
     pub title: String,
     pub eessi_status: EESSIStatus,
+    /// How many servers are failing while the overall status is MAINTENANCE, which would
+    /// otherwise hide them. Only counted with `MaintenanceAggregation::Alongside`.
+    #[serde(default)]
+    pub maintenance_failing_servers: Option<usize>,
     pub contact_email: String,
     pub last_update: String,
+    /// The same time as `last_update`, in seconds since the epoch.
+    #[serde(default)]
+    pub generated_at: i64,
     pub legend: Vec<StatusInfo>,
     pub stratum0: StratumStatus,
     pub stratum1: StratumStatus,
     pub syncservers: StratumStatus,
     pub repositories_status: RepoStatus,
     pub repositories: Vec<RepoStatus>,
-    pub config: ConfigFile,
+    /// The revision of each repository on each server, see `StatusManager::repository_matrix`.
+    #[serde(default)]
+    pub repository_matrix: Vec<RepositoryMatrixRow>,
+    /// The configuration, without the hidden servers. Left out if it isn't exposed, in which
+    /// case the defaults stand in for it.
+    #[serde(
+        default,
+        serialize_with = "crate::privacy::serialize_config",
+        skip_serializing_if = "crate::privacy::is_config_hidden"
+    )]
+    pub config: Arc<ConfigFile>,
     pub servers: Vec<ServerStatus>,
+    #[serde(default)]
+    pub run_duration_seconds: f64,
+    /// Rendered from a previously saved status.json rather than a fresh scrape.
+    #[serde(default)]
+    pub rendered_from_cache: bool,
+    /// The run was restricted by command line filters, so rule outcomes only cover a subset.
+    #[serde(default)]
+    pub partial_run: bool,
+    /// The restrictions of a partial run, for the banner on the page.
+    #[serde(default)]
+    pub scope: Vec<String>,
+    /// The most recent status changes, newest first.
+    #[serde(default)]
+    pub recent_events: Vec<Event>,
+    /// The uptime of each component over the configured windows.
+    #[serde(default)]
+    pub uptime: Vec<ComponentUptime>,
+    /// The worst status of each component per day, oldest first.
+    #[serde(default)]
+    pub daily: Vec<ComponentDays>,
+    /// The incidents from the configuration, newest first.
+    #[serde(default)]
+    pub incidents: Vec<RenderedIncident>,
+    /// The banner at the top of the page, read when the page is rendered.
+    #[serde(default)]
+    pub announcement: Option<Announcement>,
+    /// The site specific values from the configuration, for the templates.
+    #[serde(default)]
+    pub extra: BTreeMap<String, serde_json::Value>,
+    /// The links of the navigation bar.
+    #[serde(default)]
+    pub links: Vec<Link>,
+    /// The locale of the texts, such as those of the statuses.
+    #[serde(default = "crate::locale::default_locale")]
+    pub locale: String,
+    /// How often the page reloads, and how old its data may be before it is marked as stale.
+    #[serde(default)]
+    pub refresh_seconds: Option<u64>,
+    #[serde(default)]
+    pub stale_after_seconds: Option<u64>,
+    /// What the link previews of chat applications show: where the page is published, a line
+    /// about it and the absolute URL of an image.
+    #[serde(default)]
+    pub base_url: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub og_image: Option<String>,
+}
+
+/// The version of the shape of status.json. Bump it when fields are removed or renamed, and
+/// check in a copy of a document of the old version under `src/testdata` to test that it still
+/// loads. Added fields don't need a new version.
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// The program that wrote a status.json.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Generator {
+    pub name: String,
+    pub version: String,
+}
+
+impl Generator {
+    pub fn current() -> Self {
+        Generator {
+            name: env!("CARGO_PKG_NAME").to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+}
+
+/// Set the class of every status in `data`, built with the built-in classes, to the one in
+/// `status_classes` of `config`. The classes are baked into the data as strings, so they are
+/// all set here, to keep them consistent.
+pub fn apply_status_classes(data: &mut StatusPageData, config: &ConfigFile) {
+    let class = |status: Status| status.class_with_overrides(config).to_string();
+    data.eessi_status.class = class(data.eessi_status.status);
+    for info in &mut data.legend {
+        info.class = class(info.status);
+    }
+    for stratum in [
+        &mut data.stratum0,
+        &mut data.stratum1,
+        &mut data.syncservers,
+    ] {
+        stratum.status_class = class(stratum.status);
+        for server in &mut stratum.servers {
+            apply_server_status_classes(server, config);
+        }
+    }
+    for server in &mut data.servers {
+        apply_server_status_classes(server, config);
+    }
+    for repo in std::iter::once(&mut data.repositories_status).chain(&mut data.repositories) {
+        repo.revision_class = class(repo.status);
+        repo.snapshot_class = class(Status::OK);
+    }
+    for cell in data
+        .repository_matrix
+        .iter_mut()
+        .flat_map(|row| &mut row.cells)
+    {
+        cell.status_class = cell.status.map(class);
+    }
+}
+
+fn apply_server_status_classes(server: &mut ServerStatus, config: &ConfigFile) {
+    server.update_class = server.status.class_with_overrides(config).to_string();
+    server.geoapi_class = server.geodb_status.class_with_overrides(config).to_string();
+    for member in &mut server.members {
+        apply_server_status_classes(member, config);
+    }
+}
+
+/// Like `apply_status_classes`, for the detail page of a repository.
+pub fn apply_repo_detail_status_classes(detail: &mut RepoDetail, config: &ConfigFile) {
+    detail.status_class = detail.status.class_with_overrides(config).to_string();
+    for server in &mut detail.servers {
+        server.status_class = server.status.class_with_overrides(config).to_string();
+    }
 }
 
 pub trait HasStatusField {
@@ -160,7 +365,15 @@ pub trait StatusLevel: HasStatusField {
     }
 }
 
-#[derive(Serialize)]
+impl HasStatusField for Status {
+    fn status(&self) -> Status {
+        *self
+    }
+}
+
+impl StatusLevel for Status {}
+
+#[derive(Serialize, Deserialize)]
 pub struct EESSIStatus {
     pub status: Status,
     pub class: String,
@@ -168,7 +381,7 @@ pub struct EESSIStatus {
     pub description: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 pub struct StratumStatus {
     pub status: Status,
     pub status_class: String,
@@ -194,9 +407,16 @@ impl HasStatusField for RepoStatus {
     }
 }
 
+impl HasStatusField for Server {
+    fn status(&self) -> Status {
+        self.status
+    }
+}
+
 impl StatusLevel for StratumStatus {}
 impl StatusLevel for EESSIStatus {}
 impl StatusLevel for RepoStatus {}
+impl StatusLevel for Server {}
 
 // Ensure that Legend, RepoStatus, and ServerStatus are also derived from Serialize
 
@@ -204,13 +424,74 @@ impl StatusLevel for RepoStatus {}
 pub struct Repositories {
     pub name: String,
     pub revision: i32,
-    pub manifest: Manifest,
+    /// The manifest, missing if the repository failed to scrape.
+    pub manifest: Option<Manifest>,
     pub status: Status,
     /// Is the revision in sync with either the stratum0 or the stratum1s?
     pub status_revision: Status,
+    /// Outcome of the deep check of the data path, if one was performed.
+    pub data_check: Option<bool>,
+    /// The highest revision seen on the server before, if the revision went backwards.
+    #[serde(default)]
+    pub regressed_from: Option<i32>,
+    /// Whether the manifest is signed with a certificate other than the pinned ones, if the
+    /// repository is pinned.
+    #[serde(default)]
+    pub cert_fingerprint_mismatch: Option<bool>,
+    /// Why the repository failed to scrape, if it did.
+    pub error: Option<String>,
+    /// Human readable details explaining a non-OK status.
+    pub details: Vec<String>,
 }
 
-#[derive(Debug, Serialize, Clone)]
+impl Repositories {
+    /// A repository that failed to scrape on an otherwise reachable server.
+    pub fn failed(failure: &RepositoryError) -> Self {
+        Repositories {
+            name: failure.repository.clone(),
+            revision: 0,
+            manifest: None,
+            status: Status::FAILED,
+            status_revision: Status::FAILED,
+            data_check: None,
+            regressed_from: None,
+            cert_fingerprint_mismatch: None,
+            error: Some(failure.error.clone()),
+            details: vec![format!("scrape failed: {}", failure.error)],
+        }
+    }
+}
+
+/// The start of the detail of a repository whose revision went backwards.
+pub const REVISION_REGRESSION: &str = "revision went backwards";
+
+/// The start of the detail of a repository signed with a certificate that isn't pinned.
+pub const CERT_FINGERPRINT_CHANGED: &str = "certificate fingerprint changed";
+
+/// The highest revision of each repository seen on each server, by hostname and repository.
+pub type HighestRevisions = BTreeMap<String, BTreeMap<String, i32>>;
+
+/// Get the status of a server from the status of its repositories.
+///
+/// Under `DegradedIfPartial`, repositories that failed to scrape count as degraded as long as
+/// at least one repository on the server was scraped.
+pub fn server_status_from_repositories(
+    repositories: &[Repositories],
+    policy: ServerStatusFromRepos,
+    maintenance: MaintenanceAggregation,
+) -> Status {
+    let any_scraped = repositories.iter().any(|repo| repo.error.is_none());
+
+    repositories
+        .iter()
+        .map(|repo| match (policy, &repo.error) {
+            (ServerStatusFromRepos::DegradedIfPartial, Some(_)) if any_scraped => Status::DEGRADED,
+            _ => repo.status,
+        })
+        .fold(Status::OK, |status, repo| status.combine(repo, maintenance))
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Server {
     pub server_type: ServerType,
     pub backend_type: ServerBackendType,
@@ -218,17 +499,144 @@ pub struct Server {
     pub hostname: Hostname,
     pub repositories: Vec<Repositories>,
     pub status: Status,
+    #[serde(deserialize_with = "deserialize_metadata")]
     pub metadata: Option<ServerMetadata>,
+    /// Is the geo database on the server recent enough? Only shown in the GeoAPI column: it
+    /// doesn't count towards `status`, which the rules and the overall status are built from.
+    pub geodb_status: Status,
+    /// Expiry of the TLS certificate, for servers served over HTTPS.
+    pub cert_expiry: Option<DateTime<Utc>>,
+    pub cert_expiry_days: Option<i64>,
+    /// Time spent scraping the server, including failed scrapes.
+    pub scrape_duration_ms: Option<u64>,
+    /// The address contacted when scraping the server.
+    pub address: Option<String>,
+    /// For load balanced services, the individually scraped members.
+    pub members: Vec<Server>,
+    /// Set when the server failed to scrape and its last known data is shown instead.
+    #[serde(default)]
+    pub data_age_seconds: Option<i64>,
+    /// Set when the first scrape failed and the repositories were scraped again one by one.
+    #[serde(default)]
+    pub scrape_retried: bool,
 }
 
 impl Server {
+    /// Whether the server was scraped in this run, rather than failing or being shown from
+    /// stale data.
+    pub fn scraped_successfully(&self) -> bool {
+        self.backend_detected.is_some() && self.data_age_seconds.is_none()
+    }
+
     pub fn to_server_status(&self) -> ServerStatus {
+        let metadata = self.metadata.as_ref();
+
         ServerStatus {
             name: self.hostname.clone().to_string(),
             status: self.status,
             metadata: self.metadata.clone(),
+            cvmfs_version: metadata.and_then(|m| m.cvmfs_version.as_ref().map(|v| v.to_string())),
+            os: metadata.and_then(os_description),
+            last_geodb_update: metadata.and_then(|m| m.last_geodb_update.0.clone()),
+            geodb_status: self.geodb_status,
+            cert_expiry: self
+                .cert_expiry
+                .map(|t| t.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)),
+            cert_expiry_days: self.cert_expiry_days,
+            scrape_duration_ms: self.scrape_duration_ms,
+            address: self.address.clone(),
+            repositories: self
+                .repositories
+                .iter()
+                .map(|repo| ServerRepository {
+                    name: repo.name.clone(),
+                    revision: repo.manifest.as_ref().map(|_| repo.revision),
+                    status: repo.status,
+                    regressed_from: repo.regressed_from,
+                })
+                .collect(),
+            members: self.members.iter().map(Server::to_server_status).collect(),
+            data_age_seconds: self.data_age_seconds,
+            stale_note: self
+                .data_age_seconds
+                .map(|age| format!("data is {} minutes old", age / 60)),
             update_class: self.status.class().to_string(),
-            geoapi_class: Status::OK.class().to_string(),
+            geoapi_class: self.geodb_status.class().to_string(),
+        }
+    }
+}
+
+/// The serialized form of `ServerMetadata`, which the scraper only knows how to serialize.
+#[derive(Deserialize)]
+struct ServerMetadataRecord {
+    schema_version: Option<u32>,
+    cvmfs_version: Option<String>,
+    #[serde(default)]
+    last_geodb_update: MaybeRfc2822DateTime,
+    os_version_id: Option<String>,
+    os_pretty_name: Option<String>,
+    os_id: Option<String>,
+    administrator: Option<String>,
+    email: Option<String>,
+    organisation: Option<String>,
+    custom: Option<serde_json::Value>,
+}
+
+/// Deserialize server metadata as written by its `Serialize` implementation.
+///
+/// Versions that don't parse as semver are dropped rather than failing the whole document.
+pub fn deserialize_metadata<'de, D>(deserializer: D) -> Result<Option<ServerMetadata>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let record: Option<ServerMetadataRecord> = Option::deserialize(deserializer)?;
+    Ok(record.map(|r| ServerMetadata {
+        schema_version: r.schema_version,
+        cvmfs_version: r
+            .cvmfs_version
+            .and_then(|v| semver::Version::parse(&v).ok()),
+        last_geodb_update: r.last_geodb_update,
+        os_version_id: r.os_version_id,
+        os_pretty_name: r.os_pretty_name,
+        os_id: r.os_id,
+        administrator: r.administrator,
+        email: r.email,
+        organisation: r.organisation,
+        custom: r.custom,
+    }))
+}
+
+/// A human readable description of the operating system of a server, if it was reported.
+///
+/// Prefers the pretty name, and falls back to combining the OS id and version id.
+fn os_description(metadata: &ServerMetadata) -> Option<String> {
+    if let Some(pretty_name) = &metadata.os_pretty_name {
+        return Some(pretty_name.clone());
+    }
+
+    match (&metadata.os_id, &metadata.os_version_id) {
+        (Some(id), Some(version)) => Some(format!("{} {}", id, version)),
+        (Some(id), None) => Some(id.clone()),
+        _ => None,
+    }
+}
+
+/// Get the status of the geo database based on its last update.
+///
+/// If the last update is older than `max_age_days`, return WARNING. Servers that do not report
+/// a last update (S3 servers, or servers that opt out of publishing it) are considered OK, as
+/// are servers reporting a timestamp we fail to parse.
+pub fn geodb_status(
+    last_update: &MaybeRfc2822DateTime,
+    max_age_days: i64,
+    now: DateTime<Utc>,
+) -> Status {
+    match last_update.try_into_datetime() {
+        Ok(Some(updated)) if (now - updated).num_days() > max_age_days => Status::WARNING,
+        Ok(_) => Status::OK,
+        Err(e) => {
+            warn!("Unable to parse last geodb update: {}", e);
+            Status::OK
         }
     }
 }
@@ -249,59 +657,383 @@ impl ToEESSILabel for ServerType {
 
 pub struct StatusManager {
     pub servers: Vec<Server>,
+    /// How MAINTENANCE and FAILED combine when the statuses of servers and repositories are
+    /// aggregated.
+    pub maintenance_aggregation: MaintenanceAggregation,
+}
+
+/// The rules that are looked up by their id when generating the status.
+pub const RULE_IDS: [&str; 4] = [
+    "stratum0_servers",
+    "stratum1_servers",
+    "sync_servers",
+    "eessi_status",
+];
+
+/// A condition of a rule that failed to evaluate, such as one using an unknown variable. It is
+/// taken as false.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RuleError {
+    pub rule: String,
+    pub condition: String,
+    pub error: String,
 }
 
 impl StatusManager {
-    pub fn new(scraped_servers: Vec<ScrapedServer>) -> Self {
-        let servers: Vec<Server> = scraped_servers
+    pub fn new(scrapes: Vec<TimedScrape>, config: &ConfigFile) -> Self {
+        let now = Utc::now();
+        let servers: Vec<Server> = scrapes
             .iter()
-            .map(|server| match server {
-                ScrapedServer::Populated(server) => {
-                    let repositories: Vec<Repositories> = server
-                        .repositories
-                        .iter()
-                        .map(|repo| {
-                            let status_revision =
-                                Status::get_repo_revision_status(repo, &scraped_servers);
-                            Repositories {
+            .map(|scrape| {
+                let mut server = match &scrape.server {
+                    ScrapedServer::Populated(server) => {
+                        let mut repositories: Vec<Repositories> = server
+                            .repositories
+                            .iter()
+                            .map(|repo| Repositories {
                                 name: repo.name.clone(),
                                 revision: repo.revision(),
-                                manifest: repo.manifest.clone(),
-                                status: status_revision,
-                                status_revision,
-                            }
-                        })
-                        .collect();
-
-                    let overall_status = repositories
-                        .iter()
-                        .map(|repo| repo.status)
-                        .max()
-                        .unwrap_or(Status::OK);
-
-                    Server {
+                                manifest: Some(repo.manifest.clone()),
+                                // Compared with the other servers in `from_servers`.
+                                status: Status::OK,
+                                status_revision: Status::OK,
+                                data_check: None,
+                                regressed_from: None,
+                                cert_fingerprint_mismatch: None,
+                                error: None,
+                                details: Vec::new(),
+                            })
+                            .collect();
+                        repositories
+                            .extend(scrape.repository_errors.iter().map(Repositories::failed));
+
+                        Server {
+                            server_type: server.server_type,
+                            backend_type: server.backend_type,
+                            backend_detected: Some(server.backend_detected),
+                            hostname: server.hostname.clone(),
+                            repositories,
+                            status: Status::OK,
+                            metadata: Some(server.metadata.clone()),
+                            geodb_status: geodb_status(
+                                &server.metadata.last_geodb_update,
+                                config.geodb_max_age_days,
+                                now,
+                            ),
+                            cert_expiry: None,
+                            cert_expiry_days: None,
+                            scrape_duration_ms: None,
+                            address: None,
+                            members: Vec::new(),
+                            data_age_seconds: None,
+                            scrape_retried: false,
+                        }
+                    }
+                    ScrapedServer::Failed(server) => Server {
                         server_type: server.server_type,
                         backend_type: server.backend_type,
-                        backend_detected: Some(server.backend_detected),
+                        backend_detected: None,
                         hostname: server.hostname.clone(),
-                        repositories,
-                        status: overall_status,
-                        metadata: Some(server.metadata.clone()),
-                    }
-                }
-                ScrapedServer::Failed(server) => Server {
-                    server_type: server.server_type,
-                    backend_type: server.backend_type,
-                    backend_detected: None,
-                    hostname: server.hostname.clone(),
-                    repositories: Vec::new(),
-                    status: Status::FAILED,
-                    metadata: None,
-                },
+                        repositories: Vec::new(),
+                        status: Status::FAILED,
+                        metadata: None,
+                        geodb_status: Status::OK,
+                        cert_expiry: None,
+                        cert_expiry_days: None,
+                        scrape_duration_ms: None,
+                        address: None,
+                        members: Vec::new(),
+                        data_age_seconds: None,
+                        scrape_retried: false,
+                    },
+                };
+                server.scrape_duration_ms = Some(scrape.duration.as_millis() as u64);
+                server.scrape_retried = scrape.retried;
+                server.address = Some(scrape.address.to_string());
+                server
             })
             .collect();
 
-        StatusManager { servers }
+        Self::from_servers(servers, config)
+    }
+
+    /// The status of `servers` as they were scraped, which is how tests build one without
+    /// scraping (see `testing::ServerFixture`).
+    ///
+    /// The repositories of the servers that were scraped get the status of their revision
+    /// compared to the reference revision, at least `missing_reference_status` if there is no
+    /// revision to compare with, and those servers the status of their repositories. Servers
+    /// that failed to scrape keep their status. The members of load balanced services
+    /// are then grouped into one server each.
+    pub fn from_servers(mut servers: Vec<Server>, config: &ConfigFile) -> Self {
+        let references = reference_revisions(
+            servers
+                .iter()
+                .filter(|server| server.backend_detected.is_some())
+                .flat_map(|server| {
+                    server
+                        .repositories
+                        .iter()
+                        .filter(|r| r.manifest.is_some())
+                        .map(|r| (server.server_type, r.name.as_str(), r.revision))
+                }),
+        );
+        let stratum0s: Vec<String> = servers
+            .iter()
+            .filter(|s| s.server_type == ServerType::Stratum0 && s.backend_detected.is_some())
+            .map(|s| s.hostname.to_string())
+            .collect();
+        let carried: Vec<(ServerType, String, String)> = servers
+            .iter()
+            .filter(|server| server.backend_detected.is_some())
+            .flat_map(|server| {
+                server
+                    .repositories
+                    .iter()
+                    .filter(|r| r.manifest.is_some())
+                    .map(|r| {
+                        (
+                            server.server_type,
+                            server.hostname.to_string(),
+                            r.name.clone(),
+                        )
+                    })
+            })
+            .collect();
+
+        for server in servers
+            .iter_mut()
+            .filter(|server| server.backend_detected.is_some())
+        {
+            let (server_type, hostname) = (server.server_type, server.hostname.to_string());
+            for repo in server.repositories.iter_mut().filter(|r| r.error.is_none()) {
+                let lag = references
+                    .get(&repo.name)
+                    .map_or(0, |&reference| reference - repo.revision);
+                repo.status_revision = revision_status(lag);
+                if let Some(detail) =
+                    missing_reference(server_type, &hostname, &repo.name, &stratum0s, &carried)
+                {
+                    repo.status_revision = repo.status_revision.combine(
+                        config.missing_reference_status,
+                        config.maintenance_aggregation,
+                    );
+                    repo.details.push(detail);
+                }
+                repo.status = repo.status_revision;
+            }
+            server.status = server_status_from_repositories(
+                &server.repositories,
+                config.server_status_from_repos,
+                config.maintenance_aggregation,
+            );
+        }
+
+        StatusManager {
+            servers: group_members(servers, config),
+            maintenance_aggregation: config.maintenance_aggregation,
+        }
+    }
+
+    /// Show the last known data of servers that failed to scrape, if that data is at most
+    /// `max_age_minutes` old. Such servers are DEGRADED instead of FAILED.
+    pub fn apply_stale_data(
+        &mut self,
+        last_scrape: &LastScrape,
+        max_age_minutes: i64,
+        now: DateTime<Utc>,
+    ) {
+        for server in self
+            .servers
+            .iter_mut()
+            .filter(|s| s.backend_detected.is_none())
+        {
+            let Some(cached) = last_scrape.get(server) else {
+                continue;
+            };
+
+            let age = now.signed_duration_since(cached.scraped_at).num_seconds();
+            if age > max_age_minutes * 60 {
+                debug!(
+                    "Last known data for {} is too old to use ({}s)",
+                    server.hostname, age
+                );
+                continue;
+            }
+
+            warn!(
+                "Showing {} minutes old data for {}",
+                age / 60,
+                server.hostname
+            );
+            *server = Server {
+                status: Status::DEGRADED,
+                scrape_duration_ms: server.scrape_duration_ms,
+                address: server.address.clone(),
+                data_age_seconds: Some(age),
+                ..cached.server.clone()
+            };
+        }
+    }
+
+    /// Record the TLS certificate expiry for a server, degrading its status if the certificate
+    /// expires within the configured thresholds.
+    pub fn apply_certificate_expiry(
+        &mut self,
+        hostname: &Hostname,
+        expiry: DateTime<Utc>,
+        thresholds: &CertificateExpiryConfig,
+        now: DateTime<Utc>,
+    ) {
+        let maintenance = self.maintenance_aggregation;
+        if let Some(server) = self.servers.iter_mut().find(|s| &s.hostname == hostname) {
+            let days_left = (expiry - now).num_days();
+            let cert_status = certificate_status(days_left, thresholds);
+            if cert_status != Status::OK {
+                warn!(
+                    "TLS certificate for {} expires in {} days ({})",
+                    hostname, days_left, expiry
+                );
+            }
+            server.cert_expiry = Some(expiry);
+            server.cert_expiry_days = Some(days_left);
+            server.status = server.status.combine(cert_status, maintenance);
+        }
+    }
+
+    /// Repositories on populated servers that should have their data path deep checked.
+    pub fn data_check_targets(&self, config: &ConfigFile) -> Vec<DataCheckTarget> {
+        self.servers
+            .iter()
+            .filter(|server| server.data_age_seconds.is_none())
+            .flat_map(|server| {
+                server
+                    .repositories
+                    .iter()
+                    .filter(|repo| config.deep_check_enabled_for(&repo.name))
+                    .filter_map(|repo| {
+                        repo.manifest.as_ref().map(|manifest| DataCheckTarget {
+                            hostname: server.hostname.clone(),
+                            resolve_to: config
+                                .servers
+                                .iter()
+                                .find(|s| s.server.hostname == server.hostname)
+                                .and_then(|s| s.resolve_to_address().ok().flatten()),
+                            repository: repo.name.clone(),
+                            catalog_hash: manifest.c.to_string(),
+                        })
+                    })
+            })
+            .collect()
+    }
+
+    /// Fail the repositories on servers scraped in this run whose revision is lower than the
+    /// highest revision seen on that server before, such as after a restore from an old
+    /// backup. The repositories in `revision_regression_exempt` may go backwards. The status of
+    /// a server with such a repository is taken from its repositories again.
+    pub fn apply_revision_regressions(&mut self, highest: &HighestRevisions, config: &ConfigFile) {
+        let exempt = &config.revision_regression_exempt;
+        for server in self.servers.iter_mut().filter(|s| s.scraped_successfully()) {
+            let Some(highest) = highest.get(&server.hostname.to_string()) else {
+                continue;
+            };
+            for repo in server
+                .repositories
+                .iter_mut()
+                .filter(|r| r.manifest.is_some() && !exempt.contains(&r.name))
+            {
+                let Some(&was) = highest.get(&repo.name).filter(|&&was| was > repo.revision) else {
+                    continue;
+                };
+                warn!(
+                    "The revision of {} on {} went backwards from {} to {}",
+                    repo.name, server.hostname, was, repo.revision
+                );
+                repo.regressed_from = Some(was);
+                repo.status = Status::FAILED;
+                repo.status_revision = Status::FAILED;
+                repo.details.push(format!(
+                    "{} (was {}, now {})",
+                    REVISION_REGRESSION, was, repo.revision
+                ));
+            }
+            if server
+                .repositories
+                .iter()
+                .any(|r| r.regressed_from.is_some())
+            {
+                // Combined, as the status of a load balanced service comes from its members.
+                let from_repositories = server_status_from_repositories(
+                    &server.repositories,
+                    config.server_status_from_repos,
+                    config.maintenance_aggregation,
+                );
+                server.status = server
+                    .status
+                    .combine(from_repositories, config.maintenance_aggregation);
+            }
+        }
+    }
+
+    /// Fail the repositories whose manifest is signed with a certificate other than the ones
+    /// pinned in `repository_settings`, which may mean the repository was tampered with.
+    /// Repositories that aren't pinned are left alone.
+    pub fn apply_cert_fingerprints(&mut self, config: &ConfigFile) {
+        let maintenance = self.maintenance_aggregation;
+        for server in self.servers.iter_mut() {
+            for repo in server.repositories.iter_mut() {
+                let accepted = config.accepted_cert_fingerprints(&repo.name);
+                let Some(manifest) = repo.manifest.as_ref().filter(|_| !accepted.is_empty()) else {
+                    continue;
+                };
+                let fingerprint = manifest.x.to_string();
+                let mismatch = !accepted
+                    .iter()
+                    .any(|f| normalize_fingerprint(f) == normalize_fingerprint(&fingerprint));
+                repo.cert_fingerprint_mismatch = Some(mismatch);
+                if mismatch {
+                    warn!(
+                        "The certificate of {} on {} has the unexpected fingerprint {}",
+                        repo.name, server.hostname, fingerprint
+                    );
+                    repo.status = Status::FAILED;
+                    repo.details.push(format!(
+                        "{}: {} is not one of {}",
+                        CERT_FINGERPRINT_CHANGED,
+                        fingerprint,
+                        accepted.join(", ")
+                    ));
+                    server.status = server.status.combine(Status::FAILED, maintenance);
+                }
+            }
+        }
+    }
+
+    /// Record the outcome of a deep check, failing the repository (and thus the server) if
+    /// the data path was unreachable.
+    pub fn apply_data_check(&mut self, check: &DataCheckResult) {
+        let maintenance = self.maintenance_aggregation;
+        let Some(server) = self
+            .servers
+            .iter_mut()
+            .find(|s| s.hostname == check.hostname)
+        else {
+            return;
+        };
+        let Some(repo) = server
+            .repositories
+            .iter_mut()
+            .find(|r| r.name == check.repository)
+        else {
+            return;
+        };
+
+        repo.data_check = Some(check.result.is_ok());
+        if let Err(e) = &check.result {
+            repo.status = Status::FAILED;
+            repo.details.push(format!("data path unreachable: {}", e));
+            server.status = server.status.combine(Status::FAILED, maintenance);
+        }
     }
 
     pub fn get_server_status_for_all(&self) -> Vec<ServerStatus> {
@@ -312,6 +1044,30 @@ impl StatusManager {
         self.servers.iter().collect()
     }
 
+    /// The reference revision of each repository, from the servers scraped in this run. Load
+    /// balanced services contribute each of their members, as when the status was evaluated.
+    pub fn reference_revisions(&self) -> BTreeMap<String, i32> {
+        let scraped = self
+            .servers
+            .iter()
+            .flat_map(|server| {
+                if server.members.is_empty() {
+                    std::slice::from_ref(server)
+                } else {
+                    server.members.as_slice()
+                }
+            })
+            .filter(|server| server.scraped_successfully());
+
+        reference_revisions(scraped.flat_map(|server| {
+            server
+                .repositories
+                .iter()
+                .filter(|r| r.manifest.is_some())
+                .map(|r| (server.server_type, r.name.as_str(), r.revision))
+        }))
+    }
+
     pub fn get_by_type(&self, server_type: ServerType) -> Vec<&Server> {
         self.servers
             .iter()
@@ -384,32 +1140,48 @@ impl StatusManager {
         self.get_by_status(Status::MAINTENANCE)
     }
 
+    /// The servers that are failing while the overall `status` is MAINTENANCE, including the
+    /// servers in maintenance with a failing repository, if maintenance is reported alongside
+    /// failures. `None` if there are none to report.
+    pub fn maintenance_failing_servers(&self, status: Status) -> Option<usize> {
+        if status != Status::MAINTENANCE
+            || self.maintenance_aggregation != MaintenanceAggregation::Alongside
+        {
+            return None;
+        }
+        let failing = self
+            .servers
+            .iter()
+            .filter(|server| {
+                server.status == Status::FAILED
+                    || (server.status == Status::MAINTENANCE
+                        && server
+                            .repositories
+                            .iter()
+                            .any(|r| r.status == Status::FAILED))
+            })
+            .count();
+        (failing > 0).then_some(failing)
+    }
+
     pub fn status_overall(&self, conditions: Vec<Condition>) -> Status {
         debug!("Conditions for overall status: {:?}", conditions.len());
-        let status = self.evaluate_overall_conditions(conditions);
-        info!("Overall status: {:?}", status);
+        let status = self.evaluate_rule("eessi_status", conditions);
+        info!(rule = "eessi_status", status = status.as_ref(); "Overall status: {:?}", status);
         status
     }
 
     pub fn status_stratum1(&self, conditions: Vec<Condition>) -> Status {
         debug!("Conditions for stratum1s: {:?}", conditions.len());
-        let status = evaluate_conditions_with_key_value(
-            conditions,
-            "stratum1_servers",
-            self.get_by_type_ok(ServerType::Stratum1).len(),
-        );
-        info!("Stratum1 status: {:?}", status);
+        let status = self.evaluate_rule("stratum1_servers", conditions);
+        info!(rule = "stratum1_servers", status = status.as_ref(); "Stratum1 status: {:?}", status);
         status
     }
 
     pub fn status_stratum0(&self, conditions: Vec<Condition>) -> Status {
         debug!("Conditions for stratum0s: {:?}", conditions.len());
-        let status = evaluate_conditions_with_key_value(
-            conditions,
-            "stratum0_servers",
-            self.get_by_type_ok(ServerType::Stratum0).len(),
-        );
-        info!("Stratum0 status: {:?}", status);
+        let status = self.evaluate_rule("stratum0_servers", conditions);
+        info!(rule = "stratum0_servers", status = status.as_ref(); "Stratum0 status: {:?}", status);
         status
     }
 
@@ -426,6 +1198,7 @@ impl StatusManager {
                 stratum0
                     .repositories
                     .iter()
+                    .filter(|repo| repo.error.is_none())
                     .map(|repo| format!("{}:{}", repo.name, repo.revision))
             })
             .collect()
@@ -433,12 +1206,8 @@ impl StatusManager {
 
     pub fn status_syncserver(&self, conditions: Vec<Condition>) -> Status {
         debug!("Conditions for syncservers: {:?}", conditions.len());
-        let status = evaluate_conditions_with_key_value(
-            conditions,
-            "sync_servers",
-            self.get_by_type_ok(ServerType::SyncServer).len(),
-        );
-        info!("Syncserver status: {:?}", status);
+        let status = self.evaluate_rule("sync_servers", conditions);
+        info!(rule = "sync_servers", status = status.as_ref(); "Syncserver status: {:?}", status);
         status
     }
 
@@ -447,9 +1216,23 @@ impl StatusManager {
     /// We return the worst status of all repositories.
     pub fn details_repositories(&self) -> Vec<RepoStatus> {
         let mut repos: Vec<RepoStatus> = Vec::new();
+        let mut details = self.get_details_per_unique_repo();
 
         for (name, status) in self.get_status_per_unique_repo() {
+            let newest = self
+                .servers
+                .iter()
+                .flat_map(|server| &server.repositories)
+                .filter(|repo| repo.name == name)
+                .filter_map(|repo| repo.manifest.as_ref().map(|m| (repo.revision, m.t)))
+                .max();
             repos.push(RepoStatus {
+                details: details.remove(&name).unwrap_or_default(),
+                href: repo_href(&name),
+                revision: newest.map(|(revision, _)| revision),
+                published_at: newest
+                    .and_then(|(_, t)| DateTime::from_timestamp(t, 0))
+                    .map(|t| t.to_rfc3339_opts(SecondsFormat::Secs, true)),
                 name,
                 status,
                 revision_class: status.class().to_string(),
@@ -460,23 +1243,151 @@ impl StatusManager {
         repos
     }
 
+    /// The revision of every repository on every server, with the repositories as rows, by
+    /// name, and the servers as columns: the stratum0s, then the stratum1s and the sync
+    /// servers, each by hostname.
+    pub fn repository_matrix(&self) -> Vec<RepositoryMatrixRow> {
+        let references = self.reference_revisions();
+        let mut servers: Vec<&Server> = self.servers.iter().collect();
+        servers.sort_by_key(|server| {
+            let order = match server.server_type {
+                ServerType::Stratum0 => 0,
+                ServerType::Stratum1 => 1,
+                ServerType::SyncServer => 2,
+            };
+            (order, server.hostname.to_str())
+        });
+        let names: BTreeSet<&str> = servers
+            .iter()
+            .flat_map(|server| server.repositories.iter().map(|r| r.name.as_str()))
+            .collect();
+
+        names
+            .into_iter()
+            .map(|name| {
+                let reference = references.get(name).copied();
+                let cells = servers
+                    .iter()
+                    .map(|server| {
+                        let repo = server.repositories.iter().find(|r| r.name == name);
+                        let revision = repo.and_then(|r| r.manifest.as_ref().map(|_| r.revision));
+                        let status = repo.map(|r| r.status);
+                        RepositoryMatrixCell {
+                            server: server.hostname.to_string(),
+                            server_type: server.server_type.to_label().to_string(),
+                            revision,
+                            lag: reference.zip(revision).map(|(r, revision)| r - revision),
+                            status,
+                            status_class: status.map(|s| s.class().to_string()),
+                        }
+                    })
+                    .collect();
+                RepositoryMatrixRow {
+                    repository: name.to_string(),
+                    reference_revision: reference,
+                    cells,
+                }
+            })
+            .collect()
+    }
+
+    /// The state of the repository on every server that has it, for its detail page. `now` is
+    /// the time of the run, which the snapshot ages are relative to.
+    pub fn build_repo_detail(&self, title: &str, name: &str, now: DateTime<Utc>) -> RepoDetail {
+        let servers: Vec<RepoServerDetail> = self
+            .servers
+            .iter()
+            .flat_map(|server| {
+                server
+                    .repositories
+                    .iter()
+                    .filter(|repo| repo.name == name)
+                    .map(move |repo| {
+                        let manifest = repo.manifest.as_ref();
+                        RepoServerDetail {
+                            hostname: server.hostname.to_string(),
+                            server_type: server.server_type.to_label().to_string(),
+                            status: repo.status,
+                            status_class: repo.status.class().to_string(),
+                            revision: manifest.map(|_| repo.revision),
+                            published_at: manifest
+                                .and_then(|m| DateTime::from_timestamp(m.t, 0))
+                                .map(|t| t.to_rfc3339_opts(SecondsFormat::Secs, true)),
+                            snapshot_age_seconds: manifest.map(|m| now.timestamp() - m.t),
+                            ttl_seconds: manifest.map(|m| m.d),
+                            catalog_size_bytes: manifest.map(|m| m.b),
+                            catalog_hash: manifest.map(|m| m.c.to_string()),
+                            history_hash: manifest.map(|m| m.h.to_string()),
+                            error: repo.error.clone(),
+                            details: repo.details.clone(),
+                        }
+                    })
+            })
+            .collect();
+        let status = servers.iter().fold(Status::OK, |status, server| {
+            status.combine(server.status, self.maintenance_aggregation)
+        });
+
+        RepoDetail {
+            title: title.to_string(),
+            name: name.to_string(),
+            status,
+            status_class: status.class().to_string(),
+            last_update: now.to_rfc3339_opts(SecondsFormat::Secs, true),
+            details: self
+                .get_details_per_unique_repo()
+                .remove(name)
+                .unwrap_or_default(),
+            servers,
+        }
+    }
+
     fn get_status_per_unique_repo(&self) -> HashMap<String, Status> {
         let mut repo_status: HashMap<String, Status> = HashMap::new();
 
         for server in &self.servers {
             for repo in &server.repositories {
-                let status = repo_status.get(&repo.name).unwrap_or(&Status::OK);
-                let new_status = status.max(&repo.status);
-                repo_status.insert(repo.name.clone(), *new_status);
+                let status = repo_status.entry(repo.name.clone()).or_insert(Status::OK);
+                *status = status.combine(repo.status, self.maintenance_aggregation);
             }
         }
 
         repo_status
     }
 
-    fn evaluate_overall_conditions(&self, conditions: Vec<Condition>) -> Status {
+    fn get_details_per_unique_repo(&self) -> HashMap<String, Vec<String>> {
+        let mut repo_details: HashMap<String, Vec<String>> = HashMap::new();
+
+        for server in &self.servers {
+            for repo in &server.repositories {
+                repo_details.entry(repo.name.clone()).or_default().extend(
+                    repo.details
+                        .iter()
+                        .map(|detail| format!("{}: {}", server.hostname, detail)),
+                );
+            }
+        }
+
+        repo_details
+    }
+
+    /// The variables the conditions of `rule` are evaluated with: the number of servers of its
+    /// type that are OK, or everything for the overall status.
+    fn rule_scope(&self, rule: &str) -> Scope<'static> {
         let mut scope = Scope::new();
-        let engine = Engine::new();
+        let server_type = match rule {
+            "stratum0_servers" => Some(ServerType::Stratum0),
+            "stratum1_servers" => Some(ServerType::Stratum1),
+            "sync_servers" => Some(ServerType::SyncServer),
+            _ => None,
+        };
+        if let Some(server_type) = server_type {
+            scope.push(
+                rule.to_string(),
+                self.get_by_type_ok(server_type).len() as i64,
+            );
+            return scope;
+        }
 
         scope.push(
             "stratum0_servers",
@@ -500,58 +1411,143 @@ impl StatusManager {
             .count() as i64;
 
         scope.push("repos_out_of_sync", not_ok_repos);
+        scope
+    }
 
-        for condition in conditions {
-            debug!("Evaluating condition: {:?}", condition);
-            if evaluate_condition(&condition, &mut scope, &engine) {
-                return condition.status;
-            }
-        }
+    /// The status of the first condition of `rule` that holds, FAILED if none does.
+    fn evaluate_rule(&self, rule: &str, conditions: Vec<Condition>) -> Status {
+        let mut scope = self.rule_scope(rule);
+        let engine = Engine::new();
+
+        conditions
+            .iter()
+            .inspect(|condition| debug!("Evaluating condition of {}: {:?}", rule, condition))
+            .find(|&condition| evaluate_condition(condition, &mut scope, &engine))
+            .map_or(Status::FAILED, |condition| condition.status)
+    }
+
+    /// The conditions of `rule` that fail to evaluate, which `evaluate_rule` takes as false.
+    pub fn rule_errors(&self, rule: &str, conditions: &[Condition]) -> Vec<RuleError> {
+        let mut scope = self.rule_scope(rule);
+        let engine = Engine::new();
 
-        Status::FAILED
+        conditions
+            .iter()
+            .filter_map(|condition| {
+                engine
+                    .eval_expression_with_scope::<bool>(&mut scope, &condition.when)
+                    .err()
+                    .map(|e| RuleError {
+                        rule: rule.to_string(),
+                        condition: condition.when.clone(),
+                        error: e.to_string(),
+                    })
+            })
+            .collect()
     }
 }
 
-fn compare_with_other_stratum1s(
-    repo: &PopulatedRepositoryOrReplica,
-    all_servers: &[&PopulatedServer],
-) -> Status {
-    let max_divergence = all_servers
-        .iter()
-        .filter(|&&s| s.server_type == ServerType::Stratum1)
-        .flat_map(|&stratum1| {
-            stratum1
-                .repositories
-                .iter()
-                .find(|r| r.name == repo.name)
-                .map(|stratum1_repo| (repo.revision() - stratum1_repo.revision()).abs())
+/// Why there is no revision to compare `repository` on the server `hostname` with, if there
+/// isn't: none of the `stratum0s` that were scraped has it, or, if none was, no other stratum1
+/// has it. `carried` is the server type, hostname and repository of every scraped repository.
+fn missing_reference(
+    server_type: ServerType,
+    hostname: &str,
+    repository: &str,
+    stratum0s: &[String],
+    carried: &[(ServerType, String, String)],
+) -> Option<String> {
+    let carried_by = |server_type: ServerType, other_than: Option<&str>| {
+        carried.iter().any(|(t, hostname, name)| {
+            *t == server_type && name == repository && other_than != Some(hostname.as_str())
         })
-        .max()
-        .unwrap_or(0);
-
-    match max_divergence {
-        0 => Status::OK,
-        1 => Status::WARNING,
-        _ => Status::FAILED,
+    };
+
+    match server_type {
+        ServerType::Stratum0 => None,
+        _ if !stratum0s.is_empty() => (!carried_by(ServerType::Stratum0, None)).then(|| {
+            format!(
+                "no reference revision available on {}",
+                stratum0s.join(", ")
+            )
+        }),
+        _ => (!carried_by(ServerType::Stratum1, Some(hostname)))
+            .then(|| "no reference revision available on another stratum1".to_string()),
     }
 }
 
-fn compare_with_stratum0(
-    repo: &PopulatedRepositoryOrReplica,
-    stratum0: &PopulatedServer,
-) -> Status {
-    let divergence = stratum0
-        .repositories
-        .iter()
-        .find(|r| r.name == repo.name)
-        .map(|stratum0_repo| (repo.revision() - stratum0_repo.revision()).abs())
-        .unwrap_or(0);
+/// Get the status of a load balanced service from the statuses of its members.
+///
+/// The status is the best status that at least `quorum` members are at or better than. With a
+/// quorum equal to the number of members this is the worst member status. Statuses are ranked
+/// the same way as by [`Status::combine`] under `policy`.
+pub fn quorum_status(statuses: &[Status], quorum: usize, policy: MaintenanceAggregation) -> Status {
+    let mut statuses = statuses.to_vec();
+    statuses.sort_by(|&a, &b| {
+        if a == b {
+            std::cmp::Ordering::Equal
+        } else if a.combine(b, policy) == b {
+            std::cmp::Ordering::Less
+        } else {
+            std::cmp::Ordering::Greater
+        }
+    });
+    let index = quorum.clamp(1, statuses.len().max(1)) - 1;
+    statuses.get(index).copied().unwrap_or(Status::FAILED)
+}
 
-    match divergence {
-        0 => Status::OK,
-        1 => Status::WARNING,
-        _ => Status::FAILED,
+/// Fold the scrapes of members of load balanced services into one logical server each.
+///
+/// The logical server takes its repositories and metadata from the first member that was
+/// scraped successfully, and its status from the quorum of its members.
+fn group_members(servers: Vec<Server>, config: &ConfigFile) -> Vec<Server> {
+    let mut grouped: Vec<Server> = Vec::new();
+
+    for server in servers {
+        let is_member = config
+            .servers
+            .iter()
+            .any(|s| s.server.hostname == server.hostname && !s.members.is_empty());
+        match grouped.iter_mut().find(|s| s.hostname == server.hostname) {
+            Some(logical) if is_member => logical.members.push(server),
+            _ if is_member => grouped.push(Server {
+                members: vec![server.clone()],
+                ..server
+            }),
+            _ => grouped.push(server),
+        }
     }
+
+    for logical in grouped.iter_mut().filter(|s| !s.members.is_empty()) {
+        let Some(server_config) = config
+            .servers
+            .iter()
+            .find(|s| s.server.hostname == logical.hostname)
+        else {
+            continue;
+        };
+        let members = std::mem::take(&mut logical.members);
+        let representative = members
+            .iter()
+            .find(|m| m.backend_detected.is_some())
+            .unwrap_or(&members[0]);
+        let statuses: Vec<Status> = members.iter().map(|m| m.status).collect();
+
+        *logical = Server {
+            status: quorum_status(
+                &statuses,
+                server_config.quorum.unwrap_or(members.len()),
+                config.maintenance_aggregation,
+            ),
+            scrape_duration_ms: members.iter().filter_map(|m| m.scrape_duration_ms).max(),
+            address: None,
+            scrape_retried: members.iter().any(|m| m.scrape_retried),
+            ..representative.clone()
+        };
+        logical.members = members;
+    }
+
+    grouped
 }
 
 fn evaluate_condition(condition: &Condition, scope: &mut Scope, engine: &Engine) -> bool {
@@ -560,24 +1556,836 @@ fn evaluate_condition(condition: &Condition, scope: &mut Scope, engine: &Engine)
         .unwrap_or(false)
 }
 
-fn evaluate_conditions_with_key_value(
-    conditions: Vec<Condition>,
-    key: &str,
-    value: usize,
-) -> Status {
-    let mut scope = Scope::new();
-    scope.push(key, value as i64);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RepositorySettings;
+    use crate::testing::ServerFixture;
+    use chrono::TimeZone;
+    use yare::parameterized;
+
+    #[parameterized(
+        recent = { Some("Wed Oct 01 07:28:00 UTC 2025"), 30, Status::OK },
+        exactly_at_limit = { Some("Tue Sep 02 07:28:00 UTC 2025"), 30, Status::OK },
+        too_old = { Some("Mon Sep 01 07:28:00 UTC 2025"), 30, Status::WARNING },
+        tighter_limit = { Some("Tue Sep 30 07:28:00 UTC 2025"), 1, Status::WARNING },
+        missing = { None, 30, Status::OK },
+        unparseable = { Some("yesterday-ish"), 30, Status::OK },
+    )]
+    fn test_geodb_status(last_update: Option<&str>, max_age_days: i64, expected: Status) {
+        let now = Utc.with_ymd_and_hms(2025, 10, 2, 7, 28, 0).unwrap();
+        let last_update = MaybeRfc2822DateTime(last_update.map(|s| s.to_string()));
+
+        assert_eq!(geodb_status(&last_update, max_age_days, now), expected);
+    }
 
-    let engine = Engine::new();
+    #[parameterized(
+        all_ok = { &[Status::OK, Status::OK, Status::OK], 3, Status::OK },
+        worst_wins_by_default = { &[Status::OK, Status::FAILED, Status::OK], 3, Status::FAILED },
+        quorum_met = { &[Status::OK, Status::FAILED, Status::OK], 2, Status::OK },
+        quorum_missed = { &[Status::OK, Status::FAILED, Status::WARNING], 2, Status::WARNING },
+        any_member = { &[Status::FAILED, Status::FAILED, Status::OK], 1, Status::OK },
+        quorum_above_members = { &[Status::OK, Status::DEGRADED], 5, Status::DEGRADED },
+        no_members = { &[], 1, Status::FAILED },
+    )]
+    fn test_quorum_status(statuses: &[Status], quorum: usize, expected: Status) {
+        assert_eq!(
+            quorum_status(statuses, quorum, MaintenanceAggregation::default()),
+            expected
+        );
+    }
 
-    conditions
-        .iter()
-        .inspect(|condition| {
-            debug!(
-                "Evaluating condition: {:?} (key: <{:?}>, val <{:?}>)",
-                condition, key, value
-            );
+    #[parameterized(
+        masks = { MaintenanceAggregation::Masks, 2, Status::MAINTENANCE },
+        masked = { MaintenanceAggregation::Masked, 2, Status::FAILED },
+        masked_quorum_met = { MaintenanceAggregation::Masked, 1, Status::MAINTENANCE },
+        alongside = { MaintenanceAggregation::Alongside, 2, Status::MAINTENANCE },
+    )]
+    fn test_quorum_status_maintenance_with_failed(
+        policy: MaintenanceAggregation,
+        quorum: usize,
+        expected: Status,
+    ) {
+        let statuses = [Status::MAINTENANCE, Status::FAILED];
+
+        assert_eq!(quorum_status(&statuses, quorum, policy), expected);
+    }
+
+    #[parameterized(
+        stratum0_wins = { &[(ServerType::Stratum0, 10), (ServerType::Stratum1, 12)], Some(10) },
+        newest_stratum1 = { &[(ServerType::Stratum1, 8), (ServerType::Stratum1, 10)], Some(10) },
+        sync_server_ignored = { &[(ServerType::SyncServer, 12), (ServerType::Stratum1, 10)], Some(10) },
+        only_sync_servers = { &[(ServerType::SyncServer, 12)], None },
+    )]
+    fn test_reference_revisions(revisions: &[(ServerType, i32)], expected: Option<i32>) {
+        let references = reference_revisions(
+            revisions
+                .iter()
+                .map(|(server_type, revision)| (*server_type, "software.eessi.io", *revision)),
+        );
+
+        assert_eq!(references.get("software.eessi.io").copied(), expected);
+    }
+
+    #[parameterized(
+        in_sync = { 0, Status::OK },
+        one_behind = { 1, Status::WARNING },
+        one_ahead = { -1, Status::WARNING },
+        far_behind = { 2, Status::FAILED },
+    )]
+    fn test_revision_status(lag: i32, expected: Status) {
+        assert_eq!(revision_status(lag), expected);
+    }
+
+    fn repo(name: &str, status: Status) -> Repositories {
+        Repositories {
+            name: name.to_string(),
+            revision: 1,
+            manifest: None,
+            status,
+            status_revision: status,
+            data_check: None,
+            regressed_from: None,
+            cert_fingerprint_mismatch: None,
+            error: None,
+            details: vec![],
+        }
+    }
+
+    fn failed_repo(name: &str) -> Repositories {
+        Repositories::failed(&RepositoryError {
+            repository: name.to_string(),
+            error: "manifest unavailable".to_string(),
         })
-        .find(|&condition| evaluate_condition(condition, &mut scope, &engine))
-        .map_or(Status::FAILED, |condition| condition.status)
+    }
+
+    #[parameterized(
+        worst_all_ok = { &[Status::OK, Status::OK], 0, ServerStatusFromRepos::Worst, Status::OK },
+        worst_mixed = { &[Status::OK, Status::OK], 1, ServerStatusFromRepos::Worst, Status::FAILED },
+        worst_all_failed = { &[], 2, ServerStatusFromRepos::Worst, Status::FAILED },
+        partial_all_ok = { &[Status::OK, Status::OK], 0, ServerStatusFromRepos::DegradedIfPartial, Status::OK },
+        partial_mixed = { &[Status::OK, Status::OK], 1, ServerStatusFromRepos::DegradedIfPartial, Status::DEGRADED },
+        partial_mixed_with_warning = { &[Status::OK, Status::WARNING], 1, ServerStatusFromRepos::DegradedIfPartial, Status::WARNING },
+        partial_all_failed = { &[], 2, ServerStatusFromRepos::DegradedIfPartial, Status::FAILED },
+        no_repositories = { &[], 0, ServerStatusFromRepos::DegradedIfPartial, Status::OK },
+    )]
+    fn test_server_status_from_repositories(
+        scraped: &[Status],
+        failed: usize,
+        policy: ServerStatusFromRepos,
+        expected: Status,
+    ) {
+        let mut repositories: Vec<Repositories> = scraped
+            .iter()
+            .enumerate()
+            .map(|(i, status)| repo(&format!("ok{}.eessi.io", i), *status))
+            .collect();
+        repositories.extend((0..failed).map(|i| failed_repo(&format!("failed{}.eessi.io", i))));
+
+        assert_eq!(
+            server_status_from_repositories(
+                &repositories,
+                policy,
+                MaintenanceAggregation::default()
+            ),
+            expected
+        );
+    }
+
+    #[parameterized(
+        masks = { MaintenanceAggregation::Masks, Status::MAINTENANCE },
+        masked = { MaintenanceAggregation::Masked, Status::FAILED },
+        alongside = { MaintenanceAggregation::Alongside, Status::MAINTENANCE },
+    )]
+    fn test_combine_maintenance_with_failed(policy: MaintenanceAggregation, expected: Status) {
+        assert_eq!(
+            Status::MAINTENANCE.combine(Status::FAILED, policy),
+            expected
+        );
+        assert_eq!(
+            Status::FAILED.combine(Status::MAINTENANCE, policy),
+            expected
+        );
+        assert_eq!(
+            server_status_from_repositories(
+                &[
+                    repo("software.eessi.io", Status::FAILED),
+                    repo("dev.eessi.io", Status::MAINTENANCE),
+                    repo("riscv.eessi.io", Status::WARNING),
+                ],
+                ServerStatusFromRepos::Worst,
+                policy
+            ),
+            expected
+        );
+    }
+
+    #[parameterized(
+        alongside = { MaintenanceAggregation::Alongside, Status::MAINTENANCE, Some(2) },
+        masks = { MaintenanceAggregation::Masks, Status::MAINTENANCE, None },
+        masked = { MaintenanceAggregation::Masked, Status::MAINTENANCE, None },
+        not_in_maintenance = { MaintenanceAggregation::Alongside, Status::FAILED, None },
+    )]
+    fn test_maintenance_failing_servers(
+        policy: MaintenanceAggregation,
+        overall: Status,
+        expected: Option<usize>,
+    ) {
+        let mut in_maintenance = server_with(
+            "s2.example.com",
+            ServerType::Stratum1,
+            repo("software.eessi.io", Status::FAILED),
+        );
+        in_maintenance.status = Status::MAINTENANCE;
+        let manager = StatusManager {
+            servers: vec![
+                ServerFixture::stratum0("s0.example.com").failed().build(),
+                ServerFixture::stratum1("s1.example.com")
+                    .with_repo("software.eessi.io", 100)
+                    .build(),
+                in_maintenance,
+            ],
+            maintenance_aggregation: policy,
+        };
+
+        assert_eq!(manager.maintenance_failing_servers(overall), expected);
+    }
+
+    #[parameterized(
+        ok = { Status::OK, Status::WARNING, Status::WARNING },
+        degraded = { Status::DEGRADED, Status::FAILED, Status::FAILED },
+        maintenance = { Status::MAINTENANCE, Status::WARNING, Status::MAINTENANCE },
+        same = { Status::WARNING, Status::WARNING, Status::WARNING },
+    )]
+    fn test_combine(a: Status, b: Status, expected: Status) {
+        for policy in [
+            MaintenanceAggregation::Masks,
+            MaintenanceAggregation::Masked,
+            MaintenanceAggregation::Alongside,
+        ] {
+            assert_eq!(a.combine(b, policy), expected);
+            assert_eq!(b.combine(a, policy), expected);
+        }
+    }
+
+    fn server_with(hostname: &str, server_type: ServerType, repository: Repositories) -> Server {
+        Server {
+            server_type,
+            backend_type: ServerBackendType::CVMFS,
+            backend_detected: Some(ServerBackendType::CVMFS),
+            hostname: Hostname::try_from(hostname).unwrap(),
+            status: repository.status,
+            repositories: vec![repository],
+            metadata: None,
+            geodb_status: Status::OK,
+            cert_expiry: None,
+            cert_expiry_days: None,
+            scrape_duration_ms: None,
+            address: None,
+            members: vec![],
+            data_age_seconds: None,
+            scrape_retried: false,
+        }
+    }
+
+    #[test]
+    fn test_build_repo_detail() {
+        let now = Utc.with_ymd_and_hms(2025, 10, 2, 7, 28, 0).unwrap();
+        // Hex strings only deserialize from borrowed strings, so not from a `json!` value.
+        let manifest = serde_json::json!({
+            "c": "aa11", "b": 4096, "a": false, "r": "bb22", "x": "cc33", "g": false,
+            "h": "dd44", "t": now.timestamp() - 600, "d": 240, "s": 42,
+            "n": "software.eessi.io", "m": "ee55", "y": "ff66", "l": "", "signature": ""
+        })
+        .to_string();
+        let manifest: Manifest = serde_json::from_str(&manifest).unwrap();
+        let manager = StatusManager {
+            servers: vec![
+                server_with(
+                    "s1.example.com",
+                    ServerType::Stratum1,
+                    Repositories {
+                        revision: 42,
+                        manifest: Some(manifest),
+                        ..repo("software.eessi.io", Status::OK)
+                    },
+                ),
+                server_with(
+                    "s2.example.com",
+                    ServerType::Stratum1,
+                    failed_repo("software.eessi.io"),
+                ),
+                server_with(
+                    "s3.example.com",
+                    ServerType::Stratum1,
+                    repo("dev.eessi.io", Status::OK),
+                ),
+            ],
+            maintenance_aggregation: MaintenanceAggregation::default(),
+        };
+
+        let detail = manager.build_repo_detail("EESSI", "software.eessi.io", now);
+
+        assert_eq!(detail.status, Status::FAILED);
+        assert_eq!(detail.last_update, "2025-10-02T07:28:00Z");
+        assert_eq!(
+            detail.details,
+            vec!["s2.example.com: scrape failed: manifest unavailable".to_string()]
+        );
+        assert_eq!(
+            detail.servers[0],
+            RepoServerDetail {
+                hostname: "s1.example.com".to_string(),
+                server_type: "stratum1".to_string(),
+                status: Status::OK,
+                status_class: Status::OK.class().to_string(),
+                revision: Some(42),
+                published_at: Some("2025-10-02T07:18:00Z".to_string()),
+                snapshot_age_seconds: Some(600),
+                ttl_seconds: Some(240),
+                catalog_size_bytes: Some(4096),
+                catalog_hash: Some("aa11".to_string()),
+                history_hash: Some("dd44".to_string()),
+                error: None,
+                details: vec![],
+            }
+        );
+        assert_eq!(detail.servers.len(), 2);
+        assert_eq!(detail.servers[1].revision, None);
+        assert_eq!(
+            detail.servers[1].error.as_deref(),
+            Some("manifest unavailable")
+        );
+    }
+
+    /// Check that each class in `value` is the class of the status next to it.
+    fn assert_status_classes(value: &serde_json::Value, config: &ConfigFile, checked: &mut usize) {
+        let pairs = [
+            ("status", "class"),
+            ("status", "status_class"),
+            ("status", "update_class"),
+            ("status", "revision_class"),
+            ("geodb_status", "geoapi_class"),
+        ];
+        match value {
+            serde_json::Value::Object(object) => {
+                for (status, class) in pairs {
+                    if let (Some(status), Some(class)) = (object.get(status), object.get(class)) {
+                        let status: Status = serde_json::from_value(status.clone()).unwrap();
+                        assert_eq!(class, status.class_with_overrides(config), "{:?}", object);
+                        *checked += 1;
+                    }
+                }
+                object
+                    .values()
+                    .for_each(|value| assert_status_classes(value, config, checked));
+            }
+            serde_json::Value::Array(items) => items
+                .iter()
+                .for_each(|value| assert_status_classes(value, config, checked)),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn test_apply_status_classes() {
+        let mut data: StatusPageData =
+            serde_json::from_str(include_str!("testdata/status-schema-0.json")).unwrap();
+        let mut config = ConfigFile::default();
+        config
+            .status_classes
+            .insert(Status::WARNING, "theme-warning icon-alert".to_string());
+
+        apply_status_classes(&mut data, &config);
+
+        let mut json = serde_json::to_value(&data).unwrap();
+        // The days have classes of their own, such as `day-warning`.
+        json["daily"].take();
+        let mut checked = 0;
+        assert_status_classes(&json, &config, &mut checked);
+        assert!(checked > 20, "{}", checked);
+        let text = json.to_string();
+        assert!(text.contains("theme-warning icon-alert"));
+        assert!(!text.contains(Status::WARNING.class()));
+        assert!(text.contains(Status::FAILED.class()));
+    }
+
+    #[test]
+    fn test_load_schema_0() {
+        let data: StatusPageData =
+            serde_json::from_str(include_str!("testdata/status-schema-0.json")).unwrap();
+
+        assert_eq!(data.schema_version, 0);
+        assert_eq!(data.generator, Generator::default());
+        assert_eq!(data.generated_at, 0);
+        assert_eq!(data.title, "EESSI status!");
+        assert_eq!(data.eessi_status.status, Status::FAILED);
+        assert_eq!(data.repositories[0].href, "repos/software.eessi.io.html");
+        assert_eq!(data.recent_events[0].component, "overall");
+        assert_eq!(data.incidents[0].incident.id, "2025-10-02-aws");
+        assert_eq!(data.config.incidents.len(), 1);
+    }
+
+    #[test]
+    fn test_load_schema_1() {
+        let data: StatusPageData =
+            serde_json::from_str(include_str!("testdata/status-schema-1.json")).unwrap();
+
+        assert_eq!(data.schema_version, 1);
+        assert_eq!(data.generator.name, "cvmfs-status-page-rust");
+        assert_eq!(data.generated_at, 1759390080);
+        assert_eq!(data.config.incidents.len(), 1);
+    }
+
+    #[test]
+    fn test_failed_repository_keeps_error() {
+        let repository = failed_repo("software.eessi.io");
+
+        assert_eq!(repository.status, Status::FAILED);
+        assert_eq!(repository.error.as_deref(), Some("manifest unavailable"));
+        assert_eq!(
+            repository.details,
+            vec!["scrape failed: manifest unavailable".to_string()]
+        );
+    }
+
+    fn fixture(server_type: ServerType, hostname: &str) -> ServerFixture {
+        ServerFixture::new(hostname, server_type)
+    }
+
+    fn condition(status: Status, when: &str) -> Condition {
+        Condition {
+            status,
+            when: when.to_string(),
+        }
+    }
+
+    #[parameterized(
+        in_sync = { &[(ServerType::Stratum0, Some(10)), (ServerType::Stratum1, Some(10))], &[Status::OK, Status::OK] },
+        one_behind = { &[(ServerType::Stratum0, Some(10)), (ServerType::Stratum1, Some(9))], &[Status::OK, Status::WARNING] },
+        far_behind = { &[(ServerType::Stratum0, Some(10)), (ServerType::Stratum1, Some(7))], &[Status::OK, Status::FAILED] },
+        ahead = { &[(ServerType::Stratum0, Some(10)), (ServerType::Stratum1, Some(11))], &[Status::OK, Status::WARNING] },
+        stratum0_failed = { &[(ServerType::Stratum0, None), (ServerType::Stratum1, Some(10)), (ServerType::Stratum1, Some(9))], &[Status::FAILED, Status::OK, Status::WARNING] },
+        no_stratum0 = { &[(ServerType::Stratum1, Some(10)), (ServerType::Stratum1, Some(8))], &[Status::OK, Status::FAILED] },
+        newest_stratum0 = { &[(ServerType::Stratum0, Some(10)), (ServerType::Stratum0, Some(11))], &[Status::WARNING, Status::OK] },
+        sync_server_compared = { &[(ServerType::Stratum0, Some(10)), (ServerType::SyncServer, Some(12)), (ServerType::Stratum1, Some(10))], &[Status::OK, Status::FAILED, Status::OK] },
+        sync_server_only = { &[(ServerType::SyncServer, Some(5))], &[Status::WARNING] },
+        stratum1_failed = { &[(ServerType::Stratum0, Some(10)), (ServerType::Stratum1, None)], &[Status::OK, Status::FAILED] },
+        all_failed = { &[(ServerType::Stratum0, None), (ServerType::Stratum1, None)], &[Status::FAILED, Status::FAILED] },
+        mixed = { &[(ServerType::Stratum0, Some(10)), (ServerType::Stratum1, Some(10)), (ServerType::Stratum1, Some(9)), (ServerType::Stratum1, Some(8)), (ServerType::SyncServer, Some(10))], &[Status::OK, Status::OK, Status::WARNING, Status::FAILED, Status::OK] },
+    )]
+    fn test_from_servers_revisions(servers: &[(ServerType, Option<i32>)], expected: &[Status]) {
+        let servers = servers
+            .iter()
+            .enumerate()
+            .map(|(i, (server_type, revision))| {
+                let server = fixture(*server_type, &format!("s{}.example.com", i));
+                match revision {
+                    Some(revision) => server.with_repo("software.eessi.io", *revision),
+                    None => server.failed(),
+                }
+                .build()
+            })
+            .collect();
+
+        let manager = StatusManager::from_servers(servers, &ConfigFile::default());
+
+        let statuses: Vec<Status> = manager.servers.iter().map(|s| s.status).collect();
+        assert_eq!(statuses, expected);
+        for server in &manager.servers {
+            for repo in &server.repositories {
+                assert_eq!(repo.status, server.status);
+                assert_eq!(repo.status_revision, server.status);
+            }
+        }
+    }
+
+    #[parameterized(
+        all_in_sync = { &[("software.eessi.io", Some(10)), ("dev.eessi.io", Some(5))], ServerStatusFromRepos::Worst, Status::OK },
+        one_lagging = { &[("software.eessi.io", Some(10)), ("dev.eessi.io", Some(4))], ServerStatusFromRepos::Worst, Status::WARNING },
+        failed_repo = { &[("software.eessi.io", Some(10)), ("dev.eessi.io", None)], ServerStatusFromRepos::Worst, Status::FAILED },
+        failed_repo_partial = { &[("software.eessi.io", Some(10)), ("dev.eessi.io", None)], ServerStatusFromRepos::DegradedIfPartial, Status::DEGRADED },
+        failed_repo_partial_lagging = { &[("software.eessi.io", Some(9)), ("dev.eessi.io", None)], ServerStatusFromRepos::DegradedIfPartial, Status::WARNING },
+        only_failed_repos = { &[("software.eessi.io", None), ("dev.eessi.io", None)], ServerStatusFromRepos::DegradedIfPartial, Status::FAILED },
+        repo_not_on_stratum0 = { &[("riscv.eessi.io", Some(3))], ServerStatusFromRepos::Worst, Status::WARNING },
+    )]
+    fn test_from_servers_repositories(
+        repositories: &[(&str, Option<i32>)],
+        policy: ServerStatusFromRepos,
+        expected: Status,
+    ) {
+        let stratum0 = ServerFixture::stratum0("s0.example.com")
+            .with_repo("software.eessi.io", 10)
+            .with_repo("dev.eessi.io", 5);
+        let stratum1 = repositories.iter().fold(
+            ServerFixture::stratum1("s1.example.com"),
+            |server, (name, revision)| match revision {
+                Some(revision) => server.with_repo(name, *revision),
+                None => server.with_failed_repo(name, "manifest unavailable"),
+            },
+        );
+        let config = ConfigFile {
+            server_status_from_repos: policy,
+            ..ConfigFile::default()
+        };
+
+        let manager =
+            StatusManager::from_servers(vec![stratum0.build(), stratum1.build()], &config);
+
+        assert_eq!(manager.servers[0].status, Status::OK);
+        assert_eq!(manager.servers[1].status, expected);
+        let failed = manager.servers[1]
+            .repositories
+            .iter()
+            .filter(|r| r.error.is_some());
+        for repo in failed {
+            assert_eq!(repo.status, Status::FAILED);
+            assert_eq!(repo.manifest, None);
+        }
+    }
+
+    #[parameterized(
+        all_in_sync = { 3, false, Status::OK, Status::OK },
+        one_stratum1_behind = { 2, false, Status::DEGRADED, Status::DEGRADED },
+        two_stratum1s_behind = { 1, false, Status::WARNING, Status::DEGRADED },
+        all_stratum1s_behind = { 0, false, Status::FAILED, Status::FAILED },
+        stratum0_down = { 3, true, Status::OK, Status::MAINTENANCE },
+        stratum0_down_and_behind = { 1, true, Status::WARNING, Status::MAINTENANCE },
+    )]
+    fn test_from_servers_rules(
+        in_sync: usize,
+        stratum0_failed: bool,
+        stratum1_expected: Status,
+        overall_expected: Status,
+    ) {
+        let stratum0 = ServerFixture::stratum0("s0.example.com");
+        let mut servers = vec![if stratum0_failed {
+            stratum0.failed()
+        } else {
+            stratum0.with_repo("software.eessi.io", 10)
+        }
+        .build()];
+        servers.extend((0..3).map(|i| {
+            let revision = if i < in_sync { 10 } else { 8 };
+            ServerFixture::stratum1(&format!("s1-{}.example.com", i))
+                .with_repo("software.eessi.io", revision)
+                .build()
+        }));
+
+        let manager = StatusManager::from_servers(servers, &ConfigFile::default());
+
+        let stratum1 = manager.status_stratum1(vec![
+            condition(Status::OK, "stratum1_servers >= 3"),
+            condition(Status::DEGRADED, "stratum1_servers >= 2"),
+            condition(Status::WARNING, "stratum1_servers >= 1"),
+        ]);
+        let overall = manager.status_overall(vec![
+            condition(Status::MAINTENANCE, "stratum0_servers == 0"),
+            condition(
+                Status::OK,
+                "repos_out_of_sync == 0 && stratum1_servers >= 2",
+            ),
+            condition(Status::DEGRADED, "stratum1_servers >= 1"),
+        ]);
+        assert_eq!(stratum1, stratum1_expected);
+        assert_eq!(overall, overall_expected);
+    }
+
+    #[parameterized(
+        all_members_in_sync = { &[Some(10), Some(10), Some(10)], None, Status::OK },
+        every_member_by_default = { &[Some(10), Some(10), None], None, Status::FAILED },
+        quorum_met = { &[Some(10), Some(10), None], Some(2), Status::OK },
+        quorum_missed = { &[Some(10), Some(9), None], Some(2), Status::WARNING },
+        first_member_failed = { &[None, Some(10), Some(10)], Some(2), Status::OK },
+    )]
+    fn test_from_servers_members(
+        revisions: &[Option<i32>],
+        quorum: Option<usize>,
+        expected: Status,
+    ) {
+        let config: ConfigFile = serde_json::from_value(serde_json::json!({
+            "meta": {
+                "title": "EESSI Status",
+                "logging_level": "info",
+                "contact_email": "support@eessi.io",
+                "repo_url": "https://example.com",
+                "repo_url_text": "Example"
+            },
+            "servers": [
+                { "hostname": "s0.example.com", "server_type": "Stratum0" },
+                {
+                    "hostname": "cdn.example.com",
+                    "server_type": "Stratum1",
+                    "members": ["10.0.0.1", "10.0.0.2", "10.0.0.3"],
+                    "quorum": quorum
+                }
+            ],
+            "repositories": [],
+            "ignored_repositories": [],
+            "rules": []
+        }))
+        .unwrap();
+        let mut servers = vec![ServerFixture::stratum0("s0.example.com")
+            .with_repo("software.eessi.io", 10)
+            .build()];
+        servers.extend(revisions.iter().enumerate().map(|(i, revision)| {
+            let member =
+                ServerFixture::stratum1("cdn.example.com").with_scrape_duration_ms(100 * i as u64);
+            match revision {
+                Some(revision) => member.with_repo("software.eessi.io", *revision),
+                None => member.failed(),
+            }
+            .build()
+        }));
+
+        let manager = StatusManager::from_servers(servers, &config);
+
+        assert_eq!(manager.servers.len(), 2);
+        let service = &manager.servers[1];
+        assert_eq!(service.status, expected);
+        assert_eq!(service.members.len(), 3);
+        assert_eq!(service.scrape_duration_ms, Some(200));
+        // The repositories are those of the first member that was scraped.
+        assert_eq!(service.repositories.len(), 1);
+    }
+
+    #[parameterized(
+        on_stratum0 = { Some(&["software.eessi.io", "dev.eessi.io"]), &[5], Status::WARNING, Status::OK, None },
+        not_on_stratum0 = { Some(&["software.eessi.io"]), &[5], Status::WARNING, Status::WARNING, Some("no reference revision available on s0.example.com") },
+        not_on_stratum0_ok = { Some(&["software.eessi.io"]), &[5], Status::OK, Status::OK, Some("no reference revision available on s0.example.com") },
+        not_on_stratum0_failed = { Some(&["software.eessi.io"]), &[5, 5], Status::FAILED, Status::FAILED, Some("no reference revision available on s0.example.com") },
+        not_on_stratum0_lagging = { Some(&["software.eessi.io"]), &[3, 5], Status::WARNING, Status::FAILED, Some("no reference revision available on s0.example.com") },
+        on_other_stratum1 = { None, &[5, 5], Status::WARNING, Status::OK, None },
+        only_stratum1 = { None, &[5], Status::WARNING, Status::WARNING, Some("no reference revision available on another stratum1") },
+        only_stratum1_stratum0_failed = { Some(&[]), &[5], Status::WARNING, Status::WARNING, Some("no reference revision available on another stratum1") },
+    )]
+    fn test_from_servers_missing_reference(
+        stratum0_repositories: Option<&[&str]>,
+        revisions: &[i32],
+        severity: Status,
+        expected: Status,
+        detail: Option<&str>,
+    ) {
+        // A stratum0 without repositories failed to scrape.
+        let mut servers: Vec<Server> = stratum0_repositories
+            .map(|names| {
+                let stratum0 = ServerFixture::stratum0("s0.example.com");
+                if names.is_empty() {
+                    return stratum0.failed().build();
+                }
+                names
+                    .iter()
+                    .fold(stratum0, |stratum0, name| stratum0.with_repo(name, 5))
+                    .build()
+            })
+            .into_iter()
+            .collect();
+        servers.extend(revisions.iter().enumerate().map(|(i, revision)| {
+            ServerFixture::stratum1(&format!("s1-{}.example.com", i))
+                .with_repo("dev.eessi.io", *revision)
+                .build()
+        }));
+        let config = ConfigFile {
+            missing_reference_status: severity,
+            ..ConfigFile::default()
+        };
+
+        let manager = StatusManager::from_servers(servers, &config);
+
+        let stratum1 = manager.get_by_type(ServerType::Stratum1)[0];
+        let repo = &stratum1.repositories[0];
+        assert_eq!(repo.status, expected);
+        assert_eq!(repo.status_revision, expected);
+        assert_eq!(repo.details.first().map(String::as_str), detail);
+        // The stratum0 is never compared with anything.
+        for stratum0 in manager.get_by_type(ServerType::Stratum0) {
+            assert!(stratum0.repositories.iter().all(|r| r.details.is_empty()));
+        }
+    }
+
+    #[parameterized(
+        no_baseline = { None, 4200, false, false, Status::OK },
+        same = { Some(4200), 4200, false, false, Status::OK },
+        higher = { Some(4200), 4201, false, false, Status::OK },
+        lower = { Some(4200), 4000, false, false, Status::FAILED },
+        lower_exempt = { Some(4200), 4000, true, false, Status::OK },
+        lower_stale = { Some(4200), 4000, false, true, Status::OK },
+    )]
+    fn test_apply_revision_regressions(
+        highest: Option<i32>,
+        revision: i32,
+        exempt: bool,
+        stale: bool,
+        expected: Status,
+    ) {
+        let servers = vec![
+            ServerFixture::stratum0("s0.example.com")
+                .with_repo("software.eessi.io", revision)
+                .build(),
+            ServerFixture::stratum1("s1.example.com")
+                .with_repo("software.eessi.io", revision)
+                .build(),
+        ];
+        let mut manager = StatusManager::from_servers(servers, &ConfigFile::default());
+        if stale {
+            manager.servers[1].data_age_seconds = Some(600);
+        }
+        let highest: HighestRevisions = highest
+            .map(|was| {
+                let repositories = BTreeMap::from([("software.eessi.io".to_string(), was)]);
+                ("s1.example.com".to_string(), repositories)
+            })
+            .into_iter()
+            .collect();
+        let config = ConfigFile {
+            revision_regression_exempt: if exempt {
+                vec!["software.eessi.io".to_string()]
+            } else {
+                vec![]
+            },
+            ..ConfigFile::default()
+        };
+
+        manager.apply_revision_regressions(&highest, &config);
+
+        let server = &manager.servers[1];
+        let repo = &server.repositories[0];
+        assert_eq!(server.status, expected);
+        assert_eq!(repo.status, expected);
+        assert_eq!(manager.servers[0].status, Status::OK);
+        if expected == Status::FAILED {
+            assert_eq!(
+                repo.regressed_from,
+                highest["s1.example.com"].get("software.eessi.io").copied()
+            );
+            assert_eq!(
+                repo.details,
+                vec!["revision went backwards (was 4200, now 4000)".to_string()]
+            );
+        } else {
+            assert_eq!(repo.regressed_from, None);
+            assert!(repo.details.is_empty());
+        }
+    }
+
+    #[parameterized(
+        unpinned = { &[], None, Status::OK },
+        matching = { &["cc33"], Some(false), Status::OK },
+        matching_formatted = { &["CC:33"], Some(false), Status::OK },
+        mismatch = { &["aa11"], Some(true), Status::FAILED },
+        rotation_old = { &["cc33", "aa11"], Some(false), Status::OK },
+        rotation_new = { &["aa11", "cc33"], Some(false), Status::OK },
+    )]
+    fn test_apply_cert_fingerprints(
+        accepted: &[&str],
+        expected_mismatch: Option<bool>,
+        expected: Status,
+    ) {
+        // The fixture manifests are signed with the certificate cc33.
+        let servers = vec![
+            ServerFixture::stratum0("s0.example.com")
+                .with_repo("software.eessi.io", 100)
+                .with_repo("dev.eessi.io", 7)
+                .build(),
+            ServerFixture::stratum1("s1.example.com")
+                .with_repo("software.eessi.io", 100)
+                .with_repo("dev.eessi.io", 7)
+                .build(),
+        ];
+        let mut config = ConfigFile::default();
+        config.repository_settings.insert(
+            "software.eessi.io".to_string(),
+            RepositorySettings {
+                expected_cert_fingerprint: accepted.iter().map(|f| f.to_string()).collect(),
+                ..RepositorySettings::default()
+            },
+        );
+        let mut manager = StatusManager::from_servers(servers, &config);
+
+        manager.apply_cert_fingerprints(&config);
+
+        for server in &manager.servers {
+            let repo = &server.repositories[0];
+            assert_eq!(server.status, expected);
+            assert_eq!(repo.status, expected);
+            assert_eq!(repo.cert_fingerprint_mismatch, expected_mismatch);
+            if expected_mismatch == Some(true) {
+                assert_eq!(
+                    repo.details,
+                    vec!["certificate fingerprint changed: cc33 is not one of aa11".to_string()]
+                );
+            } else {
+                assert!(repo.details.is_empty());
+            }
+            assert_eq!(server.repositories[1].cert_fingerprint_mismatch, None);
+        }
+    }
+
+    #[test]
+    fn test_repository_matrix() {
+        let servers = vec![
+            ServerFixture::stratum1("s1b.example.com")
+                .with_repo("software.eessi.io", 9)
+                .with_repo("dev.eessi.io", 5)
+                .build(),
+            ServerFixture::sync_server("a-sync.example.com")
+                .with_repo("software.eessi.io", 10)
+                .build(),
+            ServerFixture::stratum0("z-s0.example.com")
+                .with_repo("software.eessi.io", 10)
+                .build(),
+            ServerFixture::stratum1("s1a.example.com")
+                .with_repo("software.eessi.io", 10)
+                .with_failed_repo("dev.eessi.io", "manifest unavailable")
+                .build(),
+            ServerFixture::stratum1("s1c.example.com").failed().build(),
+        ];
+        let manager = StatusManager::from_servers(servers, &ConfigFile::default());
+
+        let matrix = manager.repository_matrix();
+
+        let cells = |row: &RepositoryMatrixRow| -> Vec<_> {
+            row.cells
+                .iter()
+                .map(|cell| (cell.server.clone(), cell.revision, cell.lag, cell.status))
+                .collect()
+        };
+        let cell =
+            |server: &str, revision, lag, status| (server.to_string(), revision, lag, status);
+        assert_eq!(matrix.len(), 2);
+        assert_eq!(matrix[0].repository, "dev.eessi.io");
+        // The stratum0 doesn't have it, and the revision on s1a is unknown.
+        assert_eq!(matrix[0].reference_revision, Some(5));
+        // The servers are ordered by type first: by hostname alone, the sync server would
+        // come first and the stratum0 last.
+        assert_eq!(
+            cells(&matrix[0]),
+            vec![
+                cell("z-s0.example.com", None, None, None),
+                cell("s1a.example.com", None, None, Some(Status::FAILED)),
+                cell("s1b.example.com", Some(5), Some(0), Some(Status::WARNING)),
+                cell("s1c.example.com", None, None, None),
+                cell("a-sync.example.com", None, None, None),
+            ]
+        );
+        assert_eq!(matrix[1].repository, "software.eessi.io");
+        assert_eq!(matrix[1].reference_revision, Some(10));
+        assert_eq!(
+            cells(&matrix[1]),
+            vec![
+                cell("z-s0.example.com", Some(10), Some(0), Some(Status::OK)),
+                cell("s1a.example.com", Some(10), Some(0), Some(Status::OK)),
+                cell("s1b.example.com", Some(9), Some(1), Some(Status::WARNING)),
+                cell("s1c.example.com", None, None, None),
+                cell("a-sync.example.com", Some(10), Some(0), Some(Status::OK)),
+            ]
+        );
+        assert_eq!(matrix[1].cells[0].server_type, "stratum0");
+        assert_eq!(matrix[1].cells[4].server_type, "syncserver");
+        assert_eq!(
+            matrix[1].cells[2].status_class.as_deref(),
+            Some(Status::WARNING.class())
+        );
+        assert_eq!(matrix[1].cells[3].status_class, None);
+    }
 }
@@ -0,0 +1,151 @@
+use crate::diff::Changes;
+use crate::models::{Status, StatusPageData};
+
+/// A component whose status changed since the previous run, as every notification target
+/// sees it.
+#[derive(Debug, PartialEq)]
+pub struct Transition {
+    pub component: String,
+    /// `None` when the component is new, such as on a first run with a new component.
+    pub from: Option<Status>,
+    pub to: Status,
+    /// The conditions of the rule of the component that lead to the new status.
+    pub conditions: Vec<String>,
+    /// Why the component has the new status, such as which servers failed.
+    pub details: Vec<String>,
+}
+
+/// The rule evaluated for each component in `Changes`. The repositories have no rule.
+fn rule_id(component: &str) -> Option<&'static str> {
+    match component {
+        "overall" => Some("eessi_status"),
+        "stratum0" => Some("stratum0_servers"),
+        "stratum1" => Some("stratum1_servers"),
+        "syncservers" => Some("sync_servers"),
+        _ => None,
+    }
+}
+
+fn details(component: &str, data: &StatusPageData) -> Vec<String> {
+    match component {
+        "stratum0" => data.stratum0.details.clone(),
+        "stratum1" => data.stratum1.details.clone(),
+        "syncservers" => data.syncservers.details.clone(),
+        "repositories" => data.repositories_status.details.clone(),
+        _ => vec![],
+    }
+}
+
+/// The components that changed status. Removed components aren't a transition anyone needs
+/// to hear about, and are left out.
+pub fn transitions(changes: &Changes, data: &StatusPageData) -> Vec<Transition> {
+    changes
+        .components
+        .iter()
+        .filter_map(|change| {
+            let to = change.to?;
+            let conditions = rule_id(&change.name)
+                .and_then(|id| data.config.rules.iter().find(|rule| rule.id == id))
+                .map(|rule| {
+                    rule.conditions
+                        .iter()
+                        .filter(|c| c.status == to)
+                        .map(|c| c.when.clone())
+                        .collect()
+                })
+                .unwrap_or_default();
+            Some(Transition {
+                component: change.name.clone(),
+                from: change.from,
+                to,
+                conditions,
+                details: details(&change.name, data),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff::StatusChange;
+    use serde_json::json;
+
+    /// Status page data with a failed stratum1 and its rule.
+    fn status_page_data() -> StatusPageData {
+        let stratum = |status: &str, details: &[&str]| json!({ "status": status, "status_class": "", "details": details, "servers": [] });
+        serde_json::from_value(json!({
+            "title": "EESSI",
+            "eessi_status": { "status": "WARNING", "class": "", "text": "", "description": "" },
+            "contact_email": "support@eessi.io",
+            "last_update": "2025-10-02T07:28:00Z",
+            "legend": [],
+            "stratum0": stratum("OK", &[]),
+            "stratum1": stratum("FAILED", &["s1.example.com is down"]),
+            "syncservers": stratum("OK", &[]),
+            "repositories_status": {
+                "name": "", "status": "OK", "revision_class": "", "snapshot_class": ""
+            },
+            "repositories": [],
+            "config": {
+                "meta": {
+                    "title": "", "logging_level": "", "contact_email": "",
+                    "repo_url": "", "repo_url_text": ""
+                },
+                "servers": [],
+                "repositories": [],
+                "ignored_repositories": [],
+                "rules": [{
+                    "id": "stratum1_servers",
+                    "description": "Stratum1 servers",
+                    "conditions": [
+                        { "status": "FAILED", "when": "stratum1_servers == 0" },
+                        { "status": "OK", "when": "stratum1_servers >= 2" }
+                    ]
+                }]
+            },
+            "servers": []
+        }))
+        .unwrap()
+    }
+
+    fn change(name: &str, from: Option<Status>, to: Option<Status>) -> StatusChange {
+        StatusChange {
+            name: name.to_string(),
+            from,
+            to,
+        }
+    }
+
+    #[test]
+    fn test_transitions() {
+        let changes = Changes {
+            components: vec![
+                change("overall", Some(Status::OK), Some(Status::WARNING)),
+                change("stratum1", Some(Status::OK), Some(Status::FAILED)),
+                change("removed", Some(Status::OK), None),
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            transitions(&changes, &status_page_data()),
+            vec![
+                Transition {
+                    component: "overall".to_string(),
+                    from: Some(Status::OK),
+                    to: Status::WARNING,
+                    conditions: vec![],
+                    details: vec![],
+                },
+                Transition {
+                    component: "stratum1".to_string(),
+                    from: Some(Status::OK),
+                    to: Status::FAILED,
+                    conditions: vec!["stratum1_servers == 0".to_string()],
+                    details: vec!["s1.example.com is down".to_string()],
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,288 @@
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use crate::config::PagerDutyConfig;
+use crate::models::Status;
+use crate::notifications::Transition;
+
+/// The source of every event.
+const SOURCE: &str = "cvmfs-status-page";
+
+/// The severity of an incident for a status.
+fn severity(status: Status) -> &'static str {
+    match status {
+        Status::FAILED => "critical",
+        Status::DEGRADED | Status::WARNING => "warning",
+        Status::OK | Status::MAINTENANCE => "info",
+    }
+}
+
+/// The same key for every event of a component, so a component that keeps failing has one
+/// incident, and recovering resolves it.
+pub fn dedup_key(component: &str) -> String {
+    format!("{}/{}", SOURCE, component)
+}
+
+/// Whether a transition is paged for: a component that is paged for failing, or being OK
+/// again.
+pub fn pages(config: &PagerDutyConfig, transition: &Transition) -> bool {
+    config.components.contains(&transition.component)
+        && matches!(transition.to, Status::FAILED | Status::OK)
+}
+
+/// The event for a transition that is paged for: a trigger when the component fails, a
+/// resolve when it is OK again.
+pub fn event(routing_key: &str, title: &str, transition: &Transition) -> Value {
+    if transition.to == Status::OK {
+        return json!({
+            "routing_key": routing_key,
+            "event_action": "resolve",
+            "dedup_key": dedup_key(&transition.component),
+        });
+    }
+
+    json!({
+        "routing_key": routing_key,
+        "event_action": "trigger",
+        "dedup_key": dedup_key(&transition.component),
+        "payload": {
+            "summary": format!("{}: {} is {}", title, transition.component, transition.to),
+            "source": SOURCE,
+            "severity": severity(transition.to),
+            "component": transition.component,
+            "custom_details": {
+                "from": transition.from,
+                "to": transition.to,
+                "conditions": transition.conditions,
+                "details": transition.details,
+            },
+        },
+    })
+}
+
+/// The events for the transitions that are paged for. The routing key is only needed when
+/// there is something to send.
+pub fn events(
+    config: &PagerDutyConfig,
+    title: &str,
+    transitions: &[Transition],
+) -> Result<Vec<Value>> {
+    let paged: Vec<&Transition> = transitions.iter().filter(|t| pages(config, t)).collect();
+    if paged.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let routing_key = std::env::var(&config.routing_key_env).context(format!(
+        "Environment variable {} is not set",
+        config.routing_key_env
+    ))?;
+    Ok(paged
+        .into_iter()
+        .map(|t| event(&routing_key, title, t))
+        .collect())
+}
+
+/// Send an event to the Events API, retrying when rate limited or when PagerDuty is
+/// unavailable, after `Retry-After` or with an exponential backoff.
+///
+/// Fails on a connection error, a rejected event or when the retries run out.
+pub async fn send(config: &PagerDutyConfig, event: &Value) -> Result<()> {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(config.timeout_seconds))
+        .build()
+        .context("Failed to build the PagerDuty client")?;
+
+    let mut attempt = 0;
+    loop {
+        debug!("Sending event to PagerDuty at {}", config.url);
+        let response = client
+            .post(&config.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(event.to_string())
+            .send()
+            .await
+            .context(format!(
+                "Failed to send event to PagerDuty at {}",
+                config.url
+            ))?;
+
+        let status = response.status();
+        if status.is_success() {
+            info!(
+                "Event sent to PagerDuty: {} {}",
+                event["event_action"], event["dedup_key"]
+            );
+            return Ok(());
+        }
+
+        let retry = status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error();
+        if !retry || attempt >= config.retries {
+            let body = response.text().await.unwrap_or_default();
+            anyhow::bail!(
+                "PagerDuty at {} responded with {}: {}",
+                config.url,
+                status,
+                body.trim()
+            );
+        }
+
+        let delay = response
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or_else(|| Duration::from_secs(1 << attempt));
+        attempt += 1;
+        warn!(
+            "PagerDuty responded with {}, retrying in {:?} ({} of {})",
+            status, delay, attempt, config.retries
+        );
+        tokio::time::sleep(delay).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode as AxumStatusCode;
+    use std::sync::{Arc, Mutex};
+    use yare::parameterized;
+
+    fn config(url: &str) -> PagerDutyConfig {
+        PagerDutyConfig {
+            routing_key_env: "PAGERDUTY_ROUTING_KEY".to_string(),
+            components: vec!["overall".to_string()],
+            url: url.to_string(),
+            retries: 2,
+            timeout_seconds: 5,
+        }
+    }
+
+    fn transition(component: &str, from: Status, to: Status) -> Transition {
+        Transition {
+            component: component.to_string(),
+            from: Some(from),
+            to,
+            conditions: vec!["stratum1_servers == 0".to_string()],
+            details: vec![],
+        }
+    }
+
+    #[parameterized(
+        failed = { "overall", Status::OK, Status::FAILED, true },
+        resolved = { "overall", Status::FAILED, Status::OK, true },
+        degraded = { "overall", Status::OK, Status::DEGRADED, false },
+        recovering = { "overall", Status::FAILED, Status::WARNING, false },
+        maintenance = { "overall", Status::OK, Status::MAINTENANCE, false },
+        other_component = { "stratum1", Status::OK, Status::FAILED, false },
+    )]
+    fn test_pages(component: &str, from: Status, to: Status, expected: bool) {
+        let config = config("http://localhost");
+        assert_eq!(pages(&config, &transition(component, from, to)), expected);
+    }
+
+    /// A mock Events API, answering with the given statuses in turn and recording the events.
+    async fn events_api(statuses: Vec<AxumStatusCode>) -> (String, Arc<Mutex<Vec<Value>>>) {
+        let received = Arc::new(Mutex::new(vec![]));
+        let responses = Arc::new(Mutex::new(statuses.into_iter()));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/v2/enqueue", listener.local_addr().unwrap());
+
+        let recorded = received.clone();
+        let router = axum::Router::new().route(
+            "/v2/enqueue",
+            axum::routing::post(move |body: String| async move {
+                recorded
+                    .lock()
+                    .unwrap()
+                    .push(serde_json::from_str::<Value>(&body).unwrap());
+                let status = responses
+                    .lock()
+                    .unwrap()
+                    .next()
+                    .unwrap_or(AxumStatusCode::ACCEPTED);
+                (
+                    status,
+                    [("Retry-After", "0")],
+                    r#"{"status":"success","message":"Event processed"}"#,
+                )
+            }),
+        );
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        (url, received)
+    }
+
+    #[tokio::test]
+    async fn test_trigger_and_resolve() {
+        let (url, received) = events_api(vec![AxumStatusCode::TOO_MANY_REQUESTS]).await;
+        let config = config(&url);
+
+        for t in [
+            transition("overall", Status::OK, Status::FAILED),
+            transition("overall", Status::FAILED, Status::OK),
+        ] {
+            send(&config, &event("key", "EESSI", &t)).await.unwrap();
+        }
+
+        let trigger = json!({
+            "routing_key": "key",
+            "event_action": "trigger",
+            "dedup_key": "cvmfs-status-page/overall",
+            "payload": {
+                "summary": "EESSI: overall is FAILED",
+                "source": "cvmfs-status-page",
+                "severity": "critical",
+                "component": "overall",
+                "custom_details": {
+                    "from": "OK",
+                    "to": "FAILED",
+                    "conditions": ["stratum1_servers == 0"],
+                    "details": [],
+                },
+            },
+        });
+        let resolve = json!({
+            "routing_key": "key",
+            "event_action": "resolve",
+            "dedup_key": "cvmfs-status-page/overall",
+        });
+        // The trigger is sent again after the 429.
+        assert_eq!(
+            *received.lock().unwrap(),
+            vec![trigger.clone(), trigger, resolve]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_gives_up() {
+        let (url, received) = events_api(vec![
+            AxumStatusCode::TOO_MANY_REQUESTS,
+            AxumStatusCode::SERVICE_UNAVAILABLE,
+            AxumStatusCode::TOO_MANY_REQUESTS,
+        ])
+        .await;
+
+        let error = send(&config(&url), &json!({ "event_action": "trigger" }))
+            .await
+            .unwrap_err();
+
+        assert!(error.to_string().contains("429"), "{}", error);
+        assert_eq!(received.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_send_rejected() {
+        let (url, received) = events_api(vec![AxumStatusCode::BAD_REQUEST]).await;
+
+        let error = send(&config(&url), &json!({ "event_action": "trigger" }))
+            .await
+            .unwrap_err();
+
+        assert!(error.to_string().contains("400 Bad Request"), "{}", error);
+        assert_eq!(received.lock().unwrap().len(), 1);
+    }
+}
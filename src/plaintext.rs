@@ -0,0 +1,171 @@
+use crate::models::{Status, StatusPageData};
+use crate::templating::ServerStatus;
+
+/// The width the lines are truncated to.
+pub const WIDTH: usize = 80;
+
+/// The width of the column with the names of components, repositories and servers.
+const NAME_WIDTH: usize = 32;
+
+/// The width of the column with the status, the longest being "Normal service".
+const STATUS_WIDTH: usize = 14;
+
+/// Cut `line` to [`WIDTH`] characters, marking the cut with `...`.
+fn truncate(line: &str) -> String {
+    let line = line.trim_end();
+    if line.chars().count() <= WIDTH {
+        return line.to_string();
+    }
+    let cut: String = line.chars().take(WIDTH - 3).collect();
+    format!("{}...", cut.trim_end())
+}
+
+fn row(name: &str, status: Status, reason: &str) -> String {
+    let name = if name.chars().count() > NAME_WIDTH {
+        let cut: String = name.chars().take(NAME_WIDTH - 3).collect();
+        format!("{}...", cut)
+    } else {
+        name.to_string()
+    };
+    truncate(&format!(
+        "{:<name_width$} {:<status_width$} {}",
+        name,
+        status.text(),
+        reason,
+        name_width = NAME_WIDTH,
+        status_width = STATUS_WIDTH,
+    ))
+}
+
+/// Why a server isn't OK, as far as the status page knows.
+pub fn server_reason(server: &ServerStatus) -> String {
+    if let Some(note) = &server.stale_note {
+        return note.clone();
+    }
+    let repositories: Vec<&str> = server
+        .repositories
+        .iter()
+        .filter(|r| r.status != Status::OK)
+        .map(|r| r.name.as_str())
+        .collect();
+    if !repositories.is_empty() {
+        return format!("repositories not OK: {}", repositories.join(", "));
+    }
+    if server.geodb_status != Status::OK {
+        return format!("GeoDB {}", server.geodb_status.text().to_lowercase());
+    }
+    String::new()
+}
+
+/// Render the status as plain text of at most [`WIDTH`] columns, for terminals and the MOTD:
+/// the overall status, a line per component, and a line per repository and server that isn't
+/// OK.
+pub fn render(data: &StatusPageData) -> String {
+    let mut lines = vec![
+        truncate(&format!(
+            "{}: {}",
+            data.title,
+            data.eessi_status.status.text()
+        )),
+        truncate(&format!("Updated {}", data.last_update)),
+        String::new(),
+    ];
+
+    for (name, status) in [
+        ("Stratum 0", data.stratum0.status),
+        ("Stratum 1", data.stratum1.status),
+        ("Sync servers", data.syncservers.status),
+        ("Repositories", data.repositories_status.status),
+    ] {
+        lines.push(row(name, status, ""));
+    }
+
+    let problems: Vec<String> = data
+        .repositories
+        .iter()
+        .filter(|r| r.status != Status::OK)
+        .map(|r| {
+            row(
+                &r.name,
+                r.status,
+                r.details.first().map(String::as_str).unwrap_or_default(),
+            )
+        })
+        .chain(
+            data.servers
+                .iter()
+                .filter(|s| s.status != Status::OK)
+                .map(|s| row(&s.name, s.status, &server_reason(s))),
+        )
+        .collect();
+    if !problems.is_empty() {
+        lines.push(String::new());
+        lines.extend(problems);
+    }
+
+    lines.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yare::parameterized;
+
+    fn ok_data() -> StatusPageData {
+        let mut data: StatusPageData =
+            serde_json::from_str(include_str!("testdata/status-schema-0.json")).unwrap();
+        data.eessi_status.status = Status::OK;
+        data.stratum0.status = Status::OK;
+        data.stratum1.status = Status::OK;
+        data.syncservers.status = Status::OK;
+        for server in &mut data.servers {
+            server.status = Status::OK;
+        }
+        data
+    }
+
+    fn degraded_data() -> StatusPageData {
+        let mut data = ok_data();
+        data.eessi_status.status = Status::DEGRADED;
+        data.stratum1.status = Status::DEGRADED;
+        data.repositories_status.status = Status::WARNING;
+        data.repositories[0].status = Status::WARNING;
+        data.repositories[0].details = vec![
+            "Revision 1234 is behind the stratum0 (1240) on aws-eu-central-s1.eessi.science and 2 other servers".to_string(),
+        ];
+        data.servers[1].status = Status::FAILED;
+        data.servers[1].stale_note =
+            Some("Failed to scrape, showing data from 2 hours ago".to_string());
+        data
+    }
+
+    #[parameterized(
+        ok = { ok_data(), include_str!("testdata/status-ok.txt") },
+        degraded = { degraded_data(), include_str!("testdata/status-degraded.txt") },
+    )]
+    fn test_render(data: StatusPageData, expected: &str) {
+        assert_eq!(render(&data), expected);
+    }
+
+    #[test]
+    fn test_render_width() {
+        let mut data = degraded_data();
+        data.title = "x".repeat(100);
+        data.repositories[0].name = "y".repeat(100);
+
+        let rendered = render(&data);
+
+        assert!(rendered.lines().all(|line| line.chars().count() <= WIDTH));
+        assert!(rendered.is_ascii());
+    }
+
+    #[parameterized(
+        short = { "EESSI", "EESSI" },
+        trailing_space = { "EESSI   ", "EESSI" },
+        exact = { &"x".repeat(80), &"x".repeat(80) },
+        long = { &"x".repeat(81), &format!("{}...", "x".repeat(77)) },
+    )]
+    fn test_truncate(line: &str, expected: &str) {
+        assert_eq!(truncate(line), expected);
+    }
+}
@@ -0,0 +1,425 @@
+use anyhow::{Context, Result};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::config::PrecompressConfig;
+use crate::dependencies::OutputSink;
+
+/// The extension of the gzipped copy of a file, next to it.
+pub const GZIP_EXTENSION: &str = "gz";
+
+/// The extension of the brotli copy of a file, next to it, written with the `brotli` feature.
+pub const BROTLI_EXTENSION: &str = "br";
+
+/// The extensions of the files worth compressing: text, and fonts without compression of their
+/// own, rather than images and WOFF fonts.
+const EXTENSIONS: [&str; 8] = ["html", "css", "js", "json", "svg", "txt", "ttf", "eot"];
+
+/// A compressed copy of a file in the destination, for the run report.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Compressed {
+    pub path: PathBuf,
+    pub bytes: u64,
+    pub compressed_bytes: u64,
+    /// Whether it was compressed in this run, rather than kept as the file didn't change.
+    pub written: bool,
+}
+
+/// A format of the compressed copies, next to the files.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Encoding {
+    Gzip,
+    Brotli,
+}
+
+const ENCODINGS: [Encoding; 2] = [Encoding::Gzip, Encoding::Brotli];
+
+impl Encoding {
+    fn extension(self) -> &'static str {
+        match self {
+            Encoding::Gzip => GZIP_EXTENSION,
+            Encoding::Brotli => BROTLI_EXTENSION,
+        }
+    }
+
+    /// Whether copies are written. Those of a format that isn't, left by a build that wrote
+    /// them, are deleted, as they would get outdated.
+    fn enabled(self) -> bool {
+        match self {
+            Encoding::Gzip => true,
+            Encoding::Brotli => cfg!(feature = "brotli"),
+        }
+    }
+
+    fn compress(self, data: &[u8], config: &PrecompressConfig) -> Result<Vec<u8>> {
+        match self {
+            Encoding::Gzip => gzip(data, config.level),
+            Encoding::Brotli => brotli(data, config.brotli_level),
+        }
+    }
+
+    /// The copy of `path`, such as `index.html.gz`.
+    fn path(self, path: &Path) -> PathBuf {
+        let mut name = path.as_os_str().to_owned();
+        name.push(".");
+        name.push(self.extension());
+        PathBuf::from(name)
+    }
+}
+
+/// Write a gzipped copy, and with the `brotli` feature a brotli one, next to every file in
+/// `destination` worth compressing, except hidden ones and those in `skip`, which change after
+/// this is called. A copy is only written again when its file changed since, and copies of
+/// files that are gone, or no longer compressed, are deleted, so a web server never serves an
+/// outdated one.
+pub fn precompress(
+    destination: &Path,
+    config: &PrecompressConfig,
+    skip: &[&str],
+    sink: &OutputSink,
+) -> Result<Vec<Compressed>> {
+    let mut compressed = vec![];
+    precompress_dir(destination, config, skip, &mut compressed, sink)?;
+    let written = compressed.iter().filter(|c| c.written).count();
+    info!(
+        "Compressed {} files in {:?}, {} kept as they didn't change",
+        written,
+        destination,
+        compressed.len() - written
+    );
+    Ok(compressed)
+}
+
+fn precompress_dir(
+    dir: &Path,
+    config: &PrecompressConfig,
+    skip: &[&str],
+    compressed: &mut Vec<Compressed>,
+    sink: &OutputSink,
+) -> Result<()> {
+    let mut entries = fs::read_dir(dir)
+        .context(format!("Failed to read directory {:?}", dir))?
+        .collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let path = entry.path();
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with('.') || skip.contains(&name.as_ref()) {
+            continue;
+        }
+        if entry.file_type()?.is_dir() {
+            precompress_dir(&path, config, skip, compressed, sink)?;
+        } else if ENCODINGS
+            .iter()
+            .any(|encoding| extension_of(&path) == Some(encoding.extension()))
+        {
+            // Only the copies this writes, not an archive someone put there.
+            let file = path.with_extension("");
+            if is_compressible(&file) && !file.exists() {
+                remove(&path, sink);
+            }
+        } else {
+            compressed.extend(precompress_file(&path, config, sink)?);
+        }
+    }
+    Ok(())
+}
+
+/// Compress `path` if it is worth it, in each format whose copy is missing or older than it.
+fn precompress_file(
+    path: &Path,
+    config: &PrecompressConfig,
+    sink: &OutputSink,
+) -> Result<Vec<Compressed>> {
+    let metadata = fs::metadata(path).context(format!("Failed to read {:?}", path))?;
+    let wanted = is_compressible(path) && metadata.len() >= config.min_bytes;
+    let mut copies = vec![];
+    let mut outdated = vec![];
+    for encoding in ENCODINGS {
+        let copy_path = encoding.path(path);
+        if !wanted || !encoding.enabled() {
+            remove(&copy_path, sink);
+            continue;
+        }
+        // The copy is written after the file, so it is newer unless the file changed since.
+        match fs::metadata(&copy_path) {
+            Ok(copy) if copy.modified()? > metadata.modified()? => copies.push(Compressed {
+                path: copy_path,
+                bytes: metadata.len(),
+                compressed_bytes: copy.len(),
+                written: false,
+            }),
+            _ => outdated.push(encoding),
+        }
+    }
+    if outdated.is_empty() {
+        return Ok(copies);
+    }
+
+    let contents = fs::read(path).context(format!("Failed to read {:?}", path))?;
+    for encoding in outdated {
+        let copy_path = encoding.path(path);
+        let compressed = encoding.compress(&contents, config)?;
+        if compressed.len() >= contents.len() {
+            remove(&copy_path, sink);
+            continue;
+        }
+        sink.atomic_write(&copy_path, &compressed)?;
+        copies.push(Compressed {
+            path: copy_path,
+            bytes: contents.len() as u64,
+            compressed_bytes: compressed.len() as u64,
+            written: true,
+        });
+    }
+    Ok(copies)
+}
+
+/// `data` as a gzip file, compressed at `level`, from 1 to 9.
+fn gzip(data: &[u8], level: u32) -> Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(vec![], Compression::new(level));
+    encoder.write_all(data)?;
+    Ok(encoder.finish()?)
+}
+
+/// `data` compressed with brotli at `level`, from 0 to 11.
+#[cfg(feature = "brotli")]
+fn brotli(data: &[u8], level: u32) -> Result<Vec<u8>> {
+    let mut compressed = vec![];
+    {
+        // The window brotli uses by default, of 4 MiB.
+        let mut writer = brotli::CompressorWriter::new(&mut compressed, 4096, level, 22);
+        writer.write_all(data)?;
+    }
+    Ok(compressed)
+}
+
+#[cfg(not(feature = "brotli"))]
+fn brotli(_data: &[u8], _level: u32) -> Result<Vec<u8>> {
+    anyhow::bail!("Built without the brotli feature")
+}
+
+fn extension_of(path: &Path) -> Option<&str> {
+    path.extension().and_then(|extension| extension.to_str())
+}
+
+fn is_compressible(path: &Path) -> bool {
+    extension_of(path).is_some_and(|extension| EXTENSIONS.contains(&extension))
+}
+
+/// Delete a compressed copy that would be outdated. Failing to only warns, as the pages are
+/// written all the same.
+fn remove(path: &Path, sink: &OutputSink) {
+    match sink.remove_file(path) {
+        Ok(()) => info!("Deleted outdated compressed copy {:?}", path),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => warn!(
+            "Failed to delete outdated compressed copy {:?}: {}",
+            path, e
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn config() -> PrecompressConfig {
+        PrecompressConfig {
+            enabled: true,
+            level: 6,
+            brotli_level: 5,
+            min_bytes: 100,
+        }
+    }
+
+    /// The gzipped copies of `compressed`, as those are written with and without the `brotli`
+    /// feature.
+    fn gzipped(compressed: Vec<Compressed>) -> Vec<Compressed> {
+        compressed
+            .into_iter()
+            .filter(|c| extension_of(&c.path) == Some(GZIP_EXTENSION))
+            .collect()
+    }
+
+    fn page() -> String {
+        include_str!("../templates/status.html").to_string()
+    }
+
+    #[test]
+    fn test_precompress() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let destination = dir.path();
+        fs::create_dir(destination.join("repos"))?;
+        fs::write(destination.join("index.html"), page())?;
+        fs::write(
+            destination.join("repos").join("software.eessi.io.json"),
+            page(),
+        )?;
+        fs::write(destination.join("tiny.css"), "body {}")?;
+        fs::write(destination.join("eessi-512px.png"), page())?;
+        fs::write(destination.join(".state.json"), page())?;
+        fs::write(destination.join("run_report.json"), page())?;
+
+        let compressed = gzipped(precompress(
+            destination,
+            &config(),
+            &["run_report.json"],
+            &OutputSink::default(),
+        )?);
+
+        assert_eq!(
+            compressed.iter().map(|c| &c.path).collect::<Vec<_>>(),
+            [
+                &destination.join("index.html.gz"),
+                &destination.join("repos").join("software.eessi.io.json.gz")
+            ]
+        );
+        assert!(compressed.iter().all(|c| c.written));
+        assert_eq!(compressed[0].bytes, page().len() as u64);
+        assert_eq!(
+            compressed[0].compressed_bytes,
+            fs::metadata(destination.join("index.html.gz"))?.len()
+        );
+        assert!(compressed[0].compressed_bytes < compressed[0].bytes / 2);
+        let mut decompressed = String::new();
+        flate2::read::GzDecoder::new(fs::File::open(destination.join("index.html.gz"))?)
+            .read_to_string(&mut decompressed)?;
+        assert_eq!(decompressed, page());
+        for name in [
+            "tiny.css",
+            "eessi-512px.png",
+            ".state.json",
+            "run_report.json",
+        ] {
+            assert!(
+                !Encoding::Gzip.path(&destination.join(name)).exists(),
+                "{}",
+                name
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_precompress_changed() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let destination = dir.path();
+        fs::write(destination.join("index.html"), page())?;
+        fs::write(destination.join("status.css"), page())?;
+        precompress(destination, &config(), &[], &OutputSink::default())?;
+        // Set explicitly, as files written right after each other may have the same time.
+        let hour = std::time::Duration::from_secs(3600);
+        let now = std::time::SystemTime::now();
+        fs::File::options()
+            .append(true)
+            .open(destination.join("status.css"))?
+            .set_modified(now - hour)?;
+
+        // Only the page changed.
+        fs::write(destination.join("index.html"), page().repeat(2))?;
+        fs::File::options()
+            .append(true)
+            .open(destination.join("index.html"))?
+            .set_modified(now + hour)?;
+        let compressed = gzipped(precompress(
+            destination,
+            &config(),
+            &[],
+            &OutputSink::default(),
+        )?);
+
+        assert_eq!(
+            compressed
+                .iter()
+                .map(|c| (c.path.file_name().unwrap().to_str().unwrap(), c.written))
+                .collect::<Vec<_>>(),
+            [("index.html.gz", true), ("status.css.gz", false)]
+        );
+        assert_eq!(compressed[0].bytes, 2 * page().len() as u64);
+        Ok(())
+    }
+
+    #[test]
+    fn test_precompress_outdated() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let destination = dir.path();
+        fs::write(destination.join("status.1a2b3c4d.css"), page())?;
+        fs::write(destination.join("index.html"), page())?;
+        fs::write(destination.join("data.tar.gz"), page())?;
+        precompress(destination, &config(), &[], &OutputSink::default())?;
+
+        // The hashed stylesheet was deleted, and the page got too small to compress.
+        fs::remove_file(destination.join("status.1a2b3c4d.css"))?;
+        fs::write(destination.join("index.html"), "<html></html>")?;
+        let compressed = precompress(destination, &config(), &[], &OutputSink::default())?;
+
+        assert!(compressed.is_empty());
+        assert!(!destination.join("status.1a2b3c4d.css.gz").exists());
+        assert!(!destination.join("index.html.gz").exists());
+        assert!(destination.join("data.tar.gz").exists());
+        Ok(())
+    }
+
+    #[cfg(feature = "brotli")]
+    #[test]
+    fn test_precompress_brotli() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let destination = dir.path();
+        fs::write(destination.join("index.html"), page())?;
+        fs::write(destination.join("status.css"), page())?;
+        // Set explicitly, as files written right after each other may have the same time.
+        fs::File::options()
+            .append(true)
+            .open(destination.join("index.html"))?
+            .set_modified(std::time::SystemTime::now() - std::time::Duration::from_secs(3600))?;
+        precompress(destination, &config(), &[], &OutputSink::default())?;
+
+        // The stylesheet was deleted.
+        fs::remove_file(destination.join("status.css"))?;
+        let compressed = precompress(destination, &config(), &[], &OutputSink::default())?;
+
+        assert_eq!(
+            compressed
+                .iter()
+                .map(|c| (c.path.file_name().unwrap().to_str().unwrap(), c.written))
+                .collect::<Vec<_>>(),
+            [("index.html.gz", false), ("index.html.br", false)]
+        );
+        assert!(compressed[1].compressed_bytes < compressed[1].bytes / 2);
+        let mut decompressed = String::new();
+        brotli::Decompressor::new(fs::File::open(destination.join("index.html.br"))?, 4096)
+            .read_to_string(&mut decompressed)?;
+        assert_eq!(decompressed, page());
+        assert!(!destination.join("status.css.br").exists());
+        Ok(())
+    }
+
+    #[cfg(not(feature = "brotli"))]
+    #[test]
+    fn test_precompress_without_brotli() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let destination = dir.path();
+        fs::write(destination.join("index.html"), page())?;
+        fs::write(destination.join("status.css"), page())?;
+        // Left by a build with the brotli feature.
+        fs::write(destination.join("index.html.br"), "outdated")?;
+        fs::write(destination.join("status.css.br"), "outdated")?;
+        fs::write(destination.join("archive.br"), "not a copy")?;
+
+        let compressed = precompress(destination, &config(), &[], &OutputSink::default())?;
+
+        assert_eq!(compressed.len(), 2);
+        assert!(!destination.join("index.html.br").exists());
+        assert!(!destination.join("status.css.br").exists());
+        assert!(destination.join("archive.br").exists());
+        Ok(())
+    }
+}
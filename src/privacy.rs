@@ -0,0 +1,288 @@
+use serde::{Serialize, Serializer};
+use serde_json::{Map, Value};
+
+use crate::config::{ConfigFile, PrivacyConfig};
+use crate::events::Event;
+use crate::models::{StatusPageData, StratumStatus};
+use crate::templating::RepoDetail;
+
+/// Whether `text` names one of the hidden servers.
+fn mentions_hidden(text: &str, privacy: &PrivacyConfig) -> bool {
+    privacy
+        .hidden_servers
+        .iter()
+        .any(|hostname| text.contains(hostname.as_str()))
+}
+
+fn retain_visible(lines: &mut Vec<String>, privacy: &PrivacyConfig) {
+    lines.retain(|line| !mentions_hidden(line, privacy));
+}
+
+fn redact_stratum(stratum: &mut StratumStatus, privacy: &PrivacyConfig) {
+    stratum
+        .servers
+        .retain(|server| !privacy.hidden_servers.contains(&server.name));
+    retain_visible(&mut stratum.details, privacy);
+}
+
+/// Remove the hidden servers from the data, and the details that name them. The statuses they
+/// contributed to are kept, as are the metrics, which come from the scrapes themselves.
+pub fn redact(data: &mut StatusPageData, privacy: &PrivacyConfig) {
+    if privacy.hidden_servers.is_empty() {
+        return;
+    }
+    data.servers
+        .retain(|server| !privacy.hidden_servers.contains(&server.name));
+    for stratum in [
+        &mut data.stratum0,
+        &mut data.stratum1,
+        &mut data.syncservers,
+    ] {
+        redact_stratum(stratum, privacy);
+    }
+    for repository in &mut data.repositories {
+        retain_visible(&mut repository.details, privacy);
+    }
+    retain_visible(&mut data.repositories_status.details, privacy);
+    for row in &mut data.repository_matrix {
+        row.cells
+            .retain(|cell| !privacy.hidden_servers.contains(&cell.server));
+    }
+}
+
+/// Remove the hidden servers from events, which may have been recorded before they were hidden,
+/// and the regressions on them.
+pub fn redact_events(events: &mut Vec<Event>, privacy: &PrivacyConfig) {
+    events.retain(|event| {
+        event
+            .regression
+            .as_ref()
+            .is_none_or(|regression| !privacy.hidden_servers.contains(&regression.server))
+    });
+    for event in events {
+        event
+            .servers
+            .retain(|server| !privacy.hidden_servers.contains(server));
+        retain_visible(&mut event.conditions, privacy);
+    }
+}
+
+/// Remove the hidden servers from the detail page of a repository.
+pub fn redact_repo_detail(detail: &mut RepoDetail, privacy: &PrivacyConfig) {
+    detail
+        .servers
+        .retain(|server| !privacy.hidden_servers.contains(&server.hostname));
+    retain_visible(&mut detail.details, privacy);
+}
+
+/// What `redact` and `redact_events` remove, kept for the internal pages, as they are rendered
+/// from the same run as the public ones.
+#[derive(Default)]
+pub struct Unredacted {
+    fields: Map<String, Value>,
+}
+
+impl Unredacted {
+    /// Keep the fields of `data` that `redact` changes, before it does.
+    pub fn keep(data: &StatusPageData) -> serde_json::Result<Self> {
+        let fields = [
+            ("servers", serde_json::to_value(&data.servers)?),
+            ("stratum0", serde_json::to_value(&data.stratum0)?),
+            ("stratum1", serde_json::to_value(&data.stratum1)?),
+            ("syncservers", serde_json::to_value(&data.syncservers)?),
+            ("repositories", serde_json::to_value(&data.repositories)?),
+            (
+                "repository_matrix",
+                serde_json::to_value(&data.repository_matrix)?,
+            ),
+            (
+                "repositories_status",
+                serde_json::to_value(&data.repositories_status)?,
+            ),
+        ];
+        Ok(Unredacted {
+            fields: fields
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        })
+    }
+
+    /// Keep the events, before `redact_events` changes them.
+    pub fn keep_events(&mut self, events: &[Event]) -> serde_json::Result<()> {
+        self.fields
+            .insert("recent_events".to_string(), serde_json::to_value(events)?);
+        Ok(())
+    }
+
+    /// The redacted `data` with what was kept put back, and the whole configuration, for an
+    /// internal page.
+    pub fn restore(&self, data: &StatusPageData) -> serde_json::Result<Value> {
+        let mut restored = serde_json::to_value(data)?;
+        if let Value::Object(object) = &mut restored {
+            object.extend(self.fields.clone());
+            object.insert("config".to_string(), serde_json::to_value(&data.config)?);
+        }
+        Ok(restored)
+    }
+}
+
+/// Whether the configuration is left out of status.json.
+pub fn is_config_hidden(config: &ConfigFile) -> bool {
+    !config.privacy.expose_config
+}
+
+/// Serialize the configuration without the hidden servers, nor the list of them.
+pub fn serialize_config<S: Serializer>(
+    config: &ConfigFile,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    if config.privacy.hidden_servers.is_empty() {
+        return config.serialize(serializer);
+    }
+    let mut config = config.clone();
+    let hidden = std::mem::take(&mut config.privacy.hidden_servers);
+    config.servers.retain(|server| {
+        !hidden
+            .iter()
+            .any(|h| server.server.hostname.to_str() == h.as_str())
+    });
+    config.serialize(serializer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Status;
+    use crate::templating::ServerStatus;
+    use cvmfs_server_scraper::Hostname;
+    use std::sync::Arc;
+    use yare::parameterized;
+
+    const HIDDEN: &str = "sync.internal.example.com";
+
+    fn data(expose_config: bool) -> StatusPageData {
+        let mut data: StatusPageData =
+            serde_json::from_str(include_str!("testdata/status-schema-0.json")).unwrap();
+        let mut server: ServerStatus =
+            serde_json::from_value(serde_json::to_value(&data.servers[0]).unwrap()).unwrap();
+        server.name = HIDDEN.to_string();
+        data.syncservers
+            .servers
+            .push(serde_json::from_value(serde_json::to_value(&server).unwrap()).unwrap());
+        data.servers.push(server);
+        data.syncservers
+            .details
+            .push(format!("{} is unreachable", HIDDEN));
+        data.repository_matrix = serde_json::from_value(serde_json::json!([{
+            "repository": "software.eessi.io",
+            "reference_revision": 10,
+            "cells": [
+                { "server": "s0.example.com", "type": "stratum0", "revision": 10, "lag": 0,
+                  "status": "OK", "status_class": Status::OK.class() },
+                { "server": HIDDEN, "type": "syncserver", "revision": 9, "lag": 1,
+                  "status": "WARNING", "status_class": Status::WARNING.class() }
+            ]
+        }]))
+        .unwrap();
+        let config = Arc::make_mut(&mut data.config);
+        let mut server_config = config.servers[0].clone();
+        server_config.server.hostname = Hostname::try_from(HIDDEN.to_string()).unwrap();
+        config.servers.push(server_config);
+        config.privacy = PrivacyConfig {
+            expose_config,
+            hidden_servers: vec![HIDDEN.to_string()],
+        };
+        data
+    }
+
+    #[parameterized(
+        exposed = { true },
+        not_exposed = { false },
+    )]
+    fn test_hidden_server_not_in_json(expose_config: bool) {
+        let mut data = data(expose_config);
+        let servers = data.servers.len();
+        let privacy = data.config.privacy.clone();
+
+        redact(&mut data, &privacy);
+        let json = serde_json::to_string(&data).unwrap();
+
+        assert!(!json.contains(HIDDEN), "{}", json);
+        assert_eq!(data.servers.len(), servers - 1);
+        assert_eq!(data.repository_matrix[0].cells.len(), 1);
+        assert_eq!(json.contains("\"config\""), expose_config);
+        // Still there for the metrics and the rules.
+        assert!(data
+            .config
+            .servers
+            .iter()
+            .any(|s| s.server.hostname.to_str() == HIDDEN));
+    }
+
+    #[test]
+    fn test_restore() {
+        let mut data = data(false);
+        let privacy = data.config.privacy.clone();
+        let mut unredacted = Unredacted::keep(&data).unwrap();
+        let events = vec![Event {
+            servers: vec![HIDDEN.to_string()],
+            ..serde_json::from_value(serde_json::json!({
+                "timestamp": "2025-10-02T07:28:00Z",
+                "component": "syncservers",
+                "from": "OK",
+                "to": "FAILED",
+            }))
+            .unwrap()
+        }];
+        unredacted.keep_events(&events).unwrap();
+
+        redact(&mut data, &privacy);
+        let restored = unredacted.restore(&data).unwrap();
+
+        assert_eq!(
+            restored["servers"].as_array().unwrap().len(),
+            data.servers.len() + 1
+        );
+        assert!(restored["syncservers"].to_string().contains(HIDDEN));
+        assert_eq!(
+            restored["repository_matrix"][0]["cells"][1]["server"],
+            HIDDEN
+        );
+        assert_eq!(restored["recent_events"][0]["servers"][0], HIDDEN);
+        assert_eq!(restored["config"]["privacy"]["hidden_servers"][0], HIDDEN);
+        assert!(!serde_json::to_string(&data).unwrap().contains(HIDDEN));
+    }
+
+    #[test]
+    fn test_without_config_loads() {
+        let mut data = data(false);
+        let privacy = data.config.privacy.clone();
+        redact(&mut data, &privacy);
+
+        let json = serde_json::to_string(&data).unwrap();
+        let loaded: StatusPageData = serde_json::from_str(&json).unwrap();
+
+        assert!(loaded.config.servers.is_empty());
+        assert!(loaded.config.privacy.expose_config);
+    }
+
+    #[test]
+    fn test_redact_events() {
+        let privacy = data(true).config.privacy.clone();
+        let mut events: Vec<Event> = serde_json::from_value(serde_json::json!([{
+            "timestamp": "2025-10-02T07:28:00Z",
+            "component": "syncservers",
+            "from": "OK",
+            "to": "FAILED",
+            "conditions": [format!("{} is down", HIDDEN), "quorum lost"],
+            "servers": [HIDDEN, "aws-s1.eessi.io"],
+        }]))
+        .unwrap();
+
+        redact_events(&mut events, &privacy);
+
+        assert_eq!(events[0].servers, vec!["aws-s1.eessi.io"]);
+        assert_eq!(events[0].conditions, vec!["quorum lost"]);
+    }
+}
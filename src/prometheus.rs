@@ -1,5 +1,36 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use log::{info, trace, warn};
+use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::fmt::Write as _;
+use std::path::Path;
+
+use crate::dependencies::OutputSink;
+use crate::models::{Server, Status, StatusManager, StatusPageData, ToEESSILabel};
+use crate::state::RunState;
+
+/// The exposition format of the metrics file and the `/metrics` endpoint.
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum MetricsFormat {
+    /// The classic Prometheus text format, version 0.0.4.
+    #[default]
+    Prometheus,
+    #[value(name = "openmetrics")]
+    OpenMetrics,
+}
+
+impl MetricsFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            MetricsFormat::Prometheus => "text/plain; version=0.0.4",
+            MetricsFormat::OpenMetrics => {
+                "application/openmetrics-text; version=1.0.0; charset=utf-8"
+            }
+        }
+    }
+}
 
 #[derive(Clone, Copy)]
 #[allow(dead_code)]
@@ -11,6 +42,13 @@ pub enum MetricType {
     Untyped,
 }
 impl MetricType {
+    fn as_openmetrics_str(self) -> &'static str {
+        match self {
+            MetricType::Untyped => "unknown",
+            other => other.as_str(),
+        }
+    }
+
     fn as_str(self) -> &'static str {
         match self {
             MetricType::Gauge => "gauge",
@@ -27,6 +65,9 @@ pub struct Sample {
     pub labels: Vec<(String, String)>,
     pub value: f64,
     pub timestamp_ms: Option<i64>,
+    /// Appended to the metric name, for the `_bucket`, `_sum` and `_count` series of a
+    /// histogram.
+    pub suffix: &'static str,
 }
 
 impl Sample {
@@ -35,6 +76,7 @@ impl Sample {
             labels: Vec::new(),
             value,
             timestamp_ms: None,
+            suffix: "",
         }
     }
 
@@ -51,9 +93,123 @@ impl Sample {
     }
 }
 
+/// A sample as rendered, see `MetricsBuilder::flatten`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlatSample {
+    pub name: String,
+    pub labels: Vec<(String, String)>,
+    pub value: f64,
+}
+
+/// The metrics as a JSON document, see `MetricsBuilder::build_json`.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct JsonMetrics {
+    pub metrics: Vec<JsonMetric>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct JsonMetric {
+    pub name: String,
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub mtype: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub help: Option<String>,
+    pub samples: Vec<JsonSample>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct JsonSample {
+    /// `_bucket`, `_sum` or `_count` for the series of a histogram or summary.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub suffix: String,
+    pub labels: BTreeMap<String, String>,
+    #[serde(with = "json_value")]
+    pub value: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp_ms: Option<i64>,
+}
+
+/// JSON has no NaN or infinity, so those values are the strings `NaN`, `+Inf` and `-Inf`, as
+/// in the text format. Other values are numbers.
+mod json_value {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+        if value.is_finite() {
+            serializer.serialize_f64(*value)
+        } else {
+            serializer.serialize_str(&super::format_value(*value))
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Value {
+            Number(f64),
+            String(String),
+        }
+
+        match Value::deserialize(deserializer)? {
+            Value::Number(value) => Ok(value),
+            Value::String(s) => match s.as_str() {
+                "NaN" => Ok(f64::NAN),
+                "+Inf" => Ok(f64::INFINITY),
+                "-Inf" => Ok(f64::NEG_INFINITY),
+                other => Err(de::Error::custom(format!("Invalid value {:?}", other))),
+            },
+        }
+    }
+}
+
+/// Observations counted into buckets, which can be kept across runs.
+///
+/// The bucket counts are cumulative, each counting the observations less than or equal to
+/// its upper bound. The `+Inf` bucket is implicit, it is the total count.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Histogram {
+    buckets: Vec<f64>,
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    /// An empty histogram with these bucket upper bounds. The bounds are sorted, and
+    /// duplicates, `NaN` and infinite bounds are dropped.
+    pub fn new(buckets: &[f64]) -> Self {
+        let mut buckets: Vec<f64> = buckets.iter().copied().filter(|b| b.is_finite()).collect();
+        buckets.sort_by(f64::total_cmp);
+        buckets.dedup();
+
+        Histogram {
+            counts: vec![0; buckets.len()],
+            buckets,
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    pub fn observe(&mut self, value: f64) {
+        for (bound, count) in self.buckets.iter().zip(self.counts.iter_mut()) {
+            if value <= *bound {
+                *count += 1;
+            }
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+
+    /// Whether the histogram uses these bucket upper bounds, as given to `new`.
+    pub fn has_buckets(&self, buckets: &[f64]) -> bool {
+        Histogram::new(buckets).buckets == self.buckets
+    }
+}
+
 struct MetricDef {
     help: Option<String>,
     mtype: Option<MetricType>,
+    unit: Option<String>,
     samples: Vec<Sample>,
 }
 impl MetricDef {
@@ -61,21 +217,96 @@ impl MetricDef {
         Self {
             help: None,
             mtype: None,
+            unit: None,
             samples: Vec::new(),
         }
     }
 }
 
+/// What to do with samples of a metric having the same labels, which Prometheus rejects.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum DuplicateSamples {
+    /// Keep the sample added last, logging a warning.
+    #[default]
+    KeepLast,
+    /// Fail to render.
+    #[allow(dead_code)]
+    Reject,
+}
+
 pub struct MetricsBuilder {
     metrics: BTreeMap<String, MetricDef>,
+    prefix: String,
+    constant_labels: Vec<(String, String)>,
+    duplicate_samples: DuplicateSamples,
+    suppress_timestamps: bool,
+}
+
+impl Default for MetricsBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
 }
+
 impl MetricsBuilder {
     pub fn new() -> Self {
         Self {
             metrics: BTreeMap::new(),
+            prefix: String::new(),
+            constant_labels: Vec::new(),
+            duplicate_samples: DuplicateSamples::default(),
+            suppress_timestamps: false,
         }
     }
 
+    /// Leave out the timestamps of all samples when rendering, so Prometheus uses the time of
+    /// the scrape.
+    pub fn suppress_timestamps(&mut self, suppress: bool) -> &mut Self {
+        self.suppress_timestamps = suppress;
+        self
+    }
+
+    /// The timestamp of a sample as rendered.
+    fn timestamp(&self, sample: &Sample) -> Option<i64> {
+        sample.timestamp_ms.filter(|_| !self.suppress_timestamps)
+    }
+
+    /// How samples with the same labels are handled when rendering.
+    #[allow(dead_code)]
+    pub fn with_duplicate_samples(mut self, duplicate_samples: DuplicateSamples) -> Self {
+        self.duplicate_samples = duplicate_samples;
+        self
+    }
+
+    /// Prefix the name of every metric when rendering.
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Add these labels to every sample when rendering, before the sample's own labels. A
+    /// sample's own label wins over a constant label with the same name.
+    pub fn with_constant_labels<K, V>(mut self, labels: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.constant_labels = labels
+            .into_iter()
+            .map(|(k, v)| (k.into(), v.into()))
+            .collect();
+        self
+    }
+
+    /// The labels of a sample, with the constant labels merged in.
+    fn labels<'a>(&'a self, sample: &'a Sample) -> Vec<&'a (String, String)> {
+        self.constant_labels
+            .iter()
+            .filter(|(k, _)| !sample.labels.iter().any(|(own, _)| own == k))
+            .chain(sample.labels.iter())
+            .collect()
+    }
+
     pub fn set_help(&mut self, name: &str, help: impl Into<String>) -> &mut Self {
         self.metrics
             .entry(name.to_string())
@@ -92,6 +323,16 @@ impl MetricsBuilder {
         self
     }
 
+    /// The unit of the metric, only rendered in the OpenMetrics format. OpenMetrics requires
+    /// the metric name to end in the unit, such as `_seconds`.
+    pub fn set_unit(&mut self, name: &str, unit: impl Into<String>) -> &mut Self {
+        self.metrics
+            .entry(name.to_string())
+            .or_insert_with(MetricDef::new)
+            .unit = Some(unit.into());
+        self
+    }
+
     pub fn add_sample(&mut self, name: &str, sample: Sample) -> &mut Self {
         self.metrics
             .entry(name.to_string())
@@ -120,7 +361,6 @@ impl MetricsBuilder {
         self.add_sample(name, s)
     }
 
-    #[allow(dead_code)]
     pub fn add_counter(
         &mut self,
         name: &str,
@@ -160,37 +400,336 @@ impl MetricsBuilder {
         self.add_sample(name, s)
     }
 
+    /// A histogram of `observations`, see `Histogram::new` for how `buckets` are handled.
+    #[allow(dead_code)]
+    pub fn add_histogram(
+        &mut self,
+        name: &str,
+        help: &str,
+        observations: &[f64],
+        buckets: &[f64],
+        labels: &[(&str, &str)],
+        ts_ms: Option<i64>,
+    ) -> &mut Self {
+        let mut histogram = Histogram::new(buckets);
+        for observation in observations {
+            histogram.observe(*observation);
+        }
+        self.add_histogram_data(name, help, &histogram, labels, ts_ms)
+    }
+
+    /// A histogram of observations already counted into buckets, such as across runs.
+    pub fn add_histogram_data(
+        &mut self,
+        name: &str,
+        help: &str,
+        histogram: &Histogram,
+        labels: &[(&str, &str)],
+        ts_ms: Option<i64>,
+    ) -> &mut Self {
+        self.set_help(name, help)
+            .set_type(name, MetricType::Histogram);
+        let labels: Vec<(String, String)> = labels
+            .iter()
+            .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+            .collect();
+        let sample = |suffix, value, le: Option<f64>| {
+            let mut s = Sample::new(value);
+            s.labels = labels.clone();
+            if let Some(le) = le {
+                s.labels.push(("le".to_string(), format_value(le)));
+            }
+            s.timestamp_ms = ts_ms;
+            s.suffix = suffix;
+            s
+        };
+
+        for (bound, count) in histogram.buckets.iter().zip(histogram.counts.iter()) {
+            self.add_sample(name, sample("_bucket", *count as f64, Some(*bound)));
+        }
+        self.add_sample(
+            name,
+            sample("_bucket", histogram.count as f64, Some(f64::INFINITY)),
+        )
+        .add_sample(name, sample("_sum", histogram.sum, None))
+        .add_sample(name, sample("_count", histogram.count as f64, None))
+    }
+
+    /// A summary of precomputed `(quantile, value)` pairs, with the sum and count of the
+    /// observations. Fails if a quantile is outside [0, 1] or the sum is NaN.
+    #[allow(dead_code, clippy::too_many_arguments)]
+    pub fn add_summary(
+        &mut self,
+        name: &str,
+        help: &str,
+        quantiles: &[(f64, f64)],
+        sum: f64,
+        count: u64,
+        labels: &[(&str, &str)],
+        ts_ms: Option<i64>,
+    ) -> Result<&mut Self, String> {
+        if let Some((quantile, _)) = quantiles
+            .iter()
+            .find(|(quantile, _)| !(0.0..=1.0).contains(quantile))
+        {
+            return Err(format!(
+                "{}: quantile {} is outside [0, 1]",
+                name,
+                format_value(*quantile)
+            ));
+        }
+        if sum.is_nan() {
+            return Err(format!("{}: the sum is NaN", name));
+        }
+
+        self.set_help(name, help)
+            .set_type(name, MetricType::Summary);
+        let labels: Vec<(String, String)> = labels
+            .iter()
+            .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+            .collect();
+        let sample = |suffix, value, quantile: Option<f64>| {
+            let mut s = Sample::new(value);
+            s.labels = labels.clone();
+            if let Some(quantile) = quantile {
+                s.labels
+                    .push(("quantile".to_string(), format_value(quantile)));
+            }
+            s.timestamp_ms = ts_ms;
+            s.suffix = suffix;
+            s
+        };
+
+        for (quantile, value) in quantiles {
+            self.add_sample(name, sample("", *value, Some(*quantile)));
+        }
+        Ok(self
+            .add_sample(name, sample("_sum", sum, None))
+            .add_sample(name, sample("_count", count as f64, None)))
+    }
+
+    /// The samples of a metric in a deterministic order, sorted by their labels, with the
+    /// samples of a histogram or summary kept together and their buckets and quantiles in the
+    /// order they were added. Duplicates are handled according to `duplicate_samples`.
+    fn samples<'a>(&self, name: &str, def: &'a MetricDef) -> Result<Vec<&'a Sample>, String> {
+        let summary = matches!(def.mtype, Some(MetricType::Summary));
+        // The labels identifying a series, without the bucket or quantile label.
+        let series_labels = |s: &'a Sample| -> Vec<&'a (String, String)> {
+            s.labels
+                .iter()
+                .filter(|(k, _)| {
+                    !(s.suffix == "_bucket" && k == "le" || summary && k == "quantile")
+                })
+                .collect()
+        };
+        let suffix_rank = |s: &Sample| match s.suffix {
+            "_sum" => 1,
+            "_count" => 2,
+            _ => 0,
+        };
+        // Labels are compared regardless of their order.
+        let identity = |s: &'a Sample| {
+            let mut labels: Vec<&(String, String)> = s.labels.iter().collect();
+            labels.sort();
+            (s.suffix, labels)
+        };
+
+        let mut last = BTreeMap::new();
+        for (i, s) in def.samples.iter().enumerate() {
+            if let Some(previous) = last.insert(identity(s), i) {
+                let message = format!(
+                    "Duplicate sample {}{} with labels {:?}",
+                    name, s.suffix, def.samples[previous].labels
+                );
+                match self.duplicate_samples {
+                    DuplicateSamples::KeepLast => warn!("{}, keeping the last value", message),
+                    DuplicateSamples::Reject => return Err(message),
+                }
+            }
+        }
+
+        let mut samples: Vec<&Sample> = def
+            .samples
+            .iter()
+            .enumerate()
+            .filter(|(i, s)| last[&identity(s)] == *i)
+            .map(|(_, s)| s)
+            .collect();
+        samples.sort_by(|a, b| {
+            series_labels(a)
+                .cmp(&series_labels(b))
+                .then(suffix_rank(a).cmp(&suffix_rank(b)))
+        });
+        Ok(samples)
+    }
+
+    /// Every sample in rendering order, named and labelled as rendered, for other outputs to
+    /// share. Timestamps are left out, as those outputs use the time of the run.
+    pub fn flatten(&self) -> Result<Vec<FlatSample>, String> {
+        let mut flat = Vec::new();
+        for (name, def) in self.metrics.iter() {
+            let name = format!("{}{}", self.prefix, name);
+            for s in self.samples(&name, def)? {
+                flat.push(FlatSample {
+                    name: format!("{}{}", name, s.suffix),
+                    labels: self.labels(s).into_iter().cloned().collect(),
+                    value: s.value,
+                });
+            }
+        }
+        Ok(flat)
+    }
+
+    /// The metrics as a JSON document, for tooling that doesn't parse the text format.
+    /// Metrics and samples are in the same order as in the text format.
+    pub fn to_json(&self) -> Result<JsonMetrics, String> {
+        let mut metrics = Vec::new();
+        for (name, def) in self.metrics.iter() {
+            let name = format!("{}{}", self.prefix, name);
+            let samples = self
+                .samples(&name, def)?
+                .into_iter()
+                .map(|s| JsonSample {
+                    suffix: s.suffix.to_string(),
+                    labels: self.labels(s).into_iter().cloned().collect(),
+                    value: s.value,
+                    timestamp_ms: self.timestamp(s),
+                })
+                .collect();
+            metrics.push(JsonMetric {
+                name,
+                mtype: def.mtype.map(|t| t.as_str().to_string()),
+                help: def.help.clone(),
+                samples,
+            });
+        }
+        Ok(JsonMetrics { metrics })
+    }
+
+    /// Render to the JSON document of `to_json`.
+    pub fn build_json(&self) -> Result<String, String> {
+        let json = self.to_json()?;
+        serde_json::to_string_pretty(&json).map_err(|e| e.to_string())
+    }
+
+    /// Render in the given format.
+    pub fn render(&self, format: MetricsFormat) -> Result<String, String> {
+        match format {
+            MetricsFormat::Prometheus => self.build(),
+            MetricsFormat::OpenMetrics => self.build_openmetrics(),
+        }
+    }
+
     /// Render to Prometheus text exposition format.
-    pub fn build(self) -> String {
+    pub fn build(&self) -> Result<String, String> {
         let mut out = String::with_capacity(1024);
-        for (name, def) in self.metrics {
+        for (name, def) in self.metrics.iter() {
+            let name = format!("{}{}", self.prefix, name);
             if let Some(help) = &def.help {
                 let _ = writeln!(&mut out, "# HELP {} {}", name, escape_help(help));
             }
             if let Some(mt) = def.mtype {
                 let _ = writeln!(&mut out, "# TYPE {} {}", name, mt.as_str());
             }
-            for s in def.samples {
-                let _ = write!(&mut out, "{}", name);
-                if !s.labels.is_empty() {
-                    let _ = write!(&mut out, "{{");
-                    for (i, (k, v)) in s.labels.iter().enumerate() {
-                        if i > 0 {
-                            let _ = write!(&mut out, ",");
-                        }
-                        let _ = write!(&mut out, "{}=\"{}\"", k, escape_label(v));
-                    }
-                    let _ = write!(&mut out, "}}");
-                }
-                let _ = write!(&mut out, " {}", format_value(s.value));
-                if let Some(ts) = s.timestamp_ms {
+            for s in self.samples(&name, def)? {
+                let name = format!("{}{}", name, s.suffix);
+                write_sample(&mut out, &name, &self.labels(s), s.value);
+                if let Some(ts) = self.timestamp(s) {
                     let _ = write!(&mut out, " {}", ts);
                 }
                 let _ = writeln!(&mut out);
             }
         }
-        out
+        Ok(out)
+    }
+
+    /// Render to OpenMetrics text exposition format.
+    ///
+    /// Counters are described by their family name without `_total`, with the samples named
+    /// with it. Timestamps are in seconds, and the output ends with `# EOF`.
+    pub fn build_openmetrics(&self) -> Result<String, String> {
+        let mut out = String::with_capacity(1024);
+        for (name, def) in self.metrics.iter() {
+            let name = format!("{}{}", self.prefix, name);
+            let counter = matches!(def.mtype, Some(MetricType::Counter));
+            let family = if counter {
+                name.strip_suffix("_total").unwrap_or(&name)
+            } else {
+                name.as_str()
+            };
+            let sample_name = if counter {
+                format!("{}_total", family)
+            } else {
+                name.clone()
+            };
+
+            if let Some(mt) = def.mtype {
+                let _ = writeln!(&mut out, "# TYPE {} {}", family, mt.as_openmetrics_str());
+            }
+            if let Some(unit) = &def.unit {
+                let _ = writeln!(&mut out, "# UNIT {} {}", family, unit);
+            }
+            if let Some(help) = &def.help {
+                let _ = writeln!(
+                    &mut out,
+                    "# HELP {} {}",
+                    family,
+                    escape_openmetrics_help(help)
+                );
+            }
+            for s in self.samples(&name, def)? {
+                let sample_name = format!("{}{}", sample_name, s.suffix);
+                write_sample(&mut out, &sample_name, &self.labels(s), s.value);
+                if let Some(ts) = self.timestamp(s) {
+                    let _ = write!(
+                        &mut out,
+                        " {}.{:03}",
+                        ts.div_euclid(1000),
+                        ts.rem_euclid(1000)
+                    );
+                }
+                let _ = writeln!(&mut out);
+            }
+        }
+        out.push_str("# EOF\n");
+        Ok(out)
+    }
+}
+
+/// Write the name, labels and value of a sample, which are the same in both formats.
+fn write_sample(out: &mut String, name: &str, labels: &[&(String, String)], value: f64) {
+    let _ = write!(out, "{}", name);
+    if !labels.is_empty() {
+        let _ = write!(out, "{{");
+        for (i, (k, v)) in labels.iter().enumerate() {
+            if i > 0 {
+                let _ = write!(out, ",");
+            }
+            let _ = write!(out, "{}=\"{}\"", k, escape_label(v));
+        }
+        let _ = write!(out, "}}");
     }
+    let _ = write!(out, " {}", format_value(value));
+}
+
+/// Whether `name` is a valid metric name, `[a-zA-Z_:][a-zA-Z0-9_:]*`.
+pub fn is_valid_metric_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == ':')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+}
+
+/// Whether `name` is a valid label name, `[a-zA-Z_][a-zA-Z0-9_]*` and not starting with the
+/// reserved `__`.
+pub fn is_valid_label_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    !name.starts_with("__")
+        && chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
 }
 
 fn escape_label(s: &str) -> String {
@@ -208,6 +747,10 @@ fn escape_label(s: &str) -> String {
 fn escape_help(s: &str) -> String {
     s.replace('\n', r"\n")
 }
+/// OpenMetrics escapes help text like label values.
+fn escape_openmetrics_help(s: &str) -> String {
+    escape_label(s)
+}
 fn format_value(v: f64) -> String {
     if v.is_nan() {
         "NaN".to_string()
@@ -221,3 +764,1055 @@ fn format_value(v: f64) -> String {
         format!("{}", v)
     }
 }
+
+/// Write the metrics file. The temporary file is created next to it, so the rename is atomic
+/// even if the file is on another filesystem than the destination, as a textfile collector
+/// directory may be.
+pub fn write_prometheus_metrics(path: &Path, text: &str, sink: &OutputSink) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        sink.create_dir_all(dir)?;
+    }
+    sink.atomic_write(path, text.as_bytes())?;
+    info!("Prometheus metrics file written to: {:?}", path);
+    Ok(())
+}
+
+/// Write the metrics as a JSON document.
+pub fn write_metrics_json(path: &Path, builder: &MetricsBuilder, sink: &OutputSink) -> Result<()> {
+    let json = builder.build_json().map_err(anyhow::Error::msg)?;
+    sink.atomic_write(path, json.as_bytes())?;
+    info!("Metrics JSON written to: {:?}", path);
+    Ok(())
+}
+
+/// The metrics of a run. The per server metrics and the counters kept across runs are only
+/// added with the scraped servers and the state, which a run rendering from JSON doesn't have.
+/// `sink_failures` are the name, help and count of the failed sends of each configured sink.
+pub fn status_metrics(
+    status_page_data: &StatusPageData,
+    status_manager: Option<&StatusManager>,
+    run_state: Option<&RunState>,
+    timestamp: &DateTime<Utc>,
+    sink_failures: &[(&str, &str, u64)],
+) -> MetricsBuilder {
+    use crate::models::StatusLevel;
+
+    trace!("Generating Prometheus metrics");
+
+    let ts = timestamp.timestamp_millis();
+
+    let metrics_config = &status_page_data.config.metrics;
+    let mut b = MetricsBuilder::new()
+        .with_prefix(metrics_config.prefix.clone())
+        .with_constant_labels(metrics_config.constant_labels.clone());
+    // Without a sample timestamp, so the gauge is ingested however old the scrape is, and
+    // staleness can be alerted on.
+    b.add_gauge(
+        "status_page_last_scrape_timestamp_seconds",
+        "When the servers were last scraped",
+        timestamp.timestamp() as f64,
+        &[],
+        None,
+    )
+    .set_unit("status_page_last_scrape_timestamp_seconds", "seconds");
+    b.add_gauge(
+        "status_page_last_run_timestamp_seconds",
+        "When the status page was last generated",
+        Utc::now().timestamp() as f64,
+        &[],
+        None,
+    )
+    .set_unit("status_page_last_run_timestamp_seconds", "seconds");
+    add_build_info(&mut b, ts);
+    b.add_gauge(
+        "status_page_run_duration_seconds",
+        "Time spent scraping the servers and evaluating the rules",
+        status_page_data.run_duration_seconds,
+        &[],
+        Some(ts),
+    )
+    .set_unit("status_page_run_duration_seconds", "seconds")
+    .add_gauge(
+        "status_page_servers_configured",
+        "Servers in the configuration",
+        status_page_data.config.servers.len() as f64,
+        &[],
+        Some(ts),
+    );
+    for (name, help, failures) in sink_failures {
+        b.add_counter(name, help, *failures as f64, &[], None);
+    }
+    b.add_gauge(
+        "eessi_status",
+        "EESSI status",
+        status_page_data.eessi_status.level() as f64,
+        &[],
+        Some(ts),
+    )
+    .add_gauge(
+        "stratum0_status",
+        "Stratum0 status",
+        status_page_data.stratum0.level() as f64,
+        &[],
+        Some(ts),
+    )
+    .add_gauge(
+        "stratum1_status",
+        "Stratum1 status",
+        status_page_data.stratum1.level() as f64,
+        &[],
+        Some(ts),
+    )
+    .add_gauge(
+        "syncservers_status",
+        "SyncServers status",
+        status_page_data.syncservers.level() as f64,
+        &[],
+        Some(ts),
+    )
+    .add_gauge(
+        "repositories_status",
+        "Repositories status",
+        status_page_data.repositories_status.level() as f64,
+        &[],
+        Some(ts),
+    );
+
+    for (name, help, status) in [
+        (
+            "eessi_status_state",
+            "EESSI status",
+            status_page_data.eessi_status.status,
+        ),
+        (
+            "stratum0_status_state",
+            "Stratum0 status",
+            status_page_data.stratum0.status,
+        ),
+        (
+            "stratum1_status_state",
+            "Stratum1 status",
+            status_page_data.stratum1.status,
+        ),
+        (
+            "syncservers_status_state",
+            "SyncServers status",
+            status_page_data.syncservers.status,
+        ),
+        (
+            "repositories_status_state",
+            "Repositories status",
+            status_page_data.repositories_status.status,
+        ),
+    ] {
+        add_status_state(&mut b, name, help, status, ts);
+    }
+
+    let maps = vec![
+        ("overall", status_page_data.eessi_status.level() as f64),
+        ("stratum0", status_page_data.stratum0.level() as f64),
+        ("stratum1", status_page_data.stratum1.level() as f64),
+        ("syncservers", status_page_data.syncservers.level() as f64),
+        (
+            "repositories",
+            status_page_data.repositories_status.level() as f64,
+        ),
+    ];
+
+    for (category, level) in maps {
+        b.add_gauge(
+            "status_overview",
+            "Status overview",
+            level,
+            &[("category", category)],
+            Some(ts),
+        );
+    }
+
+    // Per server metrics need the scraped data, which isn't available when rendering from JSON.
+    let servers = status_manager
+        .map(|sm| sm.get_all_servers())
+        .unwrap_or_default();
+
+    add_server_scrape_metrics(&mut b, &servers, ts);
+    if let Some(status_manager) = status_manager {
+        add_revision_lag_metrics(&mut b, &servers, &status_manager.reference_revisions(), ts);
+    }
+    if let Some(run_state) = run_state {
+        add_counter_metrics(&mut b, &servers, run_state);
+    }
+
+    for server in servers {
+        let ts_ms = Some(ts);
+
+        if let Some(version) = server
+            .metadata
+            .as_ref()
+            .and_then(|m| m.cvmfs_version.as_ref())
+        {
+            b.add_gauge(
+                "cvmfs_version_info",
+                "CVMFS server version",
+                1.0,
+                &[
+                    ("server", server.hostname.to_str()),
+                    ("version", &version.to_string()),
+                ],
+                ts_ms,
+            );
+        }
+
+        b.add_gauge(
+            "data_age_seconds",
+            "Age of the data shown for the server, non-zero when stale data is used",
+            server.data_age_seconds.unwrap_or(0) as f64,
+            &[
+                ("type", server.server_type.to_label()),
+                ("server", server.hostname.to_str()),
+            ],
+            ts_ms,
+        )
+        .set_unit("data_age_seconds", "seconds");
+
+        if let Some(expiry) = server.cert_expiry {
+            b.add_gauge(
+                "tls_cert_expiry_timestamp_seconds",
+                "TLS certificate expiry",
+                expiry.timestamp() as f64,
+                &[
+                    ("type", server.server_type.to_label()),
+                    ("server", server.hostname.to_str()),
+                ],
+                ts_ms,
+            )
+            .set_unit("tls_cert_expiry_timestamp_seconds", "seconds");
+        }
+
+        // Load balanced services report per member, labelled with the member address.
+        let scraped: Vec<(&Server, Option<&str>)> = if server.members.is_empty() {
+            vec![(server, None)]
+        } else {
+            server
+                .members
+                .iter()
+                .map(|m| (m, m.address.as_deref()))
+                .collect()
+        };
+
+        for (scraped_server, member) in scraped {
+            let mut server_labels = vec![
+                ("type", server.server_type.to_label()),
+                ("server", server.hostname.to_str()),
+            ];
+            if let Some(member) = member {
+                server_labels.push(("member", member));
+            }
+
+            if let Some(duration_ms) = scraped_server.scrape_duration_ms {
+                b.add_gauge(
+                    "scrape_duration_seconds",
+                    "Time spent scraping the server",
+                    duration_ms as f64 / 1000.0,
+                    &server_labels,
+                    ts_ms,
+                )
+                .set_unit("scrape_duration_seconds", "seconds");
+            }
+
+            for repo in scraped_server.repositories.iter() {
+                let mut repo_labels = server_labels.clone();
+                repo_labels.push(("repository", repo.name.as_str()));
+
+                // Repositories that failed to scrape have no manifest to report on.
+                if let Some(manifest) = &repo.manifest {
+                    // The fields are:
+                    // - c: Cryptographic hash of the repository’s current root catalog
+                    // - b: Size of the root file catalog in bytes
+                    // - a: true if the catalog should be fetched under its alternative name
+                    // - r: MD5 hash of the repository’s current root path (usually always d41d8cd98f00b204e9800998ecf8427e)
+                    // - x: Cryptographic hash of the signing certificate
+                    // - g: true if the repository is garbage-collectable
+                    // - h: Cryptographic hash of the repository’s named tag history database
+                    // - t: Unix timestamp of this particular revision
+                    // - d: Time To Live (TTL) of the root catalog
+                    // - s: Revision number of this published revision
+                    // - n: The full name of the manifested repository
+                    // - m: Cryptographic hash of the repository JSON metadata
+                    // - y: Cryptographic hash of the reflog checksum
+                    // - l: currently unused (reserved for micro catalogs)
+                    b.add_gauge(
+                        "repo_revision",
+                        "Repository revision",
+                        repo.revision as f64,
+                        &repo_labels,
+                        ts_ms,
+                    )
+                    .add_gauge(
+                        "repo_timestamp",
+                        "Repository timestamp",
+                        manifest.t as f64,
+                        &repo_labels,
+                        ts_ms,
+                    )
+                    .add_gauge(
+                        "repo_ttl",
+                        "Repository TTL",
+                        manifest.d as f64,
+                        &repo_labels,
+                        ts_ms,
+                    )
+                    .add_gauge(
+                        "repo_catalogue_size",
+                        "Repository catalogue size",
+                        manifest.b as f64,
+                        &repo_labels,
+                        ts_ms,
+                    );
+
+                    // Stale data would report an age that keeps growing while the server is
+                    // down, so only servers scraped in this run are included.
+                    if scraped_server.scraped_successfully() {
+                        b.add_gauge(
+                            "repo_publish_age_seconds",
+                            "Time since the revision on the server was published",
+                            (timestamp.timestamp() - manifest.t) as f64,
+                            &repo_labels,
+                            ts_ms,
+                        )
+                        .set_unit("repo_publish_age_seconds", "seconds");
+                    }
+                }
+
+                if let Some(data_check) = repo.data_check {
+                    b.add_gauge(
+                        "repo_data_check",
+                        "Repository data path check (1 = reachable)",
+                        if data_check { 1.0 } else { 0.0 },
+                        &repo_labels,
+                        ts_ms,
+                    );
+                }
+
+                if let Some(mismatch) = repo.cert_fingerprint_mismatch {
+                    b.add_gauge(
+                        "repo_cert_fingerprint_mismatch",
+                        "Repository signed with a certificate that isn't pinned (1 = mismatch)",
+                        if mismatch { 1.0 } else { 0.0 },
+                        &repo_labels,
+                        ts_ms,
+                    );
+                }
+
+                if repo.manifest.is_some() {
+                    b.add_gauge(
+                        "repo_revision_regression",
+                        "Repository revision lower than the highest one seen on the server (1 = went backwards)",
+                        if repo.regressed_from.is_some() { 1.0 } else { 0.0 },
+                        &repo_labels,
+                        ts_ms,
+                    );
+                }
+            }
+        }
+    }
+
+    b
+}
+
+/// One series per status, `1` for the current status and `0` for the others.
+fn add_status_state(b: &mut MetricsBuilder, name: &str, help: &str, status: Status, ts: i64) {
+    for state in Status::all() {
+        b.add_gauge(
+            name,
+            help,
+            if state == status { 1.0 } else { 0.0 },
+            &[("state", state.as_ref())],
+            Some(ts),
+        );
+    }
+}
+
+/// Counters kept across runs in the state file.
+fn add_counter_metrics(b: &mut MetricsBuilder, servers: &[&Server], state: &RunState) {
+    b.add_counter(
+        "status_page_runs_total",
+        "Runs of the status page",
+        state.runs_total as f64,
+        &[],
+        None,
+    );
+
+    for server in servers {
+        let labels = [
+            ("type", server.server_type.to_label()),
+            ("server", server.hostname.to_str()),
+        ];
+        let counters = state.get(server);
+        if let Some(durations) = &counters.scrape_duration_seconds {
+            b.add_histogram_data(
+                "server_scrape_duration_seconds",
+                "Time spent scraping the server, across runs",
+                durations,
+                &labels,
+                None,
+            )
+            .set_unit("server_scrape_duration_seconds", "seconds");
+        }
+        b.add_counter(
+            "server_scrape_failures_total",
+            "Runs in which the server failed to scrape",
+            counters.scrape_failures_total as f64,
+            &labels,
+            None,
+        )
+        .add_counter(
+            "server_scrape_retries_total",
+            "Runs in which the repositories of the server were scraped again one by one",
+            counters.scrape_retries_total as f64,
+            &labels,
+            None,
+        );
+    }
+}
+
+/// The version of the status page, and what it was built with and from.
+fn add_build_info(b: &mut MetricsBuilder, ts: i64) {
+    b.add_gauge(
+        "status_page_build_info",
+        "Build information of the status page",
+        1.0,
+        &[
+            ("version", env!("CARGO_PKG_VERSION")),
+            ("rustc", env!("STATUS_PAGE_RUSTC_VERSION")),
+            ("git_sha", env!("STATUS_PAGE_GIT_SHA")),
+        ],
+        Some(ts),
+    );
+}
+
+/// Scrape health of every configured server, including those that failed to scrape.
+fn add_server_scrape_metrics(b: &mut MetricsBuilder, servers: &[&Server], ts: i64) {
+    use crate::models::StatusLevel;
+
+    for server in servers {
+        let labels = [
+            ("type", server.server_type.to_label()),
+            ("server", server.hostname.to_str()),
+        ];
+        let success = server.scraped_successfully();
+        let repositories = if success {
+            server
+                .repositories
+                .iter()
+                .filter(|r| r.manifest.is_some())
+                .count()
+        } else {
+            0
+        };
+
+        b.add_gauge(
+            "server_scrape_success",
+            "Whether the server was scraped successfully (1) or not (0)",
+            if success { 1.0 } else { 0.0 },
+            &labels,
+            Some(ts),
+        )
+        .add_gauge(
+            "server_status",
+            "Server status",
+            server.level() as f64,
+            &labels,
+            Some(ts),
+        )
+        .add_gauge(
+            "server_repositories_scraped",
+            "Number of repositories scraped on the server",
+            repositories as f64,
+            &labels,
+            Some(ts),
+        );
+    }
+}
+
+/// The reference revision of each repository, and how far each server lags behind it. A
+/// repository missing on a server (or failing to scrape there) has a lag of NaN.
+fn add_revision_lag_metrics(
+    b: &mut MetricsBuilder,
+    servers: &[&Server],
+    references: &BTreeMap<String, i32>,
+    ts: i64,
+) {
+    for (repository, reference) in references.iter() {
+        b.add_gauge(
+            "repo_reference_revision",
+            "Revision of the repository that servers are compared against",
+            *reference as f64,
+            &[("repository", repository)],
+            Some(ts),
+        );
+    }
+
+    for server in servers {
+        // Load balanced services report per member, labelled with the member address.
+        let scraped: Vec<(&Server, Option<&str>)> = if server.members.is_empty() {
+            vec![(server, None)]
+        } else {
+            server
+                .members
+                .iter()
+                .map(|m| (m, m.address.as_deref()))
+                .collect()
+        };
+
+        for (scraped_server, member) in scraped {
+            for (repository, reference) in references.iter() {
+                let mut labels = vec![
+                    ("type", server.server_type.to_label()),
+                    ("server", server.hostname.to_str()),
+                    ("repository", repository.as_str()),
+                ];
+                if let Some(member) = member {
+                    labels.push(("member", member));
+                }
+
+                let lag = scraped_server
+                    .repositories
+                    .iter()
+                    .find(|r| &r.name == repository && r.manifest.is_some())
+                    .filter(|_| scraped_server.scraped_successfully())
+                    .map_or(f64::NAN, |r| (reference - r.revision) as f64);
+
+                b.add_gauge(
+                    "repo_revision_lag",
+                    "Revisions the repository on the server is behind the reference revision",
+                    lag,
+                    &labels,
+                    Some(ts),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::MaintenanceAggregation;
+    use crate::testing::ServerFixture;
+    use yare::parameterized;
+
+    fn builder(label: &str) -> MetricsBuilder {
+        let mut b = MetricsBuilder::new();
+        b.add_gauge(
+            "data_age_seconds",
+            "Age of the data",
+            90.0,
+            &[("server", label)],
+            Some(1720525887957),
+        )
+        .set_unit("data_age_seconds", "seconds")
+        .add_counter("push_failures_total", "Failed \"pushes\"", 2.0, &[], None);
+        b
+    }
+
+    #[parameterized(
+        plain = { "s1", "s1" },
+        quote = { "say \"hi\"", r#"say \"hi\""# },
+        backslash = { r"C:\temp", r"C:\\temp" },
+        newline = { "two\nlines", r"two\nlines" },
+    )]
+    fn test_build(label: &str, escaped: &str) {
+        assert_eq!(
+            builder(label).build().unwrap(),
+            format!(
+                "# HELP data_age_seconds Age of the data\n\
+                 # TYPE data_age_seconds gauge\n\
+                 data_age_seconds{{server=\"{}\"}} 90 1720525887957\n\
+                 # HELP push_failures_total Failed \"pushes\"\n\
+                 # TYPE push_failures_total counter\n\
+                 push_failures_total 2\n",
+                escaped
+            )
+        );
+    }
+
+    #[parameterized(
+        plain = { "s1", "s1" },
+        quote = { "say \"hi\"", r#"say \"hi\""# },
+        backslash = { r"C:\temp", r"C:\\temp" },
+        newline = { "two\nlines", r"two\nlines" },
+    )]
+    fn test_build_openmetrics(label: &str, escaped: &str) {
+        assert_eq!(
+            builder(label).build_openmetrics().unwrap(),
+            format!(
+                "# TYPE data_age_seconds gauge\n\
+                 # UNIT data_age_seconds seconds\n\
+                 # HELP data_age_seconds Age of the data\n\
+                 data_age_seconds{{server=\"{}\"}} 90 1720525887.957\n\
+                 # TYPE push_failures counter\n\
+                 # HELP push_failures Failed \\\"pushes\\\"\n\
+                 push_failures_total 2\n\
+                 # EOF\n",
+                escaped
+            )
+        );
+    }
+
+    #[parameterized(
+        included = { false, " 1720525887957", " 1720525887.957" },
+        suppressed = { true, "", "" },
+    )]
+    fn test_suppress_timestamps(suppress: bool, timestamp: &str, openmetrics_timestamp: &str) {
+        let mut b = builder("s1");
+        b.suppress_timestamps(suppress);
+
+        assert!(b.build().unwrap().contains(&format!(
+            "data_age_seconds{{server=\"s1\"}} 90{}\n",
+            timestamp
+        )));
+        assert!(b.build_openmetrics().unwrap().contains(&format!(
+            "data_age_seconds{{server=\"s1\"}} 90{}\n",
+            openmetrics_timestamp
+        )));
+    }
+
+    #[test]
+    fn test_openmetrics_counter_without_total_suffix() {
+        let mut b = MetricsBuilder::new();
+        b.add_counter("pushes", "Pushes", 1.0, &[], None);
+        b.add_untyped("thing", "Thing", 1.0, &[], Some(5));
+
+        assert_eq!(
+            b.build_openmetrics().unwrap(),
+            "# TYPE pushes counter\n# HELP pushes Pushes\npushes_total 1\n\
+             # TYPE thing unknown\n# HELP thing Thing\nthing 1 0.005\n# EOF\n"
+        );
+    }
+
+    #[test]
+    fn test_prefix_and_constant_labels() {
+        let mut b = MetricsBuilder::new()
+            .with_prefix("eessi_statuspage_")
+            .with_constant_labels([("site", "ams"), ("server", "constant")]);
+        b.add_gauge("eessi_status", "EESSI status", 0.0, &[], None)
+            .add_gauge(
+                "repo_revision",
+                "Repository revision",
+                12.0,
+                &[("server", "s1"), ("repository", "software.eessi.io")],
+                None,
+            )
+            .add_counter("push_failures_total", "Failed pushes", 1.0, &[], None);
+
+        assert_eq!(
+            b.build().unwrap(),
+            "# HELP eessi_statuspage_eessi_status EESSI status\n\
+             # TYPE eessi_statuspage_eessi_status gauge\n\
+             eessi_statuspage_eessi_status{site=\"ams\",server=\"constant\"} 0\n\
+             # HELP eessi_statuspage_push_failures_total Failed pushes\n\
+             # TYPE eessi_statuspage_push_failures_total counter\n\
+             eessi_statuspage_push_failures_total{site=\"ams\",server=\"constant\"} 1\n\
+             # HELP eessi_statuspage_repo_revision Repository revision\n\
+             # TYPE eessi_statuspage_repo_revision gauge\n\
+             eessi_statuspage_repo_revision{site=\"ams\",server=\"s1\",repository=\"software.eessi.io\"} 12\n"
+        );
+        assert!(b
+            .build_openmetrics()
+            .unwrap()
+            .contains("# TYPE eessi_statuspage_push_failures counter\n"));
+    }
+
+    #[parameterized(
+        empty = { &[], &[0.5, 1.0], &[("0.5", 0), ("1", 0), ("+Inf", 0)], 0.0 },
+        at_boundary = { &[1.0], &[0.5, 1.0], &[("0.5", 0), ("1", 1), ("+Inf", 1)], 1.0 },
+        unsorted_buckets = { &[0.2, 0.7, 3.0], &[1.0, 0.5], &[("0.5", 1), ("1", 2), ("+Inf", 3)], 3.9 },
+        duplicate_and_infinite_buckets = { &[0.7], &[1.0, f64::INFINITY, 1.0], &[("1", 1), ("+Inf", 1)], 0.7 },
+    )]
+    fn test_add_histogram(
+        observations: &[f64],
+        buckets: &[f64],
+        expected_buckets: &[(&str, u64)],
+        sum: f64,
+    ) {
+        let mut b = MetricsBuilder::new();
+        b.add_histogram(
+            "scrape_seconds",
+            "Scrape time",
+            observations,
+            buckets,
+            &[("server", "s1")],
+            None,
+        );
+
+        let mut expected =
+            "# HELP scrape_seconds Scrape time\n# TYPE scrape_seconds histogram\n".to_string();
+        for (le, count) in expected_buckets {
+            expected.push_str(&format!(
+                "scrape_seconds_bucket{{server=\"s1\",le=\"{}\"}} {}\n",
+                le, count
+            ));
+        }
+        expected.push_str(&format!(
+            "scrape_seconds_sum{{server=\"s1\"}} {}\nscrape_seconds_count{{server=\"s1\"}} {}\n",
+            sum,
+            observations.len()
+        ));
+        assert_eq!(b.build().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_histogram_openmetrics() {
+        let mut b = MetricsBuilder::new();
+        b.add_histogram(
+            "scrape_seconds",
+            "Scrape time",
+            &[0.3],
+            &[0.5],
+            &[],
+            Some(5),
+        );
+
+        assert_eq!(
+            b.build_openmetrics().unwrap(),
+            "# TYPE scrape_seconds histogram\n# HELP scrape_seconds Scrape time\n\
+             scrape_seconds_bucket{le=\"0.5\"} 1 0.005\n\
+             scrape_seconds_bucket{le=\"+Inf\"} 1 0.005\n\
+             scrape_seconds_sum 0.3 0.005\n\
+             scrape_seconds_count 1 0.005\n# EOF\n"
+        );
+    }
+
+    #[test]
+    fn test_add_summary() {
+        let mut b = MetricsBuilder::new();
+        b.add_summary(
+            "scrape_latency_seconds",
+            "Scrape latency",
+            &[(0.5, 0.2), (0.99, 1.5)],
+            12.5,
+            40,
+            &[("server", "s1")],
+            Some(1000),
+        )
+        .unwrap();
+        b.add_summary(
+            "scrape_latency_seconds",
+            "Scrape latency",
+            &[(0.5, 0.3)],
+            3.0,
+            10,
+            &[("server", "s2")],
+            Some(1000),
+        )
+        .unwrap();
+
+        assert_eq!(
+            b.build().unwrap(),
+            "# HELP scrape_latency_seconds Scrape latency\n\
+             # TYPE scrape_latency_seconds summary\n\
+             scrape_latency_seconds{server=\"s1\",quantile=\"0.5\"} 0.2 1000\n\
+             scrape_latency_seconds{server=\"s1\",quantile=\"0.99\"} 1.5 1000\n\
+             scrape_latency_seconds_sum{server=\"s1\"} 12.5 1000\n\
+             scrape_latency_seconds_count{server=\"s1\"} 40 1000\n\
+             scrape_latency_seconds{server=\"s2\",quantile=\"0.5\"} 0.3 1000\n\
+             scrape_latency_seconds_sum{server=\"s2\"} 3 1000\n\
+             scrape_latency_seconds_count{server=\"s2\"} 10 1000\n"
+        );
+    }
+
+    #[parameterized(
+        quantile_above_one = { &[(1.5, 0.2)], 1.0 },
+        negative_quantile = { &[(-0.1, 0.2)], 1.0 },
+        nan_quantile = { &[(f64::NAN, 0.2)], 1.0 },
+        nan_sum = { &[(0.5, 0.2)], f64::NAN },
+    )]
+    fn test_add_summary_rejects(quantiles: &[(f64, f64)], sum: f64) {
+        let mut b = MetricsBuilder::new();
+        assert!(b
+            .add_summary("latency", "Latency", quantiles, sum, 1, &[], None)
+            .is_err());
+        assert_eq!(b.build().unwrap(), "");
+    }
+
+    /// Samples of several metrics, including a histogram, in the order given by `order`.
+    fn shuffled(order: &[usize]) -> String {
+        let mut b = MetricsBuilder::new();
+        for i in order {
+            match i {
+                0 => b.add_gauge("server_status", "Status", 0.0, &[("server", "s2")], None),
+                1 => b.add_gauge("server_status", "Status", 3.0, &[("server", "s1")], None),
+                2 => b.add_gauge("eessi_status", "EESSI status", 0.0, &[], None),
+                3 => b.add_histogram(
+                    "scrape_seconds",
+                    "Scrape",
+                    &[2.0],
+                    &[1.0, 5.0],
+                    &[("server", "s2")],
+                    None,
+                ),
+                _ => b.add_histogram(
+                    "scrape_seconds",
+                    "Scrape",
+                    &[0.5],
+                    &[1.0, 5.0],
+                    &[("server", "s1")],
+                    None,
+                ),
+            };
+        }
+        b.build().unwrap()
+    }
+
+    #[parameterized(
+        reversed = { &[4, 3, 2, 1, 0] },
+        interleaved = { &[3, 0, 4, 2, 1] },
+        rotated = { &[2, 3, 4, 0, 1] },
+    )]
+    fn test_build_is_deterministic(order: &[usize]) {
+        assert_eq!(shuffled(order), shuffled(&[0, 1, 2, 3, 4]));
+        assert_eq!(
+            shuffled(order),
+            "# HELP eessi_status EESSI status\n\
+             # TYPE eessi_status gauge\n\
+             eessi_status 0\n\
+             # HELP scrape_seconds Scrape\n\
+             # TYPE scrape_seconds histogram\n\
+             scrape_seconds_bucket{server=\"s1\",le=\"1\"} 1\n\
+             scrape_seconds_bucket{server=\"s1\",le=\"5\"} 1\n\
+             scrape_seconds_bucket{server=\"s1\",le=\"+Inf\"} 1\n\
+             scrape_seconds_sum{server=\"s1\"} 0.5\n\
+             scrape_seconds_count{server=\"s1\"} 1\n\
+             scrape_seconds_bucket{server=\"s2\",le=\"1\"} 0\n\
+             scrape_seconds_bucket{server=\"s2\",le=\"5\"} 1\n\
+             scrape_seconds_bucket{server=\"s2\",le=\"+Inf\"} 1\n\
+             scrape_seconds_sum{server=\"s2\"} 2\n\
+             scrape_seconds_count{server=\"s2\"} 1\n\
+             # HELP server_status Status\n\
+             # TYPE server_status gauge\n\
+             server_status{server=\"s1\"} 3\n\
+             server_status{server=\"s2\"} 0\n"
+        );
+    }
+
+    #[parameterized(
+        keep_last = { DuplicateSamples::KeepLast, Ok("status{server=\"s1\",type=\"stratum1\"} 2\n".to_string()) },
+        reject = { DuplicateSamples::Reject, Err("Duplicate sample status with labels [(\"type\", \"stratum1\"), (\"server\", \"s1\")]".to_string()) },
+    )]
+    fn test_duplicate_samples(
+        duplicate_samples: DuplicateSamples,
+        expected: Result<String, String>,
+    ) {
+        let mut b = MetricsBuilder::new().with_duplicate_samples(duplicate_samples);
+        b.add_gauge(
+            "status",
+            "Status",
+            1.0,
+            &[("type", "stratum1"), ("server", "s1")],
+            None,
+        )
+        .add_gauge(
+            "status",
+            "Status",
+            2.0,
+            &[("server", "s1"), ("type", "stratum1")],
+            None,
+        );
+
+        let expected =
+            expected.map(|sample| format!("# HELP status Status\n# TYPE status gauge\n{}", sample));
+        assert_eq!(b.build(), expected);
+    }
+
+    #[test]
+    fn test_build_json() {
+        let mut b = MetricsBuilder::new().with_prefix("eessi_");
+        b.add_gauge("lag", "Lag", f64::NAN, &[("server", "s1")], Some(5))
+            .add_counter("failures_total", "Failures", 2.0, &[], None);
+
+        let expected = serde_json::json!({
+            "metrics": [
+                {
+                    "name": "eessi_failures_total",
+                    "type": "counter",
+                    "help": "Failures",
+                    "samples": [{ "labels": {}, "value": 2.0 }]
+                },
+                {
+                    "name": "eessi_lag",
+                    "type": "gauge",
+                    "help": "Lag",
+                    "samples": [{ "labels": { "server": "s1" }, "value": "NaN", "timestamp_ms": 5 }]
+                }
+            ]
+        });
+        let json: serde_json::Value = serde_json::from_str(&b.build_json().unwrap()).unwrap();
+        assert_eq!(json, expected);
+    }
+
+    #[test]
+    fn test_build_json_round_trip() {
+        let mut b = MetricsBuilder::new()
+            .with_prefix("eessi_")
+            .with_constant_labels([("site", "ams")]);
+        b.add_gauge("lag", "Lag", f64::NAN, &[("server", "s1")], Some(5))
+            .add_gauge("lag", "Lag", 1.5, &[("server", "s2")], Some(5))
+            .add_gauge("limit", "Limit", f64::INFINITY, &[], None)
+            .add_gauge("floor", "Floor", f64::NEG_INFINITY, &[], None)
+            .add_histogram(
+                "scrape_seconds",
+                "Scrape time",
+                &[0.3, 2.0],
+                &[1.0],
+                &[],
+                None,
+            );
+
+        let json: JsonMetrics = serde_json::from_str(&b.build_json().unwrap()).unwrap();
+
+        // NaN isn't equal to itself, so the values are compared as rendered.
+        let mut parsed = Vec::new();
+        for metric in &json.metrics {
+            for s in &metric.samples {
+                let labels: Vec<(String, String)> = s.labels.clone().into_iter().collect();
+                let name = format!("{}{}", metric.name, s.suffix);
+                parsed.push((name, labels, format_value(s.value)));
+            }
+        }
+        let expected: Vec<_> = b
+            .flatten()
+            .unwrap()
+            .into_iter()
+            .map(|mut s| {
+                s.labels.sort();
+                (s.name, s.labels, format_value(s.value))
+            })
+            .collect();
+        assert_eq!(parsed, expected);
+        let types: Vec<_> = json.metrics.iter().map(|m| m.mtype.as_deref()).collect();
+        assert_eq!(
+            types,
+            [
+                Some("gauge"),
+                Some("gauge"),
+                Some("gauge"),
+                Some("histogram")
+            ]
+        );
+    }
+
+    #[parameterized(
+        number = { "1.5", Some(1.5) },
+        positive_infinity = { "\"+Inf\"", Some(f64::INFINITY) },
+        negative_infinity = { "\"-Inf\"", Some(f64::NEG_INFINITY) },
+        unknown = { "\"inf\"", None },
+    )]
+    fn test_json_value(value: &str, expected: Option<f64>) {
+        let sample = format!("{{\"labels\": {{}}, \"value\": {}}}", value);
+        let parsed = serde_json::from_str::<JsonSample>(&sample).ok();
+        assert_eq!(parsed.map(|s| s.value), expected);
+    }
+
+    #[parameterized(
+        plain = { "repo_revision", true },
+        colon = { "eessi:repo_revision", true },
+        leading_digit = { "1repo", false },
+        dash = { "repo-revision", false },
+        empty = { "", false },
+    )]
+    fn test_is_valid_metric_name(name: &str, valid: bool) {
+        assert_eq!(is_valid_metric_name(name), valid);
+    }
+
+    #[parameterized(
+        plain = { "site", true },
+        underscore = { "_site", true },
+        reserved = { "__name__", false },
+        colon = { "site:name", false },
+        empty = { "", false },
+    )]
+    fn test_is_valid_label_name(name: &str, valid: bool) {
+        assert_eq!(is_valid_label_name(name), valid);
+    }
+
+    #[test]
+    fn test_server_scrape_metrics_for_failed_server() {
+        let status_manager = StatusManager {
+            servers: vec![ServerFixture::stratum1("s1.example.com").failed().build()],
+            maintenance_aggregation: MaintenanceAggregation::default(),
+        };
+        let mut b = MetricsBuilder::new();
+
+        add_server_scrape_metrics(&mut b, &status_manager.get_all_servers(), 1000);
+
+        let text = b.build().unwrap();
+        let labels = r#"{type="stratum1",server="s1.example.com"}"#;
+        assert!(text.contains(&format!("server_scrape_success{} 0 1000\n", labels)));
+        assert!(text.contains(&format!("server_status{} 3 1000\n", labels)));
+        assert!(text.contains(&format!("server_repositories_scraped{} 0 1000\n", labels)));
+    }
+
+    #[test]
+    fn test_status_state() {
+        let mut b = MetricsBuilder::new();
+
+        add_status_state(
+            &mut b,
+            "eessi_status_state",
+            "EESSI status",
+            Status::WARNING,
+            1000,
+        );
+
+        assert_eq!(
+            b.build().unwrap(),
+            "# HELP eessi_status_state EESSI status\n\
+             # TYPE eessi_status_state gauge\n\
+             eessi_status_state{state=\"DEGRADED\"} 0 1000\n\
+             eessi_status_state{state=\"FAILED\"} 0 1000\n\
+             eessi_status_state{state=\"MAINTENANCE\"} 0 1000\n\
+             eessi_status_state{state=\"OK\"} 0 1000\n\
+             eessi_status_state{state=\"WARNING\"} 1 1000\n"
+        );
+    }
+
+    #[test]
+    fn test_build_info() {
+        let mut b = MetricsBuilder::new();
+
+        add_build_info(&mut b, 1000);
+
+        let text = b.build().unwrap();
+        let samples: Vec<&str> = text
+            .lines()
+            .filter(|line| line.starts_with("status_page_build_info{"))
+            .collect();
+        assert_eq!(samples.len(), 1);
+        assert!(samples[0].starts_with(&format!(
+            "status_page_build_info{{version=\"{}\",rustc=\"",
+            env!("CARGO_PKG_VERSION")
+        )));
+        assert!(samples[0].contains(",git_sha=\""));
+        assert!(samples[0].ends_with("} 1 1000"));
+    }
+
+    #[test]
+    fn test_revision_lag_is_nan_for_failed_server() {
+        let status_manager = StatusManager {
+            servers: vec![ServerFixture::stratum1("s1.example.com").failed().build()],
+            maintenance_aggregation: MaintenanceAggregation::default(),
+        };
+        let references = BTreeMap::from([("software.eessi.io".to_string(), 10)]);
+        let mut b = MetricsBuilder::new();
+
+        add_revision_lag_metrics(&mut b, &status_manager.get_all_servers(), &references, 1000);
+
+        let text = b.build().unwrap();
+        assert!(
+            text.contains("repo_reference_revision{repository=\"software.eessi.io\"} 10 1000\n")
+        );
+        assert!(text.contains(
+            "repo_revision_lag{type=\"stratum1\",server=\"s1.example.com\",repository=\"software.eessi.io\"} NaN 1000\n"
+        ));
+    }
+}
@@ -0,0 +1,259 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use log::{debug, info};
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::dependencies::OutputSink;
+use crate::lock::LOCK_FILE_NAME;
+
+/// The directory in the destination with a directory of the files of each run.
+pub const RELEASES_DIR: &str = "releases";
+
+/// The link in the destination to the release the web server serves.
+pub const CURRENT_LINK: &str = "current";
+
+/// How the files of a run are published.
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum PublishMode {
+    /// Write each file into the destination as it is ready.
+    #[default]
+    Direct,
+    /// Write the files into a new release, and switch the `current` link to it once the run
+    /// succeeded, so a client never sees the files of two runs mixed.
+    Symlink,
+}
+
+/// The directory the web server serves: the current release when publishing through the link.
+pub fn served_dir(destination: &Path, mode: PublishMode) -> PathBuf {
+    match mode {
+        PublishMode::Direct => destination.to_path_buf(),
+        PublishMode::Symlink => destination.join(CURRENT_LINK),
+    }
+}
+
+/// Create the release of a run started at `time`, with a copy of the files of the current
+/// release, or of the destination before the first release, so the state, the history and the
+/// manifests of the resources carry over.
+pub fn stage(destination: &Path, time: DateTime<Utc>) -> Result<PathBuf> {
+    let releases = destination.join(RELEASES_DIR);
+    fs::create_dir_all(&releases).context(format!("Failed to create directory {:?}", releases))?;
+    let release = releases.join(time.format("%Y%m%dT%H%M%S%.3fZ").to_string());
+    fs::create_dir(&release).context(format!("Failed to create release {:?}", release))?;
+
+    let current = destination.join(CURRENT_LINK);
+    let copied = if current.exists() {
+        copy_dir(&current, &release, &[])
+    } else {
+        copy_dir(
+            destination,
+            &release,
+            &[RELEASES_DIR, CURRENT_LINK, LOCK_FILE_NAME],
+        )
+    };
+    if let Err(e) = copied {
+        let _ = fs::remove_dir_all(&release);
+        return Err(e.context(format!("Failed to stage release {:?}", release)));
+    }
+    debug!("Staged release {:?}", release);
+    Ok(release)
+}
+
+/// Switch the `current` link of `destination` to `release`, atomically.
+pub fn publish(destination: &Path, release: &Path, sink: &OutputSink) -> Result<()> {
+    let name = release.file_name().context("Invalid release: no name")?;
+    sink.replace_link(
+        &Path::new(RELEASES_DIR).join(name),
+        &destination.join(CURRENT_LINK),
+    )?;
+    info!("Published release {:?}", release);
+    Ok(())
+}
+
+/// Delete all but the `keep` newest releases, but never the current one, returning the
+/// deleted ones.
+pub fn prune(destination: &Path, keep: usize) -> Result<Vec<PathBuf>> {
+    let current = fs::read_link(destination.join(CURRENT_LINK))
+        .ok()
+        .and_then(|target| target.file_name().map(OsString::from));
+    let mut releases = vec![];
+    let dir = destination.join(RELEASES_DIR);
+    for entry in fs::read_dir(&dir).context(format!("Failed to read directory {:?}", dir))? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            releases.push(entry.file_name());
+        }
+    }
+    // Named after the start of their run, so they sort from the oldest.
+    releases.sort();
+
+    let stale = releases.len().saturating_sub(keep);
+    let mut pruned = vec![];
+    for name in &releases[..stale] {
+        if Some(name) == current.as_ref() {
+            continue;
+        }
+        let path = dir.join(name);
+        fs::remove_dir_all(&path).context(format!("Failed to delete release {:?}", path))?;
+        debug!("Deleted release {:?}", path);
+        pruned.push(path);
+    }
+    Ok(pruned)
+}
+
+/// The newest release, published or not, whose run report is of the last run.
+pub fn last_release(destination: &Path) -> Option<PathBuf> {
+    let dir = destination.join(RELEASES_DIR);
+    fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+        .map(|entry| entry.path())
+        .max()
+}
+
+/// Copy the files, links and directories in `from` to `to`, but for those named in `skip`.
+/// The files are copied rather than linked, as some are appended to.
+fn copy_dir(from: &Path, to: &Path, skip: &[&str]) -> Result<()> {
+    for entry in fs::read_dir(from).context(format!("Failed to read directory {:?}", from))? {
+        let entry = entry?;
+        if skip.iter().any(|name| entry.file_name() == *name) {
+            continue;
+        }
+        let path = entry.path();
+        let target = to.join(entry.file_name());
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            fs::create_dir(&target).context(format!("Failed to create directory {:?}", target))?;
+            copy_dir(&path, &target, &[])?;
+        } else if file_type.is_symlink() {
+            std::os::unix::fs::symlink(fs::read_link(&path)?, &target)
+                .context(format!("Failed to copy link {:?}", path))?;
+        } else {
+            fs::copy(&path, &target).context(format!("Failed to copy {:?}", path))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use yare::parameterized;
+
+    fn time(second: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, second).unwrap()
+    }
+
+    fn release_names(destination: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(destination.join(RELEASES_DIR))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_stage_first_release() -> Result<()> {
+        let destination = tempfile::tempdir()?;
+        fs::write(destination.path().join("history.jsonl"), "{}\n")?;
+        fs::write(destination.path().join(LOCK_FILE_NAME), "1")?;
+        fs::create_dir(destination.path().join("webfonts"))?;
+        fs::write(destination.path().join("webfonts/fa.woff2"), "font")?;
+
+        let release = stage(destination.path(), time(0))?;
+
+        assert_eq!(
+            release,
+            destination.path().join("releases/20240301T120000.000Z")
+        );
+        assert_eq!(fs::read_to_string(release.join("history.jsonl"))?, "{}\n");
+        assert_eq!(
+            fs::read_to_string(release.join("webfonts/fa.woff2"))?,
+            "font"
+        );
+        assert!(!release.join(LOCK_FILE_NAME).exists());
+        assert!(!release.join(RELEASES_DIR).exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_stage_from_current() -> Result<()> {
+        let destination = tempfile::tempdir()?;
+        let first = stage(destination.path(), time(0))?;
+        fs::write(first.join("index.html"), "first")?;
+        std::os::unix::fs::symlink("/srv/branding/logo.png", first.join("logo.png"))?;
+        publish(destination.path(), &first, &OutputSink::default())?;
+
+        let second = stage(destination.path(), time(1))?;
+        fs::write(second.join("index.html"), "second")?;
+
+        // The current release is left as it was until the second is published.
+        let current = destination.path().join(CURRENT_LINK);
+        assert_eq!(fs::read_to_string(current.join("index.html"))?, "first");
+        assert_eq!(
+            fs::read_link(second.join("logo.png"))?,
+            Path::new("/srv/branding/logo.png")
+        );
+
+        publish(destination.path(), &second, &OutputSink::default())?;
+
+        assert_eq!(fs::read_to_string(current.join("index.html"))?, "second");
+        assert_eq!(
+            fs::read_link(&current)?,
+            Path::new("releases/20240301T120001.000Z")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_stage_existing() -> Result<()> {
+        let destination = tempfile::tempdir()?;
+        stage(destination.path(), time(0))?;
+
+        assert!(stage(destination.path(), time(0)).is_err());
+        Ok(())
+    }
+
+    #[parameterized(
+        keep_all = { 5, 2, &["20240301T120000.000Z", "20240301T120001.000Z", "20240301T120002.000Z"] },
+        keep_newest = { 2, 2, &["20240301T120001.000Z", "20240301T120002.000Z"] },
+        keep_only_current = { 1, 2, &["20240301T120002.000Z"] },
+        keep_current = { 1, 1, &["20240301T120001.000Z", "20240301T120002.000Z"] },
+    )]
+    fn test_prune(keep: usize, current: u32, expected: &[&str]) -> Result<()> {
+        let destination = tempfile::tempdir()?;
+        for second in 0..3 {
+            let release = stage(destination.path(), time(second))?;
+            if second == current {
+                publish(destination.path(), &release, &OutputSink::default())?;
+            }
+        }
+
+        let pruned = prune(destination.path(), keep)?;
+
+        assert_eq!(release_names(destination.path()), expected);
+        assert_eq!(pruned.len(), 3 - expected.len());
+        assert!(destination.path().join(CURRENT_LINK).exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_last_release() -> Result<()> {
+        let destination = tempfile::tempdir()?;
+        assert_eq!(last_release(destination.path()), None);
+
+        stage(destination.path(), time(1))?;
+        stage(destination.path(), time(0))?;
+
+        assert_eq!(
+            last_release(destination.path()),
+            Some(destination.path().join("releases/20240301T120001.000Z"))
+        );
+        Ok(())
+    }
+}
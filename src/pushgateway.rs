@@ -0,0 +1,149 @@
+use anyhow::{Context, Result};
+use log::{debug, info};
+use reqwest::Url;
+use std::time::Duration;
+
+use crate::config::PushGatewayConfig;
+use crate::prometheus::MetricsBuilder;
+
+/// The URL to push to: `<url>/metrics/job/<job>` followed by the grouping labels.
+pub fn push_url(config: &PushGatewayConfig) -> Result<Url> {
+    let mut url =
+        Url::parse(&config.url).context(format!("Invalid Pushgateway URL {}", config.url))?;
+
+    {
+        let mut segments = url
+            .path_segments_mut()
+            .map_err(|_| anyhow::anyhow!("Invalid Pushgateway URL {}", config.url))?;
+        segments
+            .pop_if_empty()
+            .extend(["metrics", "job", config.job.as_str()]);
+        for (name, value) in config.grouping.iter() {
+            segments.extend([name.as_str(), value.as_str()]);
+        }
+    }
+    Ok(url)
+}
+
+/// Push the metrics, replacing the ones previously pushed for the same grouping. They are
+/// pushed in the Prometheus format, which is all the Pushgateway accepts, and always without
+/// timestamps, as it rejects samples that carry them.
+///
+/// Fails on a connection error or a non-success response, including the response body.
+pub async fn push(config: &PushGatewayConfig, builder: &mut MetricsBuilder) -> Result<()> {
+    let metrics = builder
+        .suppress_timestamps(true)
+        .build()
+        .map_err(anyhow::Error::msg)?;
+    let url = push_url(config)?;
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(config.timeout_seconds))
+        .build()
+        .context("Failed to build the Pushgateway client")?;
+
+    let mut request = client
+        .post(url.clone())
+        .header(reqwest::header::CONTENT_TYPE, "text/plain; version=0.0.4")
+        .body(metrics);
+    if let Some(username) = env_value(&config.username_env)? {
+        request = request.basic_auth(username, env_value(&config.password_env)?);
+    }
+
+    debug!("Pushing metrics to {}", url);
+    let response = request
+        .send()
+        .await
+        .context(format!("Failed to push metrics to {}", url))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        anyhow::bail!(
+            "Pushgateway at {} responded with {}: {}",
+            url,
+            status,
+            body.trim()
+        );
+    }
+
+    info!("Metrics pushed to: {}", url);
+    Ok(())
+}
+
+/// The value of the environment variable named by `name`, if one is named.
+pub fn env_value(name: &Option<String>) -> Result<Option<String>> {
+    name.as_ref()
+        .map(|name| {
+            std::env::var(name).context(format!("Environment variable {} is not set", name))
+        })
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use yare::parameterized;
+
+    #[parameterized(
+        no_grouping = { "http://pushgateway:9091", &[], "http://pushgateway:9091/metrics/job/cvmfs_status_page" },
+        trailing_slash = { "http://pushgateway:9091/", &[], "http://pushgateway:9091/metrics/job/cvmfs_status_page" },
+        prefix = { "https://example.com/pushgateway", &[], "https://example.com/pushgateway/metrics/job/cvmfs_status_page" },
+        grouping = { "http://pushgateway:9091", &[("site", "rug"), ("instance", "status page")], "http://pushgateway:9091/metrics/job/cvmfs_status_page/instance/status%20page/site/rug" },
+    )]
+    fn test_push_url(url: &str, grouping: &[(&str, &str)], expected: &str) {
+        let config = PushGatewayConfig {
+            grouping: grouping
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<BTreeMap<_, _>>(),
+            ..PushGatewayConfig::with_url(url)
+        };
+
+        assert_eq!(push_url(&config).unwrap().as_str(), expected);
+    }
+
+    #[tokio::test]
+    async fn test_push_failure_includes_response() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let router = axum::Router::new().fallback(|| async {
+            (
+                axum::http::StatusCode::BAD_REQUEST,
+                "text format parsing error",
+            )
+        });
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let mut builder = MetricsBuilder::new();
+        builder.add_gauge("eessi_status", "Status", 0.0, &[], None);
+        let error = push(&PushGatewayConfig::with_url(&url), &mut builder)
+            .await
+            .unwrap_err();
+
+        let message = error.to_string();
+        assert!(message.contains("400 Bad Request"), "{}", message);
+        assert!(message.contains("text format parsing error"), "{}", message);
+    }
+
+    #[tokio::test]
+    async fn test_push_leaves_out_timestamps() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let router = axum::Router::new().fallback(move |body: String| async move {
+            sender.send(body).unwrap();
+        });
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        let mut builder = MetricsBuilder::new();
+        builder.add_gauge("eessi_status", "Status", 0.0, &[], Some(1_700_000_000_000));
+
+        push(&PushGatewayConfig::with_url(&url), &mut builder)
+            .await
+            .unwrap();
+
+        let body = receiver.recv().await.unwrap();
+        assert!(body.contains("eessi_status 0\n"), "{}", body);
+        assert!(!body.contains("1700000000000"), "{}", body);
+    }
+}
@@ -0,0 +1,233 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::dependencies::{FileCounts, OutputSink, PlannedWrite};
+use crate::models::{RuleError, Status, StatusManager, ToEESSILabel};
+use crate::precompress::Compressed;
+
+/// The report of the most recent run, in the destination.
+pub const REPORT_FILE: &str = "run_report.json";
+
+/// What happened during a run, for the people reading the page rather than the logs of the
+/// host running it. Each phase of the run adds to it.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct RunReport {
+    pub started_at: DateTime<Utc>,
+    /// `None` while the run is in progress.
+    pub finished_at: Option<DateTime<Utc>>,
+    pub duration_ms: Option<u64>,
+    /// The overall status, `None` if the run failed before it was known.
+    pub status: Option<Status>,
+    /// Why the run failed, `None` if it succeeded.
+    pub error: Option<String>,
+    pub servers: Vec<ServerOutcome>,
+    pub rule_errors: Vec<RuleError>,
+    /// Problems that didn't fail the run.
+    pub warnings: Vec<String>,
+    /// The resource files and templates written to the destination.
+    pub files: FileCounts,
+    pub artifacts: Vec<Artifact>,
+    pub deliveries: Vec<Delivery>,
+    /// The compressed copies of the files in the destination, with `precompress`.
+    #[serde(default)]
+    pub compressed: Vec<Compressed>,
+    /// The writes a run with `--dry-run` would have made.
+    #[serde(default)]
+    pub planned_writes: Vec<PlannedWrite>,
+}
+
+/// How the scrape of a server went.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct ServerOutcome {
+    pub hostname: String,
+    pub server_type: String,
+    pub status: Status,
+    pub scraped: bool,
+    pub retried: bool,
+    pub duration_ms: Option<u64>,
+    /// How old the data shown for a server that failed to scrape is.
+    pub stale_seconds: Option<i64>,
+}
+
+/// A file (or a directory of files) the run wrote.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Artifact {
+    pub kind: String,
+    pub path: PathBuf,
+}
+
+/// A notification or metrics sink the run sent to, such as `slack` or `pushgateway`.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Delivery {
+    pub channel: String,
+    /// The component or command it was sent for, empty for the metrics sinks and the heartbeat,
+    /// whose URLs may hold secrets.
+    pub target: String,
+    pub delivered: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl RunReport {
+    pub fn new(started_at: DateTime<Utc>) -> Self {
+        RunReport {
+            started_at,
+            finished_at: None,
+            duration_ms: None,
+            status: None,
+            error: None,
+            servers: vec![],
+            rule_errors: vec![],
+            warnings: vec![],
+            files: FileCounts::default(),
+            artifacts: vec![],
+            compressed: vec![],
+            deliveries: vec![],
+            planned_writes: vec![],
+        }
+    }
+
+    /// Log a problem that doesn't fail the run, and keep it in the report.
+    pub fn warn(&mut self, message: String) {
+        warn!("{}", message);
+        self.warnings.push(message);
+    }
+
+    pub fn artifact(&mut self, kind: &str, path: PathBuf) {
+        self.artifacts.push(Artifact {
+            kind: kind.to_string(),
+            path,
+        });
+    }
+
+    /// Keep the outcome of sending to `channel`, and pass it on to be handled as before.
+    pub fn deliver(&mut self, channel: &str, target: &str, result: Result<()>) -> Result<()> {
+        self.deliveries.push(Delivery {
+            channel: channel.to_string(),
+            target: target.to_string(),
+            delivered: result.is_ok(),
+            error: result.as_ref().err().map(|e| format!("{:#}", e)),
+        });
+        result
+    }
+
+    /// Keep the scrape outcome of each server, except the hidden ones.
+    pub fn record_servers(&mut self, status_manager: &StatusManager, hidden: &[String]) {
+        self.servers = status_manager
+            .servers
+            .iter()
+            .filter(|server| !hidden.contains(&server.hostname.to_string()))
+            .map(|server| ServerOutcome {
+                hostname: server.hostname.to_string(),
+                server_type: server.server_type.to_label().to_string(),
+                status: server.status,
+                scraped: server.scraped_successfully(),
+                retried: server.scrape_retried,
+                duration_ms: server.scrape_duration_ms,
+                stale_seconds: server.data_age_seconds,
+            })
+            .collect();
+    }
+
+    /// Mark the run as finished, with the overall status or why it failed.
+    pub fn finish(&mut self, finished_at: DateTime<Utc>, outcome: Result<Status, String>) {
+        self.finished_at = Some(finished_at);
+        self.duration_ms = Some((finished_at - self.started_at).num_milliseconds().max(0) as u64);
+        match outcome {
+            Ok(status) => self.status = Some(status),
+            Err(e) => self.error = Some(e),
+        }
+    }
+
+    /// The run finished, without failing.
+    pub fn succeeded(&self) -> bool {
+        self.finished_at.is_some() && self.error.is_none()
+    }
+
+    pub fn save(&self, destination: &Path, sink: &OutputSink) -> Result<()> {
+        let path = destination.join(REPORT_FILE);
+        let json = serde_json::to_string_pretty(self)?;
+        sink.atomic_write(&path, json.as_bytes())
+            .context(format!("Failed to write the run report to {:?}", path))
+    }
+
+    /// The report of the most recent run, `None` if there is none yet.
+    pub fn load(destination: &Path) -> Result<Option<Self>> {
+        let path = destination.join(REPORT_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let contents = fs::read_to_string(&path).context(format!("Failed to read {:?}", path))?;
+        serde_json::from_str(&contents)
+            .map(Some)
+            .context(format!("Failed to parse {:?}", path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use yare::parameterized;
+
+    fn started_at() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 10, 2, 7, 28, 0).unwrap()
+    }
+
+    #[parameterized(
+        in_progress = { None, false },
+        ok = { Some(Ok(Status::FAILED)), true },
+        failed = { Some(Err("Scraping failed for all servers".to_string())), false },
+    )]
+    fn test_succeeded(outcome: Option<Result<Status, String>>, expected: bool) {
+        let mut report = RunReport::new(started_at());
+        if let Some(outcome) = outcome {
+            report.finish(started_at() + chrono::Duration::seconds(3), outcome);
+            assert_eq!(report.duration_ms, Some(3000));
+        }
+
+        assert_eq!(report.succeeded(), expected);
+    }
+
+    #[test]
+    fn test_deliver() {
+        let mut report = RunReport::new(started_at());
+
+        assert!(report.deliver("slack", "stratum1", Ok(())).is_ok());
+        assert!(report
+            .deliver("heartbeat", "", Err(anyhow::anyhow!("Timed out")))
+            .is_err());
+
+        assert_eq!(
+            serde_json::to_value(&report.deliveries).unwrap(),
+            serde_json::json!([
+                { "channel": "slack", "target": "stratum1", "delivered": true },
+                {
+                    "channel": "heartbeat",
+                    "target": "",
+                    "delivered": false,
+                    "error": "Timed out"
+                }
+            ])
+        );
+    }
+
+    #[test]
+    fn test_save_and_load() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        assert_eq!(RunReport::load(dir.path())?, None);
+
+        let mut report = RunReport::new(started_at());
+        report.warn("Ignoring the last scrape".to_string());
+        report.artifact("JSON", dir.path().join("status.json"));
+        report.finish(started_at(), Ok(Status::OK));
+        report.save(dir.path(), &OutputSink::default())?;
+
+        assert_eq!(RunReport::load(dir.path())?, Some(report));
+        Ok(())
+    }
+}
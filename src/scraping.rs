@@ -0,0 +1,553 @@
+use futures::future::join_all;
+use futures::stream::{self, StreamExt};
+use log::{debug, info, warn};
+use std::collections::BTreeSet;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+
+use cvmfs_server_scraper::{
+    CVMFSScraperError, Hostname, PopulatedServer, ScrapeError, ScrapedServer, Server,
+    ServerBackendType,
+};
+
+use crate::client_scrape::{fetch_listing, scrape_with_client};
+use crate::config::{DeepCheckConfig, ScrapeConfig};
+
+/// A server to scrape, optionally reached through a different address than its hostname.
+pub struct ScrapeTarget {
+    pub server: Server,
+    /// An IP address or hostname to connect to instead of the address of the hostname.
+    pub resolve_to: Option<String>,
+}
+
+impl ScrapeTarget {
+    /// The address actually contacted when scraping this target.
+    pub fn address(&self) -> String {
+        self.resolve_to
+            .clone()
+            .unwrap_or_else(|| self.server.hostname.to_string())
+    }
+}
+
+/// Build a client with `builder` that connects to `address` for `hostname`, like
+/// the `--resolve` option of curl. The URLs, and so the `Host` header and the name checked by
+/// TLS, keep the hostname. `address` is an IP address or a name to look up.
+pub async fn resolving_client(
+    builder: reqwest::ClientBuilder,
+    hostname: &Hostname,
+    address: &str,
+) -> Result<reqwest::Client, String> {
+    // Port 0 keeps the port of the URL.
+    let addresses: Vec<SocketAddr> = match address.parse::<IpAddr>() {
+        Ok(ip) => vec![SocketAddr::new(ip, 0)],
+        Err(_) => tokio::net::lookup_host((address, 0))
+            .await
+            .map_err(|e| format!("Failed to resolve {}: {}", address, e))?
+            .collect(),
+    };
+    builder
+        .resolve_to_addrs(hostname.to_str(), &addresses)
+        .build()
+        .map_err(|e| format!("Failed to build a client for {}: {}", address, e))
+}
+
+/// A builder of clients whose requests time out after `timeout_seconds`, as does connecting.
+fn client_builder(config: &ScrapeConfig) -> reqwest::ClientBuilder {
+    let timeout = Duration::from_secs(config.timeout_seconds);
+    reqwest::Client::builder()
+        .timeout(timeout)
+        .connect_timeout(timeout)
+}
+
+/// How a server is contacted: by the scraper itself, or through a client of our own that
+/// connects to another address than that of its hostname. The client of a direct connection
+/// only fetches the repository list when the repositories are scraped one by one.
+enum Connection {
+    Direct(Server, reqwest::Client),
+    Resolved(Server, reqwest::Client),
+}
+
+impl Connection {
+    fn server(&self) -> &Server {
+        match self {
+            Connection::Direct(server, _) | Connection::Resolved(server, _) => server,
+        }
+    }
+
+    fn client(&self) -> &reqwest::Client {
+        match self {
+            Connection::Direct(_, client) | Connection::Resolved(_, client) => client,
+        }
+    }
+
+    /// Scrape the server, fetching up to `concurrency` of its repositories at once through our
+    /// own client. The scraper fetches them one at a time.
+    async fn scrape(
+        &self,
+        repositories: Vec<String>,
+        ignored_repositories: Vec<String>,
+        only_scrape_forced_repositories: bool,
+        concurrency: usize,
+    ) -> ScrapedServer {
+        match self {
+            Connection::Direct(server, _) => {
+                server
+                    .scrape(
+                        repositories,
+                        ignored_repositories,
+                        only_scrape_forced_repositories,
+                        None,
+                    )
+                    .await
+            }
+            Connection::Resolved(server, client) => scrape_with_client(
+                client,
+                server,
+                repositories,
+                &ignored_repositories,
+                only_scrape_forced_repositories,
+                concurrency,
+            )
+            .await
+            .unwrap_or_else(|error| ScrapedServer::Failed(server.to_failed_server(error))),
+        }
+    }
+}
+
+/// The result of scraping a single server, along with how long the scrape took.
+///
+/// Failed scrapes report the time spent before giving up.
+pub struct TimedScrape {
+    pub server: ScrapedServer,
+    pub duration: Duration,
+    /// The address contacted, which differs from the hostname when `resolve_to` is set.
+    pub address: String,
+    /// Repositories that failed to scrape on an otherwise reachable server.
+    pub repository_errors: Vec<RepositoryError>,
+    /// The first scrape failed, and the repositories were scraped again one by one.
+    pub retried: bool,
+}
+
+/// A repository that could not be scraped, and why.
+#[derive(Debug, Clone)]
+pub struct RepositoryError {
+    pub repository: String,
+    pub error: String,
+}
+
+/// Scrape all servers in parallel, timing each scrape individually.
+///
+/// This mirrors the scraping done by `cvmfs_server_scraper::Scraper`, including its validation
+/// that S3 servers are only scraped with an explicit list of repositories.
+///
+/// Servers with a `resolve_to` address are scraped through a client that connects to that
+/// address for the configured hostname, which the requests keep using. A server that isn't
+/// scraped within `deadline_seconds`, including the retry of its repositories, is FAILED.
+pub async fn scrape_servers(
+    targets: Vec<ScrapeTarget>,
+    repositories: Vec<String>,
+    ignored_repositories: Vec<String>,
+    only_scrape_forced_repositories: bool,
+    config: &ScrapeConfig,
+) -> Result<Vec<TimedScrape>, ScrapeError> {
+    if repositories.is_empty()
+        && targets
+            .iter()
+            .any(|t| t.server.backend_type == ServerBackendType::S3)
+    {
+        return Err(ScrapeError::EmptyRepositoryList(
+            "Forced repositories list cannot be empty if any servers use the S3 backend"
+                .to_string(),
+        ));
+    }
+
+    let start = Instant::now();
+    let futures = targets.iter().map(|target| {
+        let repositories = repositories.clone();
+        let ignored_repositories = ignored_repositories.clone();
+        async move {
+            let address = target.address();
+            let scrape_start = Instant::now();
+            let deadline =
+                tokio::time::Instant::now() + Duration::from_secs(config.deadline_seconds);
+            let client = match &target.resolve_to {
+                None => client_builder(config)
+                    .build()
+                    .map_err(|e| format!("Failed to build a client: {}", e)),
+                Some(resolve_to) => {
+                    resolving_client(client_builder(config), &target.server.hostname, resolve_to)
+                        .await
+                }
+            };
+            let connection = match (client, &target.resolve_to) {
+                (Ok(client), None) => Connection::Direct(target.server.clone(), client),
+                (Ok(client), Some(_)) => Connection::Resolved(target.server.clone(), client),
+                (Err(e), _) => {
+                    let error = ScrapeError::ConversionError(e).into();
+                    return TimedScrape {
+                        server: ScrapedServer::Failed(target.server.to_failed_server(error)),
+                        duration: scrape_start.elapsed(),
+                        address,
+                        repository_errors: vec![],
+                        retried: false,
+                    };
+                }
+            };
+            let scrape = connection.scrape(
+                repositories.clone(),
+                ignored_repositories.clone(),
+                only_scrape_forced_repositories,
+                config.concurrency,
+            );
+            let mut scraped = match tokio::time::timeout_at(deadline, scrape).await {
+                Ok(scraped) => scraped,
+                Err(_) => {
+                    ScrapedServer::Failed(target.server.to_failed_server(deadline_error(config)))
+                }
+            };
+            let mut repository_errors = Vec::new();
+            let retried = scraped.is_failed();
+
+            // The scraper fails the whole server if any repository fails, so retry the
+            // repositories one by one to tell a broken repository from an unreachable server,
+            // within what is left of the deadline.
+            if scraped.is_failed() {
+                let retry = scrape_per_repository(
+                    &connection,
+                    &repositories,
+                    &ignored_repositories,
+                    only_scrape_forced_repositories,
+                    config.concurrency,
+                );
+                if let Ok(Some((partial, errors))) = tokio::time::timeout_at(deadline, retry).await
+                {
+                    scraped = ScrapedServer::Populated(partial);
+                    repository_errors = errors;
+                }
+            }
+            let duration = scrape_start.elapsed();
+
+            TimedScrape {
+                server: scraped,
+                duration,
+                address,
+                repository_errors,
+                retried,
+            }
+        }
+    });
+
+    let scrapes = join_all(futures).await;
+
+    for scrape in scrapes.iter() {
+        match &scrape.server {
+            ScrapedServer::Populated(server) => {
+                info!(
+                    hostname:% = server.hostname,
+                    repositories = server.repositories.len(),
+                    duration_ms = scrape.duration.as_millis() as u64;
+                    "Scraped server: {} with {} repositories in {:?}",
+                    server.hostname,
+                    server.repositories.len(),
+                    scrape.duration
+                );
+                for failure in scrape.repository_errors.iter() {
+                    warn!(
+                        hostname:% = server.hostname,
+                        repository = failure.repository.as_str();
+                        "Scraping failed for repository {} on server: {} with error: {}",
+                        failure.repository, server.hostname, failure.error
+                    );
+                }
+            }
+            ScrapedServer::Failed(server) => warn!(
+                hostname:% = server.hostname,
+                duration_ms = scrape.duration.as_millis() as u64;
+                "Scraping failed for server: {} after {:?} with error: {}",
+                server.hostname, scrape.duration, server.error
+            ),
+        }
+    }
+
+    let succeeded = scrapes.iter().filter(|s| s.server.is_ok()).count();
+    info!(
+        servers = scrapes.len(),
+        succeeded = succeeded,
+        duration_ms = start.elapsed().as_millis() as u64;
+        "Scraped {} servers ({} succeeded), run duration: {:?}",
+        scrapes.len(),
+        succeeded,
+        start.elapsed()
+    );
+
+    Ok(scrapes)
+}
+
+/// The error of a server that wasn't scraped within `deadline_seconds`.
+fn deadline_error(config: &ScrapeConfig) -> CVMFSScraperError {
+    ScrapeError::ConversionError(format!(
+        "Scrape timed out after {} seconds",
+        config.deadline_seconds
+    ))
+    .into()
+}
+
+/// Scrape the repositories of a server each on its own, up to `concurrency` at once.
+///
+/// Returns `None` if the server itself looks unreachable, that is when its repository list
+/// can't be fetched or no repository at all could be scraped. Otherwise the successfully
+/// scraped repositories are merged into one server, along with the errors of the others.
+async fn scrape_per_repository(
+    connection: &Connection,
+    repositories: &[String],
+    ignored_repositories: &[String],
+    only_scrape_forced_repositories: bool,
+    concurrency: usize,
+) -> Option<(PopulatedServer, Vec<RepositoryError>)> {
+    let mut names: BTreeSet<String> = repositories.iter().cloned().collect();
+
+    let server = connection.server();
+    if !only_scrape_forced_repositories && server.backend_type != ServerBackendType::S3 {
+        let listing = fetch_listing(connection.client(), &server.hostname).await;
+        match listing.ok() {
+            Some(listing) => names.extend(listing.names()),
+            None if server.backend_type == ServerBackendType::CVMFS => return None,
+            None => {}
+        }
+    }
+    names.retain(|name| !ignored_repositories.contains(name));
+
+    let mut scrapes: Vec<(String, ScrapedServer)> = stream::iter(names)
+        .map(|name| async move {
+            debug!("Scraping {} on {} on its own", name, server.hostname);
+            let scraped = connection.scrape(vec![name.clone()], vec![], true, 1).await;
+            (name, scraped)
+        })
+        .buffer_unordered(concurrency.max(1))
+        .collect()
+        .await;
+    // In the order of the names, as they finish in any order.
+    scrapes.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut merged: Option<PopulatedServer> = None;
+    let mut errors = Vec::new();
+    for (name, scraped) in scrapes {
+        match scraped {
+            ScrapedServer::Populated(server) => match merged.as_mut() {
+                Some(merged) => merged.repositories.extend(server.repositories),
+                None => merged = Some(server),
+            },
+            ScrapedServer::Failed(failed) => errors.push(RepositoryError {
+                repository: name,
+                error: failed.error.to_string(),
+            }),
+        }
+    }
+
+    merged.map(|server| (server, errors))
+}
+
+/// A repository on a server whose data path should be deep checked.
+pub struct DataCheckTarget {
+    pub hostname: Hostname,
+    /// The address to connect to instead of that of the hostname, see `ScrapeTarget`.
+    pub resolve_to: Option<String>,
+    pub repository: String,
+    /// The root catalog hash from the manifest (the `c` field).
+    pub catalog_hash: String,
+}
+
+/// The outcome of a deep check, `Err` holds the reason the check failed.
+pub struct DataCheckResult {
+    pub hostname: Hostname,
+    pub repository: String,
+    pub result: Result<(), String>,
+}
+
+/// The URL of the root catalog object referenced by a manifest.
+///
+/// Objects live under `data/` split on the first two characters of the hash, and catalogs
+/// carry a `C` suffix.
+pub fn catalog_url(hostname: &Hostname, repository: &str, catalog_hash: &str) -> String {
+    let (prefix, rest) = catalog_hash.split_at(2.min(catalog_hash.len()));
+    format!(
+        "http://{}/cvmfs/{}/data/{}/{}C",
+        hostname, repository, prefix, rest
+    )
+}
+
+/// Fetch the root catalog of each target, limited by the configured timeout and concurrency.
+pub async fn check_data_paths(
+    targets: Vec<DataCheckTarget>,
+    config: &DeepCheckConfig,
+) -> Vec<DataCheckResult> {
+    if targets.is_empty() {
+        return Vec::new();
+    }
+
+    let builder =
+        || reqwest::Client::builder().timeout(Duration::from_secs(config.timeout_seconds));
+    let client = match builder().build() {
+        Ok(client) => client,
+        Err(e) => {
+            warn!("Failed to build client for deep checks: {}", e);
+            return Vec::new();
+        }
+    };
+
+    stream::iter(targets)
+        .map(|target| {
+            let client = client.clone();
+            async move {
+                let result = match &target.resolve_to {
+                    None => check_data_path(&client, &target, config.verify_hash).await,
+                    Some(resolve_to) => {
+                        match resolving_client(builder(), &target.hostname, resolve_to).await {
+                            Ok(client) => {
+                                check_data_path(&client, &target, config.verify_hash).await
+                            }
+                            Err(e) => Err(e),
+                        }
+                    }
+                };
+                if let Err(e) = &result {
+                    warn!(
+                        "Deep check failed for {} on {}: {}",
+                        target.repository, target.hostname, e
+                    );
+                }
+                DataCheckResult {
+                    hostname: target.hostname,
+                    repository: target.repository,
+                    result,
+                }
+            }
+        })
+        .buffer_unordered(config.concurrency.max(1))
+        .collect()
+        .await
+}
+
+async fn check_data_path(
+    client: &reqwest::Client,
+    target: &DataCheckTarget,
+    verify_hash: bool,
+) -> Result<(), String> {
+    let url = catalog_url(&target.hostname, &target.repository, &target.catalog_hash);
+    debug!("Deep checking {}", url);
+
+    let request = if verify_hash {
+        client.get(&url)
+    } else {
+        client.head(&url)
+    };
+
+    let response = request
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| e.to_string())?;
+
+    if !verify_hash {
+        return Ok(());
+    }
+
+    // Only plain SHA-1 hashes are verified, other algorithms carry a suffix we can't check.
+    if target.catalog_hash.len() != 40 {
+        debug!(
+            "Not verifying non SHA-1 catalog hash {}",
+            target.catalog_hash
+        );
+        return Ok(());
+    }
+
+    let body = response.bytes().await.map_err(|e| e.to_string())?;
+    let digest = openssl::sha::sha1(&body)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+
+    if digest != target.catalog_hash {
+        return Err(format!(
+            "content hash mismatch (expected {}, got {})",
+            target.catalog_hash, digest
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_catalog_url() {
+        let hostname = Hostname::try_from("s1.example.com").unwrap();
+        assert_eq!(
+            catalog_url(
+                &hostname,
+                "software.eessi.io",
+                "f2b0a4e5c5c87de6e0e1fa3b10dd3f0c8cbd4c24"
+            ),
+            "http://s1.example.com/cvmfs/software.eessi.io/data/f2/b0a4e5c5c87de6e0e1fa3b10dd3f0c8cbd4c24C"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_client_times_out() {
+        // Accepts the connection, but never answers.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let _connection = listener.accept().await;
+            std::future::pending::<()>().await
+        });
+        let config = ScrapeConfig {
+            timeout_seconds: 1,
+            ..Default::default()
+        };
+        let hostname = Hostname::try_from("s1.invalid").unwrap();
+        let client = resolving_client(client_builder(&config), &hostname, "127.0.0.1")
+            .await
+            .unwrap();
+
+        let error = client
+            .get(format!("http://{}:{}/", hostname, port))
+            .send()
+            .await
+            .unwrap_err();
+
+        assert!(error.is_timeout(), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_resolving_client_keeps_hostname() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let router = axum::Router::new().route(
+            "/host",
+            axum::routing::get(|headers: axum::http::HeaderMap| async move {
+                headers["host"].to_str().unwrap().to_string()
+            }),
+        );
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        // The .invalid domain never resolves, so the request can only reach the listener
+        // through the resolved address.
+        let hostname = Hostname::try_from("s1.invalid").unwrap();
+        let client = resolving_client(reqwest::Client::builder(), &hostname, "127.0.0.1")
+            .await
+            .unwrap();
+        let host = client
+            .get(format!("http://{}:{}/host", hostname, port))
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+
+        assert_eq!(host, format!("s1.invalid:{}", port));
+    }
+}
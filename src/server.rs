@@ -0,0 +1,350 @@
+use axum::extract::{Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use log::{debug, warn};
+use std::future::Future;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tokio::net::TcpListener;
+
+use crate::prometheus::MetricsFormat;
+use crate::report::RunReport;
+
+/// When the daemon last finished a run successfully, for `/healthz`.
+#[derive(Debug, Clone)]
+pub struct Health {
+    last_success: Arc<Mutex<Option<Instant>>>,
+    max_age: Duration,
+}
+
+impl Health {
+    /// Healthy while the last successful run finished less than `max_age` ago.
+    pub fn new(max_age: Duration) -> Self {
+        Health {
+            last_success: Arc::new(Mutex::new(None)),
+            max_age,
+        }
+    }
+
+    pub fn record_success(&self) {
+        *self.last_success.lock().unwrap() = Some(Instant::now());
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.last_success
+            .lock()
+            .unwrap()
+            .is_some_and(|last| last.elapsed() < self.max_age)
+    }
+}
+
+/// The Prometheus metrics of the most recent run, for `/metrics`.
+#[derive(Debug, Clone)]
+pub struct Metrics {
+    text: Arc<RwLock<String>>,
+    format: MetricsFormat,
+}
+
+impl Metrics {
+    /// Metrics rendered in `format`.
+    pub fn new(format: MetricsFormat) -> Self {
+        Metrics {
+            text: Arc::new(RwLock::new(String::new())),
+            format,
+        }
+    }
+
+    pub fn set(&self, text: String) {
+        *self.text.write().unwrap() = text;
+    }
+
+    pub fn get(&self) -> String {
+        self.text.read().unwrap().clone()
+    }
+}
+
+#[derive(Clone)]
+struct AppState {
+    destination: PathBuf,
+    output_file: PathBuf,
+    json_output_file: PathBuf,
+    health: Health,
+    metrics: Metrics,
+}
+
+/// The routes serving the destination directory.
+///
+/// `/` and `/status.json` serve the generated page and JSON status, whatever their filenames,
+/// and `/metrics` the metrics of the most recent run.
+/// Other paths are served from the destination, except hidden files such as the lock file.
+/// Files are read on every request, so a file replaced by `atomic_write` is served either
+/// entirely old or entirely new.
+pub fn router(
+    destination: &Path,
+    output_file: &Path,
+    json_output_file: &Path,
+    health: Health,
+    metrics: Metrics,
+) -> Router {
+    let state = AppState {
+        destination: destination.to_path_buf(),
+        output_file: output_file.to_path_buf(),
+        json_output_file: json_output_file.to_path_buf(),
+        health,
+        metrics,
+    };
+
+    Router::new()
+        .route("/", get(index))
+        .route("/status.json", get(status_json))
+        .route("/healthz", get(healthz))
+        .route("/metrics", get(metrics_text))
+        .fallback(get(file))
+        .layer(middleware::from_fn(access_log))
+        .with_state(state)
+}
+
+/// Serve until `shutdown` completes, then finish the requests in progress.
+pub async fn serve(
+    listener: TcpListener,
+    router: Router,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> std::io::Result<()> {
+    axum::serve(listener, router)
+        .with_graceful_shutdown(shutdown)
+        .await
+}
+
+async fn index(State(state): State<AppState>) -> Response {
+    serve_file(&state.destination.join(&state.output_file)).await
+}
+
+async fn status_json(State(state): State<AppState>) -> Response {
+    serve_file(&state.destination.join(&state.json_output_file)).await
+}
+
+/// The health of the daemon, with the report of the most recent run as the body once there is
+/// one, which tells why a run failed.
+async fn healthz(State(state): State<AppState>) -> Response {
+    let (status, text) = if state.health.is_healthy() {
+        (StatusCode::OK, "ok\n".to_string())
+    } else {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            format!(
+                "no successful run in the last {}s\n",
+                state.health.max_age.as_secs()
+            ),
+        )
+    };
+    match RunReport::load(&state.destination) {
+        Ok(Some(report)) => (
+            status,
+            [(header::CONTENT_TYPE, "application/json")],
+            serde_json::to_string_pretty(&report).unwrap_or_default(),
+        )
+            .into_response(),
+        Ok(None) => (status, text).into_response(),
+        Err(e) => {
+            warn!("{:#}", e);
+            (status, text).into_response()
+        }
+    }
+}
+
+async fn metrics_text(State(state): State<AppState>) -> Response {
+    let text = state.metrics.get();
+    if text.is_empty() {
+        return (StatusCode::SERVICE_UNAVAILABLE, "no metrics yet\n").into_response();
+    }
+    (
+        [(header::CONTENT_TYPE, state.metrics.format.content_type())],
+        text,
+    )
+        .into_response()
+}
+
+async fn file(State(state): State<AppState>, request: Request) -> Response {
+    match resolve(&state.destination, request.uri().path()) {
+        Some(path) => serve_file(&path).await,
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+async fn access_log(request: Request, next: Next) -> Response {
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    let started = Instant::now();
+
+    let response = next.run(request).await;
+
+    debug!(
+        method = method.as_str(),
+        path = path.as_str(),
+        status = response.status().as_u16(),
+        duration_ms = started.elapsed().as_millis() as u64;
+        "{} {} {}", method, path, response.status().as_u16()
+    );
+    response
+}
+
+/// The file in `destination` for a request path, `None` if the path leaves the destination
+/// or names a hidden file. Directories are served by their `index.html`.
+fn resolve(destination: &Path, url_path: &str) -> Option<PathBuf> {
+    let relative = Path::new(url_path.trim_start_matches('/'));
+    let mut path = destination.to_path_buf();
+    for component in relative.components() {
+        match component {
+            Component::Normal(name) if !name.to_string_lossy().starts_with('.') => path.push(name),
+            _ => return None,
+        }
+    }
+
+    if url_path.ends_with('/') || path.is_dir() {
+        path.push("index.html");
+    }
+    Some(path)
+}
+
+async fn serve_file(path: &Path) -> Response {
+    match tokio::fs::read(path).await {
+        Ok(contents) => ([(header::CONTENT_TYPE, content_type(path))], contents).into_response(),
+        Err(e)
+            if matches!(
+                e.kind(),
+                std::io::ErrorKind::NotFound | std::io::ErrorKind::NotADirectory
+            ) =>
+        {
+            StatusCode::NOT_FOUND.into_response()
+        }
+        Err(e) => {
+            warn!("Failed to read {:?}: {}", path, e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// The content type of the files written to the destination.
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("html") => "text/html; charset=utf-8",
+        Some("json") => "application/json",
+        Some("css") => "text/css; charset=utf-8",
+        Some("js") => "text/javascript; charset=utf-8",
+        Some("png") => "image/png",
+        Some("svg") => "image/svg+xml",
+        Some("woff") => "font/woff",
+        Some("woff2") => "font/woff2",
+        Some("ttf") => "font/ttf",
+        Some("eot") => "application/vnd.ms-fontobject",
+        Some("txt") => "text/plain; charset=utf-8",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use yare::parameterized;
+
+    #[parameterized(
+        root = { "/", Some("index.html") },
+        file = { "/status.css", Some("status.css") },
+        nested = { "/webfonts/fa-solid-900.woff2", Some("webfonts/fa-solid-900.woff2") },
+        directory = { "/metrics/", Some("metrics/index.html") },
+        parent = { "/../etc/passwd", None },
+        hidden = { "/.status-page.lock", None },
+    )]
+    fn test_resolve(url_path: &str, expected: Option<&str>) {
+        let destination = Path::new("/srv/status");
+        assert_eq!(
+            resolve(destination, url_path),
+            expected.map(|e| destination.join(e))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_serve() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("status.html"), "<html></html>").unwrap();
+        fs::write(dir.path().join("out.json"), "{}").unwrap();
+        fs::write(dir.path().join("status.css"), "body {}").unwrap();
+        fs::write(dir.path().join(".status-page.lock"), "1").unwrap();
+
+        let health = Health::new(Duration::from_secs(60));
+        let metrics = Metrics::new(MetricsFormat::Prometheus);
+        let router = router(
+            dir.path(),
+            Path::new("status.html"),
+            Path::new("out.json"),
+            health.clone(),
+            metrics.clone(),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+        let server = tokio::spawn(serve(listener, router, async {
+            stopped.await.ok();
+        }));
+
+        let get = |path: &str| reqwest::get(format!("{}{}", base, path));
+        let content_type = |response: &reqwest::Response| {
+            response.headers()[header::CONTENT_TYPE]
+                .to_str()
+                .unwrap()
+                .to_string()
+        };
+
+        let response = get("/").await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(content_type(&response), "text/html; charset=utf-8");
+
+        let response = get("/status.json").await.unwrap();
+        assert_eq!(content_type(&response), "application/json");
+        assert_eq!(response.text().await.unwrap(), "{}");
+
+        let response = get("/status.css").await.unwrap();
+        assert_eq!(content_type(&response), "text/css; charset=utf-8");
+
+        assert_eq!(get("/missing.html").await.unwrap().status(), 404);
+        assert_eq!(get("/status.css/").await.unwrap().status(), 404);
+        assert_eq!(get("/.status-page.lock").await.unwrap().status(), 404);
+
+        assert_eq!(get("/healthz").await.unwrap().status(), 503);
+        health.record_success();
+        let response = get("/healthz").await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.text().await.unwrap(), "ok\n");
+
+        let mut report = RunReport::new(chrono::Utc::now());
+        report.finish(chrono::Utc::now(), Err("Scraping failed".to_string()));
+        report
+            .save(dir.path(), &crate::dependencies::OutputSink::default())
+            .unwrap();
+        let response = get("/healthz").await.unwrap();
+        assert_eq!(content_type(&response), "application/json");
+        let served: RunReport = response.json().await.unwrap();
+        assert_eq!(served, report);
+
+        assert_eq!(get("/metrics").await.unwrap().status(), 503);
+        metrics.set("eessi_status 0\n".to_string());
+        let response = get("/metrics").await.unwrap();
+        assert_eq!(content_type(&response), "text/plain; version=0.0.4");
+        assert_eq!(response.text().await.unwrap(), "eessi_status 0\n");
+
+        stop.send(()).unwrap();
+        server.await.unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_health_expires() {
+        let health = Health::new(Duration::ZERO);
+        health.record_success();
+        assert!(!health.is_healthy());
+    }
+}
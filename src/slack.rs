@@ -0,0 +1,224 @@
+use anyhow::{Context, Result};
+use log::{debug, info};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use crate::config::SlackConfig;
+use crate::models::Status;
+use crate::notifications::Transition;
+
+/// The color of the bar next to the message.
+fn color(status: Status) -> &'static str {
+    match status {
+        Status::OK => "#2eb67d",
+        Status::DEGRADED | Status::WARNING => "#ecb22e",
+        Status::FAILED => "#e01e5a",
+        Status::MAINTENANCE => "#36c5f0",
+    }
+}
+
+/// Whether the transition is one of the statuses to post about.
+pub fn wanted(config: &SlackConfig, transition: &Transition) -> bool {
+    config.statuses.contains(&transition.to)
+}
+
+/// The message for a transition, as Block Kit blocks in an attachment colored by the new
+/// status. `text` is the fallback for notifications.
+pub fn payload(config: &SlackConfig, transition: &Transition) -> Value {
+    let from = transition
+        .from
+        .map_or("new".to_string(), |from| from.to_string());
+    let text = format!(
+        "{} is {} (was {})",
+        transition.component, transition.to, from
+    );
+
+    let mut blocks = vec![json!({
+        "type": "section",
+        "text": {
+            "type": "mrkdwn",
+            "text": format!(
+                "*{}* changed from {} to *{}*: {}",
+                transition.component,
+                from,
+                transition.to,
+                transition.to.text()
+            ),
+        },
+    })];
+    if !transition.conditions.is_empty() {
+        let conditions: Vec<String> = transition
+            .conditions
+            .iter()
+            .map(|c| format!("`{}`", c))
+            .collect();
+        blocks.push(json!({
+            "type": "section",
+            "text": { "type": "mrkdwn", "text": format!("Rule: {}", conditions.join(" or ")) },
+        }));
+    }
+    if !transition.details.is_empty() {
+        blocks.push(json!({
+            "type": "context",
+            "elements": [{ "type": "mrkdwn", "text": transition.details.join("\n") }],
+        }));
+    }
+    if let Some(url) = &config.status_page_url {
+        blocks.push(json!({
+            "type": "section",
+            "text": { "type": "mrkdwn", "text": format!("<{}|View the status page>", url) },
+        }));
+    }
+
+    let mut payload = json!({
+        "text": text,
+        "attachments": [{ "color": color(transition.to), "blocks": blocks }],
+    });
+    if let Some(channel) = &config.channel {
+        payload["channel"] = json!(channel);
+    }
+    payload
+}
+
+/// Post a message to the incoming webhook.
+///
+/// Fails on a connection error or a non-success response, including the response body.
+pub async fn send(config: &SlackConfig, payload: &Value) -> Result<()> {
+    let url = std::env::var(&config.webhook_url_env).context(format!(
+        "Environment variable {} is not set",
+        config.webhook_url_env
+    ))?;
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(config.timeout_seconds))
+        .build()
+        .context("Failed to build the Slack client")?;
+
+    // The webhook URL is a secret, so it is left out of the messages.
+    debug!("Posting to the Slack webhook");
+    let response = client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(payload.to_string())
+        .send()
+        .await
+        .context("Failed to post to the Slack webhook")?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        anyhow::bail!("Slack responded with {}: {}", status, body.trim());
+    }
+
+    info!("Posted to Slack");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yare::parameterized;
+
+    fn config() -> SlackConfig {
+        SlackConfig {
+            webhook_url_env: "SLACK_WEBHOOK_URL".to_string(),
+            channel: Some("#eessi-status".to_string()),
+            statuses: vec![Status::FAILED, Status::OK],
+            status_page_url: Some("https://status.eessi.io".to_string()),
+            timeout_seconds: 10,
+        }
+    }
+
+    fn transition(from: Option<Status>, to: Status) -> Transition {
+        Transition {
+            component: "stratum1".to_string(),
+            from,
+            to,
+            conditions: vec!["stratum1_servers == 0".to_string()],
+            details: vec!["No stratum1 servers scraped!".to_string()],
+        }
+    }
+
+    #[parameterized(
+        ok = { Status::FAILED, Status::OK, "#2eb67d", "Normal service" },
+        degraded = { Status::OK, Status::DEGRADED, "#ecb22e", "Degraded" },
+        warning = { Status::OK, Status::WARNING, "#ecb22e", "Warning" },
+        failed = { Status::OK, Status::FAILED, "#e01e5a", "Failed" },
+        maintenance = { Status::OK, Status::MAINTENANCE, "#36c5f0", "Maintenance" },
+    )]
+    fn test_payload(from: Status, to: Status, color: &str, text: &str) {
+        assert_eq!(
+            payload(&config(), &transition(Some(from), to)),
+            json!({
+                "channel": "#eessi-status",
+                "text": format!("stratum1 is {} (was {})", to, from),
+                "attachments": [{
+                    "color": color,
+                    "blocks": [
+                        {
+                            "type": "section",
+                            "text": {
+                                "type": "mrkdwn",
+                                "text": format!("*stratum1* changed from {} to *{}*: {}", from, to, text),
+                            },
+                        },
+                        {
+                            "type": "section",
+                            "text": { "type": "mrkdwn", "text": "Rule: `stratum1_servers == 0`" },
+                        },
+                        {
+                            "type": "context",
+                            "elements": [{ "type": "mrkdwn", "text": "No stratum1 servers scraped!" }],
+                        },
+                        {
+                            "type": "section",
+                            "text": { "type": "mrkdwn", "text": "<https://status.eessi.io|View the status page>" },
+                        },
+                    ],
+                }],
+            })
+        );
+    }
+
+    #[test]
+    fn test_payload_minimal() {
+        let config = SlackConfig {
+            channel: None,
+            status_page_url: None,
+            ..config()
+        };
+        let transition = Transition {
+            conditions: vec![],
+            details: vec![],
+            ..transition(None, Status::OK)
+        };
+
+        assert_eq!(
+            payload(&config, &transition),
+            json!({
+                "text": "stratum1 is OK (was new)",
+                "attachments": [{
+                    "color": "#2eb67d",
+                    "blocks": [{
+                        "type": "section",
+                        "text": {
+                            "type": "mrkdwn",
+                            "text": "*stratum1* changed from new to *OK*: Normal service",
+                        },
+                    }],
+                }],
+            })
+        );
+    }
+
+    #[parameterized(
+        failed = { Status::FAILED, true },
+        recovered = { Status::OK, true },
+        warning = { Status::WARNING, false },
+    )]
+    fn test_wanted(to: Status, expected: bool) {
+        assert_eq!(
+            wanted(&config(), &transition(Some(Status::DEGRADED), to)),
+            expected
+        );
+    }
+}
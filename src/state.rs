@@ -0,0 +1,308 @@
+use anyhow::Result;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::dependencies::OutputSink;
+use crate::models::{HighestRevisions, Server, StatusPageData};
+use crate::prometheus::Histogram;
+
+/// The file, in the destination directory by default, holding the counters kept across runs.
+pub const STATE_FILE_NAME: &str = ".state.json";
+
+/// Cumulative counters, so they can be exported as Prometheus counters although every run
+/// starts from scratch, and what else a run needs from the previous one.
+#[derive(Debug, Serialize, Deserialize, Default, PartialEq)]
+pub struct RunState {
+    pub runs_total: u64,
+    /// How many pushes to the Pushgateway have failed.
+    #[serde(default)]
+    pub push_failures_total: u64,
+    /// How many sends to Graphite have failed.
+    #[serde(default)]
+    pub graphite_failures_total: u64,
+    /// How many sends to StatsD have failed.
+    #[serde(default)]
+    pub statsd_failures_total: u64,
+    /// How many heartbeat pings have failed.
+    #[serde(default)]
+    pub heartbeat_failures_total: u64,
+    /// Counters per server, by hostname.
+    pub servers: BTreeMap<String, ServerCounters>,
+    /// The highest revision of each repository seen on each server, which the revisions of the
+    /// next run may not be lower than.
+    #[serde(default)]
+    pub highest_revisions: HighestRevisions,
+    /// The status of the previous run, kept when status.json wasn't written, as with
+    /// `--no-json`, to tell what changed since.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_status: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, PartialEq, Clone)]
+pub struct ServerCounters {
+    /// Runs in which the server failed to scrape, including runs showing stale data.
+    pub scrape_failures_total: u64,
+    /// Runs in which the repositories of the server were scraped again one by one.
+    pub scrape_retries_total: u64,
+    /// How long the scrapes of the server took, in seconds.
+    #[serde(default)]
+    pub scrape_duration_seconds: Option<Histogram>,
+}
+
+impl RunState {
+    /// Read the state from `path`. A missing or corrupt file starts the counters from zero.
+    pub fn load(path: &Path) -> Self {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) => {
+                warn!("Counters start from zero, failed to read {:?}: {}", path, e);
+                return RunState::default();
+            }
+        };
+
+        serde_json::from_str(&contents).unwrap_or_else(|e| {
+            warn!(
+                "Counters start from zero, failed to parse {:?}: {}",
+                path, e
+            );
+            RunState::default()
+        })
+    }
+
+    pub fn save(&self, path: &Path, sink: &OutputSink) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        sink.atomic_write(path, json.as_bytes())?;
+        info!("State written to: {:?}", path);
+        Ok(())
+    }
+
+    /// Count a run that scraped `servers`. The scrape durations are counted into
+    /// `duration_buckets`, starting over for servers whose histogram has other buckets.
+    pub fn record(&mut self, servers: &[Server], duration_buckets: &[f64]) {
+        self.runs_total += 1;
+        for server in servers {
+            let counters = self.servers.entry(server.hostname.to_string()).or_default();
+            if !server.scraped_successfully() {
+                counters.scrape_failures_total += 1;
+            }
+            if server.scrape_retried {
+                counters.scrape_retries_total += 1;
+            }
+            if let Some(duration_ms) = server.scrape_duration_ms {
+                let histogram = counters
+                    .scrape_duration_seconds
+                    .get_or_insert_with(|| Histogram::new(duration_buckets));
+                if !histogram.has_buckets(duration_buckets) {
+                    *histogram = Histogram::new(duration_buckets);
+                }
+                histogram.observe(duration_ms as f64 / 1000.0);
+            }
+        }
+    }
+
+    /// Keep the revisions of the repositories on the servers scraped in this run as the
+    /// highest ones, unless they went backwards.
+    pub fn record_revisions(&mut self, servers: &[Server]) {
+        for server in servers.iter().filter(|s| s.scraped_successfully()) {
+            let highest = self
+                .highest_revisions
+                .entry(server.hostname.to_string())
+                .or_default();
+            for repo in server.repositories.iter().filter(|r| r.manifest.is_some()) {
+                highest.insert(
+                    repo.name.clone(),
+                    repo.regressed_from.unwrap_or(repo.revision),
+                );
+            }
+        }
+    }
+
+    /// Accept the revision of `repository` on the server `hostname` in the next run, even if
+    /// it went backwards, by forgetting the highest revision seen. Returns whether there was
+    /// one.
+    pub fn acknowledge_regression(&mut self, repository: &str, hostname: &str) -> bool {
+        self.highest_revisions
+            .get_mut(hostname)
+            .and_then(|repositories| repositories.remove(repository))
+            .is_some()
+    }
+
+    /// The status kept by the previous run, if it kept one that still parses.
+    pub fn previous_status(&self) -> Option<StatusPageData> {
+        let value = self.previous_status.clone()?;
+        serde_json::from_value(value)
+            .map_err(|e| warn!("Ignoring the previous status in the state: {}", e))
+            .ok()
+    }
+
+    pub fn get(&self, server: &Server) -> ServerCounters {
+        self.servers
+            .get(&server.hostname.to_string())
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Status;
+    use crate::testing::ServerFixture;
+    use cvmfs_server_scraper::{Hostname, ServerBackendType, ServerType};
+    use yare::parameterized;
+
+    fn server(hostname: &str, scraped: bool, retried: bool) -> Server {
+        Server {
+            server_type: ServerType::Stratum1,
+            backend_type: ServerBackendType::CVMFS,
+            backend_detected: scraped.then_some(ServerBackendType::CVMFS),
+            hostname: Hostname::try_from(hostname).unwrap(),
+            repositories: vec![],
+            status: if scraped { Status::OK } else { Status::FAILED },
+            metadata: None,
+            geodb_status: Status::OK,
+            cert_expiry: None,
+            cert_expiry_days: None,
+            scrape_duration_ms: Some(1500),
+            address: None,
+            members: vec![],
+            data_age_seconds: None,
+            scrape_retried: retried,
+        }
+    }
+
+    #[test]
+    fn test_record_revisions() {
+        let mut state = RunState {
+            highest_revisions: serde_json::from_value(serde_json::json!({
+                "s1.example.com": { "software.eessi.io": 4200, "dev.eessi.io": 7 },
+                "s2.example.com": { "software.eessi.io": 100 }
+            }))
+            .unwrap(),
+            ..RunState::default()
+        };
+        let mut regressed = ServerFixture::stratum1("s1.example.com")
+            .with_repo("software.eessi.io", 4000)
+            .with_repo("dev.eessi.io", 8)
+            .with_failed_repo("riscv.eessi.io", "timeout")
+            .build();
+        regressed.repositories[0].regressed_from = Some(4200);
+
+        state.record_revisions(&[
+            regressed,
+            ServerFixture::stratum1("s2.example.com").failed().build(),
+            ServerFixture::stratum1("s3.example.com")
+                .with_repo("software.eessi.io", 5)
+                .build(),
+        ]);
+
+        // A regression keeps the highest revision until it is acknowledged.
+        assert_eq!(
+            serde_json::to_value(&state.highest_revisions).unwrap(),
+            serde_json::json!({
+                "s1.example.com": { "software.eessi.io": 4200, "dev.eessi.io": 8 },
+                "s2.example.com": { "software.eessi.io": 100 },
+                "s3.example.com": { "software.eessi.io": 5 }
+            })
+        );
+        assert!(state.acknowledge_regression("software.eessi.io", "s1.example.com"));
+        assert!(!state.acknowledge_regression("software.eessi.io", "s1.example.com"));
+        assert!(!state.acknowledge_regression("software.eessi.io", "s4.example.com"));
+        assert_eq!(
+            state.highest_revisions["s1.example.com"].get("software.eessi.io"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_previous_status_survives_runs() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join(STATE_FILE_NAME);
+        let data: StatusPageData =
+            serde_json::from_str(include_str!("testdata/status-schema-0.json"))?;
+
+        let mut state = RunState::load(&path);
+        assert!(state.previous_status().is_none());
+        state.previous_status = Some(serde_json::to_value(&data)?);
+        state.save(&path, &OutputSink::default())?;
+
+        let previous = RunState::load(&path).previous_status().unwrap();
+        assert_eq!(previous.last_update, data.last_update);
+        assert_eq!(previous.servers.len(), data.servers.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_counters_survive_runs() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join(STATE_FILE_NAME);
+
+        for _ in 0..2 {
+            let mut state = RunState::load(&path);
+            state.record(
+                &[
+                    server("s1.example.com", true, false),
+                    server("s2.example.com", false, true),
+                ],
+                &[1.0, 2.0],
+            );
+            state.push_failures_total += 1;
+            state.graphite_failures_total += 1;
+            state.statsd_failures_total += 1;
+            state.heartbeat_failures_total += 1;
+            state.save(&path, &OutputSink::default())?;
+        }
+
+        let state = RunState::load(&path);
+        assert_eq!(state.runs_total, 2);
+        assert_eq!(state.push_failures_total, 2);
+        assert_eq!(state.graphite_failures_total, 2);
+        assert_eq!(state.statsd_failures_total, 2);
+        assert_eq!(state.heartbeat_failures_total, 2);
+        let s1 = state.get(&server("s1.example.com", true, false));
+        assert_eq!(s1.scrape_failures_total, 0);
+        assert_eq!(s1.scrape_retries_total, 0);
+        let mut durations = Histogram::new(&[1.0, 2.0]);
+        durations.observe(1.5);
+        durations.observe(1.5);
+        assert_eq!(s1.scrape_duration_seconds, Some(durations));
+        let s2 = state.get(&server("s2.example.com", true, false));
+        assert_eq!(s2.scrape_failures_total, 2);
+        assert_eq!(s2.scrape_retries_total, 2);
+        Ok(())
+    }
+
+    #[parameterized(
+        missing = { None },
+        corrupt = { Some("{\"runs_total\": ") },
+        wrong_type = { Some("[]") },
+    )]
+    fn test_load_resets_counters(contents: Option<&str>) -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join(STATE_FILE_NAME);
+        if let Some(contents) = contents {
+            fs::write(&path, contents)?;
+        }
+
+        assert_eq!(RunState::load(&path), RunState::default());
+        Ok(())
+    }
+
+    #[test]
+    fn test_changed_buckets_start_over() {
+        let mut state = RunState::default();
+        let servers = [server("s1.example.com", true, false)];
+        state.record(&servers, &[1.0, 2.0]);
+        state.record(&servers, &[1.0, 5.0]);
+
+        let mut durations = Histogram::new(&[1.0, 5.0]);
+        durations.observe(1.5);
+        assert_eq!(
+            state.get(&servers[0]).scrape_duration_seconds,
+            Some(durations)
+        );
+    }
+}
@@ -0,0 +1,160 @@
+use anyhow::{Context, Result};
+use log::{debug, info};
+use tokio::net::UdpSocket;
+
+use crate::config::{StatsdConfig, TagStyle};
+use crate::prometheus::FlatSample;
+
+impl TagStyle {
+    /// A gauge line for a metric with these tags. `prefix` may be empty.
+    fn format(&self, prefix: &str, metric: &str, tags: &[(&str, String)], value: f64) -> String {
+        let name = |parts: Vec<&str>| {
+            std::iter::once(prefix.trim_end_matches('.'))
+                .filter(|p| !p.is_empty())
+                .chain(parts)
+                .collect::<Vec<&str>>()
+                .join(".")
+        };
+
+        match self {
+            TagStyle::Datadog | TagStyle::Librato if tags.is_empty() => {
+                format!("{}:{}|g", name(vec![metric]), value)
+            }
+            TagStyle::Datadog => {
+                let tags: Vec<String> = tags.iter().map(|(k, v)| format!("{}:{}", k, v)).collect();
+                format!("{}:{}|g|#{}", name(vec![metric]), value, tags.join(","))
+            }
+            TagStyle::Librato => {
+                let tags: Vec<String> = tags.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+                format!("{}#{}:{}|g", name(vec![metric]), tags.join(","), value)
+            }
+            // Plain StatsD has no tags, so the tag values become part of the name, without
+            // the dots separating its parts.
+            TagStyle::None => {
+                let values: Vec<String> = tags.iter().map(|(_, v)| v.replace('.', "_")).collect();
+                let parts = values
+                    .iter()
+                    .map(|v| v.as_str())
+                    .chain(std::iter::once(metric))
+                    .collect();
+                format!("{}:{}|g", name(parts), value)
+            }
+        }
+    }
+}
+
+/// Render the samples as StatsD gauges, one per line, named `<prefix>.<metric>`. Values that
+/// aren't finite are left out.
+pub fn render(samples: &[FlatSample], prefix: &str, style: TagStyle) -> String {
+    let mut out = String::new();
+    for sample in samples.iter().filter(|s| s.value.is_finite()) {
+        let tags: Vec<(&str, String)> = sample
+            .labels
+            .iter()
+            .map(|(k, v)| (k.as_str(), sanitize(v)))
+            .collect();
+        out.push_str(&style.format(prefix, &sanitize(&sample.name), &tags, sample.value));
+        out.push('\n');
+    }
+    out
+}
+
+/// Replace the characters separating the parts of a StatsD line by underscores.
+fn sanitize(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            ':' | '|' | '@' | '#' | ',' | '=' | ' ' | '\n' => '_',
+            c => c,
+        })
+        .collect()
+}
+
+/// Send the gauges to the StatsD agent, one datagram per gauge.
+pub async fn send(config: &StatsdConfig, lines: &str) -> Result<()> {
+    debug!("Sending metrics to StatsD at {}", config.address);
+    let socket = UdpSocket::bind("0.0.0.0:0")
+        .await
+        .context("Failed to bind a UDP socket for StatsD")?;
+    socket.connect(&config.address).await.context(format!(
+        "Failed to resolve StatsD address {}",
+        config.address
+    ))?;
+    for line in lines.lines() {
+        socket.send(line.as_bytes()).await.context(format!(
+            "Failed to send metrics to StatsD at {}",
+            config.address
+        ))?;
+    }
+
+    info!("Metrics sent to StatsD at: {}", config.address);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yare::parameterized;
+
+    fn samples() -> Vec<FlatSample> {
+        vec![
+            FlatSample {
+                name: "status_overview".to_string(),
+                labels: vec![("category".to_string(), "stratum1".to_string())],
+                value: 3.0,
+            },
+            FlatSample {
+                name: "repo_revision_lag".to_string(),
+                labels: vec![
+                    ("server".to_string(), "s1.example.org:8000".to_string()),
+                    ("repository".to_string(), "software.eessi.io".to_string()),
+                ],
+                value: 1.0,
+            },
+            FlatSample {
+                name: "repo_revision_lag".to_string(),
+                labels: vec![("server".to_string(), "s2".to_string())],
+                value: f64::NAN,
+            },
+            FlatSample {
+                name: "eessi_status".to_string(),
+                labels: vec![],
+                value: 0.0,
+            },
+        ]
+    }
+
+    #[parameterized(
+        datadog = { TagStyle::Datadog, "eessi.status_overview:3|g|#category:stratum1\n\
+            eessi.repo_revision_lag:1|g|#server:s1.example.org_8000,repository:software.eessi.io\n\
+            eessi.eessi_status:0|g\n" },
+        librato = { TagStyle::Librato, "eessi.status_overview#category=stratum1:3|g\n\
+            eessi.repo_revision_lag#server=s1.example.org_8000,repository=software.eessi.io:1|g\n\
+            eessi.eessi_status:0|g\n" },
+        none = { TagStyle::None, "eessi.stratum1.status_overview:3|g\n\
+            eessi.s1_example_org_8000.software_eessi_io.repo_revision_lag:1|g\n\
+            eessi.eessi_status:0|g\n" },
+    )]
+    fn test_render(style: TagStyle, expected: &str) {
+        assert_eq!(render(&samples(), "eessi", style), expected);
+    }
+
+    #[tokio::test]
+    async fn test_send() {
+        let agent = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let config = StatsdConfig {
+            address: agent.local_addr().unwrap().to_string(),
+            prefix: String::new(),
+            tag_style: TagStyle::Datadog,
+        };
+
+        send(&config, "eessi_status:0|g\nstratum1_status:3|g\n")
+            .await
+            .unwrap();
+
+        let mut buf = [0; 64];
+        let len = agent.recv(&mut buf).await.unwrap();
+        assert_eq!(&buf[..len], b"eessi_status:0|g");
+        let len = agent.recv(&mut buf).await.unwrap();
+        assert_eq!(&buf[..len], b"stratum1_status:3|g");
+    }
+}
@@ -0,0 +1,111 @@
+use serde::Serialize;
+
+use crate::models::{Status, StatusPageData};
+
+/// A small document with only the statuses, for load balancer health checks and uptime
+/// checkers. It has its own fields rather than reusing those of [`StatusPageData`], so it stays
+/// small when the status grows.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Summary {
+    pub status: Status,
+    pub components: SummaryComponents,
+    /// The repository with the worst status, `None` if no repositories were found.
+    pub worst_repository: Option<SummaryRepository>,
+    pub last_update: String,
+    pub generated_at: i64,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct SummaryComponents {
+    pub stratum0: Status,
+    pub stratum1: Status,
+    pub syncservers: Status,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct SummaryRepository {
+    pub name: String,
+    pub status: Status,
+}
+
+impl Summary {
+    pub fn new(data: &StatusPageData) -> Self {
+        // Of the repositories with the same status, the first by name, so the summary is stable.
+        let worst_repository = data
+            .repositories
+            .iter()
+            .max_by(|a, b| a.status.cmp(&b.status).then_with(|| b.name.cmp(&a.name)))
+            .map(|r| SummaryRepository {
+                name: r.name.clone(),
+                status: r.status,
+            });
+
+        Summary {
+            status: data.eessi_status.status,
+            components: SummaryComponents {
+                stratum0: data.stratum0.status,
+                stratum1: data.stratum1.status,
+                syncservers: data.syncservers.status,
+            },
+            worst_repository,
+            last_update: data.last_update.clone(),
+            generated_at: data.generated_at,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::templating::RepoStatus;
+    use yare::parameterized;
+
+    fn data() -> StatusPageData {
+        serde_json::from_str(include_str!("testdata/status-schema-0.json")).unwrap()
+    }
+
+    fn repo(name: &str, status: Status) -> RepoStatus {
+        RepoStatus {
+            name: name.to_string(),
+            status,
+            revision_class: status.class().to_string(),
+            snapshot_class: Status::OK.class().to_string(),
+            details: vec![],
+            href: String::new(),
+            revision: None,
+            published_at: None,
+        }
+    }
+
+    #[test]
+    fn test_summary_golden() {
+        let mut data = data();
+        data.generated_at = 1759390080;
+        data.repositories = vec![
+            repo("software.eessi.io", Status::OK),
+            repo("riscv.eessi.io", Status::WARNING),
+            repo("dev.eessi.io", Status::OK),
+        ];
+
+        let json = serde_json::to_string_pretty(&Summary::new(&data)).unwrap() + "\n";
+
+        assert_eq!(json, include_str!("testdata/summary.json"));
+    }
+
+    #[parameterized(
+        none = { &[], None },
+        worst = { &[("a", Status::OK), ("b", Status::FAILED), ("c", Status::WARNING)], Some("b") },
+        tie_by_name = { &[("c", Status::WARNING), ("a", Status::WARNING), ("b", Status::OK)], Some("a") },
+    )]
+    fn test_worst_repository(repositories: &[(&str, Status)], expected: Option<&str>) {
+        let mut data = data();
+        data.repositories = repositories.iter().map(|&(n, s)| repo(n, s)).collect();
+
+        let summary = Summary::new(&data);
+
+        assert_eq!(
+            summary.worst_repository.map(|r| r.name),
+            expected.map(str::to_string)
+        );
+    }
+}
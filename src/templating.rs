@@ -1,53 +1,241 @@
 use anyhow::{Context, Result};
+use chrono::Utc;
 use cvmfs_server_scraper::ServerMetadata;
-use log::{info, trace};
-use serde::Serialize;
-use std::io::Write;
-use std::path::Path;
-use tempfile::NamedTempFile;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
 use tera::Tera;
 
-use crate::models::Status;
+use crate::dependencies::OutputSink;
+use crate::filters;
+use crate::locale::Locale;
+use crate::minify;
+use crate::models::{deserialize_metadata, Status};
+use crate::undefined;
 
-pub fn init_templates() -> Result<Tera> {
-    Tera::new("templates/*.html").context("Failed to initialize Tera templates")
+/// The exit code of a run that failed because a template failed to load or render
+/// (`EX_CONFIG`), as it needs the templates fixed rather than a retry.
+pub const RENDER_FAILED_EXIT_CODE: i32 = 78;
+
+/// A template that failed to load or render, with Tera's error, which names the line or the
+/// variable, as its cause.
+#[derive(Debug)]
+pub struct TemplateError(pub String);
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+/// Whether the error, or one of its causes, is a `TemplateError`.
+pub fn is_template_error(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<TemplateError>().is_some()
+}
+
+/// The templates built into the binary, by name.
+pub const BUILTIN_TEMPLATES: [(&str, &str); 2] = [
+    ("status.html", include_str!("../templates/status.html")),
+    (
+        "repo_detail.html",
+        include_str!("../templates/repo_detail.html"),
+    ),
+];
+
+/// The templates the pages are rendered with: those in a template directory, if there is one,
+/// and the built-in ones for the templates it doesn't have. The filters in `filters` are
+/// registered, relative to when the templates were loaded, and so are `t`, the strings of a
+/// locale (English unless another is set with `locale`), and `locale()`, its name.
+pub struct Templates {
+    tera: Tera,
+    /// The template directory, and the templates found in it.
+    dir: Option<(PathBuf, Vec<String>)>,
+    /// Fail a render that refers to undefined variables, rather than warning about them.
+    strict: bool,
+    /// Minify the HTML files rendered to files.
+    minify: bool,
+}
+
+impl Templates {
+    /// Load the templates in `dir` and its subdirectories, or only the built-in ones without a
+    /// directory. The directory is read on every call, so edits are picked up without a rebuild.
+    pub fn load(dir: Option<&Path>) -> Result<Self> {
+        let mut builtin = Tera::default();
+        builtin
+            .add_raw_templates(BUILTIN_TEMPLATES)
+            .context(TemplateError(
+                "Failed to parse the built-in templates".to_string(),
+            ))?;
+        filters::register(&mut builtin, Utc::now());
+        let Some(dir) = dir else {
+            return Ok(Templates {
+                tera: builtin,
+                dir: None,
+                strict: false,
+                minify: false,
+            }
+            .locale(Locale::english())
+            .assets(BTreeMap::new()));
+        };
+
+        if !dir.is_dir() {
+            return Err(
+                TemplateError(format!("Template directory {:?} does not exist", dir)).into(),
+            );
+        }
+        let glob = dir.join("**").join("*.html");
+        let glob = glob.to_str().context("Invalid template directory")?;
+        let parse_error = || TemplateError(format!("Failed to parse the templates in {:?}", dir));
+        let mut tera = Tera::new(glob).with_context(parse_error)?;
+        let mut found: Vec<String> = tera.get_template_names().map(str::to_string).collect();
+        found.sort();
+        debug!("Templates found in {:?}: {}", dir, found.join(", "));
+        // Templates already loaded from the directory are kept.
+        tera.extend(&builtin).with_context(parse_error)?;
+        filters::register(&mut tera, Utc::now());
+        Ok(Templates {
+            tera,
+            dir: Some((dir.to_path_buf(), found)),
+            strict: false,
+            minify: false,
+        }
+        .locale(Locale::english())
+        .assets(BTreeMap::new()))
+    }
+
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn minify(mut self, minify: bool) -> Self {
+        self.minify = minify;
+        self
+    }
+
+    /// `asset(name="status.css")`: the hashed name of the stylesheet or script in `assets`, the
+    /// name itself for the others and without hashed assets.
+    pub fn assets(mut self, assets: BTreeMap<String, String>) -> Self {
+        self.tera
+            .register_function("asset", move |args: &HashMap<String, tera::Value>| {
+                let name = args
+                    .get("name")
+                    .and_then(tera::Value::as_str)
+                    .ok_or_else(|| tera::Error::msg("Function `asset` expects a `name` string"))?;
+                Ok(tera::Value::String(
+                    assets.get(name).map_or(name, String::as_str).to_string(),
+                ))
+            });
+        self
+    }
+
+    pub fn locale(mut self, locale: Locale) -> Self {
+        let name = tera::Value::String(locale.name().to_string());
+        self.tera
+            .register_function("locale", move |_: &_| Ok(name.clone()));
+        self.tera.register_function("t", locale);
+        self
+    }
+
+    /// Fail with the variables `template_name` refers to that aren't in `context` in strict mode,
+    /// and warn about each of them otherwise.
+    fn check_variables(&self, template_name: &str, context: &tera::Context) -> Result<()> {
+        let undefined =
+            undefined::undefined_variables(&self.tera, template_name, &context.clone().into_json());
+        if undefined.is_empty() {
+            return Ok(());
+        }
+        if self.strict {
+            return Err(TemplateError(format!(
+                "Template {} refers to undefined variables: {}",
+                template_name,
+                undefined.join(", ")
+            ))
+            .into());
+        }
+        for variable in undefined {
+            warn!(
+                "Template {} refers to undefined variable {}, use --strict-templates to fail on it",
+                template_name, variable
+            );
+        }
+        Ok(())
+    }
+
+    pub fn render(&self, template_name: &str, context: &tera::Context) -> Result<String> {
+        self.check_variables(template_name, context)?;
+        self.tera.render(template_name, context).with_context(|| {
+            TemplateError(match &self.dir {
+                Some((dir, found)) => format!(
+                    "Failed to render template {} from {:?}, which has {}",
+                    template_name,
+                    dir,
+                    if found.is_empty() {
+                        "no templates".to_string()
+                    } else {
+                        found.join(", ")
+                    }
+                ),
+                None => format!("Failed to render template: {}", template_name),
+            })
+        })
+    }
 }
 
-pub fn render_template(template_name: &str, context: &tera::Context) -> Result<String> {
-    init_templates()?
-        .render(template_name, context)
-        .context(format!("Failed to render template: {}", template_name))
+/// `value` as JSON to embed in a `<script type="application/json">` element. `<`, `>` and `&`
+/// can only be in strings, where they are escaped, so a `</script>` in the data can't end the
+/// element.
+pub fn json_island(value: &impl Serialize) -> serde_json::Result<String> {
+    Ok(serde_json::to_string(value)?
+        .replace('<', "\\u003c")
+        .replace('>', "\\u003e")
+        .replace('&', "\\u0026"))
 }
 
 pub fn render_template_to_file(
+    templates: &Templates,
     template_name: &str,
     context: &tera::Context,
     destination: &str,
     filename: &str,
+    sink: &OutputSink,
 ) -> Result<()> {
-    let rendered = render_template(template_name, context)?;
+    let mut rendered = templates.render(template_name, context)?;
+    if templates.minify && filename.ends_with(".html") {
+        rendered = minify_html(rendered, filename);
+    }
     let fqfn = Path::new(destination).join(filename);
-
-    let mut tmpfile = NamedTempFile::new_in(destination).context(format!(
-        "Failed to create temporary file in {}",
-        destination
-    ))?;
-
-    trace!("Writing to temporary file: {:?}", tmpfile.path());
-    tmpfile
-        .write_all(rendered.as_bytes())
-        .context("Failed to write to temporary file")?;
-
-    trace!("Persisting temporary file: {:?}", fqfn);
-    tmpfile
-        .persist(&fqfn)
-        .context(format!("Failed to persist temporary file to {:?}", fqfn))?;
-
+    // Only written once rendered, and atomically, so a failed render keeps the existing page.
+    sink.atomic_write(&fqfn, rendered.as_bytes())?;
     info!("Rendered template to file: {:?}", fqfn);
     Ok(())
 }
 
-#[derive(Serialize)]
+/// `html` minified, or as it is, with a warning, if it can't be: a page with whitespace is
+/// better than none.
+fn minify_html(html: String, filename: &str) -> String {
+    match minify::html(&html) {
+        Ok(minified) => {
+            info!(
+                "Minified {} from {} to {} bytes",
+                filename,
+                html.len(),
+                minified.len()
+            );
+            minified
+        }
+        Err(e) => {
+            warn!("Failed to minify {}, writing it as is: {}", filename, e);
+            html
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct StatusInfo {
     pub status: Status,
     pub class: String,
@@ -56,34 +244,154 @@ pub struct StatusInfo {
 }
 
 impl StatusInfo {
-    pub fn all() -> Vec<Self> {
+    pub fn all(locale: &Locale) -> Vec<Self> {
         Status::all()
             .iter()
             .map(|&status| StatusInfo {
                 status,
                 class: status.class().to_string(),
-                text: status.text().to_string(),
-                description: status.description().to_string(),
+                text: status.text_localized(locale).to_string(),
+                description: status.description_localized(locale).to_string(),
             })
             .collect()
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 pub struct ServerStatus {
     pub name: String,
     pub status: Status,
+    #[serde(default, deserialize_with = "deserialize_metadata")]
     pub metadata: Option<ServerMetadata>,
+    pub cvmfs_version: Option<String>,
+    pub os: Option<String>,
+    pub last_geodb_update: Option<String>,
+    pub geodb_status: Status,
+    pub cert_expiry: Option<String>,
+    pub cert_expiry_days: Option<i64>,
+    pub scrape_duration_ms: Option<u64>,
+    pub address: Option<String>,
+    /// The repositories scraped on the server.
+    #[serde(default)]
+    pub repositories: Vec<ServerRepository>,
+    /// The individual members of a load balanced service.
+    #[serde(default)]
+    pub members: Vec<ServerStatus>,
+    /// How old the shown data is, if the server failed to scrape and stale data is used.
+    pub data_age_seconds: Option<i64>,
+    pub stale_note: Option<String>,
     pub update_class: String,
     pub geoapi_class: String,
 }
 
-#[derive(Serialize)]
+/// A repository as seen on a single server.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ServerRepository {
+    pub name: String,
+    /// The revision, missing if the repository failed to scrape.
+    pub revision: Option<i32>,
+    pub status: Status,
+    /// The highest revision seen on the server before, if the revision went backwards.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub regressed_from: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct RepoStatus {
     pub name: String,
     pub status: Status,
     pub revision_class: String,
     pub snapshot_class: String,
+    #[serde(default)]
+    pub details: Vec<String>,
+    /// The detail page of the repository, relative to the status page.
+    #[serde(default)]
+    pub href: String,
+    /// The newest revision on any server, and when it was published.
+    #[serde(default)]
+    pub revision: Option<i32>,
+    #[serde(default)]
+    pub published_at: Option<String>,
+}
+
+/// The directory, in the destination, with a detail page per repository.
+pub const REPOS_DIR: &str = "repos";
+
+/// A file name for a repository that is safe to write. Anything but ASCII letters, digits,
+/// `-`, `_` and `.` is replaced with `_`, and so are leading dots.
+pub fn repo_file_name(name: &str) -> String {
+    let sanitized: String = name
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c,
+            _ => '_',
+        })
+        .collect();
+    let dots = sanitized.len() - sanitized.trim_start_matches('.').len();
+    format!("{}{}", "_".repeat(dots), &sanitized[dots..])
+}
+
+/// The detail page of a repository, relative to the status page.
+pub fn repo_href(name: &str) -> String {
+    format!("{}/{}.html", REPOS_DIR, repo_file_name(name))
+}
+
+/// Everything about a repository, for its detail page.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct RepoDetail {
+    pub title: String,
+    pub name: String,
+    pub status: Status,
+    pub status_class: String,
+    pub last_update: String,
+    pub details: Vec<String>,
+    pub servers: Vec<RepoServerDetail>,
+}
+
+/// The state of a repository on one server.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct RepoServerDetail {
+    pub hostname: String,
+    pub server_type: String,
+    pub status: Status,
+    pub status_class: String,
+    /// The manifest fields are missing if the repository failed to scrape.
+    pub revision: Option<i32>,
+    pub published_at: Option<String>,
+    /// Time since the revision was published, as of the run.
+    pub snapshot_age_seconds: Option<i64>,
+    pub ttl_seconds: Option<i32>,
+    /// The size of the root catalog.
+    #[serde(default)]
+    pub catalog_size_bytes: Option<i64>,
+    pub catalog_hash: Option<String>,
+    pub history_hash: Option<String>,
+    pub error: Option<String>,
+    pub details: Vec<String>,
+}
+
+/// A repository and its revision on every server, a row of the repository matrix.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RepositoryMatrixRow {
+    pub repository: String,
+    /// The revision the servers are compared against, if any server that has it was scraped.
+    pub reference_revision: Option<i32>,
+    /// A cell for every server, in the same order in every row.
+    pub cells: Vec<RepositoryMatrixCell>,
+}
+
+/// The state of a repository on one server. Everything but the server is missing if the server
+/// doesn't carry the repository, and the revision and lag if the repository failed to scrape.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RepositoryMatrixCell {
+    pub server: String,
+    #[serde(rename = "type")]
+    pub server_type: String,
+    pub revision: Option<i32>,
+    /// How many revisions the server is behind the reference revision, negative if ahead.
+    pub lag: Option<i32>,
+    pub status: Option<Status>,
+    pub status_class: Option<String>,
 }
 
 #[cfg(test)]
@@ -122,7 +430,7 @@ mod tests {
         maintenance = { Status::MAINTENANCE, "EESSI services are unavailable due to scheduled maintenance." }
     )]
     fn test_status_description(status: Status, expected: &str) {
-        assert_eq!(status.description(), expected);
+        assert_eq!(status.description_localized(&Locale::english()), expected);
     }
 
     #[parameterized(
@@ -138,7 +446,7 @@ mod tests {
         expected_text: &str,
         expected_description: &str,
     ) {
-        let legends = StatusInfo::all();
+        let legends = StatusInfo::all(&Locale::english());
         let info = legends
             .iter()
             .find(|info| info.text == key)
@@ -149,6 +457,25 @@ mod tests {
         assert_eq!(info.description, expected_description);
     }
 
+    #[test]
+    fn test_get_legends_localized() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::create_dir(dir.path().join("locales"))?;
+        std::fs::write(
+            dir.path().join("locales/nl.json"),
+            r#"{"status.failed.text": "Mislukt"}"#,
+        )?;
+
+        let legends = StatusInfo::all(&Locale::load("nl", Some(dir.path()))?);
+        let json = serde_json::to_value(&legends)?;
+
+        assert_eq!(json[3]["status"], "FAILED");
+        assert_eq!(json[3]["text"], "Mislukt");
+        assert_eq!(json[3]["description"], "EESSI services have failed.");
+        assert_eq!(json[0]["text"], "Normal service");
+        Ok(())
+    }
+
     #[parameterized(
         server1 = { "test_server", "status-ok", "status-warning" },
         server2 = { "another_server", "status-failed", "status-degraded" }
@@ -162,6 +489,18 @@ mod tests {
             name: name.to_string(),
             status: Status::OK,
             metadata: None,
+            cvmfs_version: Some("2.11.5".to_string()),
+            os: None,
+            last_geodb_update: None,
+            geodb_status: Status::OK,
+            cert_expiry: None,
+            cert_expiry_days: None,
+            scrape_duration_ms: Some(42),
+            address: None,
+            repositories: vec![],
+            members: vec![],
+            data_age_seconds: None,
+            stale_note: None,
             update_class: update_class.to_string(),
             geoapi_class: geoapi_class.to_string(),
         };
@@ -170,6 +509,52 @@ mod tests {
         assert!(serialized.contains(name));
         assert!(serialized.contains(update_class));
         assert!(serialized.contains(geoapi_class));
+        assert!(serialized.contains("\"cvmfs_version\":\"2.11.5\""));
+        Ok(())
+    }
+
+    #[test]
+    fn test_server_status_round_trip() -> Result<()> {
+        let json = r#"{
+            "name": "s1.example.com",
+            "status": "DEGRADED",
+            "metadata": {
+                "schema_version": 1,
+                "cvmfs_version": "2.11.5",
+                "last_geodb_update": "Wed Oct 01 07:28:00 UTC 2025",
+                "os_version_id": "9",
+                "os_pretty_name": null,
+                "os_id": "almalinux",
+                "administrator": null,
+                "email": null,
+                "organisation": null,
+                "custom": null
+            },
+            "cvmfs_version": "2.11.5",
+            "os": "almalinux 9",
+            "last_geodb_update": "Wed Oct 01 07:28:00 UTC 2025",
+            "geodb_status": "OK",
+            "cert_expiry": null,
+            "cert_expiry_days": null,
+            "scrape_duration_ms": 42,
+            "address": null,
+            "data_age_seconds": null,
+            "stale_note": null,
+            "update_class": "status-degraded",
+            "geoapi_class": "status-ok"
+        }"#;
+
+        let status: ServerStatus = serde_json::from_str(json)?;
+        assert_eq!(status.status, Status::DEGRADED);
+        assert!(status.members.is_empty());
+        let metadata = status.metadata.as_ref().expect("metadata was deserialized");
+        assert_eq!(
+            metadata.cvmfs_version.as_ref().map(|v| v.to_string()),
+            Some("2.11.5".to_string())
+        );
+
+        let reserialized = serde_json::to_string(&status)?;
+        assert!(reserialized.contains("\"cvmfs_version\":\"2.11.5\""));
         Ok(())
     }
 
@@ -187,12 +572,598 @@ mod tests {
             status: Status::OK,
             revision_class: revision_class.to_string(),
             snapshot_class: snapshot_class.to_string(),
+            details: vec![],
+            href: repo_href(name),
+            revision: None,
+            published_at: None,
         };
 
         let serialized = serde_json::to_string(&status)?;
         assert!(serialized.contains(name));
         assert!(serialized.contains(revision_class));
         assert!(serialized.contains(snapshot_class));
+        assert!(serialized.contains(&format!("repos/{}.html", name)));
         Ok(())
     }
+
+    fn context() -> tera::Context {
+        let mut context = tera::Context::new();
+        context.insert("data", &serde_json::json!({ "title": "EESSI" }));
+        context
+    }
+
+    #[test]
+    fn test_templates_builtin() -> Result<()> {
+        let templates = Templates::load(None)?.strict(true);
+        let data: crate::models::StatusPageData =
+            serde_json::from_str(include_str!("testdata/status-schema-0.json"))?;
+        let mut context = tera::Context::new();
+        context.insert("data", &data);
+
+        assert!(templates
+            .render("status.html", &context)?
+            .contains(&data.title));
+        Ok(())
+    }
+
+    #[test]
+    fn test_templates_repository_matrix() -> Result<()> {
+        let templates = Templates::load(None)?.strict(true);
+        let mut data: crate::models::StatusPageData =
+            serde_json::from_str(include_str!("testdata/status-schema-0.json"))?;
+        data.repository_matrix = serde_json::from_value(serde_json::json!([{
+            "repository": "software.eessi.io",
+            "reference_revision": 10,
+            "cells": [
+                { "server": "s0.example.com", "type": "stratum0", "revision": 10, "lag": 0,
+                  "status": "OK", "status_class": Status::OK.class() },
+                { "server": "s1.example.com", "type": "stratum1", "revision": 9, "lag": 1,
+                  "status": "WARNING", "status_class": Status::WARNING.class() },
+                { "server": "s2.example.com", "type": "stratum1", "revision": null, "lag": null,
+                  "status": null, "status_class": null }
+            ]
+        }]))?;
+        let mut context = tera::Context::new();
+        context.insert("data", &data);
+
+        let rendered = templates.render("status.html", &context)?;
+
+        for expected in [
+            r#"<th class="revision" title="stratum1">s1.example.com</th>"#,
+            r#"<td class="revision">10</td>"#,
+            r#"<td class="revision" title="Revisions behind the reference: 1"><span class="status-warning fas fa-exclamation-triangle"></span> 9</td>"#,
+            r#"<td class="revision">&ndash;</td>"#,
+        ] {
+            assert!(rendered.contains(expected), "{}: {}", expected, rendered);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_templates_stratum0_metadata() -> Result<()> {
+        let templates = Templates::load(None)?.strict(true);
+        let mut data: crate::models::StatusPageData =
+            serde_json::from_str(include_str!("testdata/status-schema-0.json"))?;
+        data.stratum0.servers[0].cvmfs_version = Some("2.11.5".to_string());
+        let mut context = tera::Context::new();
+        context.insert("data", &data);
+
+        let rendered = templates.render("status.html", &context)?;
+
+        assert!(rendered.contains(r#"<td class="main" title="CVMFS version: 2.11.5&#10;"#));
+        assert!(rendered.contains(">rug-nl-s0.eessi.science</td>"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_templates_override() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::create_dir(dir.path().join("partials"))?;
+        std::fs::write(dir.path().join("status.html"), "Custom: {{ data.title }}")?;
+        std::fs::write(dir.path().join("partials/footer.html"), "Footer")?;
+
+        let templates = Templates::load(Some(dir.path()))?.strict(true);
+
+        assert_eq!(
+            templates.render("status.html", &context())?,
+            "Custom: EESSI"
+        );
+        assert_eq!(
+            templates.render("partials/footer.html", &context())?,
+            "Footer"
+        );
+        // The directory doesn't have it, so the built-in one is used.
+        let mut context = tera::Context::new();
+        context.insert(
+            "data",
+            &RepoDetail {
+                title: "EESSI".to_string(),
+                name: "software.eessi.io".to_string(),
+                status: Status::OK,
+                status_class: Status::OK.class().to_string(),
+                last_update: "2025-10-02 07:28:00".to_string(),
+                details: vec![],
+                servers: vec![RepoServerDetail {
+                    hostname: "s1.example.com".to_string(),
+                    server_type: "stratum1".to_string(),
+                    status: Status::OK,
+                    status_class: Status::OK.class().to_string(),
+                    revision: Some(42),
+                    published_at: Some("2025-10-02T07:18:00Z".to_string()),
+                    snapshot_age_seconds: Some(600),
+                    ttl_seconds: Some(240),
+                    catalog_size_bytes: Some(70412288),
+                    catalog_hash: Some("aa11bb22cc33dd44".to_string()),
+                    history_hash: Some("dd44".to_string()),
+                    error: None,
+                    details: vec![],
+                }],
+            },
+        );
+        let rendered = templates.render("repo_detail.html", &context)?;
+        assert!(rendered.contains("software.eessi.io"));
+        assert!(rendered.contains("<td>10m</td>"));
+        assert!(rendered.contains("<td>4m</td>"));
+        assert!(rendered.contains("<td>70.4 MB</td>"));
+        assert!(rendered.contains(r#"title="aa11bb22cc33dd44">aa11bb22</td>"#));
+        Ok(())
+    }
+
+    #[test]
+    fn test_templates_extra() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(
+            dir.path().join("status.html"),
+            r#"<a href="{{ data.extra.grafana_url }}">Grafana</a> {{ data.extra.matomo.site_id }}"#,
+        )?;
+        let mut data: crate::models::StatusPageData =
+            serde_json::from_str(include_str!("testdata/status-schema-0.json"))?;
+        data.extra = serde_json::from_value(serde_json::json!({
+            "grafana_url": "https://grafana.example.com",
+            "matomo": { "site_id": 3 },
+        }))?;
+        let mut context = tera::Context::new();
+        context.insert("data", &data);
+
+        let templates = Templates::load(Some(dir.path()))?.strict(true);
+
+        // Escaped like any other value, which a browser reads as the same URL.
+        assert_eq!(
+            templates.render("status.html", &context)?,
+            r#"<a href="https:&#x2F;&#x2F;grafana.example.com">Grafana</a> 3"#
+        );
+        Ok(())
+    }
+
+    #[parameterized(
+        none = { serde_json::json!([]), None },
+        links = {
+            serde_json::json!([
+                {"text": "Documentation", "url": "https://www.eessi.io/docs/", "icon": "fas fa-book"},
+                {"text": "Dashboards", "url": "https://grafana.example.com"},
+            ]),
+            Some(r#"<span class="fas fa-book"></span> Documentation</a>"#)
+        },
+    )]
+    fn test_templates_links(links: serde_json::Value, expected: Option<&str>) -> Result<()> {
+        let templates = Templates::load(None)?.strict(true);
+        let mut data: crate::models::StatusPageData =
+            serde_json::from_str(include_str!("testdata/status-schema-0.json"))?;
+        data.links = serde_json::from_value(links)?;
+        let mut context = tera::Context::new();
+        context.insert("data", &data);
+
+        let rendered = templates.render("status.html", &context)?;
+
+        match expected {
+            None => assert!(!rendered.contains("<nav"), "{}", rendered),
+            Some(expected) => {
+                assert!(rendered.contains(r#"<nav class="links">"#), "{}", rendered);
+                assert!(rendered.contains(expected), "{}", rendered);
+                assert!(rendered.contains(">Dashboards</a>"), "{}", rendered);
+            }
+        }
+        Ok(())
+    }
+
+    #[parameterized(
+        off = { None, None },
+        refresh = { Some(300), Some(900) },
+    )]
+    fn test_templates_refresh(
+        refresh_seconds: Option<u64>,
+        stale_after_seconds: Option<u64>,
+    ) -> Result<()> {
+        let templates = Templates::load(None)?.strict(true);
+        let mut data: crate::models::StatusPageData =
+            serde_json::from_str(include_str!("testdata/status-schema-0.json"))?;
+        data.generated_at = 1759389680;
+        data.refresh_seconds = refresh_seconds;
+        data.stale_after_seconds = stale_after_seconds;
+        let mut context = tera::Context::new();
+        context.insert("data", &data);
+
+        let rendered = templates.render("status.html", &context)?;
+
+        assert_eq!(
+            rendered.contains(r#"<meta http-equiv="refresh" content="300">"#),
+            refresh_seconds.is_some()
+        );
+        assert_eq!(
+            rendered.contains(
+                r#"data-generated-at-epoch="1759389680" data-stale-after="900" data-text="Data is {minutes} minutes old""#
+            ),
+            refresh_seconds.is_some(),
+            "{}",
+            rendered
+        );
+        Ok(())
+    }
+
+    #[parameterized(
+        off = { None, None, None },
+        base_url = { Some("https://status.eessi.io/"), None, None },
+        full = { Some("https://status.eessi.io/"), Some("The status of EESSI & its mirrors"), Some("https://status.eessi.io/eessi-512px.png") },
+    )]
+    fn test_templates_open_graph(
+        base_url: Option<&str>,
+        description: Option<&str>,
+        og_image: Option<&str>,
+    ) -> Result<()> {
+        let templates = Templates::load(None)?.strict(true);
+        let mut data: crate::models::StatusPageData =
+            serde_json::from_str(include_str!("testdata/status-schema-0.json"))?;
+        data.title = "EESSI status".to_string();
+        data.base_url = base_url.map(str::to_string);
+        data.description = description.map(str::to_string);
+        data.og_image = og_image.map(str::to_string);
+        let mut context = tera::Context::new();
+        context.insert("data", &data);
+
+        let rendered = templates.render("status.html", &context)?;
+
+        let title = format!(
+            r#"<meta property="og:title" content="EESSI status: {}">"#,
+            data.eessi_status.text
+        );
+        assert_eq!(
+            rendered.contains(&title),
+            base_url.is_some(),
+            "{}",
+            rendered
+        );
+        assert_eq!(
+            rendered.contains(
+                r#"<meta property="og:url" content="https:&#x2F;&#x2F;status.eessi.io&#x2F;">"#
+            ),
+            base_url.is_some()
+        );
+        assert_eq!(
+            rendered.contains(
+                r#"<meta property="og:description" content="The status of EESSI &amp; its mirrors">"#
+            ),
+            description.is_some()
+        );
+        assert_eq!(
+            rendered.contains(r#"<meta name="twitter:card" content="summary_large_image">"#),
+            og_image.is_some()
+        );
+        assert_eq!(
+            rendered.contains(r#"property="og:image""#),
+            og_image.is_some()
+        );
+        Ok(())
+    }
+
+    #[parameterized(
+        plain = { "software.eessi.io" },
+        script = { "</script><script>alert(1)</script>" },
+        comment = { "<!-- a & b -->" },
+    )]
+    fn test_json_island(text: &str) -> Result<()> {
+        let value = serde_json::json!({ "details": [text] });
+
+        let island = json_island(&value)?;
+
+        assert!(!island.contains('<'), "{}", island);
+        assert!(!island.contains('&'), "{}", island);
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&island)?, value);
+        Ok(())
+    }
+
+    #[test]
+    fn test_templates_embedded_data() -> Result<()> {
+        let templates = Templates::load(None)?.strict(true);
+        let mut data: crate::models::StatusPageData =
+            serde_json::from_str(include_str!("testdata/status-schema-0.json"))?;
+        data.stratum0.details = vec!["</script>".to_string()];
+        let mut context = tera::Context::new();
+        context.insert("data", &data);
+        context.insert("embedded_data", &json_island(&data)?);
+
+        let rendered = templates.render("status.html", &context)?;
+
+        let start = r#"<script type="application/json" id="status-data">"#;
+        let island = rendered
+            .split_once(start)
+            .and_then(|(_, rest)| rest.split_once("</script>"))
+            .map(|(island, _)| island)
+            .expect("the page embeds the data");
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(island)?,
+            serde_json::to_value(&data)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_templates_partial_locale() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::create_dir(dir.path().join("locales"))?;
+        std::fs::write(
+            dir.path().join("locales/nl.json"),
+            r#"{
+                "page.repositories": "Repositories (nl)",
+                "page.partial_run": "Gedeeltelijke run: {scope}.",
+                "page.last_updated": "Bijgewerkt {time}"
+            }"#,
+        )?;
+        let mut data: crate::models::StatusPageData =
+            serde_json::from_str(include_str!("testdata/status-schema-0.json"))?;
+        data.partial_run = true;
+        data.scope = vec!["stratum1 s1.example.com".to_string()];
+        data.last_update = "2025-10-02T07:28:00Z".to_string();
+        let mut context = tera::Context::new();
+        context.insert("data", &data);
+
+        let templates = Templates::load(Some(dir.path()))?
+            .strict(true)
+            .locale(Locale::load("nl", Some(dir.path()))?);
+        let rendered = templates.render("status.html", &context)?;
+
+        assert!(rendered.contains(r#"<html lang="nl">"#), "{}", rendered);
+        assert!(
+            rendered.contains("<h2>Repositories (nl)</h2>"),
+            "{}",
+            rendered
+        );
+        assert!(
+            rendered.contains("Gedeeltelijke run: stratum1 s1.example.com."),
+            "{}",
+            rendered
+        );
+        assert!(
+            rendered.contains("Bijgewerkt 2025-10-02T07:28:00Z"),
+            "{}",
+            rendered
+        );
+        // Not in the locale, so in English.
+        assert!(rendered.contains("<h2>Stratum1s</h2>"), "{}", rendered);
+        assert!(
+            rendered.contains(r#"<th class="latency">Latency</th>"#),
+            "{}",
+            rendered
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_templates_render_error() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("status.html"), "{{ data.nope.title }}")?;
+        std::fs::write(dir.path().join("footer.html"), "Footer")?;
+
+        let templates = Templates::load(Some(dir.path()))?;
+        let error = templates.render("status.html", &context()).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "Failed to render template status.html from {:?}, which has footer.html, status.html",
+                dir.path()
+            )
+        );
+        Ok(())
+    }
+
+    #[parameterized(
+        syntax = { "<h1>{% if data.title %}</h1>", "status.html" },
+        unclosed = { "<h1>\n{{ data.title </h1>", "2:" },
+    )]
+    fn test_templates_broken(template: &str, cause: &str) -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("status.html"), template)?;
+
+        let error = Templates::load(Some(dir.path())).err().unwrap();
+
+        assert!(is_template_error(&error));
+        let chain = format!("{:#}", error);
+        assert!(
+            chain.starts_with(&format!(
+                "Failed to parse the templates in {:?}",
+                dir.path()
+            )),
+            "{}",
+            chain
+        );
+        assert!(chain.contains(cause), "{}", chain);
+        Ok(())
+    }
+
+    #[parameterized(
+        hashed = { "status.css", "status.62368a1a.css" },
+        not_hashed = { "eessi-512px.png", "eessi-512px.png" },
+    )]
+    fn test_templates_asset(name: &str, expected: &str) -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("status.html"), "{{ asset(name=name) }}")?;
+        let assets =
+            BTreeMap::from([("status.css".to_string(), "status.62368a1a.css".to_string())]);
+        let mut context = tera::Context::new();
+        context.insert("name", name);
+
+        let templates = Templates::load(Some(dir.path()))?;
+        assert_eq!(templates.render("status.html", &context)?, name);
+        let templates = templates.assets(assets);
+        assert_eq!(templates.render("status.html", &context)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_templates_asset_integrity() -> Result<()> {
+        let templates = Templates::load(None)?.strict(true);
+        let data: crate::models::StatusPageData =
+            serde_json::from_str(include_str!("testdata/status-schema-0.json"))?;
+        let integrity: std::collections::BTreeMap<&str, &str> = [
+            ("fa.all.min.css", "sha384-fa"),
+            ("status.css", "sha384-css"),
+            ("status.js", "sha384-js"),
+        ]
+        .into();
+        let mut context = tera::Context::new();
+        context.insert("data", &data);
+        context.insert("asset_integrity", &integrity);
+
+        let rendered = templates.render("status.html", &context)?;
+
+        assert!(
+            rendered.contains(
+                r#"<script src="status.js" type="text/javascript" integrity="sha384-js" crossorigin="anonymous"></script>"#
+            ),
+            "{}",
+            rendered
+        );
+        assert_eq!(rendered.matches(r#"crossorigin="anonymous""#).count(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_template_to_file_minified() -> Result<()> {
+        let mut data: crate::models::StatusPageData =
+            serde_json::from_str(include_str!("testdata/status-schema-0.json"))?;
+        data.stratum0.details = vec!["</div>  <!-- not a comment -->".to_string()];
+        let mut context = tera::Context::new();
+        context.insert("data", &data);
+        context.insert("embedded_data", &json_island(&data)?);
+        let destination = tempfile::tempdir()?;
+        let destination = destination.path().to_str().unwrap();
+
+        render_template_to_file(
+            &Templates::load(None)?,
+            "status.html",
+            &context,
+            destination,
+            "plain.html",
+            &OutputSink::default(),
+        )?;
+        render_template_to_file(
+            &Templates::load(None)?.minify(true),
+            "status.html",
+            &context,
+            destination,
+            "minified.html",
+            &OutputSink::default(),
+        )?;
+
+        let plain = std::fs::read_to_string(Path::new(destination).join("plain.html"))?;
+        let minified = std::fs::read_to_string(Path::new(destination).join("minified.html"))?;
+        assert!(minified.len() < plain.len(), "{}", minified);
+        assert!(!minified.contains("\n    "), "{}", minified);
+        for marker in [
+            "<!DOCTYPE html>",
+            r#"<link rel="stylesheet" type="text/css" href="status.css">"#,
+            &format!("<h1>{}</h1>", data.eessi_status.text),
+            &format!(
+                r#"<script type="application/json" id="status-data">{}</script>"#,
+                json_island(&data)?
+            ),
+            "</html>",
+        ] {
+            assert!(minified.contains(marker), "{} in {}", marker, minified);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_template_to_file_missing_variable() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("status.html"), "{{ data.nope.title }}")?;
+        let destination = tempfile::tempdir()?;
+        std::fs::write(destination.path().join("index.html"), "Previous page")?;
+
+        let templates = Templates::load(Some(dir.path()))?;
+        let error = render_template_to_file(
+            &templates,
+            "status.html",
+            &context(),
+            destination.path().to_str().unwrap(),
+            "index.html",
+            &OutputSink::default(),
+        )
+        .unwrap_err();
+
+        assert!(is_template_error(&error));
+        assert!(
+            format!("{:#}", error).contains("data.nope.title"),
+            "{:#}",
+            error
+        );
+        assert_eq!(
+            std::fs::read_to_string(destination.path().join("index.html"))?,
+            "Previous page"
+        );
+        Ok(())
+    }
+
+    #[parameterized(
+        strict = { true },
+        lenient = { false },
+    )]
+    fn test_templates_undefined_variables(strict: bool) -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(
+            dir.path().join("status.html"),
+            "{{ data.title }}{% if data.nonexistent %} | {{ data.nonexistent }}{% endif %}",
+        )?;
+
+        let templates = Templates::load(Some(dir.path()))?.strict(strict);
+        let rendered = templates.render("status.html", &context());
+
+        if strict {
+            let error = rendered.unwrap_err();
+            assert!(is_template_error(&error));
+            assert_eq!(
+                error.to_string(),
+                "Template status.html refers to undefined variables: data.nonexistent"
+            );
+        } else {
+            assert_eq!(rendered?, "EESSI");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_templates_missing_dir() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let missing = dir.path().join("templates");
+
+        let error = Templates::load(Some(&missing)).err().unwrap();
+
+        assert_eq!(
+            error.to_string(),
+            format!("Template directory {:?} does not exist", missing)
+        );
+        Ok(())
+    }
+
+    #[parameterized(
+        plain = { "software.eessi.io", "software.eessi.io" },
+        separators = { "a/b\\c d", "a_b_c_d" },
+        parent = { "../etc", "___etc" },
+        hidden = { ".hidden", "_hidden" },
+        unicode = { "réseau", "r_seau" },
+    )]
+    fn test_repo_file_name(name: &str, expected: &str) {
+        assert_eq!(repo_file_name(name), expected);
+    }
 }
@@ -0,0 +1,197 @@
+use crate::models::{Status, StatusPageData};
+use crate::report::Artifact;
+
+/// Whether to color the summary: only on a terminal, and not if `NO_COLOR` is set to anything
+/// but the empty string.
+pub fn use_color(no_color: Option<String>, is_terminal: bool) -> bool {
+    is_terminal && no_color.is_none_or(|value| value.is_empty())
+}
+
+fn paint(text: &str, status: Status, color: bool) -> String {
+    if color {
+        format!("\x1b[{}m{}\x1b[0m", status.ansi_color(), text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// A row of the table: the status, the component and how many details it has, if any.
+struct Row {
+    status: Status,
+    component: String,
+    details: Option<usize>,
+}
+
+impl Row {
+    fn new(status: Status, component: &str, details: Option<usize>) -> Self {
+        Row {
+            status,
+            component: component.to_string(),
+            details,
+        }
+    }
+}
+
+/// The table of statuses, with the columns as wide as their widest cell. The status is padded
+/// before it is colored, so the escape codes don't shift the columns.
+fn table(rows: &[Row], color: bool) -> Vec<String> {
+    let status_width = rows
+        .iter()
+        .map(|row| row.status.text().len())
+        .chain(["STATUS".len()])
+        .max()
+        .unwrap_or_default();
+    let component_width = rows
+        .iter()
+        .map(|row| row.component.chars().count())
+        .chain(["COMPONENT".len()])
+        .max()
+        .unwrap_or_default();
+
+    let mut lines = vec![format!(
+        "{:<status_width$}  {:<component_width$}  DETAILS",
+        "STATUS", "COMPONENT"
+    )];
+    lines.extend(rows.iter().map(|row| {
+        let status = format!("{:<status_width$}", row.status.text());
+        let details = row
+            .details
+            .map_or_else(|| "-".to_string(), |n| n.to_string());
+        format!(
+            "{}  {:<component_width$}  {}",
+            paint(&status, row.status, color),
+            row.component,
+            details
+        )
+    }));
+    lines
+}
+
+/// Render the summary printed after a run: a table of the overall status, the components and
+/// the repositories, followed by the files that were written.
+pub fn render(data: &StatusPageData, artifacts: &[Artifact], color: bool) -> String {
+    let mut rows = vec![
+        Row::new(data.eessi_status.status, &data.title, None),
+        Row::new(
+            data.stratum0.status,
+            "Stratum 0",
+            Some(data.stratum0.details.len()),
+        ),
+        Row::new(
+            data.stratum1.status,
+            "Stratum 1",
+            Some(data.stratum1.details.len()),
+        ),
+        Row::new(
+            data.syncservers.status,
+            "Sync servers",
+            Some(data.syncservers.details.len()),
+        ),
+        Row::new(
+            data.repositories_status.status,
+            "Repositories",
+            Some(data.repositories_status.details.len()),
+        ),
+    ];
+    rows.extend(data.repositories.iter().map(|repo| {
+        Row::new(
+            repo.status,
+            &format!("  {}", repo.name),
+            Some(repo.details.len()),
+        )
+    }));
+
+    let mut lines = table(&rows, color);
+    if !artifacts.is_empty() {
+        let label_width = artifacts
+            .iter()
+            .map(|artifact| artifact.kind.len())
+            .max()
+            .unwrap_or_default();
+        lines.push(String::new());
+        lines.extend(artifacts.iter().map(|artifact| {
+            format!(
+                "{:<label_width$}  {}",
+                artifact.kind,
+                artifact.path.display()
+            )
+        }));
+    }
+
+    lines.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yare::parameterized;
+
+    fn data() -> StatusPageData {
+        let mut data: StatusPageData =
+            serde_json::from_str(include_str!("testdata/status-schema-0.json")).unwrap();
+        data.eessi_status.status = Status::DEGRADED;
+        data.stratum0.status = Status::OK;
+        data.stratum0.details = vec![];
+        data.stratum1.status = Status::DEGRADED;
+        data.stratum1.details = vec!["1 of 2 stratum1 servers lag behind".to_string()];
+        data.syncservers.status = Status::FAILED;
+        data.syncservers.details = vec![
+            "No sync servers scraped!".to_string(),
+            "aws-eu-west-s1-sync.eessi.science failed".to_string(),
+        ];
+        data.repositories_status.status = Status::OK;
+        data.repositories_status.details = vec![];
+        data.repositories[0].status = Status::OK;
+        data.repositories[0].details = vec![];
+        data
+    }
+
+    fn artifacts() -> Vec<Artifact> {
+        [
+            ("HTML", "/srv/status/index.html"),
+            ("JSON", "/srv/status/status.json"),
+            ("metrics", "/srv/status/metrics"),
+        ]
+        .into_iter()
+        .map(|(kind, path)| Artifact {
+            kind: kind.to_string(),
+            path: path.into(),
+        })
+        .collect()
+    }
+
+    #[test]
+    fn test_render_plain() {
+        assert_eq!(
+            render(&data(), &artifacts(), false),
+            include_str!("testdata/run-summary.txt")
+        );
+    }
+
+    #[test]
+    fn test_render_color() {
+        let rendered = render(&data(), &[], true);
+
+        assert!(rendered.contains("\x1b[31mFailed        \x1b[0m  Sync servers"));
+        assert!(rendered.contains("\x1b[32mNormal service\x1b[0m  Stratum 0"));
+        let stripped = Status::all()
+            .iter()
+            .fold(rendered.replace("\x1b[0m", ""), |text, status| {
+                text.replace(&format!("\x1b[{}m", status.ansi_color()), "")
+            });
+        assert_eq!(stripped, render(&data(), &[], false));
+    }
+
+    #[parameterized(
+        terminal = { None, true, true },
+        not_a_terminal = { None, false, false },
+        no_color = { Some("1"), true, false },
+        empty_no_color = { Some(""), true, true },
+    )]
+    fn test_use_color(no_color: Option<&str>, is_terminal: bool, expected: bool) {
+        assert_eq!(
+            use_color(no_color.map(str::to_string), is_terminal),
+            expected
+        );
+    }
+}
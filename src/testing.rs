@@ -0,0 +1,132 @@
+//! Servers to build a [`StatusManager`](crate::models::StatusManager) from without scraping
+//! them, for testing the status logic. Available to the tests of this crate, and to others
+//! with the `testing` feature.
+//!
+//! ```ignore
+//! let manager = StatusManager::from_servers(
+//!     vec![
+//!         ServerFixture::stratum0("s0.example.com").with_repo("software.eessi.io", 10).build(),
+//!         ServerFixture::stratum1("s1.example.com").with_repo("software.eessi.io", 9).build(),
+//!     ],
+//!     &ConfigFile::default(),
+//! );
+//! ```
+
+use cvmfs_server_scraper::{Hostname, Manifest, ServerBackendType, ServerType};
+
+use crate::models::{Repositories, Server, Status};
+use crate::scraping::RepositoryError;
+
+/// The time the manifests of fixtures are published at, unless given.
+pub const PUBLISHED_AT: i64 = 1_759_389_480;
+
+/// A server as it was scraped, before its status is evaluated.
+#[derive(Debug, Clone)]
+pub struct ServerFixture {
+    server: Server,
+}
+
+impl ServerFixture {
+    pub fn new(hostname: &str, server_type: ServerType) -> Self {
+        ServerFixture {
+            server: Server {
+                server_type,
+                backend_type: ServerBackendType::CVMFS,
+                backend_detected: Some(ServerBackendType::CVMFS),
+                hostname: Hostname::try_from(hostname).expect("Invalid hostname"),
+                repositories: vec![],
+                status: Status::OK,
+                metadata: None,
+                geodb_status: Status::OK,
+                cert_expiry: None,
+                cert_expiry_days: None,
+                scrape_duration_ms: None,
+                address: None,
+                members: vec![],
+                data_age_seconds: None,
+                scrape_retried: false,
+            },
+        }
+    }
+
+    pub fn stratum0(hostname: &str) -> Self {
+        Self::new(hostname, ServerType::Stratum0)
+    }
+
+    pub fn stratum1(hostname: &str) -> Self {
+        Self::new(hostname, ServerType::Stratum1)
+    }
+
+    pub fn sync_server(hostname: &str) -> Self {
+        Self::new(hostname, ServerType::SyncServer)
+    }
+
+    /// Add a repository that was scraped at `revision`.
+    pub fn with_repo(self, name: &str, revision: i32) -> Self {
+        self.with_repo_published(name, revision, PUBLISHED_AT)
+    }
+
+    /// Add a repository that was scraped at `revision`, published at `published_at` seconds
+    /// since the epoch.
+    pub fn with_repo_published(mut self, name: &str, revision: i32, published_at: i64) -> Self {
+        self.server.repositories.push(Repositories {
+            name: name.to_string(),
+            revision,
+            manifest: Some(manifest(name, revision, published_at)),
+            status: Status::OK,
+            status_revision: Status::OK,
+            data_check: None,
+            regressed_from: None,
+            cert_fingerprint_mismatch: None,
+            error: None,
+            details: vec![],
+        });
+        self
+    }
+
+    /// Add a repository that failed to scrape.
+    pub fn with_failed_repo(mut self, name: &str, error: &str) -> Self {
+        self.server
+            .repositories
+            .push(Repositories::failed(&RepositoryError {
+                repository: name.to_string(),
+                error: error.to_string(),
+            }));
+        self
+    }
+
+    /// The server failed to scrape, so has no repositories.
+    pub fn failed(mut self) -> Self {
+        self.server.backend_detected = None;
+        self.server.repositories = vec![];
+        self.server.status = Status::FAILED;
+        self
+    }
+
+    pub fn with_scrape_duration_ms(mut self, duration: u64) -> Self {
+        self.server.scrape_duration_ms = Some(duration);
+        self
+    }
+
+    pub fn build(self) -> Server {
+        self.server
+    }
+}
+
+impl From<ServerFixture> for Server {
+    fn from(fixture: ServerFixture) -> Self {
+        fixture.build()
+    }
+}
+
+/// The manifest of `name` at `revision`, with made up hashes.
+pub fn manifest(name: &str, revision: i32, published_at: i64) -> Manifest {
+    // Hex strings only deserialize from borrowed strings, so not from a `json!` value.
+    let manifest = serde_json::json!({
+        "c": "aa11", "b": 4096, "a": false, "r": "bb22", "x": "cc33", "g": false,
+        "h": "dd44", "t": published_at, "d": 240, "s": revision,
+        "n": name, "m": "ee55", "y": "ff66", "l": "", "signature": ""
+    })
+    .to_string();
+    serde_json::from_str(&manifest).expect("Invalid manifest")
+}
@@ -0,0 +1,114 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::{debug, trace};
+use openssl::asn1::Asn1Time;
+use openssl::x509::X509;
+use std::time::Duration;
+
+use cvmfs_server_scraper::Hostname;
+
+use crate::config::CertificateExpiryConfig;
+use crate::models::Status;
+use crate::scraping::resolving_client;
+
+const TLS_CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Fetch the expiry time (notAfter) of the leaf certificate presented by `hostname` on port 443.
+/// With `resolve_to`, that address is contacted instead, still asking for `hostname`.
+///
+/// Certificate validation is disabled for the check itself, as we want to report on expired or
+/// otherwise invalid certificates rather than fail the handshake.
+pub async fn fetch_certificate_expiry(
+    hostname: &Hostname,
+    resolve_to: Option<&str>,
+) -> Result<DateTime<Utc>> {
+    let builder = reqwest::Client::builder()
+        .tls_info(true)
+        .danger_accept_invalid_certs(true)
+        .timeout(TLS_CHECK_TIMEOUT);
+    let client = match resolve_to {
+        None => builder.build().context("Failed to build TLS client")?,
+        Some(address) => resolving_client(builder, hostname, address)
+            .await
+            .map_err(anyhow::Error::msg)?,
+    };
+
+    let url = format!("https://{}/", hostname);
+    trace!("Checking TLS certificate for {}", url);
+    let response = client
+        .head(&url)
+        .send()
+        .await
+        .context(format!("TLS connection to {} failed", hostname))?;
+
+    let der = response
+        .extensions()
+        .get::<reqwest::tls::TlsInfo>()
+        .and_then(|info| info.peer_certificate())
+        .context(format!("No peer certificate presented by {}", hostname))?;
+
+    let not_after = certificate_not_after(der)?;
+    debug!("TLS certificate for {} expires {}", hostname, not_after);
+    Ok(not_after)
+}
+
+/// Parse the notAfter field of a DER encoded X.509 certificate.
+pub fn certificate_not_after(der: &[u8]) -> Result<DateTime<Utc>> {
+    let cert = X509::from_der(der).context("Failed to parse certificate")?;
+    let epoch = Asn1Time::from_unix(0)?;
+    let diff = epoch.diff(cert.not_after())?;
+    let seconds = diff.days as i64 * 86400 + diff.secs as i64;
+    DateTime::from_timestamp(seconds, 0).context("Certificate expiry out of range")
+}
+
+/// Get the status of a certificate based on how many days remain until it expires.
+pub fn certificate_status(days_left: i64, thresholds: &CertificateExpiryConfig) -> Status {
+    if days_left < thresholds.failed_days {
+        Status::FAILED
+    } else if days_left < thresholds.warning_days {
+        Status::WARNING
+    } else {
+        Status::OK
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openssl::hash::MessageDigest;
+    use openssl::pkey::PKey;
+    use openssl::rsa::Rsa;
+    use openssl::x509::X509Builder;
+    use yare::parameterized;
+
+    #[parameterized(
+        plenty_left = { 90, Status::OK },
+        at_warning = { 14, Status::OK },
+        warning = { 13, Status::WARNING },
+        at_failed = { 3, Status::WARNING },
+        failed = { 2, Status::FAILED },
+        expired = { -5, Status::FAILED },
+    )]
+    fn test_certificate_status(days_left: i64, expected: Status) {
+        assert_eq!(
+            certificate_status(days_left, &CertificateExpiryConfig::default()),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_certificate_not_after() -> Result<()> {
+        let key = PKey::from_rsa(Rsa::generate(2048)?)?;
+        let mut builder = X509Builder::new()?;
+        builder.set_pubkey(&key)?;
+        let not_before = Asn1Time::from_unix(1_700_000_000)?;
+        let not_after = Asn1Time::from_unix(1_760_000_000)?;
+        builder.set_not_before(&not_before)?;
+        builder.set_not_after(&not_after)?;
+        builder.sign(&key, MessageDigest::sha256())?;
+        let der = builder.build().to_der()?;
+
+        assert_eq!(certificate_not_after(&der)?.timestamp(), 1_760_000_000);
+        Ok(())
+    }
+}
@@ -0,0 +1,384 @@
+use std::collections::BTreeSet;
+use tera::ast::{Expr, ExprVal, FunctionCall, LogicOperator, Node};
+use tera::{Tera, Value};
+
+/// How deep includes are followed, in case templates include each other.
+const MAX_INCLUDE_DEPTH: usize = 8;
+
+/// Filters that keep the elements of a list as they are, so a loop over the result can still be
+/// checked.
+const SHAPE_PRESERVING_FILTERS: [&str; 5] = ["sort", "reverse", "unique", "slice", "filter"];
+
+/// What a name bound in the template, such as a loop variable, refers to.
+#[derive(Clone)]
+enum Binding {
+    /// The values it takes, such as the elements of the list looped over.
+    Values(Vec<Value>),
+    /// Anything, such as the result of a filter or a macro argument, which isn't checked.
+    Unknown,
+}
+
+struct Walker<'a> {
+    tera: &'a Tera,
+    context: &'a Value,
+    bindings: Vec<(String, Binding)>,
+    /// The variables an enclosing `if` checked with `is defined`.
+    defined: Vec<String>,
+    undefined: BTreeSet<String>,
+}
+
+/// The variables `template_name` refers to that aren't in `context`, such as `data.nonexistent`,
+/// in the order of their paths. Tera fails a render when a variable that is printed is missing,
+/// but conditions, loops and `set` treat it as empty, which hides typos.
+///
+/// A variable is only undefined if a key of its path is missing: a path through a `null`, such as
+/// an optional value behind an `if`, is fine. Variables checked with `is defined` or given a
+/// `default` are skipped, as they expect to be missing.
+pub fn undefined_variables(tera: &Tera, template_name: &str, context: &Value) -> Vec<String> {
+    let mut walker = Walker {
+        tera,
+        context,
+        bindings: vec![],
+        defined: vec![],
+        undefined: BTreeSet::new(),
+    };
+    walker.template(template_name, 0);
+    walker.undefined.into_iter().collect()
+}
+
+/// Whether a key of `path` is missing in `value`, where a `null` or a value that isn't an object
+/// or a list ends the path.
+fn is_missing(value: &Value, path: &[&str]) -> bool {
+    let Some((key, rest)) = path.split_first() else {
+        return false;
+    };
+    match value {
+        Value::Object(object) => object.get(*key).is_none_or(|value| is_missing(value, rest)),
+        Value::Array(array) => key
+            .parse::<usize>()
+            .ok()
+            .and_then(|index| array.get(index))
+            .is_some_and(|value| is_missing(value, rest)),
+        _ => false,
+    }
+}
+
+/// The variables a condition requires to be defined, such as `x` in `x is defined and x > 1`.
+fn tested_defined(condition: &Expr) -> Vec<String> {
+    if condition.negated {
+        return vec![];
+    }
+    match &condition.val {
+        ExprVal::Test(test) if test.name == "defined" && !test.negated => vec![test.ident.clone()],
+        ExprVal::Logic(logic) if logic.operator == LogicOperator::And => {
+            let mut defined = tested_defined(&logic.lhs);
+            defined.extend(tested_defined(&logic.rhs));
+            defined
+        }
+        _ => vec![],
+    }
+}
+
+impl Walker<'_> {
+    fn template(&mut self, name: &str, depth: usize) {
+        let Ok(template) = self.tera.get_template(name) else {
+            return;
+        };
+        // The blocks of a child are in its own tree, the rest of the page in its parents'.
+        self.nodes(&template.ast, depth);
+        for parent in &template.parents {
+            if let Ok(parent) = self.tera.get_template(parent) {
+                self.nodes(&parent.ast, depth);
+            }
+        }
+    }
+
+    fn nodes(&mut self, nodes: &[Node], depth: usize) {
+        for node in nodes {
+            self.node(node, depth);
+        }
+    }
+
+    fn node(&mut self, node: &Node, depth: usize) {
+        match node {
+            Node::VariableBlock(_, expr) => self.expr(expr),
+            Node::Set(_, set) => {
+                self.expr(&set.value);
+                self.bindings.push((set.key.clone(), Binding::Unknown));
+            }
+            Node::FilterSection(_, section, _) => {
+                self.call(&section.filter);
+                self.nodes(&section.body, depth);
+            }
+            Node::Block(_, block, _) => self.nodes(&block.body, depth),
+            Node::Forloop(_, forloop, _) => {
+                self.expr(&forloop.container);
+                let values = self.loop_values(&forloop.container);
+                let bound = self.bindings.len();
+                let (keys, values) = match values {
+                    Binding::Values(values) => {
+                        let keys = values
+                            .iter()
+                            .flat_map(|value| value.as_object())
+                            .flat_map(|object| object.keys().map(|key| Value::from(key.clone())))
+                            .collect();
+                        (Binding::Values(keys), Binding::Values(values))
+                    }
+                    Binding::Unknown => (Binding::Unknown, Binding::Unknown),
+                };
+                if let Some(key) = &forloop.key {
+                    self.bindings.push((key.clone(), keys));
+                }
+                self.bindings.push((forloop.value.clone(), values));
+                self.bindings.push(("loop".to_string(), Binding::Unknown));
+                self.nodes(&forloop.body, depth);
+                self.bindings.truncate(bound);
+                if let Some(body) = &forloop.empty_body {
+                    self.nodes(body, depth);
+                }
+            }
+            Node::If(branches, _) => {
+                for (_, condition, body) in &branches.conditions {
+                    // The rest of an `and` is only evaluated if the test passed.
+                    let guarded = self.defined.len();
+                    self.defined.extend(tested_defined(condition));
+                    self.expr(condition);
+                    self.nodes(body, depth);
+                    self.defined.truncate(guarded);
+                }
+                if let Some((_, body)) = &branches.otherwise {
+                    self.nodes(body, depth);
+                }
+            }
+            Node::MacroDefinition(_, definition, _) => {
+                let bound = self.bindings.len();
+                for arg in definition.args.keys() {
+                    self.bindings.push((arg.clone(), Binding::Unknown));
+                }
+                self.nodes(&definition.body, depth);
+                self.bindings.truncate(bound);
+            }
+            Node::Include(_, names, _) if depth < MAX_INCLUDE_DEPTH => {
+                for name in names {
+                    self.template(name, depth + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn call(&mut self, call: &FunctionCall) {
+        for arg in call.args.values() {
+            self.expr(arg);
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        for filter in &expr.filters {
+            self.call(filter);
+        }
+        match &expr.val {
+            ExprVal::Ident(ident) if !expr.has_default_filter() => self.ident(ident),
+            ExprVal::Math(math) => {
+                self.expr(&math.lhs);
+                self.expr(&math.rhs);
+            }
+            ExprVal::Logic(logic) => {
+                self.expr(&logic.lhs);
+                self.expr(&logic.rhs);
+            }
+            ExprVal::In(expr) => {
+                self.expr(&expr.lhs);
+                self.expr(&expr.rhs);
+            }
+            // The ident of a test is what it tests, such as whether it is defined.
+            ExprVal::Test(test) => test.args.iter().for_each(|arg| self.expr(arg)),
+            ExprVal::MacroCall(call) => call.args.values().for_each(|arg| self.expr(arg)),
+            ExprVal::FunctionCall(call) => self.call(call),
+            ExprVal::Array(items) => items.iter().for_each(|item| self.expr(item)),
+            ExprVal::StringConcat(concat) => {
+                for value in &concat.values {
+                    if let ExprVal::Ident(ident) = value {
+                        self.ident(ident);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The keys of `ident`, with an index such as `[0]` as a key, and whether that is all of it:
+    /// a subscript with a variable, such as `[key]`, ends the keys that can be checked.
+    fn path(ident: &str) -> (Vec<String>, bool) {
+        let mut keys = vec![];
+        let mut rest = ident;
+        loop {
+            let start = rest.find('[').unwrap_or(rest.len());
+            keys.extend(
+                rest[..start]
+                    .split('.')
+                    .filter(|key| !key.is_empty())
+                    .map(str::to_string),
+            );
+            if start == rest.len() {
+                return (keys, true);
+            }
+            let Some(end) = rest[start..].find(']').map(|end| start + end) else {
+                return (keys, false);
+            };
+            let index = &rest[start + 1..end];
+            if index.is_empty() || !index.chars().all(|c| c.is_ascii_digit()) {
+                return (keys, false);
+            }
+            keys.push(index.to_string());
+            rest = &rest[end + 1..];
+        }
+    }
+
+    fn binding(&self, name: &str) -> Option<&Binding> {
+        self.bindings
+            .iter()
+            .rev()
+            .find(|(bound, _)| bound == name)
+            .map(|(_, binding)| binding)
+    }
+
+    fn ident(&mut self, ident: &str) {
+        let guarded = self.defined.iter().any(|defined| {
+            ident
+                .strip_prefix(defined.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with(['.', '[']))
+        });
+        if guarded {
+            return;
+        }
+        let (path, _) = Self::path(ident);
+        let path: Vec<&str> = path.iter().map(String::as_str).collect();
+        let missing = match self.binding(path[0]) {
+            Some(Binding::Values(values)) => {
+                values.iter().any(|value| is_missing(value, &path[1..]))
+            }
+            Some(Binding::Unknown) => false,
+            None if path[0] == "__tera_context" => false,
+            None => is_missing(self.context, &path),
+        };
+        if missing {
+            self.undefined.insert(ident.to_string());
+        }
+    }
+
+    /// The elements of what a loop goes over, for checking the loop variable.
+    fn loop_values(&self, container: &Expr) -> Binding {
+        let ExprVal::Ident(ident) = &container.val else {
+            return Binding::Unknown;
+        };
+        if container
+            .filters
+            .iter()
+            .any(|filter| !SHAPE_PRESERVING_FILTERS.contains(&filter.name.as_str()))
+        {
+            return Binding::Unknown;
+        }
+        let (path, complete) = Self::path(ident);
+        if !complete {
+            return Binding::Unknown;
+        }
+        let path: Vec<&str> = path.iter().map(String::as_str).collect();
+        let (roots, keys) = match self.binding(path[0]) {
+            Some(Binding::Values(values)) => (values.clone(), &path[1..]),
+            Some(Binding::Unknown) => return Binding::Unknown,
+            None => (vec![self.context.clone()], &path[..]),
+        };
+        let mut values = vec![];
+        for root in roots {
+            let found = keys.iter().try_fold(&root, |value, key| match value {
+                Value::Object(object) => object.get(*key),
+                Value::Array(array) => key.parse::<usize>().ok().and_then(|i| array.get(i)),
+                _ => None,
+            });
+            match found {
+                Some(Value::Array(items)) => values.extend(items.iter().cloned()),
+                Some(Value::Object(object)) => values.extend(object.values().cloned()),
+                _ => {}
+            }
+        }
+        Binding::Values(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yare::parameterized;
+
+    fn context() -> Value {
+        serde_json::json!({
+            "data": {
+                "title": "EESSI",
+                "announcement": null,
+                "repositories": [
+                    { "name": "software.eessi.io", "details": [] },
+                    { "name": "dev.eessi.io", "details": ["Lagging"] }
+                ],
+                "legend": { "ok": { "text": "Normal service" } }
+            }
+        })
+    }
+
+    #[parameterized(
+        printed = { "{{ data.nonexistent }}", &["data.nonexistent"] },
+        condition = { "{% if data.nonexistent %}x{% endif %}", &["data.nonexistent"] },
+        root = { "{{ nonexistent | length }}", &["nonexistent"] },
+        loop_variable = { "{% for repo in data.repositories | sort(attribute=\"name\") %}{{ repo.nme }}{% endfor %}", &["repo.nme"] },
+        loop_container = { "{% for repo in data.repos %}{{ repo.name }}{% endfor %}", &["data.repos"] },
+        nested_loop = { "{% for repo in data.repositories %}{% for d in repo.detail %}{{ d }}{% endfor %}{% endfor %}", &["repo.detail"] },
+        filter_argument = { "{{ data.title | default(value=data.nope) }}", &["data.nope"] },
+        math = { "{{ data.count + 1 }}", &["data.count"] },
+        sorted = { "{{ data.z }}{{ data.a }}{{ data.z }}", &["data.a", "data.z"] },
+        defined = { "{{ data.title }}{% for repo in data.repositories %}{{ repo.name }}{{ loop.index }}{% endfor %}", &[] },
+        null = { "{% if data.announcement %}{{ data.announcement.html }}{% endif %}", &[] },
+        is_defined = { "{% if data.nonexistent is defined %}x{% endif %}", &[] },
+        default = { "{{ data.nonexistent | default(value=\"\") }}", &[] },
+        set = { "{% set title = data.title %}{{ title }}", &[] },
+        map_loop = { "{% for key, status in data.legend %}{{ key }}{{ status.text }}{% endfor %}", &[] },
+        subscript = { "{{ data.repositories[0].name }}", &[] },
+        subscript_missing = { "{{ data.repositories[1].nme }}", &["data.repositories[1].nme"] },
+        subscript_loop = { "{% for d in data.repositories[1].details %}{{ d }}{% endfor %}{% for r in data.repositories[0].detail %}{% endfor %}", &["data.repositories[0].detail"] },
+        variable_subscript = { "{% for r in data.repositories %}{{ data.legend[r.name].text }}{% endfor %}", &[] },
+        guarded = { "{% if data.since is defined and data.since %}{{ data.since.day }}{% endif %}", &[] },
+        guarded_subscript = { "{% if integrity is defined %}{{ integrity['status.css'] }}{% endif %}", &[] },
+        guard_ends = { "{% if data.since is defined %}{% endif %}{{ data.since }}", &["data.since"] },
+        macro_argument = { "{% macro row(repo) %}{{ repo.nme }}{% endmacro row %}", &[] },
+    )]
+    fn test_undefined_variables(template: &str, expected: &[&str]) {
+        let mut tera = Tera::default();
+        tera.add_raw_template("page.html", template).unwrap();
+
+        assert_eq!(
+            undefined_variables(&tera, "page.html", &context()),
+            expected.to_vec()
+        );
+    }
+
+    #[test]
+    fn test_undefined_variables_inheritance() {
+        let mut tera = Tera::default();
+        tera.add_raw_templates([
+            ("footer.html", "{{ data.contact }}"),
+            (
+                "base.html",
+                "{{ data.title }}{% block body %}{% endblock body %}{% include \"footer.html\" %}",
+            ),
+            (
+                "page.html",
+                "{% extends \"base.html\" %}{% block body %}{{ data.bdy }}{% endblock body %}",
+            ),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            undefined_variables(&tera, "page.html", &context()),
+            vec!["data.bdy", "data.contact"]
+        );
+    }
+}
@@ -0,0 +1,93 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const FIXTURE: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/src/testdata/status-schema-1.json"
+);
+
+/// The expected output of rendering `FIXTURE`, one file for each of `GOLDEN_FILES`.
+const GOLDEN_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden");
+
+/// The rendered files compared with their golden copies: the pages, the documents and the
+/// metrics, rather than the resources, which are copied as they are built in.
+const GOLDEN_FILES: &[&str] = &[
+    "index.html",
+    "status.json",
+    "summary.json",
+    "status.txt",
+    "status.md",
+    "metrics",
+];
+
+/// Set to write the rendered files to `GOLDEN_DIR` rather than compare with it, once a change of
+/// the output is intended.
+const UPDATE_GOLDEN: &str = "UPDATE_GOLDEN";
+
+/// The metrics that differ between runs and builds, whatever was rendered.
+const UNSTABLE_METRICS: &[&str] = &[
+    "status_page_last_run_timestamp_seconds",
+    "status_page_build_info",
+];
+
+/// The contents of a rendered file, without the samples of `UNSTABLE_METRICS`.
+fn stable_contents(name: &str, path: &Path) -> String {
+    let contents =
+        fs::read_to_string(path).unwrap_or_else(|e| panic!("Failed to read {:?}: {}", path, e));
+    if name != "metrics" {
+        return contents;
+    }
+    contents
+        .lines()
+        .filter(|line| {
+            !UNSTABLE_METRICS.iter().any(|metric| {
+                line.strip_prefix(metric)
+                    .is_some_and(|rest| rest.starts_with([' ', '{']))
+            })
+        })
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
+
+#[test]
+fn test_render_matches_golden() {
+    let destination = tempfile::tempdir().unwrap();
+    let status = Command::new(env!("CARGO_BIN_EXE_cvmfs-status-page-rust"))
+        .args([
+            "-d",
+            destination.path().to_str().unwrap(),
+            "--from-json",
+            FIXTURE,
+        ])
+        .arg("--text-output-file=status.txt")
+        .arg("--markdown-output-file=status.md")
+        .arg("--prometheus-metrics")
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let update = std::env::var_os(UPDATE_GOLDEN).is_some();
+    for name in GOLDEN_FILES {
+        let rendered = stable_contents(name, &destination.path().join(name));
+        let golden = PathBuf::from(GOLDEN_DIR).join(name);
+        if update {
+            fs::create_dir_all(GOLDEN_DIR).unwrap();
+            fs::write(&golden, rendered).unwrap();
+            continue;
+        }
+        let expected = fs::read_to_string(&golden).unwrap_or_else(|e| {
+            panic!(
+                "Failed to read {:?}, run with {}=1 to write it: {}",
+                golden, UPDATE_GOLDEN, e
+            )
+        });
+        assert!(
+            rendered == expected,
+            "{} differs from {:?}, run with {}=1 if the change is intended",
+            name,
+            golden,
+            UPDATE_GOLDEN
+        );
+    }
+}